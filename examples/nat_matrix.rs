@@ -0,0 +1,516 @@
+//! Simulates hole punch attempts across the matrix of NAT type pairs in memory and prints the
+//! success rate of each pair as a JSON line, for validating changes to the punching before
+//! shipping them to real networks.
+//!
+//! ```text
+//! cargo run --example nat-matrix --features cli,test-utils -- \
+//!     [--trials <n>] [--loss <0..1>] [--latency-ms <ms>] [--jitter-ms <ms>] \
+//!     [--punch-repeats <n>] [--seed <n>]
+//! ```
+//!
+//! Each trial runs an initiator, a target and a public relay, each a [`HolePunchService`] on a
+//! [`ManualClock`], over a simulated network that delays and drops datagrams. The initiator and
+//! target are each behind a NAT of the pair and registered with the relay. The initiator sends
+//! its request to the target, as discv5 would before it times out, and initiates an attempt
+//! through the relay. A trial succeeds directly if the target's NAT lets the request through, or
+//! punched if the WHOAREYOU of the target reaches the initiator within the attempt timeout.
+
+use async_trait::async_trait;
+use enr::{CombinedKey, EnrBuilder};
+use futures::executor::block_on;
+use nat_hole_punch::{
+    test_utils::ManualClock, AlwaysPunch, AttemptOutcome, Clock, Enr, HolePunchConfig,
+    HolePunchService, MessageNonce, NodeAddress, NodeId, NotificationSink, PunchPattern, SeededRng,
+    ServiceCommand, DEFAULT_ATTEMPT_TIMEOUT,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    env, fmt,
+    net::{IpAddr, SocketAddr},
+    process,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const DEFAULT_TRIALS: usize = 100;
+const DEFAULT_LATENCY: Duration = Duration::from_millis(50);
+/// Ports the simulated NATs map local sockets to, counting up.
+const FIRST_MAPPED_PORT: u16 = 40000;
+const RELAY: usize = 2;
+
+/// How a NAT maps local sockets and filters inbound datagrams, in the classic STUN terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NatType {
+    /// No NAT, the local socket is public.
+    Open,
+    /// One mapping for all destinations, anyone may send to it.
+    FullCone,
+    /// One mapping for all destinations, hosts sent to may send to it.
+    RestrictedCone,
+    /// One mapping for all destinations, sockets sent to may send to it.
+    PortRestrictedCone,
+    /// A mapping per destination, only the destination may send to it.
+    Symmetric,
+}
+
+impl NatType {
+    const ALL: [NatType; 5] = [
+        NatType::Open,
+        NatType::FullCone,
+        NatType::RestrictedCone,
+        NatType::PortRestrictedCone,
+        NatType::Symmetric,
+    ];
+}
+
+impl fmt::Display for NatType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NatType::Open => "open",
+            NatType::FullCone => "full-cone",
+            NatType::RestrictedCone => "restricted-cone",
+            NatType::PortRestrictedCone => "port-restricted-cone",
+            NatType::Symmetric => "symmetric",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// A NAT in front of a single host.
+struct Nat {
+    nat_type: NatType,
+    external: IpAddr,
+    next_port: u16,
+    /// The mapped ports, by destination for symmetric NATs and under `None` otherwise.
+    mappings: HashMap<Option<SocketAddr>, u16>,
+    /// The destinations sent to from each mapped port.
+    contacted: HashMap<u16, HashSet<SocketAddr>>,
+}
+
+impl Nat {
+    fn new(nat_type: NatType, external: IpAddr) -> Self {
+        Nat {
+            nat_type,
+            external,
+            next_port: FIRST_MAPPED_PORT,
+            mappings: HashMap::new(),
+            contacted: HashMap::new(),
+        }
+    }
+
+    /// Maps a datagram from `local` to `dst`. Returns the socket it leaves the NAT from.
+    fn outbound(&mut self, local: SocketAddr, dst: SocketAddr) -> SocketAddr {
+        if self.nat_type == NatType::Open {
+            return local;
+        }
+        let key = (self.nat_type == NatType::Symmetric).then_some(dst);
+        let port = *self.mappings.entry(key).or_insert_with(|| {
+            self.next_port += 1;
+            self.next_port
+        });
+        self.contacted.entry(port).or_default().insert(dst);
+        SocketAddr::new(self.external, port)
+    }
+
+    /// Returns true if a datagram from `src` to the mapped `port` is let through.
+    fn inbound(&self, port: u16, src: SocketAddr) -> bool {
+        let Some(contacted) = self.contacted.get(&port) else {
+            return false;
+        };
+        match self.nat_type {
+            NatType::Open | NatType::FullCone => true,
+            NatType::RestrictedCone => contacted.iter().any(|dst| dst.ip() == src.ip()),
+            NatType::PortRestrictedCone | NatType::Symmetric => contacted.contains(&src),
+        }
+    }
+}
+
+/// The contents of a simulated datagram.
+#[derive(Debug, Clone)]
+enum Payload {
+    /// The discv5 request of the initiator to the target.
+    Request,
+    Notification(Vec<u8>),
+    WhoAreYou(MessageNonce),
+    Packet,
+}
+
+#[derive(Debug, Clone)]
+struct Datagram {
+    src: SocketAddr,
+    dst: SocketAddr,
+    payload: Payload,
+}
+
+/// The datagrams sent by all hosts, drained by the network.
+type Outbox = Arc<Mutex<Vec<Datagram>>>;
+
+/// Sends the packets of a service into the simulated network.
+struct SimSink {
+    local: SocketAddr,
+    local_enr: Enr,
+    sessions: HashMap<NodeId, SocketAddr>,
+    outbox: Outbox,
+}
+
+impl SimSink {
+    fn send(&self, dst: SocketAddr, payload: Payload) {
+        self.outbox.lock().unwrap().push(Datagram {
+            src: self.local,
+            dst,
+            payload,
+        });
+    }
+}
+
+#[async_trait]
+impl NotificationSink for SimSink {
+    type SessionIndex = NodeAddress;
+    type Error = Infallible;
+
+    fn local_enr(&self) -> Enr {
+        self.local_enr.clone()
+    }
+
+    fn node_id(&self, session: &NodeAddress) -> NodeId {
+        session.node_id
+    }
+
+    fn session(&self, node_id: &NodeId) -> Option<NodeAddress> {
+        let socket_addr = self.sessions.get(node_id)?;
+        Some(NodeAddress::new(*socket_addr, *node_id))
+    }
+
+    async fn send_notification(
+        &mut self,
+        session: &NodeAddress,
+        notif: Vec<u8>,
+    ) -> Result<(), Infallible> {
+        self.send(session.socket_addr, Payload::Notification(notif));
+        Ok(())
+    }
+
+    async fn send_whoareyou(
+        &mut self,
+        dst: SocketAddr,
+        _initiator: &Enr,
+        nonce: MessageNonce,
+    ) -> Result<(), Infallible> {
+        self.send(dst, Payload::WhoAreYou(nonce));
+        Ok(())
+    }
+
+    async fn send_empty_packet(&mut self, dst: SocketAddr) -> Result<(), Infallible> {
+        self.send(dst, Payload::Packet);
+        Ok(())
+    }
+
+    async fn send_packet(&mut self, dst: SocketAddr, _packet: Vec<u8>) -> Result<(), Infallible> {
+        self.send(dst, Payload::Packet);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TrialOutcome {
+    /// The request of the initiator reached the target without punching.
+    Direct,
+    Punched,
+    Failed,
+}
+
+struct Args {
+    trials: usize,
+    loss: f64,
+    latency: Duration,
+    jitter: Duration,
+    punch_repeats: u8,
+    seed: u64,
+}
+
+/// A datagram in flight, due at the destination at `due` unless a NAT drops it.
+struct InFlight {
+    due: Instant,
+    from: SocketAddr,
+    datagram: Datagram,
+}
+
+/// One initiator, target and relay on a simulated network.
+struct Trial<'a> {
+    args: &'a Args,
+    rng: &'a mut StdRng,
+    clock: ManualClock,
+    /// The initiator, target and relay, in that order.
+    services: Vec<HolePunchService<SimSink>>,
+    /// The NATs of the initiator and target.
+    nats: [Nat; 2],
+    outbox: Outbox,
+    in_flight: Vec<InFlight>,
+}
+
+impl Trial<'_> {
+    /// Sends the datagrams in the outbox through the NATs of their senders into the network.
+    fn transmit(&mut self) {
+        let datagrams = std::mem::take(&mut *self.outbox.lock().unwrap());
+        for datagram in datagrams {
+            let sender = self.host_of(datagram.src);
+            let from = match sender {
+                RELAY => datagram.src,
+                host => self.nats[host].outbound(datagram.src, datagram.dst),
+            };
+            // a lost datagram still maps a socket on its way out
+            if self.rng.gen_bool(self.args.loss) {
+                continue;
+            }
+            let jitter = self.rng.gen_range(Duration::ZERO..=self.args.jitter);
+            self.in_flight.push(InFlight {
+                due: self.clock.now() + self.args.latency + jitter,
+                from,
+                datagram,
+            });
+        }
+    }
+
+    fn host_of(&self, local: SocketAddr) -> usize {
+        self.services
+            .iter()
+            .position(|service| service.sink().local == local)
+            .expect("datagrams are sent by hosts")
+    }
+
+    /// Returns the host a datagram from `from` to `dst` reaches, if any NAT lets it through.
+    fn route(&self, from: SocketAddr, dst: SocketAddr) -> Option<usize> {
+        (0..self.services.len()).find(|host| match self.nats.get(*host) {
+            Some(nat) if nat.nat_type != NatType::Open => {
+                dst.ip() == nat.external && nat.inbound(dst.port(), from)
+            }
+            _ => self.services[*host].sink().local == dst,
+        })
+    }
+
+    /// Delivers the datagrams due. Returns true if the request of the initiator arrived.
+    fn deliver(&mut self) -> bool {
+        let now = self.clock.now();
+        let (due, in_flight) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition::<Vec<_>, _>(|datagram| datagram.due <= now);
+        self.in_flight = in_flight;
+        let mut direct = false;
+        for InFlight { from, datagram, .. } in due {
+            let Some(host) = self.route(from, datagram.dst) else {
+                continue;
+            };
+            let command = match datagram.payload {
+                Payload::Request => {
+                    direct = true;
+                    continue;
+                }
+                Payload::Notification(notif) => ServiceCommand::Notification(notif),
+                Payload::WhoAreYou(nonce) => ServiceCommand::WhoAreYouReceived { nonce, src: from },
+                Payload::Packet => ServiceCommand::PacketReceived(from),
+            };
+            // failures show in the outcome of the attempt
+            _ = block_on(self.services[host].handle_command(command));
+        }
+        direct
+    }
+
+    /// The point in time the next datagram arrives or timer of a service fires, if any.
+    fn next_event(&self) -> Option<Instant> {
+        let now = self.clock.now();
+        let timers = self
+            .services
+            .iter()
+            .filter_map(|service| service.next_timeout())
+            .map(|timeout| now + timeout);
+        self.in_flight
+            .iter()
+            .map(|datagram| datagram.due)
+            .chain(timers)
+            .min()
+    }
+}
+
+fn run_trial(
+    args: &Args,
+    rng: &mut StdRng,
+    keys: &[CombinedKey; 3],
+    pair: (NatType, NatType),
+) -> TrialOutcome {
+    let clock = ManualClock::default();
+    let outbox = Outbox::default();
+    let relay_addr: SocketAddr = "198.51.100.1:9000".parse().expect("valid socket");
+    let locals: [SocketAddr; 2] = [
+        "10.0.0.1:9000".parse().expect("valid socket"),
+        "10.0.1.1:9000".parse().expect("valid socket"),
+    ];
+    let mut nats = [
+        Nat::new(pair.0, "203.0.113.1".parse().expect("valid ip")),
+        Nat::new(pair.1, "203.0.113.2".parse().expect("valid ip")),
+    ];
+    // hosts without NAT are at their public ip
+    let locals = [0, 1].map(|host| match nats[host].nat_type {
+        NatType::Open => SocketAddr::new(nats[host].external, locals[host].port()),
+        _ => locals[host],
+    });
+    // both peers are registered with the relay, which observes them at their mapped sockets
+    let observed = [0, 1].map(|host| nats[host].outbound(locals[host], relay_addr));
+    let enr = |key: &CombinedKey, addr: SocketAddr| {
+        let mut builder = EnrBuilder::new("v4");
+        builder.ip(addr.ip()).udp4(addr.port());
+        builder.build(key).expect("valid ENR")
+    };
+    let enrs = [
+        enr(&keys[0], observed[0]),
+        enr(&keys[1], observed[1]),
+        enr(&keys[2], relay_addr),
+    ];
+    let ids = enrs.clone().map(|enr| enr.node_id());
+    let sessions = [
+        vec![(ids[RELAY], relay_addr), (ids[1], observed[1])],
+        vec![(ids[RELAY], relay_addr)],
+        vec![(ids[0], observed[0]), (ids[1], observed[1])],
+    ];
+    let services = [locals[0], locals[1], relay_addr]
+        .into_iter()
+        .zip(enrs.clone())
+        .zip(sessions)
+        .map(|((local, local_enr), sessions)| {
+            let sink = SimSink {
+                local,
+                local_enr,
+                sessions: sessions.into_iter().collect(),
+                outbox: outbox.clone(),
+            };
+            let config = HolePunchConfig {
+                punch_pattern: PunchPattern {
+                    repeats: args.punch_repeats,
+                    ..Default::default()
+                },
+                // every trial is worth punching for, whatever the distance of its random peers
+                punch_policy: Arc::new(AlwaysPunch),
+                clock: Arc::new(clock.clone()),
+                rng: Arc::new(SeededRng::new(rng.gen())),
+                ..Default::default()
+            };
+            HolePunchService::new(sink, config).0
+        })
+        .collect();
+    let mut trial = Trial {
+        args,
+        rng,
+        clock,
+        services,
+        nats,
+        outbox,
+        in_flight: Vec::new(),
+    };
+
+    // the request that times out, mapping the initiator's socket towards the target
+    trial.services[0].sink().send(observed[1], Payload::Request);
+    let nonce = trial.rng.gen::<MessageNonce>();
+    let relay = NodeAddress::new(relay_addr, ids[RELAY]);
+    let target = NodeAddress::new(observed[1], ids[1]);
+    let mut outcome =
+        block_on(trial.services[0].initiate(vec![relay], enrs[0].clone(), nonce, target))
+            .expect("attempt is initiated");
+
+    let deadline = trial.clock.now() + DEFAULT_ATTEMPT_TIMEOUT;
+    loop {
+        trial.transmit();
+        match outcome.try_recv() {
+            Ok(Some(AttemptOutcome::Punched { .. })) => return TrialOutcome::Punched,
+            Ok(Some(AttemptOutcome::Failed(_) | AttemptOutcome::Cancelled)) | Err(_) => {
+                return TrialOutcome::Failed
+            }
+            Ok(None) => {}
+        }
+        let Some(next) = trial.next_event().filter(|next| *next <= deadline) else {
+            return TrialOutcome::Failed;
+        };
+        // timers due right away fire on the next tick
+        let now = trial.clock.now();
+        trial.clock.advance(
+            next.saturating_duration_since(now)
+                .max(Duration::from_millis(1)),
+        );
+        if trial.deliver() {
+            return TrialOutcome::Direct;
+        }
+        for service in trial.services.iter_mut() {
+            _ = block_on(service.handle_command(ServiceCommand::Tick));
+        }
+    }
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        trials: DEFAULT_TRIALS,
+        loss: 0.0,
+        latency: DEFAULT_LATENCY,
+        jitter: Duration::ZERO,
+        punch_repeats: 0,
+        seed: rand::random(),
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let value = argv.next().ok_or(format!("missing value of {arg}"))?;
+        match arg.as_str() {
+            "--trials" => args.trials = value.parse().map_err(|_| "invalid trials")?,
+            "--loss" => {
+                args.loss = value
+                    .parse()
+                    .ok()
+                    .filter(|loss| (0.0..=1.0).contains(loss))
+                    .ok_or("invalid loss, expected a probability")?;
+            }
+            "--latency-ms" => {
+                let ms = value.parse().map_err(|_| "invalid latency")?;
+                args.latency = Duration::from_millis(ms);
+            }
+            "--jitter-ms" => {
+                let ms = value.parse().map_err(|_| "invalid jitter")?;
+                args.jitter = Duration::from_millis(ms);
+            }
+            "--punch-repeats" => {
+                args.punch_repeats = value.parse().map_err(|_| "invalid punch repeats")?;
+            }
+            "--seed" => args.seed = value.parse().map_err(|_| "invalid seed")?,
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("nat-matrix: {e}");
+        process::exit(2);
+    });
+    eprintln!("nat-matrix: seed {}", args.seed);
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let keys = [(); 3].map(|_| CombinedKey::generate_secp256k1());
+
+    for initiator in NatType::ALL {
+        for target in NatType::ALL {
+            let mut counts = HashMap::<TrialOutcome, usize>::new();
+            for _ in 0..args.trials {
+                let outcome = run_trial(&args, &mut rng, &keys, (initiator, target));
+                *counts.entry(outcome).or_default() += 1;
+            }
+            let count = |outcome| counts.get(&outcome).copied().unwrap_or_default();
+            let succeeded = count(TrialOutcome::Direct) + count(TrialOutcome::Punched);
+            println!(
+                "{{\"initiator\":\"{initiator}\",\"target\":\"{target}\",\"trials\":{},\
+                 \"direct\":{},\"punched\":{},\"failed\":{},\"success_rate\":{:.3}}}",
+                args.trials,
+                count(TrialOutcome::Direct),
+                count(TrialOutcome::Punched),
+                count(TrialOutcome::Failed),
+                succeeded as f64 / args.trials.max(1) as f64,
+            );
+        }
+    }
+}
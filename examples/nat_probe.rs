@@ -0,0 +1,272 @@
+//! Probes the NAT the local node is behind and prints a JSON report.
+//!
+//! ```text
+//! cargo run --example nat-probe --features cli -- \
+//!     [--server <host:port>]... [--lifetime-probe <secs,secs,..>] [--timeout-ms <ms>]
+//! ```
+//!
+//! Queries two STUN servers for the socket the NAT maps the local socket to, bind probes the
+//! mapped IP with [`nat_hole_punch::probe_nat`] and classifies the NAT by whether both servers
+//! see the same mapping. With `--lifetime-probe`, the mapping is left idle for each of the given
+//! durations in turn until it changes, bounding the hole lifetime to configure.
+
+use nat_hole_punch::{probe_nat, BindProbeResult, NatStatus, ProbeConfig};
+use std::{
+    env, fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    process, thread,
+    time::Duration,
+};
+
+const DEFAULT_SERVERS: [&str; 2] = ["stun.l.google.com:19302", "stun1.l.google.com:19302"];
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_a442;
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const STUN_HEADER_LEN: usize = 20;
+
+/// How the NAT maps the local socket, as far as two STUN servers can tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NatType {
+    /// The local socket is reachable as is.
+    OpenInternet,
+    /// The NAT maps the local socket to the same socket for all destinations, holes can be
+    /// punched.
+    EndpointIndependent,
+    /// The NAT maps the local socket to a socket per destination, holes punched by the target
+    /// don't match the socket the initiator is seen at.
+    EndpointDependent,
+    /// No STUN server answered.
+    UdpBlocked,
+    /// Only one STUN server answered.
+    Unknown,
+}
+
+impl fmt::Display for NatType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NatType::OpenInternet => "open-internet",
+            NatType::EndpointIndependent => "endpoint-independent-mapping",
+            NatType::EndpointDependent => "endpoint-dependent-mapping",
+            NatType::UdpBlocked => "udp-blocked",
+            NatType::Unknown => "unknown",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug)]
+struct Args {
+    servers: Vec<String>,
+    lifetime_probes: Vec<Duration>,
+    timeout: Duration,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        servers: Vec::new(),
+        lifetime_probes: Vec::new(),
+        timeout: DEFAULT_TIMEOUT,
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let mut value = || argv.next().ok_or(format!("missing value of {arg}"));
+        match arg.as_str() {
+            "--server" => args.servers.push(value()?),
+            "--lifetime-probe" => {
+                for secs in value()?.split(',') {
+                    let secs = secs
+                        .parse()
+                        .map_err(|_| format!("invalid lifetime probe {secs}"))?;
+                    args.lifetime_probes.push(Duration::from_secs(secs));
+                }
+            }
+            "--timeout-ms" => {
+                let ms = value()?.parse().map_err(|_| "invalid timeout")?;
+                args.timeout = Duration::from_millis(ms).max(Duration::from_millis(1));
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    if args.servers.is_empty() {
+        args.servers = DEFAULT_SERVERS.iter().map(|s| s.to_string()).collect();
+    }
+    Ok(args)
+}
+
+/// Asks the STUN server at `server` which socket it sees `socket` at.
+fn stun_query(socket: &UdpSocket, server: SocketAddr) -> Option<SocketAddr> {
+    let txid: [u8; 12] = rand::random();
+    let mut req = Vec::with_capacity(STUN_HEADER_LEN);
+    req.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    req.extend_from_slice(&0u16.to_be_bytes());
+    req.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    req.extend_from_slice(&txid);
+    socket.send_to(&req, server).ok()?;
+
+    let mut buf = [0u8; 512];
+    loop {
+        let (len, src) = socket.recv_from(&mut buf).ok()?;
+        if src != server {
+            continue;
+        }
+        if let Some(mapped) = parse_binding_response(&buf[..len], &txid) {
+            return Some(mapped);
+        }
+    }
+}
+
+/// Returns the mapped socket of a binding response to the request with the given transaction
+/// id, preferring the XOR-MAPPED-ADDRESS.
+fn parse_binding_response(resp: &[u8], txid: &[u8; 12]) -> Option<SocketAddr> {
+    if resp.len() < STUN_HEADER_LEN
+        || u16::from_be_bytes([resp[0], resp[1]]) != STUN_BINDING_RESPONSE
+        || resp[4..8] != STUN_MAGIC_COOKIE.to_be_bytes()
+        || &resp[8..20] != txid
+    {
+        return None;
+    }
+    let mut mapped = None;
+    let mut attrs = &resp[STUN_HEADER_LEN..];
+    while attrs.len() >= 4 {
+        let ty = u16::from_be_bytes([attrs[0], attrs[1]]);
+        let len = u16::from_be_bytes([attrs[2], attrs[3]]) as usize;
+        let value = attrs.get(4..4 + len)?;
+        match ty {
+            STUN_XOR_MAPPED_ADDRESS => return parse_address(value, Some(txid)),
+            STUN_MAPPED_ADDRESS => mapped = parse_address(value, None),
+            _ => {}
+        }
+        // attributes are padded to 4 bytes
+        attrs = attrs.get(4 + len.div_ceil(4) * 4..).unwrap_or_default();
+    }
+    mapped
+}
+
+/// Parses a (XOR-)MAPPED-ADDRESS attribute, XORed with the cookie and transaction id if given.
+fn parse_address(value: &[u8], xor_txid: Option<&[u8; 12]>) -> Option<SocketAddr> {
+    let mut key = [0u8; 16];
+    if let Some(txid) = xor_txid {
+        key[..4].copy_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        key[4..].copy_from_slice(txid);
+    }
+    let port = u16::from_be_bytes([value.get(2)? ^ key[0], value.get(3)? ^ key[1]]);
+    let ip = match value.get(1)? {
+        0x01 => {
+            let mut octets: [u8; 4] = value.get(4..8)?.try_into().ok()?;
+            octets.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        0x02 => {
+            let mut octets: [u8; 16] = value.get(4..20)?.try_into().ok()?;
+            octets.iter_mut().zip(key).for_each(|(b, k)| *b ^= k);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+fn json_opt(value: Option<impl fmt::Display>) -> String {
+    value.map_or("null".to_string(), |value| format!("\"{value}\""))
+}
+
+fn json_num(value: Option<u64>) -> String {
+    value.map_or("null".to_string(), |value| value.to_string())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("nat-probe: {e}");
+        process::exit(2);
+    });
+    let servers = args
+        .servers
+        .iter()
+        .filter_map(|server| server.to_socket_addrs().ok()?.find(SocketAddr::is_ipv4))
+        .collect::<Vec<_>>();
+    let socket = UdpSocket::bind("0.0.0.0:0").unwrap_or_else(|e| {
+        eprintln!("nat-probe: failed to bind socket, {e}");
+        process::exit(1);
+    });
+    socket
+        .set_read_timeout(Some(args.timeout))
+        .expect("timeout is non-zero");
+    let local = socket.local_addr().expect("socket is bound");
+
+    let mapped = servers
+        .iter()
+        .map(|server| (*server, stun_query(&socket, *server)))
+        .collect::<Vec<_>>();
+    let answered = mapped
+        .iter()
+        .filter_map(|(_, mapped)| *mapped)
+        .collect::<Vec<_>>();
+
+    let bind_probe = answered
+        .first()
+        .map(|mapped| probe_nat(mapped.ip(), &ProbeConfig::default()));
+    let nat_status = bind_probe
+        .as_ref()
+        .map_or(NatStatus::Unknown, |report| report.result.into());
+    let nat_type = match answered.as_slice() {
+        [] => NatType::UdpBlocked,
+        [first, ..] if nat_status == NatStatus::Public && first.port() == local.port() => {
+            NatType::OpenInternet
+        }
+        [_] => NatType::Unknown,
+        [first, rest @ ..] if rest.iter().all(|mapped| mapped == first) => {
+            NatType::EndpointIndependent
+        }
+        _ => NatType::EndpointDependent,
+    };
+
+    // leaves the mapping idle for each duration in turn, until the server sees it change
+    let mut lifetime_min = None;
+    let mut lifetime_max = None;
+    if let (Some(server), Some(first)) = (servers.first(), answered.first()) {
+        for idle in args.lifetime_probes.iter() {
+            thread::sleep(*idle);
+            match stun_query(&socket, *server) {
+                Some(mapped) if mapped == *first => lifetime_min = Some(idle.as_secs()),
+                Some(_) => {
+                    lifetime_max = Some(idle.as_secs());
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    let queries = mapped
+        .iter()
+        .map(|(server, mapped)| {
+            format!(
+                "{{\"server\":\"{server}\",\"mapped\":{}}}",
+                json_opt(mapped.as_ref())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    let bind_probe = bind_probe.map_or("null".to_string(), |report| {
+        let result = match report.result {
+            BindProbeResult::NotBehindNat => "not-behind-nat".to_string(),
+            BindProbeResult::BehindNat => "behind-nat".to_string(),
+            BindProbeResult::Inconclusive(kind) => format!("inconclusive: {kind}"),
+        };
+        format!(
+            "{{\"result\":\"{result}\",\"tried_ports\":{:?}}}",
+            report.tried_ports
+        )
+    });
+    println!(
+        "{{\"local\":\"{local}\",\"stun\":[{queries}],\"bind_probe\":{bind_probe},\
+         \"nat_status\":\"{nat_status}\",\"nat_type\":\"{nat_type}\",\
+         \"mapping_lifetime_min_secs\":{},\"mapping_lifetime_max_secs\":{}}}",
+        json_num(lifetime_min),
+        json_num(lifetime_max),
+    );
+}
@@ -0,0 +1,220 @@
+//! A test relay over a minimal UDP transport, for exercising initiator and target
+//! implementations against a relay on a public host.
+//!
+//! ```text
+//! cargo run --example relay --features cli -- \
+//!     [--listen <ip:port>] [--rate-limit <per initiator>,<total>] [--stats-secs <secs>]
+//! ```
+//!
+//! The transport carries no discv5 sessions. A peer registers with the relay by sending a
+//! datagram of [`HELLO`] followed by its RLP encoded ENR, after which it can initiate attempts
+//! and be the target of relayed attempts. Any other datagram of a registered peer is handed to
+//! the [`HolePunchService`] as an encoded notification, unless [`quick_reject`] discards it
+//! first. The statistics of the service are printed as a JSON line every `--stats-secs`.
+
+use async_trait::async_trait;
+use futures::executor::block_on;
+use nat_hole_punch::{
+    quick_reject, Enr, HolePunchConfig, HolePunchService, MessageNonce, NodeAddress, NodeId,
+    NotificationSink, ServiceCommand, DEFAULT_TICK_INTERVAL,
+};
+use std::{
+    collections::HashMap,
+    env, io,
+    net::{SocketAddr, UdpSocket},
+    process,
+    time::{Duration, Instant},
+};
+
+/// First byte of a datagram registering its sender, followed by the sender's ENR.
+const HELLO: u8 = 0xff;
+const DEFAULT_LISTEN: &str = "0.0.0.0:9000";
+const DEFAULT_STATS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Sends the packets of the service as plain datagrams.
+struct UdpSink {
+    socket: UdpSocket,
+    local_enr: Enr,
+    /// The registered peers, by node id.
+    peers: HashMap<NodeId, SocketAddr>,
+}
+
+#[async_trait]
+impl NotificationSink for UdpSink {
+    type SessionIndex = NodeAddress;
+    type Error = io::Error;
+
+    fn local_enr(&self) -> Enr {
+        self.local_enr.clone()
+    }
+
+    fn node_id(&self, session: &NodeAddress) -> NodeId {
+        session.node_id
+    }
+
+    fn session(&self, node_id: &NodeId) -> Option<NodeAddress> {
+        let socket_addr = self.peers.get(node_id)?;
+        Some(NodeAddress::new(*socket_addr, *node_id))
+    }
+
+    async fn send_notification(
+        &mut self,
+        session: &NodeAddress,
+        notif: Vec<u8>,
+    ) -> Result<(), io::Error> {
+        self.socket.send_to(&notif, session.socket_addr).map(|_| ())
+    }
+
+    /// Never called on a relay. The transport has no discv5 packets, the bare nonce stands in
+    /// for the WHOAREYOU.
+    async fn send_whoareyou(
+        &mut self,
+        dst: SocketAddr,
+        _initiator: &Enr,
+        nonce: MessageNonce,
+    ) -> Result<(), io::Error> {
+        self.socket.send_to(&nonce, dst).map(|_| ())
+    }
+
+    async fn send_empty_packet(&mut self, dst: SocketAddr) -> Result<(), io::Error> {
+        self.socket.send_to(&[], dst).map(|_| ())
+    }
+
+    async fn send_packet(&mut self, dst: SocketAddr, packet: Vec<u8>) -> Result<(), io::Error> {
+        self.socket.send_to(&packet, dst).map(|_| ())
+    }
+}
+
+struct Args {
+    listen: SocketAddr,
+    rate_limit: Option<(u32, u32)>,
+    stats_interval: Duration,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        listen: DEFAULT_LISTEN.parse().expect("valid socket"),
+        rate_limit: None,
+        stats_interval: DEFAULT_STATS_INTERVAL,
+    };
+    let mut argv = env::args().skip(1);
+    while let Some(arg) = argv.next() {
+        let value = argv.next().ok_or(format!("missing value of {arg}"))?;
+        match arg.as_str() {
+            "--listen" => args.listen = value.parse().map_err(|_| "invalid listen socket")?,
+            "--rate-limit" => {
+                let (per_initiator, total) = value
+                    .split_once(',')
+                    .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok()?)))
+                    .ok_or("invalid rate limit")?;
+                args.rate_limit = Some((per_initiator, total));
+            }
+            "--stats-secs" => {
+                let secs = value.parse().map_err(|_| "invalid stats interval")?;
+                args.stats_interval = Duration::from_secs(secs);
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("relay: {e}");
+        process::exit(2);
+    });
+    let socket = UdpSocket::bind(args.listen).unwrap_or_else(|e| {
+        eprintln!("relay: failed to bind {}, {e}", args.listen);
+        process::exit(1);
+    });
+    socket
+        .set_read_timeout(Some(DEFAULT_TICK_INTERVAL))
+        .expect("tick interval is non-zero");
+
+    let key = enr::CombinedKey::generate_secp256k1();
+    let mut builder = enr::EnrBuilder::new("v4");
+    builder.ip(args.listen.ip()).udp4(args.listen.port());
+    let local_enr = builder.build(&key).expect("valid ENR");
+    eprintln!("relay: listening on {} as {}", args.listen, local_enr);
+
+    let sink = UdpSink {
+        socket: socket.try_clone().expect("socket can be cloned"),
+        local_enr,
+        peers: HashMap::new(),
+    };
+    let mut config = HolePunchConfig::default();
+    if let Some((per_initiator, total)) = args.rate_limit {
+        config.rate_limit_per_initiator = per_initiator;
+        config.rate_limit_total = total;
+    }
+    let (mut service, _tx) = HolePunchService::new(sink, config);
+
+    let mut buf = [0u8; 1280];
+    let mut last_tick = Instant::now();
+    let mut last_stats = Instant::now();
+    let mut rejected = 0u64;
+    loop {
+        match socket.recv_from(&mut buf) {
+            Ok((len, src)) if buf.first() == Some(&HELLO) && len > 1 => {
+                match rlp::decode::<Enr>(&buf[1..len]) {
+                    Ok(enr) => {
+                        service.sink_mut().peers.insert(enr.node_id(), src);
+                    }
+                    Err(e) => eprintln!("relay: bad hello from {src}, {e}"),
+                }
+            }
+            Ok((len, _)) if quick_reject(&buf[..len]).is_some() => {
+                // garbage is dropped before looking up the peer or parsing any RLP
+                rejected += 1;
+            }
+            Ok((len, src)) => {
+                let node_id = service
+                    .sink()
+                    .peers
+                    .iter()
+                    .find_map(|(node_id, addr)| (*addr == src).then_some(*node_id));
+                match node_id {
+                    Some(node_id) => {
+                        let command = ServiceCommand::NotificationFrom {
+                            src: NodeAddress::new(src, node_id),
+                            notif: buf[..len].to_vec(),
+                        };
+                        if let Err(e) = block_on(service.handle_command(command)) {
+                            eprintln!("relay: failed handling notification from {src}, {e}");
+                        }
+                    }
+                    None => eprintln!("relay: dropped datagram from unregistered {src}"),
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(e) => {
+                eprintln!("relay: socket failed, {e}");
+                process::exit(1);
+            }
+        }
+        if last_tick.elapsed() >= DEFAULT_TICK_INTERVAL {
+            last_tick = Instant::now();
+            _ = block_on(service.handle_command(ServiceCommand::Tick));
+        }
+        if last_stats.elapsed() >= args.stats_interval {
+            last_stats = Instant::now();
+            let stats = service.stats();
+            println!(
+                "{{\"peers\":{},\"relayed\":{},\"relayed_punched\":{},\"relayed_failed\":{},\
+                 \"rate_limited\":{},\"failed\":{},\"rejected\":{}}}",
+                service.sink().peers.len(),
+                stats.punches_relayed,
+                stats.relayed_punched,
+                stats.relayed_failed,
+                stats.punches_failed.rate_limited,
+                stats.punches_failed.total(),
+                rejected,
+            );
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{
+    clock::system_clock, probe_nat_with_rng, rng::thread_rng, BindProbeResult, Enr, NodeId,
+    ProbeConfig, SharedClock, SharedRng,
+};
+use enr::{CombinedKey, EnrError};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+/// Default min number of distinct voter subnets for an observed address to win the vote.
+pub const DEFAULT_MIN_VOTE_SUBNETS: usize = 3;
+/// Default time a vote counts.
+pub const DEFAULT_VOTE_TTL: Duration = Duration::from_secs(300);
+
+/// Returns the subnet of `ip` that a single operator is likely to control, the /24 for IPv4 and
+/// the /48 for IPv6.
+pub fn subnet(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(a, b, c, 0))
+        }
+        IpAddr::V6(ip) => {
+            let mut octets = ip.octets();
+            octets[6..].fill(0);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Vote {
+    observed: SocketAddr,
+    voter_subnet: IpAddr,
+    cast: Instant,
+}
+
+/// Accumulates the reflexive address of the local node as reported by peers, for example in
+/// PONGs. An address is weighted by the number of distinct subnets its voters are in, so a
+/// single misbehaving peer, or many peers run by the same operator, can't poison it.
+#[derive(Debug, Clone)]
+pub struct AddressVote {
+    votes: HashMap<NodeId, Vote>,
+    min_subnets: usize,
+    ttl: Duration,
+    clock: SharedClock,
+    rng: SharedRng,
+}
+
+impl Default for AddressVote {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_VOTE_SUBNETS, DEFAULT_VOTE_TTL)
+    }
+}
+
+impl AddressVote {
+    pub fn new(min_subnets: usize, ttl: Duration) -> Self {
+        AddressVote {
+            votes: HashMap::new(),
+            min_subnets,
+            ttl,
+            clock: system_clock(),
+            rng: thread_rng(),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Draws the ports to probe from `rng` instead of the thread local generator.
+    pub fn with_rng(mut self, rng: SharedRng) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Records that `voter`, at `voter_ip`, observed the local node at `observed`. Replaces any
+    /// previous vote of the voter.
+    pub fn vote(&mut self, voter: NodeId, voter_ip: IpAddr, observed: SocketAddr) {
+        self.votes.insert(
+            voter,
+            Vote {
+                observed,
+                voter_subnet: subnet(voter_ip),
+                cast: self.clock.now(),
+            },
+        );
+    }
+
+    /// Number of votes that count.
+    pub fn len(&self) -> usize {
+        self.votes.len()
+    }
+
+    /// Returns true if there are no votes.
+    pub fn is_empty(&self) -> bool {
+        self.votes.is_empty()
+    }
+
+    /// Forgets the votes that have outlived the time to live at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.votes
+            .retain(|_, vote| now.saturating_duration_since(vote.cast) < ttl);
+    }
+
+    /// Returns the observed address with the most distinct voter subnets, if it has at least the
+    /// min number of subnets and no other address ties with it.
+    pub fn majority(&self) -> Option<SocketAddr> {
+        let mut subnets: HashMap<SocketAddr, HashSet<IpAddr>> = HashMap::new();
+        for vote in self.votes.values() {
+            subnets
+                .entry(vote.observed)
+                .or_default()
+                .insert(vote.voter_subnet);
+        }
+        let mut weights = subnets
+            .into_iter()
+            .map(|(observed, subnets)| (observed, subnets.len()))
+            .collect::<Vec<_>>();
+        weights.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        match weights.as_slice() {
+            [(observed, weight), rest @ ..]
+                if *weight >= self.min_subnets
+                    && rest.first().is_none_or(|(_, next)| next < weight) =>
+            {
+                Some(*observed)
+            }
+            _ => None,
+        }
+    }
+
+    /// Probes whether the local node is behind NAT at the majority address, see
+    /// [`crate::is_behind_nat`].
+    pub fn probe_nat(&self) -> Option<BindProbeResult> {
+        self.majority().map(|observed| {
+            probe_nat_with_rng(observed.ip(), &ProbeConfig::default(), &*self.rng).result
+        })
+    }
+
+    /// Sets the UDP socket of the local ENR to the majority address, if it differs. Returns the
+    /// new socket if the ENR was updated.
+    pub fn update_enr(
+        &self,
+        enr: &mut Enr,
+        key: &CombinedKey,
+    ) -> Result<Option<SocketAddr>, EnrError> {
+        let Some(observed) = self.majority() else {
+            return Ok(None);
+        };
+        let current = match observed {
+            SocketAddr::V4(_) => enr.udp4_socket().map(SocketAddr::V4),
+            SocketAddr::V6(_) => enr.udp6_socket().map(SocketAddr::V6),
+        };
+        if current == Some(observed) {
+            return Ok(None);
+        }
+        enr.set_udp_socket(observed, key)?;
+        Ok(Some(observed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_subnet_cant_poison() {
+        let mut votes = AddressVote::new(2, DEFAULT_VOTE_TTL);
+        let honest: SocketAddr = "198.51.100.1:9000".parse().unwrap();
+        let poisoned: SocketAddr = "203.0.113.1:9000".parse().unwrap();
+
+        // many sybils in one /24
+        for i in 1..=10 {
+            let ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, i));
+            votes.vote(NodeId::random(), ip, poisoned);
+        }
+        assert_eq!(None, votes.majority());
+
+        votes.vote(NodeId::random(), "10.0.0.1".parse().unwrap(), honest);
+        votes.vote(NodeId::random(), "10.0.1.1".parse().unwrap(), honest);
+        assert_eq!(Some(honest), votes.majority());
+    }
+}
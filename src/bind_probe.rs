@@ -0,0 +1,312 @@
+use crate::{
+    rng::{SourceRng, ThreadRng},
+    RandomSource, DEFAULT_PORT_BIND_TRIES, USER_AND_DYNAMIC_PORTS,
+};
+use rand::Rng;
+use std::{
+    collections::HashSet,
+    fmt,
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+/// The outcome of probing whether the local node is behind NAT by binding to its observed
+/// reachable IP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindProbeResult {
+    /// The observed IP is assigned to a local interface, the node isn't behind NAT.
+    NotBehindNat,
+    /// The observed IP isn't assigned to any local interface, the node is behind NAT.
+    BehindNat,
+    /// None of the tried ports could be bound for reasons that say nothing about NAT, for
+    /// example firewall policies or permissions. Contains the error of the last try.
+    Inconclusive(ErrorKind),
+}
+
+impl BindProbeResult {
+    /// Returns true if the probe concluded that the node is behind NAT.
+    pub fn is_behind_nat(&self) -> bool {
+        matches!(self, BindProbeResult::BehindNat)
+    }
+}
+
+/// What is known about the local node being behind NAT.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NatStatus {
+    /// Not probed yet, or probing was inconclusive.
+    #[default]
+    Unknown,
+    /// The node is reachable at its observed address.
+    Public,
+    /// The node is behind NAT.
+    BehindNat,
+    /// The node binds its observed address, but a stateful firewall drops unsolicited packets to
+    /// it, so peers reach it only through holes punched like through NAT.
+    Firewalled,
+    /// The node is behind carrier-grade NAT, its addresses being in the shared address space, see
+    /// [`is_cgnat`]. Often a NAT of the ISP in front of one of the user, so mappings time out
+    /// sooner and depend on the destination more often.
+    BehindCgnat,
+    /// The observed address is on a local interface other than the one the traffic of the node
+    /// leaves through, so binding it succeeds while peers reach the node over the egress path,
+    /// typically through NAT.
+    SecondaryInterface,
+}
+
+impl NatStatus {
+    /// Combines a bind probe with a peer-assisted probe of whether a peer reached the node
+    /// without it sending to the peer first. A bindable but unreachable node is
+    /// [`NatStatus::Firewalled`].
+    pub fn from_probes(bind: BindProbeResult, reached_unsolicited: Option<bool>) -> Self {
+        match (NatStatus::from(bind), reached_unsolicited) {
+            (NatStatus::Public, Some(false)) => NatStatus::Firewalled,
+            (status, _) => status,
+        }
+    }
+
+    /// Returns true if peers need a punched hole to reach the node, behind NAT or a firewall.
+    pub fn needs_punch(&self) -> bool {
+        matches!(
+            self,
+            NatStatus::BehindNat
+                | NatStatus::Firewalled
+                | NatStatus::BehindCgnat
+                | NatStatus::SecondaryInterface
+        )
+    }
+}
+
+/// Returns true if `ip` is in the shared address space of carrier-grade NAT, 100.64.0.0/10 by
+/// RFC 6598.
+pub fn is_cgnat(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            a == 100 && b & 0xc0 == 64
+        }
+        IpAddr::V6(ip) => ip
+            .to_ipv4_mapped()
+            .is_some_and(|ip| is_cgnat(&IpAddr::V4(ip))),
+    }
+}
+
+impl From<BindProbeResult> for NatStatus {
+    fn from(res: BindProbeResult) -> Self {
+        match res {
+            BindProbeResult::NotBehindNat => NatStatus::Public,
+            BindProbeResult::BehindNat => NatStatus::BehindNat,
+            BindProbeResult::Inconclusive(_) => NatStatus::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for NatStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            NatStatus::Unknown => "unknown",
+            NatStatus::Public => "public",
+            NatStatus::BehindNat => "behind-nat",
+            NatStatus::Firewalled => "firewalled",
+            NatStatus::BehindCgnat => "behind-cgnat",
+            NatStatus::SecondaryInterface => "secondary-interface",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for NatStatus {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(NatStatus::Unknown),
+            "public" => Ok(NatStatus::Public),
+            "behind-nat" => Ok(NatStatus::BehindNat),
+            "firewalled" => Ok(NatStatus::Firewalled),
+            "behind-cgnat" => Ok(NatStatus::BehindCgnat),
+            "secondary-interface" => Ok(NatStatus::SecondaryInterface),
+            _ => Err("invalid nat status"),
+        }
+    }
+}
+
+/// Which ports the bind probe may try, per address family.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeConfig {
+    /// Ports to try for an IPv4 observed IP.
+    pub v4_ports: RangeInclusive<u16>,
+    /// Ports to try for an IPv6 observed IP.
+    pub v6_ports: RangeInclusive<u16>,
+    /// Ports never to try, for example ports reserved by other services.
+    pub excluded_ports: HashSet<u16>,
+    /// Max number of ports to try.
+    pub tries: usize,
+}
+
+impl Default for ProbeConfig {
+    fn default() -> Self {
+        ProbeConfig {
+            v4_ports: USER_AND_DYNAMIC_PORTS,
+            v6_ports: USER_AND_DYNAMIC_PORTS,
+            excluded_ports: HashSet::new(),
+            tries: DEFAULT_PORT_BIND_TRIES,
+        }
+    }
+}
+
+impl ProbeConfig {
+    /// The ports to try for the given IP.
+    pub fn ports(&self, ip: &IpAddr) -> RangeInclusive<u16> {
+        match ip {
+            IpAddr::V4(_) => self.v4_ports.clone(),
+            IpAddr::V6(_) => self.v6_ports.clone(),
+        }
+    }
+}
+
+/// The outcome of a bind probe and the ports it tried, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeReport {
+    pub result: BindProbeResult,
+    pub tried_ports: Vec<u16>,
+}
+
+/// Helper function to test if the local node is behind NAT based on the node's observed reachable
+/// socket.
+///
+/// Failing to bind because the address isn't available locally is conclusive on all platforms.
+/// Failing because of permissions, e.g. an excluded port range on Windows, or because the port
+/// is in use, is retried at another random port.
+pub fn is_behind_nat(
+    observed_ip: IpAddr,
+    unused_port_range: Option<RangeInclusive<u16>>,
+    max_retries: Option<usize>,
+) -> BindProbeResult {
+    let ports = unused_port_range.unwrap_or(USER_AND_DYNAMIC_PORTS);
+    let config = ProbeConfig {
+        v4_ports: ports.clone(),
+        v6_ports: ports,
+        tries: max_retries.unwrap_or(DEFAULT_PORT_BIND_TRIES),
+        ..Default::default()
+    };
+    probe_nat(observed_ip, &config).result
+}
+
+/// Like [`is_behind_nat`], but tries ports according to the per address family config and
+/// reports which ports were tried.
+pub fn probe_nat(observed_ip: IpAddr, config: &ProbeConfig) -> ProbeReport {
+    probe_nat_with_rng(observed_ip, config, &ThreadRng)
+}
+
+/// Like [`probe_nat`], but draws the ports to try from `rng`.
+pub fn probe_nat_with_rng(
+    observed_ip: IpAddr,
+    config: &ProbeConfig,
+    rng: &dyn RandomSource,
+) -> ProbeReport {
+    let ports = config.ports(&observed_ip);
+    let mut tried_ports = Vec::new();
+    if !ports
+        .clone()
+        .any(|port| !config.excluded_ports.contains(&port))
+    {
+        return ProbeReport {
+            result: BindProbeResult::Inconclusive(ErrorKind::InvalidInput),
+            tried_ports,
+        };
+    }
+    // If the node cannot bind to the observed address because it isn't assigned locally, we
+    // conclude it is behind NAT.
+    let mut rng = SourceRng(rng);
+    let mut last_error = ErrorKind::Other;
+    for _ in 0..config.tries {
+        let rnd_port = loop {
+            let port: u16 = rng.gen_range(ports.clone());
+            if !config.excluded_ports.contains(&port) {
+                break port;
+            }
+        };
+        tried_ports.push(rnd_port);
+        let socket_addr = SocketAddr::new(observed_ip, rnd_port);
+        let result = match UdpSocket::bind(socket_addr) {
+            Ok(_) => BindProbeResult::NotBehindNat,
+            Err(e) if e.kind() == ErrorKind::AddrNotAvailable => BindProbeResult::BehindNat,
+            // PermissionDenied, AddrInUse and others may succeed at another port
+            Err(e) => {
+                last_error = e.kind();
+                continue;
+            }
+        };
+        return ProbeReport {
+            result,
+            tried_ports,
+        };
+    }
+    ProbeReport {
+        result: BindProbeResult::Inconclusive(last_error),
+        tried_ports,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_ip_not_behind_nat() {
+        let res = is_behind_nat("127.0.0.1".parse().unwrap(), None, None);
+        assert_eq!(BindProbeResult::NotBehindNat, res);
+    }
+
+    #[test]
+    fn test_probe_reports_tried_ports() {
+        let config = ProbeConfig {
+            v4_ports: 40000..=40001,
+            excluded_ports: [40000].into(),
+            ..Default::default()
+        };
+        let report = probe_nat("192.0.2.1".parse().unwrap(), &config);
+        assert_eq!(BindProbeResult::BehindNat, report.result);
+        assert_eq!(vec![40001], report.tried_ports);
+
+        let config = ProbeConfig {
+            v6_ports: 50000..=50000,
+            excluded_ports: [40000, 40001].into(),
+            ..config
+        };
+        let report = probe_nat("::1".parse().unwrap(), &config);
+        assert!(report.tried_ports.iter().all(|port| *port == 50000));
+        let report = probe_nat("127.0.0.1".parse().unwrap(), &config);
+        assert!(matches!(report.result, BindProbeResult::Inconclusive(_)));
+        assert!(report.tried_ports.is_empty());
+    }
+
+    #[test]
+    fn test_bindable_but_unreachable_firewalled() {
+        let bindable = BindProbeResult::NotBehindNat;
+        assert_eq!(
+            NatStatus::Firewalled,
+            NatStatus::from_probes(bindable, Some(false))
+        );
+        assert_eq!(
+            NatStatus::Public,
+            NatStatus::from_probes(bindable, Some(true))
+        );
+        assert_eq!(NatStatus::Public, NatStatus::from_probes(bindable, None));
+        let nated = BindProbeResult::BehindNat;
+        assert_eq!(
+            NatStatus::BehindNat,
+            NatStatus::from_probes(nated, Some(false))
+        );
+        assert_eq!(Ok(NatStatus::Firewalled), "firewalled".parse());
+    }
+
+    #[test]
+    fn test_unassigned_ip_behind_nat() {
+        // TEST-NET-1, never assigned to a local interface
+        let res = is_behind_nat("192.0.2.1".parse().unwrap(), None, None);
+        assert_eq!(BindProbeResult::BehindNat, res);
+    }
+}
@@ -0,0 +1,220 @@
+use async_trait::async_trait;
+use futures::channel::oneshot;
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, OnceLock,
+    },
+    thread,
+    time::Instant,
+};
+
+/// A clock shared by the timing subsystems of the service.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A source of time. Timers of the service read the time from a clock, so that tests can step
+/// time manually, see [`crate::test_utils::ManualClock`].
+#[async_trait]
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+    /// Resolves once the clock reaches `deadline`.
+    async fn sleep_until(&self, deadline: Instant);
+}
+
+/// The system clock. Sleeps are served by one timer thread shared by all of the process, prefer
+/// a runtime clock like [`TokioClock`] where one is available.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            return;
+        }
+        _ = Timer::shared().wake_at(deadline).await;
+    }
+}
+
+/// Number of pending sleeps below which cancelled sleeps are left until their deadline.
+const MIN_PRUNE_LEN: usize = 32;
+
+/// Pending [`SystemClock`] sleeps, woken by a single timer thread.
+#[derive(Default)]
+struct Timer {
+    /// Senders waking the sleeps, by deadline and registration order.
+    sleeps: Mutex<BTreeMap<(Instant, u64), oneshot::Sender<()>>>,
+    /// Notifies the timer thread of a new earliest deadline.
+    earliest: Condvar,
+    /// Registration order, telling apart sleeps with the same deadline.
+    seq: AtomicU64,
+    /// Number of sleeps after the timer thread last dropped the cancelled ones.
+    pruned_len: AtomicUsize,
+}
+
+impl Timer {
+    /// Returns the timer, starting its thread on first use.
+    fn shared() -> &'static Timer {
+        static TIMER: OnceLock<Timer> = OnceLock::new();
+        let mut started = false;
+        let timer = TIMER.get_or_init(|| {
+            started = true;
+            Timer::default()
+        });
+        if started {
+            thread::Builder::new()
+                .name("nat-hole-punch-timer".into())
+                .spawn(|| timer.run())
+                .expect("failed to spawn timer thread");
+        }
+        timer
+    }
+
+    /// Registers a sleep until `deadline`.
+    fn wake_at(&self, deadline: Instant) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let mut sleeps = self.sleeps.lock().unwrap_or_else(|e| e.into_inner());
+        let earliest = sleeps.keys().next().is_none_or(|(at, _)| deadline < *at);
+        sleeps.insert((deadline, seq), tx);
+        if earliest || self.needs_pruning(sleeps.len()) {
+            self.earliest.notify_one();
+        }
+        rx
+    }
+
+    /// Returns true if the sleeps doubled since the cancelled ones were last dropped. Pruning
+    /// only then keeps registering a sleep amortised constant time.
+    fn needs_pruning(&self, len: usize) -> bool {
+        len > 2 * self.pruned_len.load(Ordering::Relaxed).max(MIN_PRUNE_LEN)
+    }
+
+    fn run(&self) {
+        let mut sleeps = self.sleeps.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let now = Instant::now();
+            while let Some(entry) = sleeps.first_entry() {
+                if entry.key().0 > now {
+                    break;
+                }
+                _ = entry.remove().send(());
+            }
+            if self.needs_pruning(sleeps.len()) {
+                sleeps.retain(|_, tx| !tx.is_canceled());
+                self.pruned_len.store(sleeps.len(), Ordering::Relaxed);
+            }
+            sleeps = match sleeps.keys().next() {
+                Some((at, _)) => {
+                    let timeout = at.saturating_duration_since(now);
+                    self.earliest
+                        .wait_timeout(sleeps, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .earliest
+                    .wait(sleeps)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// The system clock, sleeping with tokio timers.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        tokio::time::sleep_until(deadline.into()).await
+    }
+}
+
+/// Returns the default clock, the [`SystemClock`].
+pub(crate) fn system_clock() -> SharedClock {
+    Arc::new(SystemClock)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, FutureExt};
+    use std::time::Duration;
+
+    #[test]
+    fn test_system_clock_sleep_until() {
+        let clock = SystemClock;
+        let deadline = clock.now() + Duration::from_millis(10);
+        block_on(clock.sleep_until(deadline));
+        assert!(clock.now() >= deadline);
+    }
+
+    #[test]
+    fn test_system_clock_sleeps_share_timer() {
+        let clock = SystemClock;
+        let start = clock.now();
+        let deadlines = [30, 10, 20].map(|ms| start + Duration::from_millis(ms));
+        let woken = Arc::new(Mutex::new(Vec::new()));
+        let sleeps = deadlines.map(|deadline| {
+            let woken = woken.clone();
+            async move {
+                clock.sleep_until(deadline).await;
+                woken.lock().unwrap().push(deadline);
+            }
+        });
+        // a cancelled sleep doesn't hold up the others
+        assert_eq!(
+            None,
+            clock
+                .sleep_until(start + Duration::from_millis(5))
+                .now_or_never()
+        );
+        block_on(futures::future::join_all(sleeps));
+
+        let mut expected = deadlines.to_vec();
+        expected.sort();
+        assert_eq!(expected, *woken.lock().unwrap());
+        assert!(clock.now() >= expected[2]);
+    }
+
+    #[test]
+    fn test_cancelled_sleeps_pruned() {
+        let clock = SystemClock;
+        let far = clock.now() + Duration::from_secs(3600);
+        for _ in 0..4 * MIN_PRUNE_LEN {
+            assert_eq!(None, clock.sleep_until(far).now_or_never());
+        }
+        let timer = Timer::shared();
+        let pending = |timer: &Timer| {
+            timer
+                .sleeps
+                .lock()
+                .unwrap()
+                .keys()
+                .filter(|(at, _)| *at == far)
+                .count()
+        };
+        for _ in 0..100 {
+            if pending(timer) <= 2 * MIN_PRUNE_LEN {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(pending(timer) <= 2 * MIN_PRUNE_LEN);
+    }
+}
@@ -0,0 +1,174 @@
+//! Decoding of notifications captured off the wire, for network monitors and pcap processing
+//! tools. Needs neither a [`crate::NatHolePunch`] implementation nor a runtime.
+//!
+//! ```
+//! use nat_hole_punch::codec::decode_any;
+//!
+//! # let captured = nat_hole_punch::PunchReport([1; 12], true, None, None).rlp_encode();
+//! let decoded = decode_any(&captured).unwrap();
+//! println!("{} needing {}", decoded.notif_type, decoded.min_version);
+//! ```
+
+use crate::{
+    notification::decode_frame, rlp_backend::DecoderError, DecodeLimits, Extensions, Notification,
+    NotificationType, ProtocolNamespace, ProtocolVersion, TypeNumbering, WireConfig,
+    MIN_HOLE_PUNCH_PROTOCOL_VERSION,
+};
+
+/// A notification decoded by [`decode_any`], along with what its encoding tells about the
+/// sender.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedNotification {
+    pub notification: Notification,
+    pub notif_type: NotificationType,
+    /// Whether the type is numbered the legacy way, see [`TypeNumbering::Legacy`].
+    pub legacy_numbering: bool,
+    /// The revision the sender advertised in a [`ProtocolVersion`] extension, if any.
+    pub advertised_version: Option<ProtocolVersion>,
+    /// The lowest revision that decodes the notification, given the items it carries.
+    pub min_version: ProtocolVersion,
+    /// Whether the ENR was compressed, see [`WireConfig::compress_enr`].
+    pub compressed_enr: bool,
+    /// Number of bytes after the RLP list, the tag of any [`WireConfig::namespace`] and the
+    /// padding, see [`WireConfig::padding_bucket`].
+    pub padding: usize,
+}
+
+/// Decodes a notification of any type, numbering and revision, enforcing the default
+/// [`DecodeLimits`].
+pub fn decode_any(data: &[u8]) -> Result<DecodedNotification, DecoderError> {
+    decode_any_with(data, compat_wire())
+}
+
+/// Like [`decode_any`], for notifications of the given namespace.
+pub fn decode_any_in(
+    data: &[u8],
+    namespace: ProtocolNamespace,
+) -> Result<DecodedNotification, DecoderError> {
+    let wire = WireConfig {
+        namespace: Some(namespace),
+        ..compat_wire()
+    };
+    decode_any_with(data, wire)
+}
+
+fn decode_any_with(data: &[u8], wire: WireConfig) -> Result<DecodedNotification, DecoderError> {
+    let (notif_type, rlp) = decode_frame(data, &DecodeLimits::default(), &wire)?;
+    let compressed_enr = matches!(
+        notif_type,
+        NotificationType::RelayInit | NotificationType::RelayMsg
+    ) && !rlp.at(0)?.is_list();
+    let padding = data.len() - 1 - rlp.as_raw().len();
+    let notification = Notification::rlp_decode_with_wire(data, &DecodeLimits::default(), &wire)?;
+    let legacy_numbering = data[0] != notif_type.to_byte(TypeNumbering::Current);
+
+    Ok(DecodedNotification {
+        advertised_version: extensions(&notification)
+            .and_then(Extensions::get::<ProtocolVersion>)
+            .and_then(Result::ok),
+        min_version: min_version(&notification, compressed_enr),
+        notification,
+        notif_type,
+        legacy_numbering,
+        compressed_enr,
+        padding,
+    })
+}
+
+fn extensions(notif: &Notification) -> Option<&Extensions> {
+    match notif {
+        Notification::RelayInit(notif) => notif.5.as_ref(),
+        Notification::RelayMsg(notif) => notif.6.as_ref(),
+        Notification::RelayAbort(notif) => notif.2.as_ref(),
+        Notification::PunchReport(notif) => notif.3.as_ref(),
+        Notification::EchoAfter(notif) => notif.3.as_ref(),
+        Notification::Echo(notif) => notif.1.as_ref(),
+    }
+}
+
+/// The revisions introducing the items, see [`crate::HOLE_PUNCH_PROTOCOL_VERSION`].
+fn min_version(notif: &Notification, compressed_enr: bool) -> ProtocolVersion {
+    let extensions = (extensions(notif).is_some(), 8);
+    let revisions = match notif {
+        Notification::RelayInit(notif) => vec![
+            (notif.3.is_some(), 2),
+            (notif.4.is_some(), 3),
+            (compressed_enr, 5),
+            extensions,
+        ],
+        Notification::RelayMsg(notif) => vec![
+            (notif.2.is_some(), 2),
+            (notif.3.is_some(), 3),
+            (notif.4.is_some(), 4),
+            (compressed_enr, 5),
+            (notif.5.is_some(), 7),
+            extensions,
+        ],
+        Notification::RelayAbort(_) => vec![extensions],
+        Notification::PunchReport(_) => vec![(true, 6), extensions],
+        Notification::EchoAfter(_) | Notification::Echo(_) => vec![(true, 9)],
+    };
+    let revision = revisions
+        .into_iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, revision)| revision)
+        .max()
+        .unwrap_or(MIN_HOLE_PUNCH_PROTOCOL_VERSION);
+    ProtocolVersion(revision)
+}
+
+/// The raw items of the RLP list of a notification, for tools inspecting items this crate
+/// doesn't know of.
+pub fn raw_items(data: &[u8]) -> Result<Vec<&[u8]>, DecoderError> {
+    let (_, rlp) = decode_frame(data, &DecodeLimits::default(), &compat_wire())?;
+    Ok(rlp.iter().map(|item| item.as_raw()).collect())
+}
+
+fn compat_wire() -> WireConfig {
+    WireConfig {
+        type_numbering: TypeNumbering::Compat,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PunchReport, RelayInit, MESSAGE_NONCE_LENGTH};
+    use enr::{CombinedKey, EnrBuilder, NodeId};
+
+    #[test]
+    fn test_decode_any() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4").build(&key).unwrap();
+        let notif = RelayInit(
+            enr,
+            NodeId::random(),
+            [1; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+        );
+        let legacy = WireConfig {
+            type_numbering: TypeNumbering::Legacy,
+            padding_bucket: Some(64),
+            ..Default::default()
+        };
+        let decoded = decode_any(&notif.to_rlp_with(&legacy)).unwrap();
+        assert_eq!(Notification::from(notif.clone()), decoded.notification);
+        assert!(decoded.legacy_numbering && !decoded.compressed_enr);
+        assert!(decoded.padding > 0);
+        assert_eq!(ProtocolVersion(1), decoded.min_version);
+        assert_eq!(None, decoded.advertised_version);
+
+        let mut extensions = Extensions::default();
+        extensions.insert(&ProtocolVersion::CURRENT);
+        let report = PunchReport([1; MESSAGE_NONCE_LENGTH], true, None, Some(extensions));
+        let decoded = decode_any(&report.rlp_encode()).unwrap();
+        assert_eq!(NotificationType::PunchReport, decoded.notif_type);
+        assert_eq!(Some(ProtocolVersion::CURRENT), decoded.advertised_version);
+        assert_eq!(ProtocolVersion(8), decoded.min_version);
+        // the ENR, the target and the nonce
+        assert_eq!(3, raw_items(&notif.rlp_encode()).unwrap().len());
+    }
+}
@@ -0,0 +1,689 @@
+use crate::{
+    AttemptId, Candidate, CandidateKind, Candidates, Echo, EchoAfter, Enr, Extensions, NodeAddress,
+    NodeId, Notification, NotificationType, PunchReport, ReasonCode, RelayAbort, RelayInit,
+    RelayMsg,
+};
+use std::{fmt, net::SocketAddr, time::Duration};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DebugJsonError {
+    #[error("malformed json at byte {0}")]
+    Syntax(usize),
+    #[error("field {0} is missing or of the wrong type")]
+    Field(&'static str),
+    #[error("field {0} has an invalid value")]
+    Value(&'static str),
+    #[error("unknown notification type {0}")]
+    UnknownType(String),
+}
+
+/// The subset of JSON the debug encoding uses. Objects keep their keys in order, so that the
+/// encoding of a value is canonical.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// A parsed JSON value. Object keys are owned, unlike those of the [`Json`] written.
+#[derive(Debug)]
+enum Parsed {
+    Null,
+    Bool(bool),
+    Number(u64),
+    String(String),
+    Array(Vec<Parsed>),
+    Object(Vec<(String, Parsed)>),
+}
+
+/// Max nesting of arrays and objects parsed, the canonical form nests at most 3 levels.
+const MAX_DEPTH: usize = 8;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn parse(input: &str) -> Result<Parsed, DebugJsonError> {
+        let mut parser = Parser {
+            input: input.as_bytes(),
+            pos: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.input.len() {
+            return Err(DebugJsonError::Syntax(parser.pos));
+        }
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), DebugJsonError> {
+        self.skip_whitespace();
+        if self.input.get(self.pos) != Some(&byte) {
+            return Err(DebugJsonError::Syntax(self.pos));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, literal: &str, value: Parsed) -> Result<Parsed, DebugJsonError> {
+        if !self.input[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(DebugJsonError::Syntax(self.pos));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Parsed, DebugJsonError> {
+        self.skip_whitespace();
+        match self.input.get(self.pos) {
+            Some(b'n') => self.literal("null", Parsed::Null),
+            Some(b't') => self.literal("true", Parsed::Bool(true)),
+            Some(b'f') => self.literal("false", Parsed::Bool(false)),
+            Some(b'"') => self.string().map(Parsed::String),
+            Some(b'0'..=b'9') => self.number(),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::object),
+            _ => Err(DebugJsonError::Syntax(self.pos)),
+        }
+    }
+
+    /// Parses an array or object, rejecting input nested deeper than [`MAX_DEPTH`] before it
+    /// can overflow the stack.
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Parsed, DebugJsonError>,
+    ) -> Result<Parsed, DebugJsonError> {
+        if self.depth == MAX_DEPTH {
+            return Err(DebugJsonError::Syntax(self.pos));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn number(&mut self) -> Result<Parsed, DebugJsonError> {
+        let start = self.pos;
+        while self.input.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .map(Parsed::Number)
+            .ok_or(DebugJsonError::Syntax(start))
+    }
+
+    fn string(&mut self) -> Result<String, DebugJsonError> {
+        self.expect(b'"')?;
+        let mut s = String::new();
+        loop {
+            let start = self.pos;
+            let Some(&byte) = self.input.get(self.pos) else {
+                return Err(DebugJsonError::Syntax(start));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => return Ok(s),
+                b'\\' => {
+                    let escaped = self.input.get(self.pos).copied();
+                    self.pos += 1;
+                    match escaped {
+                        Some(b'"') => s.push('"'),
+                        Some(b'\\') => s.push('\\'),
+                        Some(b'/') => s.push('/'),
+                        Some(b'n') => s.push('\n'),
+                        Some(b't') => s.push('\t'),
+                        Some(b'r') => s.push('\r'),
+                        Some(b'u') => {
+                            let c = self
+                                .input
+                                .get(self.pos..self.pos + 4)
+                                .and_then(|digits| std::str::from_utf8(digits).ok())
+                                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                                .and_then(char::from_u32)
+                                .ok_or(DebugJsonError::Syntax(start))?;
+                            s.push(c);
+                            self.pos += 4;
+                        }
+                        _ => return Err(DebugJsonError::Syntax(start)),
+                    }
+                }
+                _ => {
+                    // the bytes of a multi-byte char are pushed once the char is complete
+                    let len = match byte {
+                        0x00..=0x7f => 1,
+                        0xc0..=0xdf => 2,
+                        0xe0..=0xef => 3,
+                        _ => 4,
+                    };
+                    let end = start + len;
+                    let c = self
+                        .input
+                        .get(start..end)
+                        .and_then(|bytes| std::str::from_utf8(bytes).ok())
+                        .ok_or(DebugJsonError::Syntax(start))?;
+                    s.push_str(c);
+                    self.pos = end;
+                }
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Parsed, DebugJsonError> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b']') {
+            self.pos += 1;
+            return Ok(Parsed::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Array(items));
+                }
+                _ => return Err(DebugJsonError::Syntax(self.pos)),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Parsed, DebugJsonError> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.input.get(self.pos) == Some(&b'}') {
+            self.pos += 1;
+            return Ok(Parsed::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.input.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Parsed::Object(fields));
+                }
+                _ => return Err(DebugJsonError::Syntax(self.pos)),
+            }
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> Json {
+    Json::String(format!("0x{}", hex::encode(bytes)))
+}
+
+fn optional<T>(value: Option<T>, f: impl FnOnce(T) -> Json) -> Json {
+    value.map_or(Json::Null, f)
+}
+
+fn candidates_json(candidates: &Candidates) -> Json {
+    let kind = |kind| match kind {
+        CandidateKind::Host => "host",
+        CandidateKind::Reflexive => "reflexive",
+        CandidateKind::Relayed => "relayed",
+    };
+    Json::Array(
+        candidates
+            .iter()
+            .map(|candidate| {
+                Json::Object(vec![
+                    ("kind", Json::String(kind(candidate.kind).to_string())),
+                    ("priority", Json::Number(candidate.priority.into())),
+                    ("addr", Json::String(candidate.addr.to_string())),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn extensions_json(extensions: &Extensions) -> Json {
+    Json::Array(
+        extensions
+            .iter()
+            .map(|ext| {
+                Json::Object(vec![
+                    ("type", Json::Number(ext.ty.into())),
+                    ("data", hex(&ext.data)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// The fields of a parsed JSON object.
+struct Fields<'a>(&'a [(String, Parsed)]);
+
+impl<'a> Fields<'a> {
+    fn of(value: &'a Parsed, name: &'static str) -> Result<Self, DebugJsonError> {
+        match value {
+            Parsed::Object(fields) => Ok(Fields(fields)),
+            _ => Err(DebugJsonError::Field(name)),
+        }
+    }
+
+    fn get(&self, name: &'static str) -> Result<&'a Parsed, DebugJsonError> {
+        self.0
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or(DebugJsonError::Field(name))
+    }
+
+    /// The field, `None` if it is null.
+    fn optional(&self, name: &'static str) -> Result<Option<&'a Parsed>, DebugJsonError> {
+        match self.get(name)? {
+            Parsed::Null => Ok(None),
+            value => Ok(Some(value)),
+        }
+    }
+
+    fn str(&self, name: &'static str) -> Result<&'a str, DebugJsonError> {
+        as_str(self.get(name)?, name)
+    }
+
+    fn number(&self, name: &'static str) -> Result<u64, DebugJsonError> {
+        as_number(self.get(name)?, name)
+    }
+
+    fn bool(&self, name: &'static str) -> Result<bool, DebugJsonError> {
+        match self.get(name)? {
+            Parsed::Bool(b) => Ok(*b),
+            _ => Err(DebugJsonError::Field(name)),
+        }
+    }
+
+    fn bytes<const L: usize>(&self, name: &'static str) -> Result<[u8; L], DebugJsonError> {
+        as_bytes(self.get(name)?, name)
+    }
+
+    fn optional_bytes<const L: usize>(
+        &self,
+        name: &'static str,
+    ) -> Result<Option<[u8; L]>, DebugJsonError> {
+        self.optional(name)?
+            .map(|value| as_bytes(value, name))
+            .transpose()
+    }
+
+    fn parse<T: std::str::FromStr>(&self, name: &'static str) -> Result<T, DebugJsonError> {
+        self.str(name)?
+            .parse()
+            .map_err(|_| DebugJsonError::Value(name))
+    }
+
+    fn enr(&self, name: &'static str) -> Result<Enr, DebugJsonError> {
+        self.parse(name)
+    }
+
+    fn node_id(&self, name: &'static str) -> Result<NodeId, DebugJsonError> {
+        self.bytes(name).map(|raw| NodeId::new(&raw))
+    }
+
+    fn candidates(&self) -> Result<Option<Candidates>, DebugJsonError> {
+        let Some(value) = self.optional("candidates")? else {
+            return Ok(None);
+        };
+        let Parsed::Array(items) = value else {
+            return Err(DebugJsonError::Field("candidates"));
+        };
+        let candidates = items
+            .iter()
+            .map(|item| {
+                let fields = Fields::of(item, "candidates")?;
+                let kind = match fields.str("kind")? {
+                    "host" => CandidateKind::Host,
+                    "reflexive" => CandidateKind::Reflexive,
+                    "relayed" => CandidateKind::Relayed,
+                    _ => return Err(DebugJsonError::Value("kind")),
+                };
+                let priority = u32::try_from(fields.number("priority")?)
+                    .map_err(|_| DebugJsonError::Value("priority"))?;
+                Ok(Candidate {
+                    kind,
+                    priority,
+                    addr: fields.parse("addr")?,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(Candidates::new(candidates)))
+    }
+
+    fn extensions(&self) -> Result<Option<Extensions>, DebugJsonError> {
+        let Some(value) = self.optional("extensions")? else {
+            return Ok(None);
+        };
+        let Parsed::Array(items) = value else {
+            return Err(DebugJsonError::Field("extensions"));
+        };
+        let mut extensions = Extensions::default();
+        for item in items {
+            let fields = Fields::of(item, "extensions")?;
+            let ty =
+                u16::try_from(fields.number("type")?).map_err(|_| DebugJsonError::Value("type"))?;
+            extensions.insert_raw(ty, as_hex(fields.get("data")?, "data")?);
+        }
+        Ok(Some(extensions))
+    }
+}
+
+fn as_str<'a>(value: &'a Parsed, name: &'static str) -> Result<&'a str, DebugJsonError> {
+    match value {
+        Parsed::String(s) => Ok(s),
+        _ => Err(DebugJsonError::Field(name)),
+    }
+}
+
+fn as_number(value: &Parsed, name: &'static str) -> Result<u64, DebugJsonError> {
+    match value {
+        Parsed::Number(n) => Ok(*n),
+        _ => Err(DebugJsonError::Field(name)),
+    }
+}
+
+fn as_hex(value: &Parsed, name: &'static str) -> Result<Vec<u8>, DebugJsonError> {
+    as_str(value, name)?
+        .strip_prefix("0x")
+        .and_then(|digits| hex::decode(digits).ok())
+        .ok_or(DebugJsonError::Value(name))
+}
+
+fn as_bytes<const L: usize>(value: &Parsed, name: &'static str) -> Result<[u8; L], DebugJsonError> {
+    as_hex(value, name)?
+        .try_into()
+        .map_err(|_| DebugJsonError::Value(name))
+}
+
+impl<const N: usize, const I: usize> Notification<N, I> {
+    /// A canonical, human readable JSON form of the notification, for sharing encodings in spec
+    /// discussions and bug reports. Byte fields are 0x-prefixed hex, ENRs are their base64 text
+    /// form and absent optional fields are null. See [`Self::from_debug_json`].
+    pub fn to_debug_json(&self) -> String {
+        let ty = |ty: NotificationType| ("type", Json::String(ty.to_string()));
+        let attempt_id = |id: &Option<AttemptId>| optional(id.as_ref(), |id| hex(id));
+        let candidates = |c: &Option<Candidates>| optional(c.as_ref(), candidates_json);
+        let extensions = |e: &Option<Extensions>| optional(e.as_ref(), extensions_json);
+        let json = match self {
+            Notification::RelayInit(RelayInit(initiator, target, nonce, id, c, e)) => vec![
+                ty(NotificationType::RelayInit),
+                ("initiator", Json::String(initiator.to_base64())),
+                ("target", hex(&target.raw())),
+                ("nonce", hex(nonce)),
+                ("attempt_id", attempt_id(id)),
+                ("candidates", candidates(c)),
+                ("extensions", extensions(e)),
+            ],
+            Notification::RelayMsg(RelayMsg(initiator, nonce, id, c, observed, relay, e)) => {
+                vec![
+                    ty(NotificationType::RelayMsg),
+                    ("initiator", Json::String(initiator.to_base64())),
+                    ("nonce", hex(nonce)),
+                    ("attempt_id", attempt_id(id)),
+                    ("candidates", candidates(c)),
+                    (
+                        "observed",
+                        optional(*observed, |addr| Json::String(addr.to_string())),
+                    ),
+                    ("relay_id", optional(*relay, |relay| hex(&relay.raw()))),
+                    ("extensions", extensions(e)),
+                ]
+            }
+            Notification::RelayAbort(RelayAbort(nonce, reason, e)) => vec![
+                ty(NotificationType::RelayAbort),
+                ("nonce", hex(nonce)),
+                ("reason", Json::String(reason.to_string())),
+                ("extensions", extensions(e)),
+            ],
+            Notification::PunchReport(PunchReport(nonce, punched, id, e)) => vec![
+                ty(NotificationType::PunchReport),
+                ("nonce", hex(nonce)),
+                ("punched", Json::Bool(*punched)),
+                ("attempt_id", attempt_id(id)),
+                ("extensions", extensions(e)),
+            ],
+            Notification::EchoAfter(EchoAfter(sender, nonce, delay, e)) => vec![
+                ty(NotificationType::EchoAfter),
+                ("sender", hex(&sender.raw())),
+                ("nonce", hex(nonce)),
+                ("delay_ms", Json::Number(delay.as_millis() as u64)),
+                ("extensions", extensions(e)),
+            ],
+            Notification::Echo(Echo(nonce, e)) => vec![
+                ty(NotificationType::Echo),
+                ("nonce", hex(nonce)),
+                ("extensions", extensions(e)),
+            ],
+        };
+        Json::Object(json).to_string()
+    }
+
+    /// Reconstructs a notification from the form of [`Self::to_debug_json`].
+    pub fn from_debug_json(json: &str) -> Result<Self, DebugJsonError> {
+        let parsed = Parser::parse(json)?;
+        let fields = Fields::of(&parsed, "notification")?;
+        let nonce = || fields.bytes::<N>("nonce");
+        let notif = match fields.str("type")? {
+            "RelayInit" => Notification::RelayInit(RelayInit(
+                fields.enr("initiator")?,
+                fields.node_id("target")?,
+                nonce()?,
+                fields.optional_bytes("attempt_id")?,
+                fields.candidates()?,
+                fields.extensions()?,
+            )),
+            "RelayMsg" => Notification::RelayMsg(RelayMsg(
+                fields.enr("initiator")?,
+                nonce()?,
+                fields.optional_bytes("attempt_id")?,
+                fields.candidates()?,
+                fields
+                    .optional("observed")?
+                    .map(|value| {
+                        as_str(value, "observed")?
+                            .parse::<SocketAddr>()
+                            .map_err(|_| DebugJsonError::Value("observed"))
+                    })
+                    .transpose()?,
+                fields
+                    .optional_bytes("relay_id")?
+                    .map(|raw| NodeId::new(&raw)),
+                fields.extensions()?,
+            )),
+            "RelayAbort" => {
+                let reason = fields.str("reason")?;
+                let reason = (1..=u8::MAX)
+                    .filter_map(|code| ReasonCode::try_from(code).ok())
+                    .find(|code| code.to_string() == reason)
+                    .ok_or(DebugJsonError::Value("reason"))?;
+                Notification::RelayAbort(RelayAbort(nonce()?, reason, fields.extensions()?))
+            }
+            "PunchReport" => Notification::PunchReport(PunchReport(
+                nonce()?,
+                fields.bool("punched")?,
+                fields.optional_bytes("attempt_id")?,
+                fields.extensions()?,
+            )),
+            "EchoAfter" => Notification::EchoAfter(EchoAfter(
+                fields.node_id("sender")?,
+                nonce()?,
+                Duration::from_millis(fields.number("delay_ms")?),
+                fields.extensions()?,
+            )),
+            "Echo" => Notification::Echo(Echo(nonce()?, fields.extensions()?)),
+            other => return Err(DebugJsonError::UnknownType(other.to_string())),
+        };
+        Ok(notif)
+    }
+}
+
+impl NodeAddress {
+    /// A canonical, human readable JSON form of the address, see
+    /// [`Notification::to_debug_json`].
+    pub fn to_debug_json(&self) -> String {
+        Json::Object(vec![
+            ("socket_addr", Json::String(self.socket_addr.to_string())),
+            ("node_id", hex(&self.node_id.raw())),
+        ])
+        .to_string()
+    }
+
+    /// Reconstructs an address from the form of [`Self::to_debug_json`].
+    pub fn from_debug_json(json: &str) -> Result<Self, DebugJsonError> {
+        let parsed = Parser::parse(json)?;
+        let fields = Fields::of(&parsed, "node address")?;
+        Ok(NodeAddress::new(
+            fields.parse("socket_addr")?,
+            fields.node_id("node_id")?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MessageNonce, ProtocolVersion, ATTEMPT_ID_LENGTH, MESSAGE_NONCE_LENGTH};
+    use enr::{CombinedKey, EnrBuilder};
+
+    #[test]
+    fn test_debug_json_round_trip() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .build(&key)
+            .unwrap();
+        let nonce: MessageNonce = [3; MESSAGE_NONCE_LENGTH];
+        let addr: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let mut extensions = Extensions::default();
+        extensions.insert(&ProtocolVersion::CURRENT);
+        let notifs: Vec<Notification> = vec![
+            RelayInit::builder(enr.clone(), NodeId::random(), nonce)
+                .with_attempt_id([7; ATTEMPT_ID_LENGTH])
+                .with_candidates(Candidates::new(vec![Candidate::new(
+                    CandidateKind::Reflexive,
+                    addr,
+                )]))
+                .with_extension(&ProtocolVersion::CURRENT)
+                .build()
+                .into(),
+            RelayMsg::builder(enr, nonce)
+                .with_observed("[2001:db8::1]:9000".parse().unwrap())
+                .build()
+                .into(),
+            RelayAbort(nonce, ReasonCode::AddressFamilyMismatch, None).into(),
+            PunchReport(nonce, true, None, Some(extensions)).into(),
+            EchoAfter(NodeId::random(), nonce, Duration::from_secs(30), None).into(),
+            Echo(nonce, None).into(),
+        ];
+        for notif in notifs {
+            let json = notif.to_debug_json();
+            assert_eq!(Ok(notif), Notification::from_debug_json(&json), "{json}");
+        }
+
+        let abort = RelayAbort([1; MESSAGE_NONCE_LENGTH], ReasonCode::Busy, None);
+        let json = Notification::<MESSAGE_NONCE_LENGTH>::RelayAbort(abort).to_debug_json();
+        assert_eq!(
+            r#"{"type":"RelayAbort","nonce":"0x010101010101010101010101","reason":"busy","extensions":null}"#,
+            json
+        );
+
+        let node = NodeAddress::new(addr, NodeId::random());
+        assert_eq!(
+            Ok(node),
+            NodeAddress::from_debug_json(&node.to_debug_json())
+        );
+        assert!(matches!(
+            NodeAddress::from_debug_json(r#"{"socket_addr": "192.0.2.1:9000"}"#),
+            Err(DebugJsonError::Field("node_id"))
+        ));
+    }
+
+    #[test]
+    fn test_debug_json_nesting_bounded() {
+        let nested = "[".repeat(100_000);
+        assert!(matches!(
+            Notification::<MESSAGE_NONCE_LENGTH>::from_debug_json(&nested),
+            Err(DebugJsonError::Syntax(MAX_DEPTH))
+        ));
+        assert!(matches!(
+            NodeAddress::from_debug_json(&"{\"a\":".repeat(100_000)),
+            Err(DebugJsonError::Syntax(_))
+        ));
+    }
+}
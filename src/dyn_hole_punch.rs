@@ -0,0 +1,105 @@
+use crate::{
+    BoxError, Enr, HolePunchError, MessageNonce, NatHolePunch, NodeAddress, NodeId, Notification,
+};
+use futures::{future::BoxFuture, FutureExt};
+use std::net::SocketAddr;
+
+/// An object safe facade of [`NatHolePunch`], for holding the hole punch implementation as a
+/// `Box<dyn DynHolePunch>` and for mocking it in unit tests, see
+/// [`crate::test_utils::MockHolePunch`]. Sessions are indexed by [`NodeAddress`] like in discv5
+/// and errors are boxed. Implemented for any [`NatHolePunch`] indexing sessions by
+/// [`NodeAddress`].
+pub trait DynHolePunch: Send {
+    /// See [`NatHolePunch::on_request_time_out`].
+    fn on_request_time_out(
+        &mut self,
+        relay: NodeAddress,
+        local_enr: Enr,
+        timed_out_message_nonce: MessageNonce,
+        target_session_index: NodeAddress,
+    ) -> BoxFuture<'_, Result<(), HolePunchError>>;
+    /// See [`NatHolePunch::initiate_punch`].
+    fn initiate_punch(
+        &mut self,
+        target: NodeId,
+        via: NodeAddress,
+    ) -> BoxFuture<'_, Result<MessageNonce, HolePunchError>>;
+    /// See [`NatHolePunch::on_notification`].
+    fn on_notification<'a>(
+        &'a mut self,
+        decrypted_notif: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), HolePunchError>>;
+    /// See [`NatHolePunch::handle_notification`].
+    fn handle_notification<'a>(
+        &'a mut self,
+        decrypted_notif: &'a [u8],
+        inspect: Box<dyn FnOnce(&Notification) + Send + 'a>,
+    ) -> BoxFuture<'a, Result<(), HolePunchError>>;
+    /// See [`NatHolePunch::on_hole_punch_expired`].
+    fn on_hole_punch_expired(
+        &mut self,
+        dst: SocketAddr,
+    ) -> BoxFuture<'_, Result<(), HolePunchError>>;
+}
+
+impl<T> DynHolePunch for T
+where
+    T: NatHolePunch<SessionIndex = NodeAddress> + Send,
+    T::Discv5Error: Into<BoxError>,
+{
+    fn on_request_time_out(
+        &mut self,
+        relay: NodeAddress,
+        local_enr: Enr,
+        timed_out_message_nonce: MessageNonce,
+        target_session_index: NodeAddress,
+    ) -> BoxFuture<'_, Result<(), HolePunchError>> {
+        NatHolePunch::on_request_time_out(
+            self,
+            relay,
+            local_enr,
+            timed_out_message_nonce,
+            target_session_index,
+        )
+        .map(|res| res.map_err(HolePunchError::into_boxed))
+        .boxed()
+    }
+
+    fn initiate_punch(
+        &mut self,
+        target: NodeId,
+        via: NodeAddress,
+    ) -> BoxFuture<'_, Result<MessageNonce, HolePunchError>> {
+        NatHolePunch::initiate_punch(self, target, via)
+            .map(|res| res.map_err(HolePunchError::into_boxed))
+            .boxed()
+    }
+
+    fn on_notification<'a>(
+        &'a mut self,
+        decrypted_notif: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), HolePunchError>> {
+        NatHolePunch::on_notification(self, decrypted_notif)
+            .map(|res| res.map_err(HolePunchError::into_boxed))
+            .boxed()
+    }
+
+    fn handle_notification<'a>(
+        &'a mut self,
+        decrypted_notif: &'a [u8],
+        inspect: Box<dyn FnOnce(&Notification) + Send + 'a>,
+    ) -> BoxFuture<'a, Result<(), HolePunchError>> {
+        NatHolePunch::handle_notification(self, decrypted_notif, inspect)
+            .map(|res| res.map_err(HolePunchError::into_boxed))
+            .boxed()
+    }
+
+    fn on_hole_punch_expired(
+        &mut self,
+        dst: SocketAddr,
+    ) -> BoxFuture<'_, Result<(), HolePunchError>> {
+        NatHolePunch::on_hole_punch_expired(self, dst)
+            .map(|res| res.map_err(HolePunchError::into_boxed))
+            .boxed()
+    }
+}
@@ -1,15 +1,236 @@
-use rlp::DecoderError;
-use std::fmt::{Debug, Display};
+use crate::{rlp_backend::DecoderError, NodeId, ReasonCode, ValidationError};
+use std::{
+    error::Error as StdError,
+    fmt::{Debug, Display},
+};
 use thiserror::Error;
 
+/// A type erased discv5 error.
+pub type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// Hole punching errors. Without a type parameter the discv5 error is boxed, so errors of
+/// different discv5 error types can be stored uniformly, see [`HolePunchError::into_boxed`].
 #[derive(Debug, Error)]
-pub enum HolePunchError<Discv5Error: Debug + Display> {
+pub enum HolePunchError<Discv5Error: Debug + Display = BoxError> {
     #[error("error parsing notification, {0}")]
     NotificationError(#[from] DecoderError),
+    #[error("invalid notification, {0}")]
+    Invalid(#[from] ValidationError),
     #[error("failed initiating a hole punch attempt, {0}")]
     InitiatorError(Discv5Error),
     #[error("failed relaying a hole punch attempt, {0}")]
     RelayError(Discv5Error),
     #[error("failed as target of a hole punch attempt, {0}")]
     TargetError(Discv5Error),
+    #[error("failed keeping a punched hole open, {0}")]
+    KeepaliveError(Discv5Error),
+    #[error("no relay to initiate a hole punch attempt through")]
+    NoRelay,
+    #[error("relay has no session with the hole punch target {0}")]
+    UnknownTarget(NodeId),
+    #[error("initiator ENR has no UDP socket to punch a hole towards")]
+    InitiatorUnreachable,
+    #[error("notification rate limit exceeded")]
+    RateLimited,
+    #[error("notification is a replay")]
+    Replayed,
+    #[error("relay queue is full")]
+    RelayQueueFull,
+    #[error("relay is overloaded, aborted hole punch attempt")]
+    Overloaded,
+    #[error("peer {0} doesn't support hole punching")]
+    Unsupported(NodeId),
+    #[error("peer aborted hole punch attempt, {0}")]
+    Aborted(ReasonCode),
+    #[error("notification rejected by an inbound filter")]
+    Filtered,
+    #[error("hole punch attempt throttled locally")]
+    LocallyThrottled,
+    #[error("punch policy declined target {0}")]
+    Declined(NodeId),
+    #[error("encoded notification of {0} bytes doesn't fit in a packet")]
+    PacketTooLarge(usize),
+    #[error("initiator and target {0} share no address family")]
+    AddressFamilyMismatch(NodeId),
+    #[error("failed answering or sending an echo probe, {0}")]
+    EchoError(Discv5Error),
+    #[error("echo probe refused, delay too long or too many pending")]
+    EchoRefused,
+    #[error("{0} isn't implemented")]
+    NotImplemented(&'static str),
+}
+
+/// Errors of this node as initiator of a hole punch attempt.
+#[derive(Debug, Error)]
+pub enum InitiatorError<Discv5Error: Debug + Display = BoxError> {
+    #[error("no relay to initiate a hole punch attempt through")]
+    NoRelay,
+    #[error("peer {0} doesn't support hole punching")]
+    Unsupported(NodeId),
+    #[error("hole punch attempt throttled locally")]
+    LocallyThrottled,
+    #[error("punch policy declined target {0}")]
+    Declined(NodeId),
+    #[error("encoded relay init of {0} bytes doesn't fit in a packet")]
+    PacketTooLarge(usize),
+    #[error("failed sending relay init to the relay, {0}")]
+    RelayInitSendFailed(Discv5Error),
+}
+
+/// Errors of this node as relay of a hole punch attempt.
+#[derive(Debug, Error)]
+pub enum RelayError<Discv5Error: Debug + Display = BoxError> {
+    #[error("relay has no session with the hole punch target {0}")]
+    UnknownTarget(NodeId),
+    #[error("target {0} doesn't support hole punching")]
+    Unsupported(NodeId),
+    #[error("initiator exceeded the notification rate limit")]
+    RateLimited,
+    #[error("relay init is a replay")]
+    Replayed,
+    #[error("relay queue is full")]
+    QueueFull,
+    #[error("relay is overloaded, aborted hole punch attempt")]
+    Overloaded,
+    #[error("encoded relay message of {0} bytes doesn't fit in a packet")]
+    PacketTooLarge(usize),
+    #[error("initiator and target {0} share no address family")]
+    AddressFamilyMismatch(NodeId),
+    #[error("application refused to relay the attempt")]
+    ConsentDenied,
+    #[error("failed sending relay message to the target, {0}")]
+    RelayMsgSendFailed(Discv5Error),
+}
+
+/// Errors of this node as target of a hole punch attempt.
+#[derive(Debug, Error)]
+pub enum TargetError<Discv5Error: Debug + Display = BoxError> {
+    #[error("relay exceeded the notification rate limit")]
+    RateLimited,
+    #[error("initiator ENR has no UDP socket to punch a hole towards")]
+    InitiatorUnreachable,
+    #[error("failed sending WHOAREYOU to the initiator, {0}")]
+    WhoAreYouSendFailed(Discv5Error),
+}
+
+impl<E: Debug + Display> From<InitiatorError<E>> for HolePunchError<E> {
+    fn from(e: InitiatorError<E>) -> Self {
+        match e {
+            InitiatorError::NoRelay => HolePunchError::NoRelay,
+            InitiatorError::Unsupported(node_id) => HolePunchError::Unsupported(node_id),
+            InitiatorError::LocallyThrottled => HolePunchError::LocallyThrottled,
+            InitiatorError::Declined(node_id) => HolePunchError::Declined(node_id),
+            InitiatorError::PacketTooLarge(len) => HolePunchError::PacketTooLarge(len),
+            InitiatorError::RelayInitSendFailed(e) => HolePunchError::InitiatorError(e),
+        }
+    }
+}
+
+impl<E: Debug + Display> From<RelayError<E>> for HolePunchError<E> {
+    fn from(e: RelayError<E>) -> Self {
+        match e {
+            RelayError::UnknownTarget(node_id) => HolePunchError::UnknownTarget(node_id),
+            RelayError::Unsupported(node_id) => HolePunchError::Unsupported(node_id),
+            RelayError::RateLimited => HolePunchError::RateLimited,
+            RelayError::Replayed => HolePunchError::Replayed,
+            RelayError::QueueFull => HolePunchError::RelayQueueFull,
+            RelayError::Overloaded => HolePunchError::Overloaded,
+            RelayError::PacketTooLarge(len) => HolePunchError::PacketTooLarge(len),
+            RelayError::AddressFamilyMismatch(node_id) => {
+                HolePunchError::AddressFamilyMismatch(node_id)
+            }
+            RelayError::ConsentDenied => HolePunchError::Filtered,
+            RelayError::RelayMsgSendFailed(e) => HolePunchError::RelayError(e),
+        }
+    }
+}
+
+impl<E: Debug + Display> From<TargetError<E>> for HolePunchError<E> {
+    fn from(e: TargetError<E>) -> Self {
+        match e {
+            TargetError::RateLimited => HolePunchError::RateLimited,
+            TargetError::InitiatorUnreachable => HolePunchError::InitiatorUnreachable,
+            TargetError::WhoAreYouSendFailed(e) => HolePunchError::TargetError(e),
+        }
+    }
+}
+
+impl<E: Debug + Display> HolePunchError<E> {
+    /// Maps the discv5 error, if any, with `f`.
+    pub fn map_discv5_error<E2: Debug + Display>(
+        self,
+        f: impl FnOnce(E) -> E2,
+    ) -> HolePunchError<E2> {
+        match self {
+            HolePunchError::NotificationError(e) => HolePunchError::NotificationError(e),
+            HolePunchError::Invalid(e) => HolePunchError::Invalid(e),
+            HolePunchError::InitiatorError(e) => HolePunchError::InitiatorError(f(e)),
+            HolePunchError::RelayError(e) => HolePunchError::RelayError(f(e)),
+            HolePunchError::TargetError(e) => HolePunchError::TargetError(f(e)),
+            HolePunchError::KeepaliveError(e) => HolePunchError::KeepaliveError(f(e)),
+            HolePunchError::NoRelay => HolePunchError::NoRelay,
+            HolePunchError::UnknownTarget(node_id) => HolePunchError::UnknownTarget(node_id),
+            HolePunchError::InitiatorUnreachable => HolePunchError::InitiatorUnreachable,
+            HolePunchError::RateLimited => HolePunchError::RateLimited,
+            HolePunchError::Replayed => HolePunchError::Replayed,
+            HolePunchError::RelayQueueFull => HolePunchError::RelayQueueFull,
+            HolePunchError::Overloaded => HolePunchError::Overloaded,
+            HolePunchError::Unsupported(node_id) => HolePunchError::Unsupported(node_id),
+            HolePunchError::Aborted(code) => HolePunchError::Aborted(code),
+            HolePunchError::Filtered => HolePunchError::Filtered,
+            HolePunchError::LocallyThrottled => HolePunchError::LocallyThrottled,
+            HolePunchError::Declined(node_id) => HolePunchError::Declined(node_id),
+            HolePunchError::PacketTooLarge(len) => HolePunchError::PacketTooLarge(len),
+            HolePunchError::AddressFamilyMismatch(node_id) => {
+                HolePunchError::AddressFamilyMismatch(node_id)
+            }
+            HolePunchError::EchoError(e) => HolePunchError::EchoError(f(e)),
+            HolePunchError::EchoRefused => HolePunchError::EchoRefused,
+            HolePunchError::NotImplemented(hook) => HolePunchError::NotImplemented(hook),
+        }
+    }
+
+    /// Boxes the discv5 error, erasing its type.
+    pub fn into_boxed(self) -> HolePunchError
+    where
+        E: Into<BoxError>,
+    {
+        self.map_discv5_error(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_boxed() {
+        let errors: Vec<HolePunchError> = vec![
+            HolePunchError::<String>::RelayError("session dropped".to_string()).into_boxed(),
+            HolePunchError::<std::io::Error>::KeepaliveError(std::io::ErrorKind::Other.into())
+                .into_boxed(),
+        ];
+
+        assert_eq!(
+            "failed relaying a hole punch attempt, session dropped",
+            errors[0].to_string()
+        );
+        assert!(matches!(errors[1], HolePunchError::KeepaliveError(_)));
+    }
+
+    #[test]
+    fn test_role_errors_into_hole_punch_error() {
+        let target = NodeId::random();
+        let errors: Vec<HolePunchError<String>> = vec![
+            InitiatorError::RelayInitSendFailed("session dropped".to_string()).into(),
+            RelayError::UnknownTarget(target).into(),
+            RelayError::QueueFull.into(),
+            TargetError::WhoAreYouSendFailed("socket closed".to_string()).into(),
+        ];
+
+        assert!(matches!(&errors[0], HolePunchError::InitiatorError(e) if e == "session dropped"));
+        assert!(matches!(errors[1], HolePunchError::UnknownTarget(id) if id == target));
+        assert!(matches!(errors[2], HolePunchError::RelayQueueFull));
+        assert!(matches!(&errors[3], HolePunchError::TargetError(e) if e == "socket closed"));
+    }
 }
@@ -0,0 +1,161 @@
+use crate::{is_cgnat, NatStatus};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+};
+
+/// An IP address assigned to a local network interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceAddr {
+    /// The name of the interface, for example `eth0`.
+    pub name: String,
+    pub ip: IpAddr,
+}
+
+/// What the local network interfaces tell about the IP the local node is observed at. Unlike the
+/// bind probe, tells which interface has the IP, which may be a secondary one that the traffic
+/// of the node doesn't go out over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceReport {
+    /// The interface the observed IP is assigned to, if any.
+    pub interface: Option<String>,
+    /// The interface holding the address the traffic of the node leaves from, if known.
+    pub egress_interface: Option<String>,
+    /// Whether the observed IP, or a local non-loopback IPv4 address, is in the shared address
+    /// space of carrier-grade NAT, see [`is_cgnat`].
+    pub cgnat: bool,
+    /// The non-loopback addresses of the local interfaces.
+    pub local_addrs: Vec<InterfaceAddr>,
+}
+
+impl InterfaceReport {
+    /// Matches `observed_ip`, and the address the traffic of the node leaves from if known,
+    /// against the given interface addresses.
+    pub fn new(observed_ip: IpAddr, egress_ip: Option<IpAddr>, addrs: Vec<InterfaceAddr>) -> Self {
+        let interface_of = |ip: IpAddr| {
+            addrs
+                .iter()
+                .find(|addr| addr.ip == ip)
+                .map(|addr| addr.name.clone())
+        };
+        let interface = interface_of(observed_ip);
+        let egress_interface = egress_ip.and_then(interface_of);
+        let local_addrs = addrs
+            .into_iter()
+            .filter(|addr| !addr.ip.is_loopback())
+            .collect::<Vec<_>>();
+        let cgnat = is_cgnat(&observed_ip) || local_addrs.iter().any(|addr| is_cgnat(&addr.ip));
+        InterfaceReport {
+            interface,
+            egress_interface,
+            cgnat,
+            local_addrs,
+        }
+    }
+
+    /// Returns true if the observed IP is assigned to a local interface.
+    pub fn observed_is_local(&self) -> bool {
+        self.interface.is_some()
+    }
+
+    /// The NAT status the interfaces point to. A node without the observed IP on any interface is
+    /// behind NAT, behind carrier-grade NAT if its addresses are in the shared address space. A
+    /// node with the observed IP on another interface than the one its traffic leaves through
+    /// is reported as such, unless the egress interface is unknown.
+    pub fn nat_status(&self) -> NatStatus {
+        match (&self.interface, &self.egress_interface) {
+            (Some(interface), Some(egress)) if interface != egress => NatStatus::SecondaryInterface,
+            (Some(_), _) => NatStatus::Public,
+            (None, _) if self.cgnat => NatStatus::BehindCgnat,
+            (None, _) => NatStatus::BehindNat,
+        }
+    }
+}
+
+/// Enumerates the local network interfaces and matches `observed_ip` against their addresses.
+/// `local_ip` is the IP the socket of the node is bound to. If it's unspecified, the address
+/// the traffic leaves from is looked up, see [`egress_ip`].
+pub fn probe_interfaces(observed_ip: IpAddr, local_ip: IpAddr) -> io::Result<InterfaceReport> {
+    let egress = if local_ip.is_unspecified() {
+        egress_ip(observed_ip.is_ipv6()).ok()
+    } else {
+        Some(local_ip)
+    };
+    local_addrs().map(|addrs| InterfaceReport::new(observed_ip, egress, addrs))
+}
+
+/// The local IP the traffic towards the internet leaves from, as the routing table picks it for
+/// a connected UDP socket. Sends nothing.
+pub fn egress_ip(ipv6: bool) -> io::Result<IpAddr> {
+    let (bind, dst) = if ipv6 {
+        (
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+            SocketAddr::from((Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1), 9)),
+        )
+    } else {
+        (
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::from((Ipv4Addr::new(198, 51, 100, 1), 9)),
+        )
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(dst)?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// The IP addresses assigned to the local network interfaces, loopback included.
+pub fn local_addrs() -> io::Result<Vec<InterfaceAddr>> {
+    Ok(if_addrs::get_if_addrs()?
+        .into_iter()
+        .map(|iface| InterfaceAddr {
+            ip: iface.ip(),
+            name: iface.name,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_report() {
+        let loopback: IpAddr = "127.0.0.1".parse().unwrap();
+        let report = probe_interfaces(loopback, loopback).unwrap();
+        assert!(report.observed_is_local());
+        assert_eq!(NatStatus::Public, report.nat_status());
+        assert!(report.local_addrs.iter().all(|addr| !addr.ip.is_loopback()));
+
+        let addrs = vec![
+            InterfaceAddr {
+                name: "lo".to_string(),
+                ip: loopback,
+            },
+            InterfaceAddr {
+                name: "wwan0".to_string(),
+                ip: "100.72.1.5".parse().unwrap(),
+            },
+            InterfaceAddr {
+                name: "eth1".to_string(),
+                ip: "198.51.100.7".parse().unwrap(),
+            },
+        ];
+        let observed: IpAddr = "198.51.100.7".parse().unwrap();
+        // the observed IP is on a secondary interface, traffic leaves over the cellular one
+        let report = InterfaceReport::new(observed, Some(addrs[1].ip), addrs.clone());
+        assert_eq!(Some("eth1"), report.interface.as_deref());
+        assert_eq!(Some("wwan0"), report.egress_interface.as_deref());
+        assert_eq!(NatStatus::SecondaryInterface, report.nat_status());
+        assert_eq!(2, report.local_addrs.len());
+        // traffic leaves over the interface with the observed IP
+        let report = InterfaceReport::new(observed, Some(observed), addrs.clone());
+        assert_eq!(NatStatus::Public, report.nat_status());
+
+        let unobserved = "203.0.113.9".parse().unwrap();
+        let report = InterfaceReport::new(unobserved, None, addrs.clone());
+        assert!(report.cgnat);
+        assert_eq!(NatStatus::BehindCgnat, report.nat_status());
+        let report = InterfaceReport::new(unobserved, None, addrs[2..].to_vec());
+        assert_eq!(NatStatus::BehindNat, report.nat_status());
+    }
+}
@@ -0,0 +1,268 @@
+//! Differential decode testing against notification encodings produced by other discv5
+//! implementations.
+//!
+//! A case is a file with the extension `case` holding one field per line:
+//!
+//! ```text
+//! encoded <hex>
+//! verdict accept|reject
+//! type relay-init|relay-msg
+//! initiator <enr>
+//! target <node-id-hex>
+//! nonce <hex>
+//! ```
+//!
+//! Only `encoded` and `verdict` are required. The other fields are asserted if present and the
+//! verdict is `accept`.
+
+use crate::{Enr, Notification, RelayInit, RelayMsg, MESSAGE_NONCE_LENGTH};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// The file extension of cases.
+pub const CASE_EXTENSION: &str = "case";
+
+#[derive(Debug, Error)]
+pub enum InteropError {
+    #[error("failed reading cases, {0}")]
+    Io(#[from] io::Error),
+    #[error("malformed case {case} at line {line}, {reason}")]
+    Malformed {
+        case: String,
+        line: usize,
+        reason: &'static str,
+    },
+}
+
+/// The verdict expected of the decoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Accept,
+    Reject,
+}
+
+/// An externally produced encoding and what decoding it is expected to yield.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct InteropCase {
+    pub name: String,
+    pub encoded: Vec<u8>,
+    pub verdict: Option<Verdict>,
+    /// `relay-init` or `relay-msg`.
+    pub notif_type: Option<String>,
+    pub initiator: Option<Enr>,
+    pub target: Option<Vec<u8>>,
+    pub nonce: Option<Vec<u8>>,
+}
+
+impl InteropCase {
+    /// Parses a case named `name`.
+    pub fn parse(name: &str, s: &str) -> Result<Self, InteropError> {
+        let mut case = InteropCase {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        for (index, line) in s.lines().enumerate() {
+            let malformed = |reason| InteropError::Malformed {
+                case: name.to_string(),
+                line: index + 1,
+                reason,
+            };
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                [] => {}
+                ["encoded", hex] => {
+                    case.encoded = hex::decode(hex).map_err(|_| malformed("invalid hex"))?
+                }
+                ["verdict", "accept"] => case.verdict = Some(Verdict::Accept),
+                ["verdict", "reject"] => case.verdict = Some(Verdict::Reject),
+                ["type", notif_type @ ("relay-init" | "relay-msg")] => {
+                    case.notif_type = Some(notif_type.to_string())
+                }
+                ["initiator", enr] => {
+                    case.initiator = Some(enr.parse().map_err(|_| malformed("invalid enr"))?)
+                }
+                ["target", hex] => {
+                    case.target = Some(hex::decode(hex).map_err(|_| malformed("invalid hex"))?)
+                }
+                ["nonce", hex] => {
+                    case.nonce = Some(hex::decode(hex).map_err(|_| malformed("invalid hex"))?)
+                }
+                _ => return Err(malformed("unknown field")),
+            }
+        }
+        if case.verdict.is_none() {
+            return Err(InteropError::Malformed {
+                case: name.to_string(),
+                line: 0,
+                reason: "missing verdict",
+            });
+        }
+        Ok(case)
+    }
+
+    /// Decodes the encoding and compares the verdict and field values to the expected ones.
+    pub fn run(&self) -> CaseResult {
+        let decoded = Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&self.encoded);
+        let notif = match (self.verdict, decoded) {
+            (Some(Verdict::Reject), Err(_)) => return CaseResult::Pass,
+            (Some(Verdict::Reject), Ok(notif)) => {
+                return CaseResult::Fail(format!("accepted {notif}"))
+            }
+            (_, Err(e)) => return CaseResult::Fail(format!("rejected, {e}")),
+            (_, Ok(notif)) => notif,
+        };
+        let (notif_type, initiator, target, nonce) = match &notif {
+            Notification::RelayInit(RelayInit(initiator, target, nonce, ..)) => {
+                ("relay-init", initiator, Some(target.raw().to_vec()), nonce)
+            }
+            Notification::RelayMsg(RelayMsg(initiator, nonce, ..)) => {
+                ("relay-msg", initiator, None, nonce)
+            }
+            Notification::RelayAbort(_)
+            | Notification::PunchReport(_)
+            | Notification::EchoAfter(_)
+            | Notification::Echo(_) => return CaseResult::Fail(format!("unexpected {notif}")),
+        };
+        if let Some(expected) = &self.notif_type {
+            if expected != notif_type {
+                return CaseResult::Fail(format!("type {notif_type}, expected {expected}"));
+            }
+        }
+        if let Some(expected) = &self.initiator {
+            if expected != initiator {
+                return CaseResult::Fail(format!("initiator {initiator}, expected {expected}"));
+            }
+        }
+        if self.target.is_some() && self.target != target {
+            return CaseResult::Fail(format!(
+                "target 0x{}, expected 0x{}",
+                hex::encode(target.unwrap_or_default()),
+                hex::encode(self.target.as_deref().unwrap_or_default())
+            ));
+        }
+        if let Some(expected) = &self.nonce {
+            if expected.as_slice() != nonce.as_slice() {
+                return CaseResult::Fail(format!(
+                    "nonce 0x{}, expected 0x{}",
+                    hex::encode(nonce),
+                    hex::encode(expected)
+                ));
+            }
+        }
+        CaseResult::Pass
+    }
+}
+
+/// The result of a case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaseResult {
+    Pass,
+    /// Contains what diverged.
+    Fail(String),
+}
+
+/// The results of all cases of a directory, ordered by case name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InteropReport {
+    pub results: Vec<(String, CaseResult)>,
+}
+
+impl InteropReport {
+    /// Returns the names of the failed cases.
+    pub fn failed(&self) -> impl Iterator<Item = &str> {
+        self.results
+            .iter()
+            .filter_map(|(name, result)| match result {
+                CaseResult::Fail(_) => Some(name.as_str()),
+                CaseResult::Pass => None,
+            })
+    }
+
+    /// Returns true if all cases passed.
+    pub fn is_success(&self) -> bool {
+        self.failed().next().is_none()
+    }
+}
+
+/// One line per case, `<name> pass` or `<name> fail <divergence>`.
+impl fmt::Display for InteropReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, result) in self.results.iter() {
+            match result {
+                CaseResult::Pass => writeln!(f, "{name} pass")?,
+                CaseResult::Fail(divergence) => writeln!(f, "{name} fail {divergence}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Loads the cases in `dir`, ordered by name.
+pub fn load_cases(dir: impl AsRef<Path>) -> Result<Vec<InteropCase>, InteropError> {
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == CASE_EXTENSION));
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            InteropCase::parse(&name, &fs::read_to_string(&path)?)
+        })
+        .collect()
+}
+
+/// Runs the cases in `dir`.
+pub fn run_dir(dir: impl AsRef<Path>) -> Result<InteropReport, InteropError> {
+    let results = load_cases(dir)?
+        .into_iter()
+        .map(|case| {
+            let result = case.run();
+            (case.name, result)
+        })
+        .collect();
+    Ok(InteropReport { results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RELAY_MSG_ENCODED: &str = "08f893f884b8407098ad865b00a582051940cb9cf36836572411a47278783077011599ed5cd16b76f2635f4e234738f30813a89eb9137e3e3df5266e3a1f11df72ecf1145ccb9c01826964827634826970847f00000189736563703235366b31a103ca634cae0d49acb401d8a4c6b6fe8c55b70d115bf400769cc1400f3258cd31388375647082765f8c0102030405060708090a0b0c";
+
+    #[test]
+    fn test_run_dir() {
+        let dir = std::env::temp_dir().join(format!("interop-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a-relay-msg.case"),
+            format!(
+                "encoded {RELAY_MSG_ENCODED}\nverdict accept\ntype relay-msg\nnonce 0102030405060708090a0b0c\n"
+            ),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b-wrong-nonce.case"),
+            format!("encoded {RELAY_MSG_ENCODED}\nverdict accept\nnonce 01\n"),
+        )
+        .unwrap();
+        fs::write(dir.join("c-garbage.case"), "encoded ff00\nverdict reject\n").unwrap();
+        fs::write(dir.join("ignored.txt"), "").unwrap();
+
+        let report = run_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(3, report.results.len());
+        assert_eq!(vec!["b-wrong-nonce"], report.failed().collect::<Vec<_>>());
+        assert!(report
+            .to_string()
+            .starts_with("a-relay-msg pass\nb-wrong-nonce fail nonce"));
+    }
+}
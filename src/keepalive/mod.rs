@@ -0,0 +1,342 @@
+use crate::{clock::system_clock, SharedClock, DEFAULT_HOLE_PUNCH_LIFETIME};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+mod policy;
+mod sender;
+
+pub use policy::{
+    ActivityBased, Aggressive, HoleState, KeepalivePolicy, Lazy, DEFAULT_LAZY_MAX_UNANSWERED,
+};
+pub use sender::{KeepaliveSender, DEFAULT_KEEPALIVE_BATCH_SIZE};
+
+/// Tracks punched holes and schedules keep-alive packets for idle holes, as decided by its
+/// [`KeepalivePolicy`]. Any packet observed on a hole, sent or received, resets its timer.
+#[derive(Debug, Clone)]
+pub struct KeepaliveScheduler {
+    /// The time a hole stays open without traffic.
+    hole_lifetime: Duration,
+    /// The time a hole to a specific destination stays open without traffic, for paths whose NAT
+    /// mappings live shorter or longer than most.
+    lifetime_overrides: HashMap<SocketAddr, Duration>,
+    /// The observed activity per punched hole.
+    holes: HashMap<SocketAddr, HoleState>,
+    /// Holes given up on since last drained, see [`Self::drain_given_up`].
+    given_up: Vec<SocketAddr>,
+    /// Holes expiring this soon after an expired hole are kept alive early along with it, so
+    /// that keep-alive packets go out in batches.
+    batch_window: Duration,
+    policy: Arc<dyn KeepalivePolicy>,
+    clock: SharedClock,
+}
+
+impl Default for KeepaliveScheduler {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME))
+    }
+}
+
+impl KeepaliveScheduler {
+    pub fn new(hole_lifetime: Duration) -> Self {
+        KeepaliveScheduler {
+            hole_lifetime,
+            lifetime_overrides: HashMap::new(),
+            holes: HashMap::new(),
+            given_up: Vec::new(),
+            batch_window: Duration::ZERO,
+            policy: Arc::new(Lazy::default()),
+            clock: system_clock(),
+        }
+    }
+
+    /// Decides keep-alives with `policy` instead of the default [`Lazy`] policy.
+    pub fn with_policy(mut self, policy: Arc<dyn KeepalivePolicy>) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The time a hole stays open without traffic.
+    pub fn hole_lifetime(&self) -> Duration {
+        self.hole_lifetime
+    }
+
+    /// Groups expiries falling within `batch_window` of each other into one batch, see
+    /// [`KeepaliveSender`].
+    pub fn set_batch_window(&mut self, batch_window: Duration) {
+        self.batch_window = batch_window;
+    }
+
+    /// The time the hole to `dst` stays open without traffic.
+    pub fn lifetime(&self, dst: &SocketAddr) -> Duration {
+        self.lifetime_overrides
+            .get(dst)
+            .copied()
+            .unwrap_or(self.hole_lifetime)
+    }
+
+    /// Overrides the lifetime of the hole to `dst`, for example as measured on the path or
+    /// configured for known infrastructure peers. Outlives tracking of the hole. Returns the
+    /// previous override, if any.
+    pub fn set_lifetime(&mut self, dst: SocketAddr, lifetime: Duration) -> Option<Duration> {
+        self.lifetime_overrides.insert(dst, lifetime)
+    }
+
+    /// Removes the lifetime override of the hole to `dst`.
+    pub fn clear_lifetime(&mut self, dst: &SocketAddr) -> Option<Duration> {
+        self.lifetime_overrides.remove(dst)
+    }
+
+    /// Starts tracking a punched hole to `dst`. Returns false if the hole was already tracked, in
+    /// which case its timer is reset.
+    pub fn insert(&mut self, dst: SocketAddr) -> bool {
+        self.holes
+            .insert(dst, HoleState::new(self.clock.now()))
+            .is_none()
+    }
+
+    /// Starts tracking a punched hole to `dst` that has been idle for `idle`, for example one kept
+    /// alive by a previous process. Returns false if the hole was already tracked.
+    pub fn insert_idle(&mut self, dst: SocketAddr, idle: Duration) -> bool {
+        let now = self.clock.now();
+        let mut hole = HoleState::new(now);
+        hole.last_activity = now.checked_sub(idle).unwrap_or(now);
+        self.holes.insert(dst, hole).is_none()
+    }
+
+    /// Stops tracking the hole to `dst`. Returns true if the hole was tracked.
+    pub fn remove(&mut self, dst: &SocketAddr) -> bool {
+        self.holes.remove(dst).is_some()
+    }
+
+    /// Returns true if a hole to `dst` is tracked.
+    pub fn contains(&self, dst: &SocketAddr) -> bool {
+        self.holes.contains_key(dst)
+    }
+
+    /// Returns the tracked holes.
+    pub fn holes(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.holes.keys()
+    }
+
+    /// Number of tracked holes.
+    pub fn len(&self) -> usize {
+        self.holes.len()
+    }
+
+    /// Returns true if no holes are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.holes.is_empty()
+    }
+
+    /// Returns the activity observed on the hole to `dst`, if it is tracked.
+    pub fn hole(&self, dst: &SocketAddr) -> Option<&HoleState> {
+        self.holes.get(dst)
+    }
+
+    /// A packet was sent to `dst`, resets the timer of the hole if it is tracked.
+    pub fn touch_on_send(&mut self, dst: &SocketAddr) {
+        if let Some(hole) = self.holes.get_mut(dst) {
+            hole.last_activity = self.clock.now();
+        }
+    }
+
+    /// A packet was received from `src`, resets the timer of the hole if it is tracked.
+    pub fn touch_on_recv(&mut self, src: &SocketAddr) {
+        if let Some(hole) = self.holes.get_mut(src) {
+            let now = self.clock.now();
+            hole.last_activity = now;
+            hole.last_recv = Some(now);
+            hole.unanswered = 0;
+        }
+    }
+
+    /// The point in time at which a keep-alive packet is next due, if any holes are tracked.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.holes
+            .iter()
+            .map(|(dst, hole)| self.policy.keepalive_due(hole, self.lifetime(dst)))
+            .min()
+    }
+
+    /// Returns the holes given up on by the policy since last called. They are no longer
+    /// tracked.
+    pub fn drain_given_up(&mut self) -> Vec<SocketAddr> {
+        std::mem::take(&mut self.given_up)
+    }
+
+    /// Sleeps on the clock until the next idle hole expires, then returns the expired holes, see
+    /// [`Self::poll_expired`]. Returns immediately if no holes are tracked.
+    pub async fn wait_expired(&mut self) -> Vec<SocketAddr> {
+        let Some(deadline) = self.next_deadline() else {
+            return Vec::new();
+        };
+        self.clock.sleep_until(deadline).await;
+        self.poll_expired(self.clock.now())
+    }
+
+    /// Returns the holes a keep-alive packet is due on at `now`. If any hole is due, the holes
+    /// due within the batch window are returned too. Due holes the policy gives up on are
+    /// untracked instead, see [`Self::drain_given_up`]. The timers of the returned holes are
+    /// reset, as the caller is expected to send a keep-alive packet to each of them.
+    pub fn poll_expired(&mut self, now: Instant) -> Vec<SocketAddr> {
+        if self.next_deadline().is_none_or(|deadline| now < deadline) {
+            return Vec::new();
+        }
+        let mut due = Vec::new();
+        for (dst, hole) in self.holes.iter() {
+            if now + self.batch_window < self.policy.keepalive_due(hole, self.lifetime(dst)) {
+                continue;
+            }
+            if self.policy.give_up(hole, now) {
+                self.given_up.push(*dst);
+            } else {
+                due.push((*dst, *hole));
+            }
+        }
+        let expired = self.policy.select(&due);
+        for (dst, _) in due.iter().filter(|(dst, _)| !expired.contains(dst)) {
+            self.given_up.push(*dst);
+        }
+        for dst in self.given_up.iter() {
+            self.holes.remove(dst);
+        }
+        for dst in expired.iter() {
+            if let Some(hole) = self.holes.get_mut(dst) {
+                hole.last_activity = now;
+                hole.unanswered += 1;
+            }
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_idle_holes_expire() {
+        let lifetime = Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME);
+        let mut scheduler = KeepaliveScheduler::new(lifetime);
+
+        let idle: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let active: SocketAddr = "192.0.2.2:9000".parse().unwrap();
+        scheduler.insert(idle);
+        scheduler.insert(active);
+
+        // backdate both holes to simulate a lifetime passing without traffic
+        let start = Instant::now() - lifetime;
+        scheduler.holes.insert(idle, HoleState::new(start));
+        scheduler.holes.insert(active, HoleState::new(start));
+        scheduler.touch_on_recv(&active);
+
+        assert_eq!(vec![idle], scheduler.poll_expired(Instant::now()));
+        // timer of expired hole is reset
+        assert!(scheduler.poll_expired(Instant::now()).is_empty());
+    }
+
+    #[test]
+    fn test_lifetime_override() {
+        let mut scheduler = KeepaliveScheduler::default();
+        let cgnat: SocketAddr = "100.64.0.1:9000".parse().unwrap();
+        let other: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        scheduler.set_lifetime(cgnat, Duration::from_secs(5));
+        scheduler.insert(cgnat);
+        scheduler.insert(other);
+
+        let now = Instant::now();
+        assert!(scheduler.next_deadline().unwrap() < now + Duration::from_secs(6));
+        assert_eq!(
+            vec![cgnat],
+            scheduler.poll_expired(now + Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn test_expiries_batched() {
+        let lifetime = Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME);
+        let mut scheduler = KeepaliveScheduler::new(lifetime);
+        scheduler.set_batch_window(Duration::from_secs(2));
+        let first: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let second: SocketAddr = "192.0.2.2:9000".parse().unwrap();
+        let later: SocketAddr = "192.0.2.3:9000".parse().unwrap();
+        let now = Instant::now();
+        scheduler.holes.insert(first, HoleState::new(now));
+        scheduler
+            .holes
+            .insert(second, HoleState::new(now + Duration::from_secs(1)));
+        scheduler
+            .holes
+            .insert(later, HoleState::new(now + Duration::from_secs(5)));
+
+        // nothing is due before the first hole expires
+        assert!(scheduler.poll_expired(now + lifetime / 2).is_empty());
+        let mut expired = scheduler.poll_expired(now + lifetime);
+        expired.sort();
+        assert_eq!(vec![first, second], expired);
+    }
+
+    #[test]
+    fn test_touch_untracked_hole_is_noop() {
+        let mut scheduler = KeepaliveScheduler::default();
+        let peer: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+
+        scheduler.touch_on_send(&peer);
+
+        assert!(!scheduler.contains(&peer));
+        assert!(scheduler.next_deadline().is_none());
+    }
+
+    #[test]
+    fn test_keepalive_policies() {
+        let lifetime = Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME);
+        let now = Instant::now();
+        let silent: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let chatty: SocketAddr = "192.0.2.2:9000".parse().unwrap();
+
+        let mut aggressive = KeepaliveScheduler::new(lifetime).with_policy(Arc::new(Aggressive));
+        aggressive.holes.insert(silent, HoleState::new(now));
+        assert_eq!(vec![silent], aggressive.poll_expired(now + lifetime / 2));
+
+        let mut lazy = KeepaliveScheduler::new(lifetime).with_policy(Arc::new(Lazy {
+            max_unanswered: Some(2),
+        }));
+        lazy.holes.insert(silent, HoleState::new(now));
+        assert!(lazy.poll_expired(now + lifetime / 2).is_empty());
+        assert_eq!(vec![silent], lazy.poll_expired(now + lifetime));
+        assert_eq!(vec![silent], lazy.poll_expired(now + lifetime * 2));
+        // two keep-alives went unanswered
+        assert!(lazy.poll_expired(now + lifetime * 3).is_empty());
+        assert_eq!(vec![silent], lazy.drain_given_up());
+        assert!(lazy.is_empty());
+
+        let mut activity = KeepaliveScheduler::new(lifetime).with_policy(Arc::new(ActivityBased {
+            idle_timeout: lifetime * 2,
+            max_holes: Some(1),
+        }));
+        let third: SocketAddr = "192.0.2.3:9000".parse().unwrap();
+        for dst in [silent, chatty, third] {
+            activity.holes.insert(dst, HoleState::new(now));
+        }
+        activity.holes.get_mut(&chatty).unwrap().last_recv = Some(now + lifetime / 2);
+        activity.holes.get_mut(&third).unwrap().last_recv = Some(now + lifetime / 4);
+        // only the most recently heard from peer is kept
+        assert_eq!(vec![chatty], activity.poll_expired(now + lifetime));
+        let mut given_up = activity.drain_given_up();
+        given_up.sort();
+        assert_eq!(vec![silent, third], given_up);
+        // the peer went silent for longer than the idle timeout
+        assert!(activity.poll_expired(now + lifetime * 3).is_empty());
+        assert_eq!(vec![chatty], activity.drain_given_up());
+    }
+}
@@ -0,0 +1,115 @@
+use std::{
+    cmp::Reverse,
+    fmt::Debug,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Default number of keep-alive packets the [`Lazy`] policy sends without hearing from the peer
+/// before giving up on a hole, if it gives up at all.
+pub const DEFAULT_LAZY_MAX_UNANSWERED: u32 = 3;
+
+/// The activity on a punched hole, as seen by the [`super::KeepaliveScheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HoleState {
+    /// The time the hole started being tracked.
+    pub punched: Instant,
+    /// The time of the last packet sent or received on the hole.
+    pub last_activity: Instant,
+    /// The time of the last packet received from the peer, if any.
+    pub last_recv: Option<Instant>,
+    /// Number of keep-alive packets sent since the peer was last heard from.
+    pub unanswered: u32,
+}
+
+impl HoleState {
+    pub fn new(now: Instant) -> Self {
+        HoleState {
+            punched: now,
+            last_activity: now,
+            last_recv: None,
+            unanswered: 0,
+        }
+    }
+}
+
+/// Decides when punched holes are kept alive, and when they are left to close.
+pub trait KeepalivePolicy: Debug + Send + Sync {
+    /// The point in time a keep-alive packet is due on `hole`, which stays open for `lifetime`
+    /// without traffic.
+    fn keepalive_due(&self, hole: &HoleState, lifetime: Duration) -> Instant;
+    /// Returns true if `hole` should be left to close instead of kept alive when a keep-alive
+    /// packet is due on it at `now`.
+    fn give_up(&self, hole: &HoleState, now: Instant) -> bool;
+    /// Picks the holes to send keep-alive packets to out of the holes due at once, the others
+    /// are given up on. Picks all by default.
+    fn select(&self, due: &[(SocketAddr, HoleState)]) -> Vec<SocketAddr> {
+        due.iter().map(|(dst, _)| *dst).collect()
+    }
+}
+
+/// Keeps holes alive at half their lifetime, never giving up on them. Survives NATs timing out
+/// mappings earlier than configured at the cost of twice the keep-alive packets.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aggressive;
+
+impl KeepalivePolicy for Aggressive {
+    fn keepalive_due(&self, hole: &HoleState, lifetime: Duration) -> Instant {
+        hole.last_activity + lifetime / 2
+    }
+
+    fn give_up(&self, _hole: &HoleState, _now: Instant) -> bool {
+        false
+    }
+}
+
+/// Keeps holes alive only once they have been idle for their whole lifetime. Optionally gives up
+/// on holes after a number of keep-alive packets without hearing from the peer. The default
+/// policy, never giving up.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lazy {
+    /// Max keep-alive packets sent without hearing from the peer, see
+    /// [`DEFAULT_LAZY_MAX_UNANSWERED`].
+    pub max_unanswered: Option<u32>,
+}
+
+impl KeepalivePolicy for Lazy {
+    fn keepalive_due(&self, hole: &HoleState, lifetime: Duration) -> Instant {
+        hole.last_activity + lifetime
+    }
+
+    fn give_up(&self, hole: &HoleState, _now: Instant) -> bool {
+        self.max_unanswered
+            .is_some_and(|max_unanswered| hole.unanswered >= max_unanswered)
+    }
+}
+
+/// Keeps holes alive once idle for their whole lifetime, but only while the peer has been heard
+/// from within `idle_timeout`, so that holes to peers that went away are not kept open forever.
+#[derive(Debug, Clone, Copy)]
+pub struct ActivityBased {
+    /// The time a hole is kept alive without receiving from the peer, counted from when it was
+    /// punched if the peer was never heard from.
+    pub idle_timeout: Duration,
+    /// Max number of holes kept alive at once, the holes whose peers were heard from last are
+    /// picked. Unlimited if `None`.
+    pub max_holes: Option<usize>,
+}
+
+impl KeepalivePolicy for ActivityBased {
+    fn keepalive_due(&self, hole: &HoleState, lifetime: Duration) -> Instant {
+        hole.last_activity + lifetime
+    }
+
+    fn give_up(&self, hole: &HoleState, now: Instant) -> bool {
+        let last_heard = hole.last_recv.unwrap_or(hole.punched);
+        now.saturating_duration_since(last_heard) > self.idle_timeout
+    }
+
+    fn select(&self, due: &[(SocketAddr, HoleState)]) -> Vec<SocketAddr> {
+        let mut due = due.to_vec();
+        due.sort_by_key(|(_, hole)| Reverse(hole.last_recv.unwrap_or(hole.punched)));
+        due.truncate(self.max_holes.unwrap_or(usize::MAX));
+        due.into_iter().map(|(dst, _)| dst).collect()
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+};
+
+/// Default max number of keep-alive packets handed to the OS in one syscall.
+pub const DEFAULT_KEEPALIVE_BATCH_SIZE: usize = 64;
+
+/// Sends the keep-alive packets of a batch of expired holes, see
+/// [`super::KeepaliveScheduler::poll_expired`]. With the `sendmmsg` feature on Linux a batch
+/// costs one syscall, elsewhere one per packet.
+#[derive(Debug)]
+pub struct KeepaliveSender {
+    /// The socket the holes were punched from.
+    socket: UdpSocket,
+    batch_size: usize,
+}
+
+impl KeepaliveSender {
+    /// Sends keep-alive packets from `socket`, which must be the socket that punched the holes,
+    /// for example a clone of the discv5 socket.
+    pub fn new(socket: UdpSocket) -> Self {
+        Self::with_batch_size(socket, DEFAULT_KEEPALIVE_BATCH_SIZE)
+    }
+
+    pub fn with_batch_size(socket: UdpSocket, batch_size: usize) -> Self {
+        KeepaliveSender {
+            socket,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Sends `packet` to each of `dsts`. Returns the number of packets sent, which may be less
+    /// than the number of destinations if the OS stops accepting packets mid batch.
+    pub fn send(&self, dsts: &[SocketAddr], packet: &[u8]) -> io::Result<usize> {
+        let mut sent = 0;
+        for batch in dsts.chunks(self.batch_size) {
+            let batch_sent = match self.send_batch(batch, packet) {
+                Ok(batch_sent) => batch_sent,
+                Err(e) if sent == 0 => return Err(e),
+                Err(_) => return Ok(sent),
+            };
+            sent += batch_sent;
+            if batch_sent < batch.len() {
+                break;
+            }
+        }
+        Ok(sent)
+    }
+
+    #[cfg(all(target_os = "linux", feature = "sendmmsg"))]
+    fn send_batch(&self, dsts: &[SocketAddr], packet: &[u8]) -> io::Result<usize> {
+        use socket2::SockAddr;
+        use std::os::fd::AsRawFd;
+
+        let addrs = dsts
+            .iter()
+            .map(|dst| SockAddr::from(*dst))
+            .collect::<Vec<_>>();
+        let mut iov = libc::iovec {
+            iov_base: packet.as_ptr() as *mut libc::c_void,
+            iov_len: packet.len(),
+        };
+        let mut msgs = addrs
+            .iter()
+            .map(|addr| {
+                // SAFETY: msghdr is a plain C struct for which all zeros is a valid value.
+                let mut hdr: libc::msghdr = unsafe { std::mem::zeroed() };
+                hdr.msg_name = addr.as_ptr() as *mut libc::c_void;
+                hdr.msg_namelen = addr.len();
+                hdr.msg_iov = &mut iov;
+                hdr.msg_iovlen = 1;
+                libc::mmsghdr {
+                    msg_hdr: hdr,
+                    msg_len: 0,
+                }
+            })
+            .collect::<Vec<_>>();
+        // SAFETY: the headers point into `addrs` and `iov`, which outlive the call, and the
+        // kernel only reads from them, the packet included.
+        let sent = unsafe {
+            libc::sendmmsg(
+                self.socket.as_raw_fd(),
+                msgs.as_mut_ptr(),
+                msgs.len() as libc::c_uint,
+                0,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(sent as usize)
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "sendmmsg")))]
+    fn send_batch(&self, dsts: &[SocketAddr], packet: &[u8]) -> io::Result<usize> {
+        for (sent, dst) in dsts.iter().enumerate() {
+            if let Err(e) = self.socket.send_to(packet, dst) {
+                if sent == 0 {
+                    return Err(e);
+                }
+                return Ok(sent);
+            }
+        }
+        Ok(dsts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_send_batches() {
+        let a = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let b = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let dsts = [
+            a.local_addr().unwrap(),
+            b.local_addr().unwrap(),
+            a.local_addr().unwrap(),
+        ];
+        let sender = KeepaliveSender::with_batch_size(UdpSocket::bind("127.0.0.1:0").unwrap(), 2);
+
+        assert_eq!(3, sender.send(&dsts, &[0xc0]).unwrap());
+
+        let mut buf = [0u8; 4];
+        for socket in [&a, &b, &a] {
+            assert_eq!(1, socket.recv(&mut buf).unwrap());
+            assert_eq!(0xc0, buf[0]);
+        }
+    }
+}
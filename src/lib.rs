@@ -1,19 +1,140 @@
 use async_trait::async_trait;
-use rand::Rng;
 use std::{
     fmt::{Debug, Display},
-    net::{IpAddr, SocketAddr, UdpSocket},
+    net::SocketAddr,
     ops::RangeInclusive,
 };
 
+mod address_vote;
+mod bind_probe;
+mod clock;
+pub mod codec;
+mod debug_json;
+mod dyn_hole_punch;
 mod error;
+#[cfg(feature = "interfaces")]
+mod interfaces;
+#[cfg(feature = "interop-test")]
+pub mod interop;
+mod keepalive;
+#[cfg(feature = "libp2p")]
+pub mod libp2p;
 mod macro_rules;
+mod node_address;
 mod notification;
+mod persist;
+mod redact;
+mod rlp_backend;
+mod rng;
+mod runtime;
+#[cfg(feature = "sealed")]
+mod sealed;
+mod service;
+mod session_key;
+mod support;
+#[cfg(feature = "tcp")]
+mod tcp;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+mod whoareyou;
 
-pub use error::HolePunchError;
+pub use address_vote::{subnet, AddressVote, DEFAULT_MIN_VOTE_SUBNETS, DEFAULT_VOTE_TTL};
+pub use bind_probe::{
+    is_behind_nat, is_cgnat, probe_nat, probe_nat_with_rng, BindProbeResult, NatStatus,
+    ProbeConfig, ProbeReport,
+};
+#[cfg(feature = "tokio")]
+pub use clock::TokioClock;
+pub use clock::{Clock, SharedClock, SystemClock};
+pub use debug_json::DebugJsonError;
+pub use dyn_hole_punch::DynHolePunch;
+pub use error::{BoxError, HolePunchError, InitiatorError, RelayError, TargetError};
+#[cfg(feature = "interfaces")]
+pub use interfaces::{egress_ip, local_addrs, probe_interfaces, InterfaceAddr, InterfaceReport};
+pub use keepalive::{
+    ActivityBased, Aggressive, HoleState, KeepalivePolicy, KeepaliveScheduler, KeepaliveSender,
+    Lazy, DEFAULT_KEEPALIVE_BATCH_SIZE, DEFAULT_LAZY_MAX_UNANSWERED,
+};
+pub use node_address::{IpPreference, NodeAddress};
+pub use notification::{
+    nonce_from_rlp_compat, peek_notif_type, peek_notif_type_with, quick_reject, quick_reject_with,
+    AttemptId, Candidate, CandidateKind, Candidates, DecodeLimits, Echo, EchoAfter, Enr, Extension,
+    ExtensionCodec, ExtensionType, Extensions, FallbackStrategy, MessageNonce, NodeId, NodeIdBytes,
+    Notification, NotificationType, ProtocolNamespace, ProtocolVersion, PunchReport, PunchToward,
+    ReasonCode, RecentNonces, RejectReason, RelayAbort, RelayExtras, RelayInit, RelayInitBuilder,
+    RelayMsg, RelayMsgBuilder, TypeNumbering, ValidationError, ValidationPolicy, WireConfig,
+    ATTEMPT_ID_LENGTH, DEFAULT_MAX_ENR_SIZE, DEFAULT_MAX_NOTIFICATION_SIZE, MAX_CANDIDATES,
+    MAX_EXTENSIONS, MAX_RECENT_NONCES, MESSAGE_NONCE_LENGTH, NAMESPACE_TAG_LENGTH, NODE_ID_LENGTH,
+};
+#[cfg(feature = "embedded")]
 pub use notification::{
-    Enr, MessageNonce, NodeId, Notification, RelayInit, RelayMsg, MESSAGE_NONCE_LENGTH,
-    NODE_ID_LENGTH, REALYINIT_MSG_TYPE, REALYMSG_MSG_TYPE,
+    BufferTooSmall, EchoAfterRef, EchoRef, NotificationRef, PunchReportRef, RelayAbortRef,
+    RelayInitRef, RelayMsgRef,
+};
+#[allow(deprecated)]
+pub use notification::{
+    REALYABORT_MSG_TYPE, REALYINIT_MSG_TYPE, REALYMSG_MSG_TYPE, REALY_INIT_NOTIF_TYPE,
+    REALY_MSG_NOTIF_TYPE,
+};
+pub use persist::{FileStateStore, FileStateStoreError, PersistedHole, PersistedState, StateStore};
+pub use redact::{redaction, set_redaction, Redacted, RedactedDisplay, Redaction};
+pub use rlp_backend::DecoderError;
+pub use rng::{RandomSource, SeededRng, SharedRng, ThreadRng};
+#[cfg(feature = "async-std")]
+pub use runtime::AsyncStdRuntime;
+#[cfg(feature = "tokio")]
+pub use runtime::TokioRuntime;
+pub use runtime::{Runtime, UdpSocket};
+#[cfg(feature = "sealed")]
+pub use sealed::{
+    open, seal, SealError, EPHEMERAL_KEY_LENGTH, SEAL_OVERHEAD, SEAL_TAG_LENGTH,
+    X25519_EPHEMERAL_KEY_LENGTH,
+};
+pub use service::{
+    AlwaysAllow, AlwaysPunch, Attempt, AttemptFuture, AttemptOutcome, AttemptRecord,
+    AttemptRegistry, AttemptTimeout, AuditLog, BackToBack, Backoff, BackoffLedger, BurstPacing,
+    CacheEvictions, CandidatePair, Checklist, DeadlineBudget, DispatchError, DistanceHeuristic,
+    DropPolicy, EchoProbe, EchoProbes, FailureCounts, FailureReason, FairScheduling,
+    FinishingPunch, FinishingPunches, HolePunchConfig, HolePunchEvent, HolePunchService,
+    InboundFilterFn, InboundFilters, InboundKind, InitiationThrottle, KeepalivePayload,
+    KeepalivePayloadFn, LatencyHistogram, LoadMonitor, LocalEndpoint, LruCache,
+    NotificationDispatcher, NotificationSink, Pacer, PathRaces, PendingNonceTable, PunchContext,
+    PunchPattern, PunchPayload, PunchPayloadFn, PunchPolicy, PunchTimings, PunchedAttempt,
+    PunchedPeer, PunchedPeers, QueuedAttempt, RateLimiter, Reachability, ReachabilityCache,
+    ReachabilityEntry, RebindDetector, RelayCandidateBook, RelayConcurrency, RelayConsent,
+    RelayFallback, RelayQueue, RelayQueueMetrics, RelayScore, RelayScores, RelayTry, ReplayCache,
+    ScheduledEcho, ScheduledEchoes, ScheduledPunch, ScheduledPunches, ServiceCommand, SessionMap,
+    Source, SplitPolicy, Stats, SubnetDiversity, TargetLookup, Verdict, DEFAULT_ATTEMPT_CAPACITY,
+    DEFAULT_ATTEMPT_TIMEOUT, DEFAULT_AUDIT_LOG_SIZE, DEFAULT_COMMAND_BUFFER,
+    DEFAULT_FAILURE_COOLDOWN, DEFAULT_FIREWALL_EVIDENCE, DEFAULT_INITIATION_RATE,
+    DEFAULT_MAX_ECHO_DELAY, DEFAULT_MAX_FAILURE_COOLDOWN, DEFAULT_MAX_SCHEDULED_ECHOES,
+    DEFAULT_PACING_BURST_CAP, DEFAULT_PACING_RATE, DEFAULT_PENDING_NONCE_TTL,
+    DEFAULT_PUNCH_MAX_DISTANCE, DEFAULT_PUNCH_PARALLELISM, DEFAULT_PUNCH_PARALLELISM_PER_TARGET,
+    DEFAULT_PUNCH_SPACING, DEFAULT_RATE_LIMIT_PER_INITIATOR, DEFAULT_RATE_LIMIT_PER_RELAY,
+    DEFAULT_RATE_LIMIT_TOTAL, DEFAULT_RATE_LIMIT_WINDOW, DEFAULT_REACHABILITY_CAPACITY,
+    DEFAULT_REBIND_THRESHOLD, DEFAULT_REBIND_WINDOW, DEFAULT_RELAY_BOOK_CAPACITY,
+    DEFAULT_RELAY_CANDIDATES_PER_TARGET, DEFAULT_RELAY_CONCURRENCY,
+    DEFAULT_RELAY_CONCURRENCY_PER_INITIATOR, DEFAULT_RELAY_QUEUE_DEPTH, DEFAULT_RELAY_RATE,
+    DEFAULT_RELAY_SUBNET_V4, DEFAULT_RELAY_SUBNET_V6, DEFAULT_REPLAY_CACHE_CAPACITY,
+    DEFAULT_REPLAY_TTL, DEFAULT_SERVED_ATTEMPT_TTL, DEFAULT_TICK_INTERVAL, ECHO_GRACE,
+    LATENCY_BUCKETS_MS,
+};
+pub use session_key::SessionKey;
+pub use support::{
+    advertise_hole_punch, advertise_supported_versions, hole_punch_version, local_versions,
+    negotiate_version, supported_versions, supports_hole_punch, HOLE_PUNCH_ENR_KEY,
+    HOLE_PUNCH_PROTOCOL_VERSION, HOLE_PUNCH_VERSIONS_ENR_KEY, MIN_HOLE_PUNCH_PROTOCOL_VERSION,
+};
+#[cfg(feature = "tcp")]
+pub use tcp::{
+    advertise_reflexive_tcp, enr_tcp_sockets, SimultaneousOpen, DEFAULT_TCP_CONNECT_TIMEOUT,
+    DEFAULT_TCP_CONNECT_TRIES,
+};
+pub use whoareyou::{
+    IdNonce, MaskingIv, WhoAreYou, ID_NONCE_LENGTH, MASKING_IV_LENGTH, PROTOCOL_ID,
+    PROTOCOL_VERSION, STATIC_HEADER_LENGTH, WHOAREYOU_AUTHDATA_LENGTH, WHOAREYOU_FLAG,
 };
 
 /// The expected shortest lifetime in most NAT configurations of a punched hole in seconds.
@@ -23,75 +144,118 @@ pub const DEFAULT_PORT_BIND_TRIES: usize = 4;
 /// Port range that is not impossible to bind to.
 pub const USER_AND_DYNAMIC_PORTS: RangeInclusive<u16> = 1025..=u16::MAX;
 
+/// Hooks for discv5, or an overlay with `N` byte message nonces and `I` byte node ids, to drive
+/// hole punching.
 #[async_trait]
-pub trait NatHolePunch {
+pub trait NatHolePunch<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH> {
     /// A type in discv5 for indexing sessions. Discv5 indexes sessions based on combination
     /// `(socket, node-id)`.
     type SessionIndex: Send + Sync;
     /// A discv5 error type.
     type Discv5Error: Display + Debug;
     /// A request times out. Should trigger the initiation of a hole punch attempt, given a
-    /// transitive route to the target exists.
+    /// transitive route to the target exists. The relay is the node which passed the hole punch
+    /// target to this node, [`HolePunchService`] tracks these, see [`RelayCandidateBook`].
     async fn on_request_time_out(
         &mut self,
         relay: Self::SessionIndex,
         local_enr: Enr, // initiator-enr
-        timed_out_message_nonce: MessageNonce,
+        timed_out_message_nonce: MessageNonce<N>,
         target_session_index: Self::SessionIndex,
     ) -> Result<(), HolePunchError<Self::Discv5Error>>;
+    /// The application wants a hole to `target` without a request to it having timed out, for
+    /// example because a protocol urgently needs that peer. Should trigger a hole punch attempt
+    /// through the relay `via`, as for a timed out request, using a freshly generated nonce.
+    /// Returns the nonce, which the WHOAREYOU from the target will carry. Fails with
+    /// [`HolePunchError::NotImplemented`] unless implemented.
+    async fn initiate_punch(
+        &mut self,
+        _target: NodeId,
+        _via: Self::SessionIndex,
+    ) -> Result<MessageNonce<N>, HolePunchError<Self::Discv5Error>> {
+        Err(HolePunchError::NotImplemented("initiate_punch"))
+    }
     /// A notification is received over discv5.
     async fn on_notification(
         &mut self,
         decrypted_notif: &[u8],
     ) -> Result<(), HolePunchError<Self::Discv5Error>> {
-        match Notification::rlp_decode(decrypted_notif)? {
+        self.handle_notification(decrypted_notif, |_| ()).await
+    }
+    /// Like [`Self::on_notification`], but shows the decoded notification to `inspect`, for
+    /// callers that also log or inspect it. `inspect` gets a reference before the handler takes
+    /// the notification, so that it isn't copied.
+    async fn handle_notification<F>(
+        &mut self,
+        decrypted_notif: &[u8],
+        inspect: F,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>>
+    where
+        F: FnOnce(&Notification<N, I>) + Send,
+    {
+        let notif = Notification::<N, I>::rlp_decode(decrypted_notif)?;
+        inspect(&notif);
+        match notif {
             Notification::RelayInit(relay_init_notif) => self.on_relay_init(relay_init_notif).await,
             Notification::RelayMsg(relay_msg_notif) => self.on_relay_msg(relay_msg_notif).await,
+            Notification::RelayAbort(relay_abort_notif) => {
+                self.on_relay_abort(relay_abort_notif).await
+            }
+            Notification::PunchReport(punch_report_notif) => {
+                self.on_punch_report(punch_report_notif).await
+            }
+            Notification::EchoAfter(echo_after_notif) => self.on_echo_after(echo_after_notif).await,
+            Notification::Echo(echo_notif) => self.on_echo(echo_notif).await,
         }
     }
     /// A [`RelayInit`] notification is received indicating this node is the relay. Should trigger
     /// sending a [`RelayMsg`] to the target.
     async fn on_relay_init(
         &mut self,
-        notif: RelayInit,
+        notif: RelayInit<N, I>,
     ) -> Result<(), HolePunchError<Self::Discv5Error>>;
     /// A [`RelayMsg`] notification is received indicating this node is the target. Should trigger
     /// a WHOAREYOU to be sent to the initiator using the `nonce` in the [`RelayMsg`].
     async fn on_relay_msg(
         &mut self,
-        notif: RelayMsg,
+        notif: RelayMsg<N, I>,
     ) -> Result<(), HolePunchError<Self::Discv5Error>>;
-    /// A punched hole closes. Should trigger an empty packet to be sent to the peer.
+    /// A [`RelayAbort`] notification is received indicating this node is the initiator and the
+    /// relay won't relay the attempt. Should trigger trying another relay. Ignored unless
+    /// implemented, the attempt then moves on once the try over the relay times out.
+    async fn on_relay_abort(
+        &mut self,
+        _notif: RelayAbort<N>,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        Ok(())
+    }
+    /// A [`PunchReport`] notification is received indicating this node is the relay of a
+    /// resolved attempt. Ignored unless implemented, relays may use it to score their forwarding,
+    /// counting only reports from the initiator of the attempt.
+    async fn on_punch_report(
+        &mut self,
+        _notif: PunchReport<N>,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        Ok(())
+    }
+    /// An [`EchoAfter`] notification is received. Should trigger an [`Echo`] to the sender once
+    /// the delay passed. Ignored unless implemented.
+    async fn on_echo_after(
+        &mut self,
+        _notif: EchoAfter<N, I>,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        Ok(())
+    }
+    /// An [`Echo`] notification is received, answering an [`EchoAfter`] sent by this node. The NAT
+    /// mapping of this node towards the sender outlived the delay of the probe. Ignored unless
+    /// implemented.
+    async fn on_echo(&mut self, _notif: Echo<N>) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        Ok(())
+    }
+    /// A punched hole closes. Should trigger an empty packet to be sent to the peer. Holes that
+    /// see traffic in either direction don't expire, see [`KeepaliveScheduler`].
     async fn on_hole_punch_expired(
         &mut self,
         dst: SocketAddr,
     ) -> Result<(), HolePunchError<Self::Discv5Error>>;
 }
-
-/// Helper function to test if the local node is behind NAT based on the node's observed reachable
-/// socket.
-pub fn is_behind_nat(
-    observed_ip: IpAddr,
-    unused_port_range: Option<RangeInclusive<u16>>,
-    max_retries: Option<usize>,
-) -> bool {
-    // If the node cannot bind to the observed address at any of some random ports, we
-    // conclude it is behind NAT.
-    let mut rng = rand::thread_rng();
-    let unused_port_range = match unused_port_range {
-        Some(range) => range,
-        None => USER_AND_DYNAMIC_PORTS,
-    };
-    let retries = match max_retries {
-        Some(max) => max,
-        None => DEFAULT_PORT_BIND_TRIES,
-    };
-    for _ in 0..retries {
-        let rnd_port: u16 = rng.gen_range(unused_port_range.clone());
-        let socket_addr: SocketAddr = format!("{}:{}", observed_ip, rnd_port).parse().unwrap();
-        if UdpSocket::bind(socket_addr).is_ok() {
-            return false;
-        }
-    }
-    true
-}
@@ -0,0 +1,352 @@
+//! Adapter between the hole punching of this crate and libp2p's DCUtR and identify, for nodes
+//! running discv5 and libp2p side by side with one source of truth on which peers are
+//! reachable.
+//!
+//! libp2p types are exchanged in their canonical forms, a [`PeerId`] as the bytes
+//! `libp2p::PeerId::from_bytes` takes and addresses as the text `Multiaddr::from_str` parses,
+//! so that the crate isn't tied to a libp2p release.
+//!
+//! Outcomes flow both ways through the [`ReachabilityCache`] of the service, see
+//! [`crate::HolePunchService::reachability_mut`]. Events of the service map to
+//! [`Libp2pEvent`]s with [`Libp2pAdapter::map_event`], and DCUtR outcomes are recorded with
+//! [`Libp2pAdapter::on_dcutr_event`].
+
+use crate::{
+    AttemptOutcome, Enr, HolePunchEvent, MessageNonce, Reachability, ReachabilityCache,
+    ServiceCommand,
+};
+use enr::{ed25519_dalek, k256, CombinedPublicKey, EnrKeyUnambiguous, EnrPublicKey, NodeId};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Multihash code of the identity hash, which libp2p peer ids of keys up to 42 bytes use.
+const IDENTITY_MULTIHASH: u8 = 0x00;
+/// Key types of the libp2p public key protobuf.
+const KEY_TYPE_ED25519: u8 = 1;
+const KEY_TYPE_SECP256K1: u8 = 2;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Libp2pError {
+    #[error("invalid peer id, {0}")]
+    InvalidPeerId(&'static str),
+    #[error("invalid multiaddr, {0}")]
+    InvalidMultiaddr(&'static str),
+}
+
+/// A libp2p peer id of a secp256k1 or ed25519 key, the key types discv5 nodes have.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PeerId(Vec<u8>);
+
+impl PeerId {
+    /// The peer id of the node with the given public key.
+    pub fn from_public_key(key: &CombinedPublicKey) -> Self {
+        let key_type = match key {
+            CombinedPublicKey::Secp256k1(_) => KEY_TYPE_SECP256K1,
+            CombinedPublicKey::Ed25519(_) => KEY_TYPE_ED25519,
+        };
+        let key = key.encode();
+        // the protobuf of the key, wrapped in an identity multihash
+        let len = key.len() as u8;
+        let mut bytes = vec![IDENTITY_MULTIHASH, 4 + len, 0x08, key_type, 0x12, len];
+        bytes.extend_from_slice(&key);
+        PeerId(bytes)
+    }
+
+    /// The peer id of the node of the ENR.
+    pub fn from_enr(enr: &Enr) -> Self {
+        Self::from_public_key(&enr.public_key())
+    }
+
+    /// Parses the bytes of a peer id. Only peer ids of secp256k1 and ed25519 keys map to node
+    /// ids.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Libp2pError> {
+        let peer_id = PeerId(bytes.to_vec());
+        peer_id.public_key()?;
+        Ok(peer_id)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The public key the peer id is of.
+    pub fn public_key(&self) -> Result<CombinedPublicKey, Libp2pError> {
+        let [IDENTITY_MULTIHASH, len, 0x08, key_type, 0x12, key_len, key @ ..] = self.0.as_slice()
+        else {
+            return Err(Libp2pError::InvalidPeerId(
+                "not an identity multihash of a key",
+            ));
+        };
+        if *len as usize != key.len() + 4 || *key_len as usize != key.len() {
+            return Err(Libp2pError::InvalidPeerId("length mismatch"));
+        }
+        match *key_type {
+            KEY_TYPE_SECP256K1 => k256::ecdsa::SigningKey::decode_public(key)
+                .map(CombinedPublicKey::Secp256k1)
+                .map_err(|_| Libp2pError::InvalidPeerId("invalid secp256k1 key")),
+            KEY_TYPE_ED25519 => ed25519_dalek::Keypair::decode_public(key)
+                .map(CombinedPublicKey::Ed25519)
+                .map_err(|_| Libp2pError::InvalidPeerId("invalid ed25519 key")),
+            _ => Err(Libp2pError::InvalidPeerId("unsupported key type")),
+        }
+    }
+
+    /// The discv5 node id of the peer.
+    pub fn node_id(&self) -> Result<NodeId, Libp2pError> {
+        self.public_key().map(NodeId::from)
+    }
+}
+
+/// Base58, as libp2p displays peer ids.
+impl fmt::Display for PeerId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", bs58::encode(&self.0).into_string())
+    }
+}
+
+impl FromStr for PeerId {
+    type Err = Libp2pError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| Libp2pError::InvalidPeerId("invalid base58"))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// The multiaddr of a UDP socket, for example `/ip4/192.0.2.1/udp/9000`.
+pub fn to_multiaddr(socket: SocketAddr) -> String {
+    match socket.ip() {
+        IpAddr::V4(ip) => format!("/ip4/{ip}/udp/{}", socket.port()),
+        IpAddr::V6(ip) => format!("/ip6/{ip}/udp/{}", socket.port()),
+    }
+}
+
+/// The socket of a multiaddr starting with an IP and a UDP or TCP port. Any protocols after the
+/// port, for example `/quic-v1` or `/p2p/<peer id>`, are ignored.
+pub fn from_multiaddr(addr: &str) -> Result<SocketAddr, Libp2pError> {
+    let mut protocols = addr.strip_prefix('/').unwrap_or(addr).split('/');
+    let ip = match (protocols.next(), protocols.next()) {
+        (Some("ip4"), Some(ip)) => ip
+            .parse::<std::net::Ipv4Addr>()
+            .map(IpAddr::V4)
+            .map_err(|_| Libp2pError::InvalidMultiaddr("invalid ip4"))?,
+        (Some("ip6"), Some(ip)) => ip
+            .parse::<std::net::Ipv6Addr>()
+            .map(IpAddr::V6)
+            .map_err(|_| Libp2pError::InvalidMultiaddr("invalid ip6"))?,
+        _ => return Err(Libp2pError::InvalidMultiaddr("no ip4 or ip6 address")),
+    };
+    let port = match (protocols.next(), protocols.next()) {
+        (Some("udp" | "tcp"), Some(port)) => port
+            .parse()
+            .map_err(|_| Libp2pError::InvalidMultiaddr("invalid port"))?,
+        _ => return Err(Libp2pError::InvalidMultiaddr("no udp or tcp port")),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// The outcome of a direct connection upgrade, as DCUtR reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DcutrOutcome {
+    /// A direct connection to the peer at the given multiaddr was established.
+    Succeeded {
+        remote_addr: String,
+    },
+    Failed,
+}
+
+/// A direct connection upgrade to a peer resolved, the counterpart of `libp2p_dcutr::Event`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DcutrEvent {
+    pub remote_peer_id: PeerId,
+    pub outcome: DcutrOutcome,
+}
+
+/// What libp2p learns from an event of the [`crate::HolePunchService`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Libp2pEvent {
+    /// A hole to the peer was punched, or punching one failed.
+    Dcutr(DcutrEvent),
+    /// A relay observed the local node at the given multiaddr, the counterpart of an observed
+    /// address reported by identify, for `Swarm::add_external_address` once confirmed.
+    NewExternalAddrCandidate(String),
+}
+
+/// Maps between events of the [`crate::HolePunchService`] and libp2p's DCUtR and identify.
+#[derive(Debug, Clone, Default)]
+pub struct Libp2pAdapter {
+    /// The peer ids of the targets of attempts in flight, by the nonce of the attempt.
+    targets: HashMap<MessageNonce, PeerId>,
+}
+
+impl Libp2pAdapter {
+    /// Tells the adapter of an attempt initiated to `target`, so that its outcome maps to a
+    /// [`DcutrEvent`] with the target's peer id.
+    pub fn attempt_started(&mut self, nonce: MessageNonce, target: &Enr) {
+        self.targets.insert(nonce, PeerId::from_enr(target));
+    }
+
+    /// Maps an event of the service to what libp2p learns from it, if anything.
+    pub fn map_event(&mut self, event: &HolePunchEvent) -> Option<Libp2pEvent> {
+        match event {
+            HolePunchEvent::TargetPunched { nonce, src, .. } => {
+                let remote_peer_id = self.targets.remove(nonce)?;
+                Some(Libp2pEvent::Dcutr(DcutrEvent {
+                    remote_peer_id,
+                    outcome: DcutrOutcome::Succeeded {
+                        remote_addr: to_multiaddr(*src),
+                    },
+                }))
+            }
+            HolePunchEvent::AttemptResolved {
+                nonce,
+                outcome: AttemptOutcome::Failed(_) | AttemptOutcome::Cancelled,
+                ..
+            } => {
+                let remote_peer_id = self.targets.remove(nonce)?;
+                Some(Libp2pEvent::Dcutr(DcutrEvent {
+                    remote_peer_id,
+                    outcome: DcutrOutcome::Failed,
+                }))
+            }
+            HolePunchEvent::ObservedByRelay { observed, .. } => Some(
+                Libp2pEvent::NewExternalAddrCandidate(to_multiaddr(*observed)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Records the outcome of a direct connection upgrade by libp2p in the reachability cache
+    /// shared with the service.
+    pub fn on_dcutr_event(
+        &self,
+        reachability: &mut ReachabilityCache,
+        event: &DcutrEvent,
+    ) -> Result<(), Libp2pError> {
+        let reachability_of_peer = match event.outcome {
+            DcutrOutcome::Succeeded { .. } => Reachability::Punched,
+            DcutrOutcome::Failed => Reachability::Unreachable,
+        };
+        reachability.insert(event.remote_peer_id.node_id()?, reachability_of_peer);
+        Ok(())
+    }
+
+    /// The last known reachability of a peer, so that libp2p can skip upgrading connections to
+    /// peers the service already punched or failed to punch.
+    pub fn reachability(
+        &self,
+        reachability: &ReachabilityCache,
+        peer: &PeerId,
+    ) -> Result<Option<Reachability>, Libp2pError> {
+        let node_id = peer.node_id()?;
+        Ok(reachability.get(&node_id).map(|entry| entry.reachability))
+    }
+
+    /// The command voting for the address identify reports the peer at `peer_addr` observed the
+    /// local node at.
+    pub fn identify_observed<I>(
+        &self,
+        peer: &PeerId,
+        peer_addr: &str,
+        observed: &str,
+    ) -> Result<ServiceCommand<I>, Libp2pError> {
+        Ok(ServiceCommand::AddressObserved {
+            voter: peer.node_id()?,
+            voter_ip: from_multiaddr(peer_addr)?.ip(),
+            observed: from_multiaddr(observed)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PunchTimings;
+    use enr::{CombinedKey, EnrBuilder};
+    use std::time::Instant;
+
+    #[test]
+    fn test_peer_id_of_enr() {
+        for key in [
+            CombinedKey::generate_secp256k1(),
+            CombinedKey::generate_ed25519(),
+        ] {
+            let enr = EnrBuilder::new("v4").build(&key).unwrap();
+            let peer_id = PeerId::from_enr(&enr);
+            assert_eq!(Ok(enr.node_id()), peer_id.node_id());
+            assert_eq!(Ok(peer_id.clone()), peer_id.to_string().parse());
+        }
+
+        // the well known prefixes of libp2p's peer ids of secp256k1 and ed25519 keys. The next
+        // character of secp256k1 peer ids depends on the parity of the compressed key
+        let secp256k1 = EnrBuilder::new("v4")
+            .build(&CombinedKey::generate_secp256k1())
+            .unwrap();
+        let ed25519 = EnrBuilder::new("v4")
+            .build(&CombinedKey::generate_ed25519())
+            .unwrap();
+        assert!(PeerId::from_enr(&secp256k1)
+            .to_string()
+            .starts_with("16Uiu2HA"));
+        assert!(PeerId::from_enr(&ed25519)
+            .to_string()
+            .starts_with("12D3KooW"));
+    }
+
+    #[test]
+    fn test_multiaddr() {
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+        assert_eq!("/ip4/192.0.2.1/udp/9000", to_multiaddr(v4));
+        assert_eq!(Ok(v4), from_multiaddr(&to_multiaddr(v4)));
+        assert_eq!(Ok(v6), from_multiaddr("/ip6/2001:db8::1/udp/9000/quic-v1"));
+        assert!(from_multiaddr("/dns4/example.com/tcp/443").is_err());
+    }
+
+    #[test]
+    fn test_map_events() {
+        let key = CombinedKey::generate_secp256k1();
+        let target = EnrBuilder::new("v4").build(&key).unwrap();
+        let mut adapter = Libp2pAdapter::default();
+        let nonce = [1u8; 12];
+        let src: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        adapter.attempt_started(nonce, &target);
+        let now = Instant::now();
+        let timings = PunchTimings {
+            initiated: now,
+            relay_sent: now,
+            whoareyou_received: now,
+            established: None,
+        };
+
+        let event = adapter.map_event(&HolePunchEvent::TargetPunched {
+            attempt_id: None,
+            nonce,
+            src,
+            timings,
+        });
+        let Some(Libp2pEvent::Dcutr(event)) = event else {
+            panic!("should map to a dcutr event");
+        };
+        assert_eq!(PeerId::from_enr(&target), event.remote_peer_id);
+
+        // outcomes reported by libp2p land in the shared cache
+        let mut cache = ReachabilityCache::default();
+        let failed = DcutrEvent {
+            outcome: DcutrOutcome::Failed,
+            ..event
+        };
+        adapter.on_dcutr_event(&mut cache, &failed).unwrap();
+        assert_eq!(
+            Ok(Some(Reachability::Unreachable)),
+            adapter.reachability(&cache, &failed.remote_peer_id)
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    notification::{decode_ip, decode_node_id},
+    redact::{redaction, RedactedSocket},
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpStream},
+    Enr, NodeId, RedactedDisplay, Redaction, NODE_ID_LENGTH,
+};
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr, SocketAddrV4},
+};
+
+/// Which address family to pick when an ENR has both an IPv4 and an IPv6 UDP socket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IpPreference {
+    #[default]
+    V4,
+    V6,
+}
+
+/// A node's UDP socket and node id, like the `NodeAddress` discv5 indexes sessions by. Maps the
+/// node ids in notifications to concrete sockets. The flow info and scope id of an IPv6 socket
+/// are kept locally, see [`NodeAddress::with_scope_id`], but never encoded, so the wire carries
+/// the canonical form of ip, port and node id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeAddress {
+    pub socket_addr: SocketAddr,
+    pub node_id: NodeId,
+}
+
+impl NodeAddress {
+    pub fn new(socket_addr: SocketAddr, node_id: NodeId) -> Self {
+        NodeAddress {
+            socket_addr,
+            node_id,
+        }
+    }
+
+    /// Returns the address of the node at the UDP socket of the ENR, IPv4 first. Returns `None` if
+    /// the ENR has no UDP socket.
+    pub fn from_enr(enr: &Enr) -> Option<Self> {
+        Self::from_enr_with(enr, IpPreference::default())
+    }
+
+    /// Returns the address of the node at the UDP socket of the ENR in the preferred address
+    /// family, falling back to the other family.
+    pub fn from_enr_with(enr: &Enr, preference: IpPreference) -> Option<Self> {
+        let v4 = enr.udp4_socket().map(SocketAddr::V4);
+        let v6 = enr.udp6_socket().map(SocketAddr::V6);
+        let socket_addr = match preference {
+            IpPreference::V4 => v4.or(v6),
+            IpPreference::V6 => v6.or(v4),
+        }?;
+        Some(NodeAddress::new(socket_addr, enr.node_id()))
+    }
+
+    /// Returns the addresses of the node at each UDP socket of the ENR, IPv4 first, so that a
+    /// peer reachable over both families is addressed over either.
+    pub fn all_from_enr(enr: &Enr) -> Vec<Self> {
+        let v4 = enr.udp4_socket().map(SocketAddr::V4);
+        let v6 = enr.udp6_socket().map(SocketAddr::V6);
+        v4.into_iter()
+            .chain(v6)
+            .map(|socket_addr| NodeAddress::new(socket_addr, enr.node_id()))
+            .collect()
+    }
+
+    /// Sets the scope id of an IPv6 socket, for example the interface of a link-local peer.
+    /// Addresses from ENRs and from the wire have none. IPv4 sockets are left as is.
+    pub fn with_scope_id(mut self, scope_id: u32) -> Self {
+        if let SocketAddr::V6(ref mut socket) = self.socket_addr {
+            socket.set_scope_id(scope_id);
+        }
+        self
+    }
+
+    /// Sets the flow info of an IPv6 socket. IPv4 sockets are left as is.
+    pub fn with_flow_info(mut self, flow_info: u32) -> Self {
+        if let SocketAddr::V6(ref mut socket) = self.socket_addr {
+            socket.set_flowinfo(flow_info);
+        }
+        self
+    }
+
+    /// The address as it is sent on the wire, without flow info and scope id.
+    pub fn canonical(&self) -> Self {
+        self.with_flow_info(0).with_scope_id(0)
+    }
+
+    /// Returns true if the address belongs to the node of the ENR and is one of its UDP sockets.
+    pub fn matches_enr(&self, enr: &Enr) -> bool {
+        if self.node_id != enr.node_id() {
+            return false;
+        }
+        match self.socket_addr {
+            SocketAddr::V4(socket) => enr.udp4_socket() == Some(socket),
+            SocketAddr::V6(socket) => {
+                enr.udp6_socket() == Some(socket)
+                    // an IPv4 socket may be reported mapped into IPv6
+                    || socket.ip().to_ipv4_mapped().is_some_and(|ip| {
+                        enr.udp4_socket() == Some(SocketAddrV4::new(ip, socket.port()))
+                    })
+            }
+        }
+    }
+}
+
+/// Encoded as a list of the ip, port and node id, the canonical form.
+impl Encodable for NodeAddress {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(3);
+        match self.socket_addr.ip() {
+            IpAddr::V4(ip) => s.append(&(&ip.octets() as &[u8])),
+            IpAddr::V6(ip) => s.append(&(&ip.octets() as &[u8])),
+        };
+        s.append(&self.socket_addr.port());
+        s.append(&(&self.node_id.raw() as &[u8]));
+    }
+}
+
+impl Decodable for NodeAddress {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 3 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let ip = decode_ip(&rlp.val_at::<Vec<u8>>(0)?)?;
+        let port = rlp.val_at::<u16>(1)?;
+        let node_id = decode_node_id::<NODE_ID_LENGTH>(rlp, 2)?;
+
+        Ok(NodeAddress::new(SocketAddr::new(ip, port), node_id))
+    }
+}
+
+impl fmt::Display for NodeAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_redacted(f, redaction())
+    }
+}
+
+impl RedactedDisplay for NodeAddress {
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>, redaction: Redaction) -> fmt::Result {
+        write!(
+            f,
+            "Node: {}, addr: {}",
+            self.node_id,
+            RedactedSocket(self.socket_addr, redaction)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rlp_backend::RlpCodec;
+    use enr::{CombinedKey, EnrBuilder};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn test_from_enr_by_preference() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .ip6("2001:db8::1".parse().unwrap())
+            .udp6(9001)
+            .build(&key)
+            .unwrap();
+
+        let v4 = NodeAddress::from_enr(&enr).unwrap();
+        assert_eq!(
+            "192.0.2.1:9000".parse::<SocketAddr>().unwrap(),
+            v4.socket_addr
+        );
+        let v6 = NodeAddress::from_enr_with(&enr, IpPreference::V6).unwrap();
+        assert_eq!(
+            "[2001:db8::1]:9001".parse::<SocketAddr>().unwrap(),
+            v6.socket_addr
+        );
+        assert!(v4.matches_enr(&enr) && v6.matches_enr(&enr));
+
+        let mapped = NodeAddress::new("[::ffff:192.0.2.1]:9000".parse().unwrap(), enr.node_id());
+        assert!(mapped.matches_enr(&enr));
+        let other_port = NodeAddress::new("192.0.2.1:9001".parse().unwrap(), enr.node_id());
+        assert!(!other_port.matches_enr(&enr));
+
+        let no_socket = EnrBuilder::new("v4").build(&key).unwrap();
+        assert_eq!(None, NodeAddress::from_enr(&no_socket));
+    }
+
+    #[test]
+    fn test_rlp_round_trip() {
+        for socket_addr in ["192.0.2.1:9000", "[2001:db8::1]:9001"] {
+            let address = NodeAddress::new(socket_addr.parse().unwrap(), NodeId::random());
+            assert_eq!(
+                address,
+                NodeAddress::decode_rlp(&address.encode_rlp()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_mixed_size_node_ids() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let zeros = rng.gen_range(0..=NODE_ID_LENGTH);
+            let mut raw = [0u8; NODE_ID_LENGTH];
+            rng.fill(&mut raw[zeros..]);
+            let ip = rng.gen::<[u8; 4]>();
+            let port = rng.gen::<u16>();
+            let address = NodeAddress::new(SocketAddr::from((ip, port)), NodeId::from(raw));
+            // any number of the leading zero bytes of the node id may be left out
+            let cut = rng.gen_range(0..=zeros);
+            let mut s = RlpStream::new_list(3);
+            s.append(&(&ip as &[u8]));
+            s.append(&port);
+            s.append(&(&raw[cut..]));
+
+            let decoded = NodeAddress::decode_rlp(&s.out()).unwrap();
+            assert_eq!(address, decoded);
+            // and re-encodes in the canonical, full length form
+            assert_eq!(address.encode_rlp(), decoded.encode_rlp());
+        }
+
+        let mut s = RlpStream::new_list(3);
+        s.append(&(&[192u8, 0, 2, 1] as &[u8]));
+        s.append(&9000u16);
+        s.append(&(&[1u8; NODE_ID_LENGTH + 1] as &[u8]));
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            NodeAddress::decode_rlp(&s.out())
+        );
+    }
+
+    #[test]
+    fn test_scope_id_kept_locally() {
+        let canonical = NodeAddress::new("[fe80::1]:9000".parse().unwrap(), NodeId::random());
+        let scoped = canonical.with_scope_id(3).with_flow_info(7);
+
+        let SocketAddr::V6(socket) = scoped.socket_addr else {
+            panic!("scoped socket should be IPv6");
+        };
+        assert_eq!((3, 7), (socket.scope_id(), socket.flowinfo()));
+        assert_ne!(canonical, scoped);
+        assert_eq!(canonical, scoped.canonical());
+
+        // ip, port and node id
+        let encoded = scoped.encode_rlp();
+        assert_eq!(canonical.encode_rlp(), encoded);
+        assert_eq!(1 + 17 + 3 + 33, encoded.len());
+        assert_eq!(canonical, NodeAddress::decode_rlp(&encoded).unwrap());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{
+    AttemptId, Candidates, Enr, ExtensionCodec, Extensions, MessageNonce, RelayInit, RelayMsg,
+    MESSAGE_NONCE_LENGTH, NODE_ID_LENGTH,
+};
+use enr::NodeId;
+use std::net::SocketAddr;
+
+/// Builds a [`RelayInit`], see [`RelayInit::builder`]. The required fields are taken up front, the
+/// optional ones are left out unless set.
+#[derive(Clone, Debug)]
+pub struct RelayInitBuilder<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>
+{
+    notif: RelayInit<N, I>,
+}
+
+impl<const N: usize> RelayInit<N> {
+    /// Starts building the relay init of an attempt by `initiator` to `target`, triggered by the
+    /// timed out request with `nonce`. Overlays with other node id lengths use
+    /// [`RelayInitBuilder::new`].
+    pub fn builder(initiator: Enr, target: NodeId, nonce: MessageNonce<N>) -> RelayInitBuilder<N> {
+        RelayInitBuilder::new(initiator, target, nonce)
+    }
+}
+
+impl<const N: usize, const I: usize> RelayInitBuilder<N, I> {
+    /// See [`RelayInit::builder`].
+    pub fn new(initiator: Enr, target: NodeId, nonce: MessageNonce<N>) -> Self {
+        RelayInitBuilder {
+            notif: RelayInit(initiator, target, nonce, None, None, None),
+        }
+    }
+
+    pub fn with_attempt_id(mut self, attempt_id: AttemptId) -> Self {
+        self.notif.3 = Some(attempt_id);
+        self
+    }
+
+    pub fn with_candidates(mut self, candidates: Candidates) -> Self {
+        self.notif.4 = Some(candidates);
+        self
+    }
+
+    /// Adds the extension, replacing any of the same type.
+    pub fn with_extension<T: ExtensionCodec>(mut self, ext: &T) -> Self {
+        self.notif
+            .5
+            .get_or_insert_with(Extensions::default)
+            .insert(ext);
+        self
+    }
+
+    pub fn build(self) -> RelayInit<N, I> {
+        self.notif
+    }
+}
+
+/// Builds a [`RelayMsg`], see [`RelayMsg::builder`]. The required fields are taken up front, the
+/// optional ones are left out unless set.
+#[derive(Clone, Debug)]
+pub struct RelayMsgBuilder<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH> {
+    notif: RelayMsg<N, I>,
+}
+
+impl<const N: usize> RelayMsg<N> {
+    /// Starts building the relay message of an attempt by `initiator`, triggered by the timed
+    /// out request with `nonce`. Overlays with other node id lengths use [`RelayMsgBuilder::new`].
+    pub fn builder(initiator: Enr, nonce: MessageNonce<N>) -> RelayMsgBuilder<N> {
+        RelayMsgBuilder::new(initiator, nonce)
+    }
+}
+
+impl<const N: usize, const I: usize> RelayMsgBuilder<N, I> {
+    /// See [`RelayMsg::builder`].
+    pub fn new(initiator: Enr, nonce: MessageNonce<N>) -> Self {
+        RelayMsgBuilder {
+            notif: RelayMsg(initiator, nonce, None, None, None, None, None),
+        }
+    }
+
+    pub fn with_attempt_id(mut self, attempt_id: AttemptId) -> Self {
+        self.notif.2 = Some(attempt_id);
+        self
+    }
+
+    pub fn with_candidates(mut self, candidates: Candidates) -> Self {
+        self.notif.3 = Some(candidates);
+        self
+    }
+
+    /// The socket the relay observes the target at.
+    pub fn with_observed(mut self, observed: SocketAddr) -> Self {
+        self.notif.4 = Some(observed);
+        self
+    }
+
+    pub fn with_relay_id(mut self, relay_id: NodeId) -> Self {
+        self.notif.5 = Some(relay_id);
+        self
+    }
+
+    /// Adds the extension, replacing any of the same type.
+    pub fn with_extension<T: ExtensionCodec>(mut self, ext: &T) -> Self {
+        self.notif
+            .6
+            .get_or_insert_with(Extensions::default)
+            .insert(ext);
+        self
+    }
+
+    pub fn build(self) -> RelayMsg<N, I> {
+        self.notif
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Candidate, CandidateKind, ProtocolVersion, ATTEMPT_ID_LENGTH};
+    use enr::{CombinedKey, EnrBuilder};
+
+    #[test]
+    fn test_builders_match_tuple_structs() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4").build(&key).unwrap();
+        let target = NodeId::random();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let attempt_id = [2u8; ATTEMPT_ID_LENGTH];
+        let addr: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let candidates = Candidates::new(vec![Candidate::new(CandidateKind::Host, addr)]);
+        let mut extensions = Extensions::default();
+        extensions.insert(&ProtocolVersion::CURRENT);
+
+        let init = RelayInit::builder(enr.clone(), target, nonce)
+            .with_attempt_id(attempt_id)
+            .with_candidates(candidates.clone())
+            .with_extension(&ProtocolVersion::CURRENT)
+            .build();
+        assert_eq!(
+            RelayInit(
+                enr.clone(),
+                target,
+                nonce,
+                Some(attempt_id),
+                Some(candidates),
+                Some(extensions)
+            ),
+            init
+        );
+
+        let relay_id = NodeId::random();
+        let msg = RelayMsg::builder(enr.clone(), nonce)
+            .with_observed(addr)
+            .with_relay_id(relay_id)
+            .build();
+        assert_eq!(
+            RelayMsg(enr, nonce, None, None, Some(addr), Some(relay_id), None),
+            msg
+        );
+    }
+}
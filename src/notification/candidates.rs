@@ -0,0 +1,163 @@
+use super::decode_ip;
+use crate::rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::{
+    cmp::Reverse,
+    fmt,
+    net::{IpAddr, SocketAddr},
+};
+
+/// Max number of candidates carried by a notification.
+pub const MAX_CANDIDATES: usize = 8;
+
+/// How a node learned an address it may be reachable at, like the candidate types of ICE. The
+/// numeric values are stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandidateKind {
+    /// An address bound on a local interface.
+    Host = 0,
+    /// The address a NAT maps a local address to, as observed by other peers.
+    Reflexive = 1,
+    /// An address on a relay forwarding packets to the node.
+    Relayed = 2,
+}
+
+impl CandidateKind {
+    /// The type preference of the kind in the ICE priority formula, direct paths first.
+    pub const fn type_preference(self) -> u32 {
+        match self {
+            CandidateKind::Host => 126,
+            CandidateKind::Reflexive => 100,
+            CandidateKind::Relayed => 0,
+        }
+    }
+}
+
+impl TryFrom<u8> for CandidateKind {
+    type Error = DecoderError;
+
+    fn try_from(kind: u8) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(CandidateKind::Host),
+            1 => Ok(CandidateKind::Reflexive),
+            2 => Ok(CandidateKind::Relayed),
+            _ => Err(DecoderError::Custom("invalid candidate kind")),
+        }
+    }
+}
+
+/// An address a node may be reachable at, with its priority. Higher priorities are probed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    pub kind: CandidateKind,
+    pub priority: u32,
+    pub addr: SocketAddr,
+}
+
+impl Candidate {
+    /// Returns a candidate with the default ICE priority of its kind.
+    pub fn new(kind: CandidateKind, addr: SocketAddr) -> Self {
+        Candidate {
+            kind,
+            priority: kind.type_preference() << 24 | u32::from(u16::MAX) << 8 | 255,
+            addr,
+        }
+    }
+}
+
+impl Encodable for Candidate {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(4);
+        s.append(&(self.kind as u8));
+        s.append(&self.priority);
+        match self.addr.ip() {
+            IpAddr::V4(ip) => s.append(&(&ip.octets() as &[u8])),
+            IpAddr::V6(ip) => s.append(&(&ip.octets() as &[u8])),
+        };
+        s.append(&self.addr.port());
+    }
+}
+
+impl Decodable for Candidate {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if rlp.item_count()? != 4 {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let kind = CandidateKind::try_from(rlp.val_at::<u8>(0)?)?;
+        let priority = rlp.val_at::<u32>(1)?;
+        let ip = decode_ip(&rlp.val_at::<Vec<u8>>(2)?)?;
+        let port = rlp.val_at::<u16>(3)?;
+
+        Ok(Candidate {
+            kind,
+            priority,
+            addr: SocketAddr::new(ip, port),
+        })
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} {} ({})", self.kind, self.addr, self.priority)
+    }
+}
+
+/// The addresses a node may be reachable at, ordered by priority, highest first. An optional
+/// extension of [`crate::RelayInit`] and [`crate::RelayMsg`] since protocol revision 3, so that
+/// the target can probe the initiator at more than the sockets of its ENR.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidates(Vec<Candidate>);
+
+impl Candidates {
+    /// Orders the candidates by priority, keeping the [`MAX_CANDIDATES`] highest ones.
+    pub fn new(mut candidates: Vec<Candidate>) -> Self {
+        candidates.sort_by_key(|candidate| Reverse(candidate.priority));
+        candidates.truncate(MAX_CANDIDATES);
+        Candidates(candidates)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Candidate> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl AsRef<[Candidate]> for Candidates {
+    fn as_ref(&self) -> &[Candidate] {
+        &self.0
+    }
+}
+
+impl Encodable for Candidates {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.append_list(&self.0);
+    }
+}
+
+impl Decodable for Candidates {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if rlp.item_count()? > MAX_CANDIDATES {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        Ok(Candidates::new(rlp.as_list()?))
+    }
+}
+
+impl fmt::Display for Candidates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut candidates = self.0.iter();
+        if let Some(first) = candidates.next() {
+            write!(f, "{}", first)?;
+        }
+        for candidate in candidates {
+            write!(f, ", {}", candidate)?;
+        }
+        Ok(())
+    }
+}
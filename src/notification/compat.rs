@@ -0,0 +1,179 @@
+use super::{MessageNonce, NODE_ID_LENGTH};
+use crate::rlp_backend::DecoderError;
+use enr::NodeId;
+
+/// The bytes of a node id received on the wire, `I` bytes long in overlays with node ids shorter
+/// than discv5's. Encoders treating node ids as integers drop their leading zero bytes, which
+/// [`Self::from_rlp_compat`] restores the way this crate's decoders do, for downstreams that
+/// decode node ids themselves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeIdBytes<const I: usize = NODE_ID_LENGTH>(pub [u8; I]);
+
+impl<const I: usize> NodeIdBytes<I> {
+    /// Node ids are held in a discv5 [`NodeId`], left padded with zeros.
+    const FITS_NODE_ID: () = assert!(I <= NODE_ID_LENGTH, "node id longer than discv5's");
+
+    /// Normalizes the payload of the RLP string holding a node id, left padding it with zeros if
+    /// it's shorter than `I`. An empty payload is the all zero node id. Longer payloads are
+    /// rejected.
+    pub fn from_rlp_compat(payload: &[u8]) -> Result<Self, DecoderError> {
+        left_pad(payload).map(NodeIdBytes)
+    }
+
+    /// The last `I` bytes of the node id, the ones on the wire. The leading bytes of a node id of
+    /// an overlay with shorter node ids are zero.
+    pub fn from_node_id(node_id: &NodeId) -> Self {
+        let () = Self::FITS_NODE_ID;
+        let raw = node_id.raw();
+        debug_assert!(
+            raw[..NODE_ID_LENGTH - I].iter().all(|byte| *byte == 0),
+            "node id longer than {I} bytes"
+        );
+        let mut bytes = [0u8; I];
+        bytes.copy_from_slice(&raw[NODE_ID_LENGTH - I..]);
+        NodeIdBytes(bytes)
+    }
+}
+
+impl<const I: usize> From<NodeIdBytes<I>> for NodeId {
+    fn from(bytes: NodeIdBytes<I>) -> Self {
+        let () = NodeIdBytes::<I>::FITS_NODE_ID;
+        NodeId::from(left_pad::<NODE_ID_LENGTH>(&bytes.0).expect("checked length"))
+    }
+}
+
+impl From<NodeId> for NodeIdBytes {
+    fn from(node_id: NodeId) -> Self {
+        NodeIdBytes(node_id.raw())
+    }
+}
+
+/// Normalizes the payload of the RLP string holding a nonce of `N` bytes, as
+/// [`NodeIdBytes::from_rlp_compat`] does node ids.
+pub fn nonce_from_rlp_compat<const N: usize>(
+    payload: &[u8],
+) -> Result<MessageNonce<N>, DecoderError> {
+    left_pad(payload)
+}
+
+fn left_pad<const L: usize>(payload: &[u8]) -> Result<[u8; L], DecoderError> {
+    if payload.len() > L {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    let mut padded = [0u8; L];
+    padded[L - payload.len()..].copy_from_slice(payload);
+    Ok(padded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rlp_backend::RlpStream;
+    use crate::{
+        Enr, Notification, NotificationType, RelayInit, TypeNumbering, MESSAGE_NONCE_LENGTH,
+    };
+    use enr::{CombinedKey, EnrBuilder};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Random bytes, the first `zeros` of them zero, as integers with that many leading zero
+    /// bytes are.
+    fn with_leading_zeros<const L: usize>(rng: &mut StdRng, zeros: usize) -> [u8; L] {
+        let mut bytes: [u8; L] = [0; L];
+        rng.fill(&mut bytes[zeros..]);
+        if zeros < L {
+            bytes[zeros] |= 1;
+        }
+        bytes
+    }
+
+    /// The bytes without their leading zeros, as an encoder treating them as an integer writes
+    /// them.
+    fn stripped(bytes: &[u8]) -> &[u8] {
+        let zeros = bytes.iter().take_while(|byte| **byte == 0).count();
+        &bytes[zeros..]
+    }
+
+    #[test]
+    fn test_node_id_bytes_from_rlp_compat() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let zeros = rng.gen_range(0..=NODE_ID_LENGTH);
+            let raw = with_leading_zeros::<NODE_ID_LENGTH>(&mut rng, zeros);
+            let node_id = NodeId::from(raw);
+            // the full length, stripped and any partly stripped encoding normalize alike
+            let cut = rng.gen_range(0..=zeros);
+            for payload in [&raw[..], stripped(&raw), &raw[cut..]] {
+                let bytes = NodeIdBytes::<NODE_ID_LENGTH>::from_rlp_compat(payload).unwrap();
+                assert_eq!(node_id, NodeId::from(bytes));
+            }
+        }
+
+        // overlays with shorter node ids normalize the same way
+        let short = with_leading_zeros::<20>(&mut rng, 3);
+        let bytes = NodeIdBytes::<20>::from_rlp_compat(stripped(&short)).unwrap();
+        assert_eq!(NodeIdBytes(short), bytes);
+        assert_eq!(bytes, NodeIdBytes::from_node_id(&NodeId::from(bytes)));
+
+        assert_eq!(
+            NodeIdBytes([0; NODE_ID_LENGTH]),
+            NodeIdBytes::from_rlp_compat(&[]).unwrap()
+        );
+        let max = NodeIdBytes([u8::MAX; NODE_ID_LENGTH]);
+        assert_eq!(max, NodeIdBytes::from_rlp_compat(&max.0).unwrap());
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            NodeIdBytes::<NODE_ID_LENGTH>::from_rlp_compat(&[1; NODE_ID_LENGTH + 1])
+        );
+    }
+
+    #[test]
+    fn test_nonce_from_rlp_compat() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let zeros = rng.gen_range(0..=MESSAGE_NONCE_LENGTH);
+            let nonce = with_leading_zeros::<MESSAGE_NONCE_LENGTH>(&mut rng, zeros);
+            assert_eq!(Ok(nonce), nonce_from_rlp_compat(stripped(&nonce)));
+            // overlays with shorter nonces normalize the same way
+            let short = with_leading_zeros::<8>(&mut rng, zeros.min(8));
+            assert_eq!(Ok(short), nonce_from_rlp_compat::<8>(stripped(&short)));
+        }
+
+        assert_eq!(Ok([0; MESSAGE_NONCE_LENGTH]), nonce_from_rlp_compat(&[]));
+        let max = [u8::MAX; MESSAGE_NONCE_LENGTH];
+        assert_eq!(Ok(max), nonce_from_rlp_compat(&max));
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            nonce_from_rlp_compat::<MESSAGE_NONCE_LENGTH>(&[1; MESSAGE_NONCE_LENGTH + 1])
+        );
+    }
+
+    #[test]
+    fn test_relay_init_decodes_stripped_target_and_nonce() {
+        let enr: Enr = EnrBuilder::new("v4")
+            .build(&CombinedKey::generate_secp256k1())
+            .unwrap();
+        let mut rng = StdRng::seed_from_u64(2);
+        for _ in 0..200 {
+            let zeros = rng.gen_range(0..=4);
+            let target = with_leading_zeros::<NODE_ID_LENGTH>(&mut rng, zeros);
+            let zeros = rng.gen_range(0..=4);
+            let nonce = with_leading_zeros::<MESSAGE_NONCE_LENGTH>(&mut rng, zeros);
+            let mut s = RlpStream::new_list(3);
+            s.append(&enr);
+            s.append(&stripped(&target));
+            s.append(&stripped(&nonce));
+            let mut encoded = vec![NotificationType::RelayInit.to_byte(TypeNumbering::default())];
+            encoded.extend_from_slice(&s.out());
+
+            let Notification::RelayInit(RelayInit(_, decoded_target, decoded_nonce, ..)) =
+                Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded).unwrap()
+            else {
+                panic!("expected a relay init");
+            };
+            assert_eq!(
+                (NodeId::from(target), nonce),
+                (decoded_target, decoded_nonce)
+            );
+        }
+    }
+}
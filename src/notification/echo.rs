@@ -0,0 +1,178 @@
+use super::{
+    append_optional, decode_extensions, decode_node_id, decode_nonce, optional_len, RlpNodeId,
+};
+use crate::{
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpCodec, RlpStream},
+    Extensions, MessageNonce, NodeId, Notification, NotificationType, WireConfig,
+    MESSAGE_NONCE_LENGTH, NODE_ID_LENGTH,
+};
+use std::{fmt, time::Duration};
+
+/// A notification asking the receiver to send back an [`Echo`] once the delay passed. Contains
+/// the node id of the sender, the nonce of the probe, the delay and optionally extensions. The
+/// sender stays silent towards the receiver meanwhile, so whether the echo arrives tells if the
+/// NAT mapping of the sender outlived the silence.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EchoAfter<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>(
+    pub NodeId,
+    pub MessageNonce<N>,
+    pub Duration,
+    pub Option<Extensions>,
+);
+
+/// A notification sent back to the sender of an [`EchoAfter`] once its delay passed. Contains the
+/// nonce of the probe and optionally extensions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Echo<const N: usize = MESSAGE_NONCE_LENGTH>(pub MessageNonce<N>, pub Option<Extensions>);
+
+impl<const N: usize, const I: usize> From<Notification<N, I>> for EchoAfter<N, I> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::EchoAfter(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
+
+impl<const N: usize, const I: usize> From<Notification<N, I>> for Echo<N> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::Echo(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
+
+impl<const N: usize, const I: usize> EchoAfter<N, I> {
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
+
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(56);
+        buf.push(NotificationType::EchoAfter.to_byte(wire.type_numbering));
+        self.encode_rlp_to(&mut buf);
+        wire.pad(&mut buf);
+        buf
+    }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+}
+
+impl<const N: usize> Echo<N> {
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
+
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(16);
+        buf.push(NotificationType::Echo.to_byte(wire.type_numbering));
+        self.encode_rlp_to(&mut buf);
+        wire.pad(&mut buf);
+        buf
+    }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+}
+
+impl<const N: usize, const I: usize> Encodable for EchoAfter<N, I> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let EchoAfter(sender, nonce, delay, extensions) = self;
+
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [extensions.map(|item| item as &dyn Encodable)];
+
+        s.begin_list(3 + optional_len(&optional));
+        s.append(&RlpNodeId::<I>(*sender));
+        s.append(&(nonce as &[u8]));
+        s.append(&u64::try_from(delay.as_millis()).unwrap_or(u64::MAX));
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize, const I: usize> Decodable for EchoAfter<N, I> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !(3..=4).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let sender = decode_node_id::<I>(rlp, 0)?;
+        let nonce = decode_nonce(rlp, 1)?;
+        let delay = Duration::from_millis(rlp.val_at::<u64>(2)?);
+        let extensions = decode_extensions(rlp, 3)?;
+
+        Ok(EchoAfter(sender, nonce, delay, extensions))
+    }
+}
+
+impl<const N: usize> Encodable for Echo<N> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let Echo(nonce, extensions) = self;
+
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [extensions.map(|item| item as &dyn Encodable)];
+
+        s.begin_list(1 + optional_len(&optional));
+        s.append(&(nonce as &[u8]));
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize> Decodable for Echo<N> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !(1..=2).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let nonce = decode_nonce(rlp, 0)?;
+        let extensions = decode_extensions(rlp, 1)?;
+
+        Ok(Echo(nonce, extensions))
+    }
+}
+
+impl<const N: usize, const I: usize> fmt::Display for EchoAfter<N, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sender = hex::encode(self.0.raw());
+        let nonce = hex::encode(self.1);
+        write!(
+            f,
+            "EchoAfter: Sender: 0x{}..{}, Nonce: 0x{}..{}, Delay: {}ms",
+            &sender[0..4],
+            &sender[sender.len() - 4..],
+            &nonce[0..2],
+            &nonce[nonce.len() - 2..],
+            self.2.as_millis()
+        )
+    }
+}
+
+impl<const N: usize> fmt::Display for Echo<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nonce = hex::encode(self.0);
+        write!(
+            f,
+            "Echo: Nonce: 0x{}..{}",
+            &nonce[0..2],
+            &nonce[nonce.len() - 2..]
+        )
+    }
+}
@@ -0,0 +1,491 @@
+use super::{
+    decode_frame, decode_ip, nonce_from_rlp_compat, optional_at, DecodeLimits, NodeIdBytes,
+    NotificationType, ReasonCode, WireConfig,
+};
+use crate::{
+    rlp_backend::{DecoderError, Rlp},
+    AttemptId, MessageNonce, NodeId, MESSAGE_NONCE_LENGTH, NODE_ID_LENGTH,
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use thiserror::Error;
+
+/// The buffer given to [`RelayInitRef::forward_into`] is shorter than the encoding, which needs
+/// the contained number of bytes.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("buffer too small, need {0} bytes")]
+pub struct BufferTooSmall(pub usize);
+
+/// A [`crate::RelayInit`] decoded without allocating, borrowing the variable length fields from
+/// the received datagram. The ENR is kept encoded and its signature is left for the target to
+/// verify, candidates and extensions are only checked to be RLP lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayInitRef<'a, const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>
+{
+    /// The RLP encoded ENR of the initiator, a list, or a string if compressed.
+    pub enr: &'a [u8],
+    pub target: NodeId,
+    pub nonce: MessageNonce<N>,
+    pub attempt_id: Option<AttemptId>,
+    /// The RLP encoded candidates.
+    pub candidates: Option<&'a [u8]>,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::RelayMsg`] decoded without allocating, see [`RelayInitRef`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayMsgRef<'a, const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH> {
+    /// The RLP encoded ENR of the initiator, a list, or a string if compressed.
+    pub enr: &'a [u8],
+    pub nonce: MessageNonce<N>,
+    pub attempt_id: Option<AttemptId>,
+    /// The RLP encoded candidates.
+    pub candidates: Option<&'a [u8]>,
+    pub observed: Option<SocketAddr>,
+    pub relay_id: Option<NodeId>,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::RelayAbort`] decoded without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayAbortRef<'a, const N: usize = MESSAGE_NONCE_LENGTH> {
+    pub nonce: MessageNonce<N>,
+    pub reason: ReasonCode,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::PunchReport`] decoded without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunchReportRef<'a, const N: usize = MESSAGE_NONCE_LENGTH> {
+    pub nonce: MessageNonce<N>,
+    pub punched: bool,
+    pub attempt_id: Option<AttemptId>,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::EchoAfter`] decoded without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoAfterRef<'a, const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>
+{
+    pub sender: NodeId,
+    pub nonce: MessageNonce<N>,
+    pub delay: Duration,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::Echo`] decoded without allocating.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoRef<'a, const N: usize = MESSAGE_NONCE_LENGTH> {
+    pub nonce: MessageNonce<N>,
+    /// The RLP encoded extensions.
+    pub extensions: Option<&'a [u8]>,
+}
+
+/// A [`crate::Notification`] decoded without allocating, for relays on devices without a heap,
+/// like home routers. Forwarding a [`RelayInitRef`] doesn't allocate either, see
+/// [`RelayInitRef::forward_into`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationRef<'a, const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>
+{
+    RelayInit(RelayInitRef<'a, N, I>),
+    RelayMsg(RelayMsgRef<'a, N, I>),
+    RelayAbort(RelayAbortRef<'a, N>),
+    PunchReport(PunchReportRef<'a, N>),
+    EchoAfter(EchoAfterRef<'a, N, I>),
+    Echo(EchoRef<'a, N>),
+}
+
+impl<'a, const N: usize, const I: usize> NotificationRef<'a, N, I> {
+    /// Decodes a notification under the default [`DecodeLimits`] and [`WireConfig`].
+    pub fn decode(data: &'a [u8]) -> Result<Self, DecoderError> {
+        Self::decode_with(data, &DecodeLimits::default(), &WireConfig::default())
+    }
+
+    /// Decodes a notification under the given size caps and wire format, accepting what
+    /// [`crate::Notification::rlp_decode_with_wire`] accepts, except for invalid ENRs,
+    /// candidates and extensions.
+    pub fn decode_with(
+        data: &'a [u8],
+        limits: &DecodeLimits,
+        wire: &WireConfig,
+    ) -> Result<Self, DecoderError> {
+        let (msg_type, rlp) = decode_frame(data, limits, wire)?;
+        let count = rlp.item_count()?;
+        let check_count = |min: usize, max: usize| {
+            if (min..=max).contains(&count) {
+                Ok(())
+            } else {
+                Err(DecoderError::RlpIncorrectListLen)
+            }
+        };
+        let notif = match msg_type {
+            NotificationType::RelayInit => {
+                check_count(3, 6)?;
+                NotificationRef::RelayInit(RelayInitRef {
+                    enr: enr_at(&rlp, 0)?,
+                    target: node_id_at::<I>(&rlp, 1)?,
+                    nonce: nonce_at(&rlp, 2)?,
+                    attempt_id: attempt_id_at(&rlp, 3)?,
+                    candidates: list_at(&rlp, 4)?,
+                    extensions: list_at(&rlp, 5)?,
+                })
+            }
+            NotificationType::RelayMsg => {
+                check_count(2, 7)?;
+                NotificationRef::RelayMsg(RelayMsgRef {
+                    enr: enr_at(&rlp, 0)?,
+                    nonce: nonce_at(&rlp, 1)?,
+                    attempt_id: attempt_id_at(&rlp, 2)?,
+                    candidates: list_at(&rlp, 3)?,
+                    observed: socket_addr_at(&rlp, 4)?,
+                    relay_id: optional_at(&rlp, 5)?
+                        .map(|_| node_id_at::<I>(&rlp, 5))
+                        .transpose()?,
+                    extensions: list_at(&rlp, 6)?,
+                })
+            }
+            NotificationType::RelayAbort => {
+                check_count(2, 3)?;
+                NotificationRef::RelayAbort(RelayAbortRef {
+                    nonce: nonce_at(&rlp, 0)?,
+                    reason: ReasonCode::try_from(rlp.val_at::<u8>(1)?)?,
+                    extensions: list_at(&rlp, 2)?,
+                })
+            }
+            NotificationType::PunchReport => {
+                check_count(2, 4)?;
+                NotificationRef::PunchReport(PunchReportRef {
+                    nonce: nonce_at(&rlp, 0)?,
+                    punched: rlp.val_at(1)?,
+                    attempt_id: attempt_id_at(&rlp, 2)?,
+                    extensions: list_at(&rlp, 3)?,
+                })
+            }
+            NotificationType::EchoAfter => {
+                check_count(3, 4)?;
+                NotificationRef::EchoAfter(EchoAfterRef {
+                    sender: node_id_at::<I>(&rlp, 0)?,
+                    nonce: nonce_at(&rlp, 1)?,
+                    delay: Duration::from_millis(rlp.val_at(2)?),
+                    extensions: list_at(&rlp, 3)?,
+                })
+            }
+            NotificationType::Echo => {
+                check_count(1, 2)?;
+                NotificationRef::Echo(EchoRef {
+                    nonce: nonce_at(&rlp, 0)?,
+                    extensions: list_at(&rlp, 1)?,
+                })
+            }
+        };
+        Ok(notif)
+    }
+}
+
+impl<'a, const N: usize, const I: usize> RelayInitRef<'a, N, I> {
+    /// Encodes the [`crate::RelayMsg`] forwarding this notification to the target into `buf`,
+    /// returning its length. Like [`crate::RelayInit::into_relay_msg`], the attempt id is echoed,
+    /// and `observed` and `relay_id` are appended, as far as `wire` enables them. The ENR is
+    /// forwarded as received, regardless of [`WireConfig::compress_enr`].
+    pub fn forward_into(
+        &self,
+        buf: &mut [u8],
+        wire: &WireConfig,
+        observed: Option<SocketAddr>,
+        relay_id: Option<NodeId>,
+    ) -> Result<usize, BufferTooSmall> {
+        let attempt_id = self.attempt_id.filter(|_| wire.attempt_ids);
+        let relay_id = relay_id
+            .filter(|_| wire.relay_ids)
+            .map(|id| NodeIdBytes::<I>::from_node_id(&id).0);
+        let observed = observed.filter(|_| wire.observed_addrs).map(RlpSocket::new);
+        let optional = [
+            attempt_id.as_ref().map(|id| Item::Bytes(id)),
+            self.candidates.map(Item::Raw),
+            observed.as_ref().map(Item::Socket),
+            relay_id.as_ref().map(|id| Item::Bytes(id)),
+            self.extensions.map(Item::Raw),
+        ];
+        let optional_len = optional
+            .iter()
+            .rposition(Option::is_some)
+            .map_or(0, |last| last + 1);
+        let items = [Some(Item::Raw(self.enr)), Some(Item::Bytes(&self.nonce))]
+            .into_iter()
+            .chain(optional.into_iter().take(optional_len))
+            .map(|item| item.unwrap_or(Item::Bytes(&[])));
+
+        let payload_len: usize = items.clone().map(|item| item.len()).sum();
+        let len = 1 + header_len(payload_len) + payload_len;
+        let padded_len = wire.padded_len(len);
+        if buf.len() < padded_len {
+            return Err(BufferTooSmall(padded_len));
+        }
+        buf[0] = NotificationType::RelayMsg.to_byte(wire.type_numbering);
+        let mut pos = 1 + write_header(&mut buf[1..], 0xc0, payload_len);
+        for item in items {
+            pos += item.write(&mut buf[pos..]);
+        }
+        let (notif, trailer) = buf[..padded_len].split_at_mut(len);
+        wire.fill_trailer(notif, trailer);
+        Ok(padded_len)
+    }
+}
+
+/// An item of a notification encoded by [`RelayInitRef::forward_into`].
+#[derive(Clone, Copy)]
+enum Item<'a> {
+    /// An already encoded item.
+    Raw(&'a [u8]),
+    Bytes(&'a [u8]),
+    Socket(&'a RlpSocket),
+}
+
+impl Item<'_> {
+    fn len(&self) -> usize {
+        match self {
+            Item::Raw(raw) => raw.len(),
+            Item::Bytes(bytes) => bytes_len(bytes),
+            Item::Socket(socket) => {
+                let payload_len = socket.payload_len();
+                header_len(payload_len) + payload_len
+            }
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) -> usize {
+        match self {
+            Item::Raw(raw) => {
+                buf[..raw.len()].copy_from_slice(raw);
+                raw.len()
+            }
+            Item::Bytes(bytes) => write_bytes(buf, bytes),
+            Item::Socket(socket) => {
+                let header = write_header(buf, 0xc0, socket.payload_len());
+                let ip = write_bytes(&mut buf[header..], socket.ip());
+                header + ip + write_bytes(&mut buf[header + ip..], socket.port())
+            }
+        }
+    }
+}
+
+/// A socket encoded as a list of its ip and port, without allocating.
+struct RlpSocket {
+    ip: [u8; 16],
+    ip_len: usize,
+    port: [u8; 2],
+}
+
+impl RlpSocket {
+    fn new(addr: SocketAddr) -> Self {
+        let mut ip = [0u8; 16];
+        let ip_len = match addr.ip() {
+            IpAddr::V4(v4) => {
+                ip[..4].copy_from_slice(&v4.octets());
+                4
+            }
+            IpAddr::V6(v6) => {
+                ip.copy_from_slice(&v6.octets());
+                16
+            }
+        };
+        RlpSocket {
+            ip,
+            ip_len,
+            port: addr.port().to_be_bytes(),
+        }
+    }
+
+    fn ip(&self) -> &[u8] {
+        &self.ip[..self.ip_len]
+    }
+
+    /// The port as an RLP integer, without leading zeros.
+    fn port(&self) -> &[u8] {
+        let zeros = self.port.iter().take_while(|byte| **byte == 0).count();
+        &self.port[zeros..]
+    }
+
+    fn payload_len(&self) -> usize {
+        bytes_len(self.ip()) + bytes_len(self.port())
+    }
+}
+
+/// The length of the header of an RLP item holding `len` bytes.
+fn header_len(len: usize) -> usize {
+    if len <= 55 {
+        1
+    } else {
+        1 + len_of_len(len)
+    }
+}
+
+fn len_of_len(len: usize) -> usize {
+    (usize::BITS - len.leading_zeros()).div_ceil(8) as usize
+}
+
+/// The length of `bytes` encoded as an RLP string.
+fn bytes_len(bytes: &[u8]) -> usize {
+    match bytes {
+        [byte] if *byte < 0x80 => 1,
+        _ => header_len(bytes.len()) + bytes.len(),
+    }
+}
+
+/// Writes the header of an RLP string, `offset` 0x80, or list, 0xc0, holding `len` bytes.
+fn write_header(buf: &mut [u8], offset: u8, len: usize) -> usize {
+    if len <= 55 {
+        buf[0] = offset + len as u8;
+        return 1;
+    }
+    let len_of_len = len_of_len(len);
+    buf[0] = offset + 55 + len_of_len as u8;
+    buf[1..=len_of_len]
+        .copy_from_slice(&len.to_be_bytes()[usize::BITS as usize / 8 - len_of_len..]);
+    1 + len_of_len
+}
+
+fn write_bytes(buf: &mut [u8], bytes: &[u8]) -> usize {
+    if let [byte] = bytes {
+        if *byte < 0x80 {
+            buf[0] = *byte;
+            return 1;
+        }
+    }
+    let header = write_header(buf, 0x80, bytes.len());
+    buf[header..header + bytes.len()].copy_from_slice(bytes);
+    header + bytes.len()
+}
+
+/// Returns the item at `index` unless it's absent or an empty string standing in for an absent
+/// item.
+fn enr_at<'a>(rlp: &Rlp<'a>, index: usize) -> Result<&'a [u8], DecoderError> {
+    let enr = rlp.at(index)?;
+    if enr.is_empty() {
+        return Err(DecoderError::Custom("empty ENR"));
+    }
+    Ok(enr.as_raw())
+}
+
+fn nonce_at<const N: usize>(rlp: &Rlp<'_>, index: usize) -> Result<MessageNonce<N>, DecoderError> {
+    nonce_from_rlp_compat(rlp.at(index)?.data()?)
+}
+
+fn node_id_at<const I: usize>(rlp: &Rlp<'_>, index: usize) -> Result<NodeId, DecoderError> {
+    NodeIdBytes::<I>::from_rlp_compat(rlp.at(index)?.data()?).map(NodeId::from)
+}
+
+fn attempt_id_at(rlp: &Rlp<'_>, index: usize) -> Result<Option<AttemptId>, DecoderError> {
+    let Some(attempt_id) = optional_at(rlp, index)? else {
+        return Ok(None);
+    };
+    attempt_id
+        .data()?
+        .try_into()
+        .map(Some)
+        .map_err(|_| DecoderError::Custom("invalid attempt id length"))
+}
+
+fn list_at<'a>(rlp: &Rlp<'a>, index: usize) -> Result<Option<&'a [u8]>, DecoderError> {
+    match optional_at(rlp, index)? {
+        Some(item) if item.is_list() => Ok(Some(item.as_raw())),
+        Some(_) => Err(DecoderError::RlpExpectedToBeList),
+        None => Ok(None),
+    }
+}
+
+fn socket_addr_at(rlp: &Rlp<'_>, index: usize) -> Result<Option<SocketAddr>, DecoderError> {
+    let Some(socket) = optional_at(rlp, index)? else {
+        return Ok(None);
+    };
+    if socket.item_count()? != 2 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    let ip = decode_ip(socket.at(0)?.data()?)?;
+    Ok(Some(SocketAddr::new(ip, socket.val_at(1)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Candidate, CandidateKind, Candidates, Notification, ProtocolVersion, RelayExtras, RelayInit,
+    };
+    use enr::{CombinedKey, EnrBuilder};
+
+    #[test]
+    fn test_decode_and_forward_without_allocating() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4").build(&key).unwrap();
+        let addr: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let init = RelayInit::builder(enr, NodeId::random(), [1u8; MESSAGE_NONCE_LENGTH])
+            .with_attempt_id([2u8; 4])
+            .with_candidates(Candidates::new(vec![Candidate::new(
+                CandidateKind::Host,
+                addr,
+            )]))
+            .with_extension(&ProtocolVersion::CURRENT)
+            .build();
+        let wire = WireConfig {
+            attempt_ids: true,
+            observed_addrs: true,
+            relay_ids: true,
+            padding_bucket: Some(64),
+            ..Default::default()
+        };
+        let encoded = init.to_rlp_with(&wire);
+
+        let NotificationRef::RelayInit(init_ref) =
+            NotificationRef::<MESSAGE_NONCE_LENGTH>::decode_with(
+                &encoded,
+                &DecodeLimits::default(),
+                &wire,
+            )
+            .unwrap()
+        else {
+            panic!("expected a relay init")
+        };
+        assert_eq!(init.1, init_ref.target);
+        assert_eq!(init.2, init_ref.nonce);
+        assert_eq!(init.3, init_ref.attempt_id);
+
+        // forwarding encodes what the allocating path does
+        let observed: SocketAddr = "[2001:db8::1]:30303".parse().unwrap();
+        let relay_id = NodeId::random();
+        let mut buf = [0u8; 1280];
+        let len = init_ref
+            .forward_into(&mut buf, &wire, Some(observed), Some(relay_id))
+            .unwrap();
+        let msg = init.clone().into_relay_msg(RelayExtras {
+            echo_attempt_id: true,
+            observed: Some(observed),
+            relay_id: Some(relay_id),
+            ..Default::default()
+        });
+        assert_eq!(msg.to_rlp_with(&wire), &buf[..len]);
+        assert_eq!(
+            Err(BufferTooSmall(len)),
+            init_ref.forward_into(&mut buf[..len - 1], &wire, Some(observed), Some(relay_id))
+        );
+
+        let NotificationRef::RelayMsg(msg_ref) =
+            NotificationRef::<MESSAGE_NONCE_LENGTH>::decode(&buf[..len]).unwrap()
+        else {
+            panic!("expected a relay msg")
+        };
+        assert_eq!(init_ref.enr, msg_ref.enr);
+        assert_eq!(Some(observed), msg_ref.observed);
+        assert_eq!(Some(relay_id), msg_ref.relay_id);
+        assert_eq!(
+            Notification::RelayMsg(msg),
+            Notification::rlp_decode(&buf[..len]).unwrap()
+        );
+    }
+}
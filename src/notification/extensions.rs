@@ -0,0 +1,117 @@
+use crate::rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpStream};
+use std::fmt;
+
+/// Max number of extensions carried by a notification.
+pub const MAX_EXTENSIONS: usize = 16;
+
+/// Type of an [`Extension`]. Types are assigned by the features that define them.
+pub type ExtensionType = u16;
+
+/// A typed opaque item of the extension list of a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    pub ty: ExtensionType,
+    pub data: Vec<u8>,
+}
+
+/// Encodes and decodes the payload of an extension of a given type, so that features can add
+/// fields to notifications without a new protocol revision.
+pub trait ExtensionCodec: Sized {
+    /// The type the extension is sent as.
+    const TYPE: ExtensionType;
+
+    fn encode(&self) -> Vec<u8>;
+    fn decode(data: &[u8]) -> Result<Self, DecoderError>;
+}
+
+/// The extension list at the end of a notification, optional since protocol revision 8. Decoders
+/// skip extensions of unknown types, so that notifications can grow new fields without breaking
+/// peers of the same revision.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extensions(Vec<Extension>);
+
+impl Extensions {
+    /// Decodes the extension of the codec's type, if present.
+    pub fn get<T: ExtensionCodec>(&self) -> Option<Result<T, DecoderError>> {
+        self.get_raw(T::TYPE).map(T::decode)
+    }
+
+    /// Encodes `ext`, replacing any extension of the same type.
+    pub fn insert<T: ExtensionCodec>(&mut self, ext: &T) {
+        self.insert_raw(T::TYPE, ext.encode());
+    }
+
+    /// The payload of the extension of the given type, if present.
+    pub fn get_raw(&self, ty: ExtensionType) -> Option<&[u8]> {
+        self.0
+            .iter()
+            .find(|ext| ext.ty == ty)
+            .map(|ext| ext.data.as_slice())
+    }
+
+    /// Adds an extension, replacing any extension of the same type.
+    pub fn insert_raw(&mut self, ty: ExtensionType, data: Vec<u8>) {
+        match self.0.iter_mut().find(|ext| ext.ty == ty) {
+            Some(ext) => ext.data = data,
+            None => self.0.push(Extension { ty, data }),
+        }
+    }
+
+    /// Removes the extension of the given type, returning its payload.
+    pub fn remove(&mut self, ty: ExtensionType) -> Option<Vec<u8>> {
+        let index = self.0.iter().position(|ext| ext.ty == ty)?;
+        Some(self.0.remove(index).data)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Extension> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Encodable for Extensions {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(self.0.len());
+        for ext in self.0.iter() {
+            s.begin_list(2);
+            s.append(&ext.ty);
+            s.append(&ext.data);
+        }
+    }
+}
+
+impl Decodable for Extensions {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if rlp.item_count()? > MAX_EXTENSIONS {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let mut extensions = Extensions::default();
+        for ext in rlp.iter() {
+            if ext.item_count()? != 2 {
+                return Err(DecoderError::RlpIncorrectListLen);
+            }
+            extensions.insert_raw(ext.val_at(0)?, ext.val_at(1)?);
+        }
+        Ok(extensions)
+    }
+}
+
+impl fmt::Display for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut types = self.0.iter().map(|ext| ext.ty);
+        if let Some(first) = types.next() {
+            write!(f, "{}", first)?;
+        }
+        for ty in types {
+            write!(f, ", {}", ty)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+use super::{ExtensionCodec, ExtensionType};
+use crate::rlp_backend::DecoderError;
+use parse_display_derive::Display;
+
+/// What a relay without a session with the target tried instead of relaying, sent as an
+/// extension of the [`crate::RelayAbort`] so the initiator knows whether retrying the relay may
+/// pay off. The numeric values are stable.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+#[display(style = "kebab-case")]
+pub enum FallbackStrategy {
+    /// The relay pinged the target at the sockets of its ENR, starting a discv5 handshake with a
+    /// random packet. A later try over the relay may find a session with the target.
+    WakeupPing = 1,
+}
+
+impl ExtensionCodec for FallbackStrategy {
+    const TYPE: ExtensionType = 4;
+
+    fn encode(&self) -> Vec<u8> {
+        vec![*self as u8]
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecoderError> {
+        match data {
+            [1] => Ok(FallbackStrategy::WakeupPing),
+            [_] => Err(DecoderError::Custom("invalid fallback strategy")),
+            _ => Err(DecoderError::Custom("fallback strategy is one byte")),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+/// Default max size of a notification, the max size of a discv5 packet.
+pub const DEFAULT_MAX_NOTIFICATION_SIZE: usize = 1280;
+/// Default max size of the ENR in a notification, the max size of an ENR by EIP-778.
+pub const DEFAULT_MAX_ENR_SIZE: usize = 300;
+
+/// Size caps enforced before RLP parsing a received notification, so that garbage is dropped
+/// cheaply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Max size of an encoded notification, including any padding.
+    pub max_notification_size: usize,
+    /// Max size of the encoded ENR in a notification.
+    pub max_enr_size: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_notification_size: DEFAULT_MAX_NOTIFICATION_SIZE,
+            max_enr_size: DEFAULT_MAX_ENR_SIZE,
+        }
+    }
+}
@@ -1,89 +1,455 @@
-use crate::impl_from_variant_wrap;
+use crate::rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpCodec, RlpStream};
 pub use enr::{CombinedKey, NodeId};
 use parse_display_derive::Display;
-use rlp::{DecoderError, Rlp};
+use std::net::{IpAddr, SocketAddr};
 
+mod builder;
+mod candidates;
+mod compat;
+mod echo;
+#[cfg(feature = "embedded")]
+mod embedded;
+mod extensions;
+mod fallback;
+mod limits;
+mod notif_type;
+mod pool;
+mod punch_report;
+mod punch_toward;
+mod quick_reject;
+mod reason;
+mod recent_nonces;
+mod relay_abort;
 mod relay_init;
 mod relay_msg;
+mod snappy;
+mod validate;
+mod version;
+mod wire;
 
-pub use relay_init::RelayInit;
+pub use builder::{RelayInitBuilder, RelayMsgBuilder};
+pub use candidates::{Candidate, CandidateKind, Candidates, MAX_CANDIDATES};
+pub use compat::{nonce_from_rlp_compat, NodeIdBytes};
+pub use echo::{Echo, EchoAfter};
+#[cfg(feature = "embedded")]
+pub use embedded::{
+    BufferTooSmall, EchoAfterRef, EchoRef, NotificationRef, PunchReportRef, RelayAbortRef,
+    RelayInitRef, RelayMsgRef,
+};
+pub use extensions::{Extension, ExtensionCodec, ExtensionType, Extensions, MAX_EXTENSIONS};
+pub use fallback::FallbackStrategy;
+pub use limits::{DecodeLimits, DEFAULT_MAX_ENR_SIZE, DEFAULT_MAX_NOTIFICATION_SIZE};
+pub use notif_type::{peek_notif_type, peek_notif_type_with, NotificationType, TypeNumbering};
+pub use punch_report::PunchReport;
+pub use punch_toward::PunchToward;
+pub use quick_reject::{quick_reject, quick_reject_with, RejectReason};
+pub use reason::ReasonCode;
+pub use recent_nonces::{RecentNonces, MAX_RECENT_NONCES};
+pub use relay_abort::RelayAbort;
+pub use relay_init::{RelayExtras, RelayInit};
 pub use relay_msg::RelayMsg;
+pub use validate::{ValidationError, ValidationPolicy};
+pub use version::ProtocolVersion;
+pub use wire::{ProtocolNamespace, WireConfig, NAMESPACE_TAG_LENGTH};
 
 /// Discv5 message nonce length in bytes.
 pub const MESSAGE_NONCE_LENGTH: usize = 12;
-/// Discv5 node id length in bytes.
+/// Attempt id length in bytes.
+pub const ATTEMPT_ID_LENGTH: usize = 4;
+/// Discv5 node id length in bytes. Overlays using shorter node ids set `I`, holding them left
+/// padded in a [`NodeId`].
 pub const NODE_ID_LENGTH: usize = 32;
 /// RelayInit notification type.
+#[deprecated(note = "use `NotificationType::RelayInit.to_byte(TypeNumbering::Current)`")]
 pub const REALYINIT_MSG_TYPE: u8 = 7;
 /// RelayMsg notification type.
+#[deprecated(note = "use `NotificationType::RelayMsg.to_byte(TypeNumbering::Current)`")]
 pub const REALYMSG_MSG_TYPE: u8 = 8;
+/// RelayAbort notification type.
+#[deprecated(note = "use `NotificationType::RelayAbort.to_byte(TypeNumbering::Current)`")]
+pub const REALYABORT_MSG_TYPE: u8 = 9;
+/// RelayInit notification type in the legacy numbering.
+#[deprecated(note = "use `NotificationType::RelayInit.to_byte(TypeNumbering::Legacy)`")]
+pub const REALY_INIT_NOTIF_TYPE: u8 = 0;
+/// RelayMsg notification type in the legacy numbering.
+#[deprecated(note = "use `NotificationType::RelayMsg.to_byte(TypeNumbering::Legacy)`")]
+pub const REALY_MSG_NOTIF_TYPE: u8 = 1;
 
 /// Enr using same key type as sigp/discv5.
 pub type Enr = enr::Enr<CombinedKey>;
-/// Discv5 message nonce.
-pub type MessageNonce = [u8; MESSAGE_NONCE_LENGTH];
+/// Message nonce, of [`MESSAGE_NONCE_LENGTH`] bytes in discv5. Overlays using other nonce sizes
+/// set `N`.
+pub type MessageNonce<const N: usize = MESSAGE_NONCE_LENGTH> = [u8; N];
+/// Random id of a hole punch attempt, generated by the initiator and echoed by the relay, so that
+/// the logs of the three nodes can be correlated. Optional on the wire since protocol revision 2,
+/// see [`WireConfig::attempt_ids`].
+pub type AttemptId = [u8; ATTEMPT_ID_LENGTH];
 
-/// A unicast notification sent over discv5.
-#[derive(Debug, Display, PartialEq, Eq)]
-pub enum Notification {
+/// A unicast notification sent over discv5, or an overlay with `N` byte message nonces and `I`
+/// byte node ids.
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+pub enum Notification<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH> {
     /// A notification to initialise a one-shot relay circuit for hole-punching.
     #[display("Notification: {0}")]
-    RelayInit(RelayInit),
+    RelayInit(RelayInit<N, I>),
     /// The notification relayed to target of hole punch attempt.
     #[display("Notification: {0}")]
-    RelayMsg(RelayMsg),
+    RelayMsg(RelayMsg<N, I>),
+    /// The notification sent back to the initiator by a relay that won't relay the attempt.
+    #[display("Notification: {0}")]
+    RelayAbort(RelayAbort<N>),
+    /// The notification sent back to the relay by the initiator once a relayed attempt resolved.
+    #[display("Notification: {0}")]
+    PunchReport(PunchReport<N>),
+    /// The notification asking the receiver to echo after a delay, to measure the lifetime of the
+    /// NAT mapping of the sender.
+    #[display("Notification: {0}")]
+    EchoAfter(EchoAfter<N, I>),
+    /// The notification echoed to the sender of an [`EchoAfter`] once its delay passed.
+    #[display("Notification: {0}")]
+    Echo(Echo<N>),
+}
+
+impl<const N: usize, const I: usize> From<RelayInit<N, I>> for Notification<N, I> {
+    fn from(notif: RelayInit<N, I>) -> Self {
+        Self::RelayInit(notif)
+    }
+}
+
+impl<const N: usize, const I: usize> From<RelayMsg<N, I>> for Notification<N, I> {
+    fn from(notif: RelayMsg<N, I>) -> Self {
+        Self::RelayMsg(notif)
+    }
+}
+
+impl<const N: usize, const I: usize> From<RelayAbort<N>> for Notification<N, I> {
+    fn from(notif: RelayAbort<N>) -> Self {
+        Self::RelayAbort(notif)
+    }
+}
+
+impl<const N: usize, const I: usize> From<PunchReport<N>> for Notification<N, I> {
+    fn from(notif: PunchReport<N>) -> Self {
+        Self::PunchReport(notif)
+    }
 }
 
-impl_from_variant_wrap!(, RelayInit, Notification, Self::RelayInit);
-impl_from_variant_wrap!(, RelayMsg, Notification, Self::RelayMsg);
+impl<const N: usize, const I: usize> From<EchoAfter<N, I>> for Notification<N, I> {
+    fn from(notif: EchoAfter<N, I>) -> Self {
+        Self::EchoAfter(notif)
+    }
+}
+
+impl<const N: usize, const I: usize> From<Echo<N>> for Notification<N, I> {
+    fn from(notif: Echo<N>) -> Self {
+        Self::Echo(notif)
+    }
+}
 
-impl Notification {
+impl<const N: usize, const I: usize> Notification<N, I> {
+    /// Decodes a notification, stripping any zero padding after the RLP list, see
+    /// [`WireConfig::padding_bucket`]. Enforces the default [`DecodeLimits`].
     pub fn rlp_decode(data: &[u8]) -> Result<Self, DecoderError> {
-        if data.len() < 3 {
-            return Err(DecoderError::RlpIsTooShort);
+        Self::rlp_decode_with(data, &DecodeLimits::default())
+    }
+
+    /// Decodes a notification, dropping it before parsing its ENR if it exceeds the given size
+    /// caps.
+    pub fn rlp_decode_with(data: &[u8], limits: &DecodeLimits) -> Result<Self, DecoderError> {
+        Self::rlp_decode_with_wire(data, limits, &WireConfig::default())
+    }
+
+    /// Decodes a notification according to the given wire format options, see
+    /// [`WireConfig::type_numbering`].
+    pub fn rlp_decode_with_wire(
+        data: &[u8],
+        limits: &DecodeLimits,
+        wire: &WireConfig,
+    ) -> Result<Self, DecoderError> {
+        let (msg_type, rlp) = decode_frame(data, limits, wire)?;
+        match msg_type {
+            NotificationType::RelayInit => Ok(RelayInit::decode_with(&rlp, limits)?.into()),
+            NotificationType::RelayMsg => Ok(RelayMsg::decode_with(&rlp, limits)?.into()),
+            NotificationType::RelayAbort => Ok(RelayAbort::decode(&rlp)?.into()),
+            NotificationType::PunchReport => Ok(PunchReport::decode(&rlp)?.into()),
+            NotificationType::EchoAfter => Ok(EchoAfter::decode(&rlp)?.into()),
+            NotificationType::Echo => Ok(Echo::decode(&rlp)?.into()),
         }
-        let msg_type = data[0];
+    }
 
-        let rlp = Rlp::new(&data[1..]);
-        let list_len = rlp.item_count()?;
-        if list_len < 2 {
-            return Err(DecoderError::RlpIsTooShort);
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
+
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
+        match self {
+            Notification::RelayInit(notif) => notif.to_rlp_with(wire),
+            Notification::RelayMsg(notif) => notif.to_rlp_with(wire),
+            Notification::RelayAbort(notif) => notif.to_rlp_with(wire),
+            Notification::PunchReport(notif) => notif.to_rlp_with(wire),
+            Notification::EchoAfter(notif) => notif.to_rlp_with(wire),
+            Notification::Echo(notif) => notif.to_rlp_with(wire),
         }
+    }
 
-        let initiator = rlp.val_at::<Enr>(0)?;
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+}
+
+/// The item at `index` of the list, or `None` if the list is shorter or the item is empty.
+pub(crate) fn optional_at<'a>(
+    rlp: &Rlp<'a>,
+    index: usize,
+) -> Result<Option<Rlp<'a>>, DecoderError> {
+    if rlp.item_count()? <= index {
+        return Ok(None);
+    }
+    let item = rlp.at(index)?;
+    Ok((!item.is_empty()).then_some(item))
+}
 
-        let nonce_bytes = rlp.val_at::<Vec<u8>>(list_len - 1)?;
+/// Checks the size caps, type, padding and namespace of a notification, returning its type and its RLP list.
+pub(crate) fn decode_frame<'a>(
+    data: &'a [u8],
+    limits: &DecodeLimits,
+    wire: &WireConfig,
+) -> Result<(NotificationType, Rlp<'a>), DecoderError> {
+    if data.len() < 3 {
+        return Err(DecoderError::RlpIsTooShort);
+    }
+    if data.len() > limits.max_notification_size {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+    let msg_type = peek_notif_type_with(data, wire.type_numbering)?;
 
-        if nonce_bytes.len() > MESSAGE_NONCE_LENGTH {
-            return Err(DecoderError::RlpIsTooBig);
+    let list_info = Rlp::new(&data[1..]).payload_info()?;
+    let list_end = 1 + list_info.header_len + list_info.value_len;
+    if data.len() < list_end {
+        return Err(DecoderError::RlpIsTooShort);
+    }
+    if !wire.check_trailer(&data[..list_end], &data[list_end..]) {
+        if wire.namespace.is_some() {
+            return Err(DecoderError::Custom("foreign protocol namespace"));
         }
-        let mut nonce = [0u8; MESSAGE_NONCE_LENGTH];
-        nonce[MESSAGE_NONCE_LENGTH - nonce_bytes.len()..].copy_from_slice(&nonce_bytes);
+        return Err(DecoderError::RlpInconsistentLengthAndData);
+    }
 
-        match msg_type {
-            REALYINIT_MSG_TYPE => {
-                if list_len != 3 {
-                    return Err(DecoderError::RlpIncorrectListLen);
-                }
-                let tgt_bytes = rlp.val_at::<Vec<u8>>(1)?;
-                if tgt_bytes.len() > NODE_ID_LENGTH {
-                    return Err(DecoderError::RlpIsTooBig);
-                }
-                let mut tgt = [0u8; NODE_ID_LENGTH];
-                tgt[NODE_ID_LENGTH - tgt_bytes.len()..].copy_from_slice(&tgt_bytes);
-                let tgt = NodeId::from(tgt);
-
-                Ok(RelayInit(initiator, tgt, nonce).into())
+    let rlp = Rlp::new(&data[1..list_end]);
+    if let Some(namespace) = wire.namespace {
+        let attempt_id_index = match msg_type {
+            NotificationType::RelayInit => Some(3),
+            NotificationType::RelayMsg | NotificationType::PunchReport => Some(2),
+            _ => None,
+        };
+        if let Some(attempt_id) = attempt_id_index
+            .map(|index| optional_at(&rlp, index))
+            .transpose()?
+            .flatten()
+        {
+            if !namespace.is_attempt_id(attempt_id.data()?) {
+                return Err(DecoderError::Custom("foreign protocol namespace"));
             }
-            REALYMSG_MSG_TYPE => {
-                if list_len != 2 {
-                    return Err(DecoderError::RlpIncorrectListLen);
-                }
-                Ok(RelayMsg(initiator, nonce).into())
-            }
-            _ => Err(DecoderError::Custom("invalid notification type")),
         }
     }
+    // notifications carrying an ENR lead with it
+    if matches!(
+        msg_type,
+        NotificationType::RelayInit | NotificationType::RelayMsg
+    ) && rlp.at(0)?.as_raw().len() > limits.max_enr_size
+    {
+        return Err(DecoderError::Custom("ENR exceeds size cap"));
+    }
+    Ok((msg_type, rlp))
+}
+
+/// A notification nested in another RLP structure is encoded as a string holding its type
+/// prefixed encoding, like the message data of a discv5 packet.
+impl<const N: usize, const I: usize> Encodable for Notification<N, I> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.append(&self.to_rlp());
+    }
+}
+
+impl<const N: usize, const I: usize> Decodable for Notification<N, I> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        Notification::rlp_decode(rlp.data()?)
+    }
+}
+
+/// Decodes a nonce, left padding it if it was encoded with less than `N` bytes, see
+/// [`nonce_from_rlp_compat`].
+pub(crate) fn decode_nonce<const N: usize>(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<MessageNonce<N>, DecoderError> {
+    nonce_from_rlp_compat(rlp.at(index)?.data()?)
+}
+
+/// Decodes the optional attempt id at `index`, absent in notifications of protocol revision 1.
+/// An empty string stands in for an absent attempt id followed by other optional items.
+pub(crate) fn decode_attempt_id(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<Option<AttemptId>, DecoderError> {
+    if rlp.item_count()? <= index {
+        return Ok(None);
+    }
+    let attempt_id = rlp.val_at::<Vec<u8>>(index)?;
+    if attempt_id.is_empty() {
+        return Ok(None);
+    }
+    attempt_id
+        .try_into()
+        .map(Some)
+        .map_err(|_| DecoderError::Custom("invalid attempt id length"))
+}
+
+/// Decodes the optional candidates at `index`, absent in notifications before protocol revision 3.
+pub(crate) fn decode_candidates(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<Option<Candidates>, DecoderError> {
+    if rlp.item_count()? <= index || rlp.at(index)?.is_empty() {
+        return Ok(None);
+    }
+    rlp.val_at(index).map(Some)
+}
+
+/// Decodes the optional extension list at `index`, absent in notifications before protocol
+/// revision 8. Items after the extension list are rejected, new fields go into extensions.
+pub(crate) fn decode_extensions(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<Option<Extensions>, DecoderError> {
+    if rlp.item_count()? <= index || rlp.at(index)?.is_empty() {
+        return Ok(None);
+    }
+    rlp.val_at(index).map(Some)
+}
+
+/// Decodes the optional socket at `index`, encoded as a list of its ip and port.
+pub(crate) fn decode_socket_addr(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<Option<SocketAddr>, DecoderError> {
+    if rlp.item_count()? <= index || rlp.at(index)?.is_empty() {
+        return Ok(None);
+    }
+    let socket = rlp.at(index)?;
+    if socket.item_count()? != 2 {
+        return Err(DecoderError::RlpIncorrectListLen);
+    }
+    let ip = decode_ip(&socket.val_at::<Vec<u8>>(0)?)?;
+    Ok(Some(SocketAddr::new(ip, socket.val_at(1)?)))
+}
+
+/// Decodes the optional node id of the relay at `index`, absent in notifications before protocol
+/// revision 7.
+pub(crate) fn decode_relay_id<const I: usize>(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<Option<NodeId>, DecoderError> {
+    if rlp.item_count()? <= index || rlp.at(index)?.is_empty() {
+        return Ok(None);
+    }
+    decode_node_id::<I>(rlp, index).map(Some)
+}
+
+/// Decodes an IPv4 or IPv6 address from its octets.
+pub(crate) fn decode_ip(octets: &[u8]) -> Result<IpAddr, DecoderError> {
+    if let Ok(octets) = <[u8; 4]>::try_from(octets) {
+        Ok(IpAddr::from(octets))
+    } else if let Ok(octets) = <[u8; 16]>::try_from(octets) {
+        Ok(IpAddr::from(octets))
+    } else {
+        Err(DecoderError::Custom("invalid ip length"))
+    }
+}
+
+/// A socket encoded as a list of its ip and port.
+pub(crate) struct RlpSocketAddr(pub SocketAddr);
+
+impl Encodable for RlpSocketAddr {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2);
+        match self.0.ip() {
+            IpAddr::V4(ip) => s.append(&(&ip.octets() as &[u8])),
+            IpAddr::V6(ip) => s.append(&(&ip.octets() as &[u8])),
+        };
+        s.append(&self.0.port());
+    }
+}
+
+/// Number of the optional trailing items of a notification that [`append_optional`] appends,
+/// up to the last present one.
+pub(crate) fn optional_len(items: &[Option<&dyn Encodable>]) -> usize {
+    items
+        .iter()
+        .rposition(Option::is_some)
+        .map_or(0, |last| last + 1)
+}
+
+/// Appends the optional trailing items of a notification. An empty string stands in for an
+/// absent item followed by present ones, trailing absent items are left out, so that peers of
+/// older protocol revisions can decode notifications not using the newer items.
+pub(crate) fn append_optional(s: &mut RlpStream, items: &[Option<&dyn Encodable>]) {
+    for item in items.iter().take(optional_len(items)) {
+        match item {
+            Some(item) => s.append_raw(&item.rlp_bytes(), 1),
+            None => s.append_empty_data(),
+        };
+    }
+}
+
+/// Appends the ENR, compressed if asked for and that makes it shorter.
+pub(crate) fn append_enr(s: &mut RlpStream, enr: &Enr, compress: bool) {
+    let plain = enr.encode_rlp();
+    if compress {
+        let compressed = snappy::compress(&plain);
+        if compressed.len() < plain.len() {
+            s.append(&compressed);
+            return;
+        }
+    }
+    s.append_raw(&plain, 1);
+}
+
+/// Decodes the ENR at `index`, an RLP list, or a string holding a compressed RLP list that
+/// decompresses to at most `max_size` bytes.
+pub(crate) fn decode_enr(
+    rlp: &Rlp<'_>,
+    index: usize,
+    max_size: usize,
+) -> Result<Enr, DecoderError> {
+    let enr = rlp.at(index)?;
+    if enr.is_list() {
+        return enr.as_val();
+    }
+    let plain = snappy::decompress(enr.data()?, max_size)?;
+    Enr::decode_rlp(&plain)
+}
+
+/// Decodes a node id of `I` bytes, left padding it if it was encoded with less, see
+/// [`NodeIdBytes::from_rlp_compat`].
+pub(crate) fn decode_node_id<const I: usize>(
+    rlp: &Rlp<'_>,
+    index: usize,
+) -> Result<NodeId, DecoderError> {
+    NodeIdBytes::<I>::from_rlp_compat(rlp.at(index)?.data()?).map(NodeId::from)
+}
+
+/// A node id encoded with `I` bytes, see [`NodeIdBytes::from_node_id`].
+pub(crate) struct RlpNodeId<const I: usize>(pub NodeId);
+
+impl<const I: usize> Encodable for RlpNodeId<I> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.encoder()
+            .encode_value(&NodeIdBytes::<I>::from_node_id(&self.0).0);
+    }
 }
 
 #[cfg(test)]
@@ -108,9 +474,9 @@ mod tests {
         let mut nonce = [0u8; MESSAGE_NONCE_LENGTH];
         nonce[MESSAGE_NONCE_LENGTH - nonce_bytes.len()..].copy_from_slice(&nonce_bytes);
 
-        let notif = RelayInit(inr_enr, tgt_node_id, nonce);
+        let notif: RelayInit = RelayInit(inr_enr, tgt_node_id, nonce, None, None, None);
 
-        let encoded_notif = notif.clone().rlp_encode();
+        let encoded_notif = notif.to_rlp();
         let decoded_notif = Notification::rlp_decode(&encoded_notif).expect("Should decode");
 
         assert_eq!(notif, decoded_notif.into());
@@ -127,11 +493,640 @@ mod tests {
         let mut nonce = [0u8; MESSAGE_NONCE_LENGTH];
         nonce[MESSAGE_NONCE_LENGTH - nonce_bytes.len()..].copy_from_slice(&nonce_bytes);
 
-        let notif = RelayMsg(inr_enr, nonce);
+        let notif: RelayMsg = RelayMsg(inr_enr, nonce, None, None, None, None, None);
 
-        let encoded_notif = notif.clone().rlp_encode();
+        let encoded_notif = notif.to_rlp();
         let decoded_notif = Notification::rlp_decode(&encoded_notif).expect("Should decode");
 
         assert_eq!(notif, decoded_notif.into());
     }
+
+    #[test]
+    fn test_encode_decode_attempt_id() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let attempt_id = Some([0xab; ATTEMPT_ID_LENGTH]);
+
+        let relay_init: RelayInit = RelayInit(
+            inr_enr.clone(),
+            NodeId::random(),
+            nonce,
+            attempt_id,
+            None,
+            None,
+        );
+        let relay_msg = RelayMsg(inr_enr.clone(), nonce, attempt_id, None, None, None, None);
+        for notif in [Notification::from(relay_init), relay_msg.into()] {
+            let decoded = Notification::rlp_decode(&notif.to_rlp()).unwrap();
+            assert_eq!(notif, decoded);
+        }
+
+        // revision 1 notifications carry no attempt id
+        let legacy = RelayMsg::<MESSAGE_NONCE_LENGTH>(inr_enr, nonce, None, None, None, None, None)
+            .rlp_encode();
+        let decoded: RelayMsg = Notification::rlp_decode(&legacy).unwrap().into();
+        assert_eq!(None, decoded.2);
+    }
+
+    #[test]
+    fn test_encode_decode_candidates() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let candidates = Candidates::new(vec![
+            Candidate::new(CandidateKind::Relayed, "198.51.100.1:4000".parse().unwrap()),
+            Candidate::new(CandidateKind::Host, "[2001:db8::1]:9000".parse().unwrap()),
+        ]);
+        assert_eq!(CandidateKind::Host, candidates.as_ref()[0].kind);
+
+        // candidates follow an absent attempt id
+        let relay_init: RelayInit = RelayInit(
+            inr_enr.clone(),
+            NodeId::random(),
+            nonce,
+            None,
+            Some(candidates.clone()),
+            None,
+        );
+        let relay_msg = RelayMsg(
+            inr_enr,
+            nonce,
+            Some([0xab; ATTEMPT_ID_LENGTH]),
+            Some(candidates),
+            None,
+            None,
+            None,
+        );
+        for notif in [Notification::from(relay_init), relay_msg.into()] {
+            let decoded = Notification::rlp_decode(&notif.to_rlp()).unwrap();
+            assert_eq!(notif, decoded);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_observed_socket() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let observed = "[2001:db8::7]:30303".parse().unwrap();
+        // absent attempt id and candidates stand in as empty items
+        let notif = RelayMsg(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            Some(observed),
+            None,
+            None,
+        );
+
+        let encoded = notif.to_rlp();
+        assert_eq!(5, Rlp::new(&encoded[1..]).item_count().unwrap());
+        let decoded: RelayMsg = Notification::rlp_decode(&encoded).unwrap().into();
+        assert_eq!(notif, decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_relay_id() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let relay = NodeId::random();
+        let notif = RelayMsg(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            Some(relay),
+            None,
+        );
+
+        let encoded = notif.to_rlp();
+        assert_eq!(6, Rlp::new(&encoded[1..]).item_count().unwrap());
+        let decoded: RelayMsg = Notification::rlp_decode(&encoded).unwrap().into();
+        assert_eq!(Some(relay), decoded.5);
+        assert_eq!(notif, decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_extensions() {
+        struct Ttl(u32);
+
+        impl ExtensionCodec for Ttl {
+            const TYPE: ExtensionType = 7;
+
+            fn encode(&self) -> Vec<u8> {
+                self.0.to_be_bytes().to_vec()
+            }
+
+            fn decode(data: &[u8]) -> Result<Self, DecoderError> {
+                let bytes = data
+                    .try_into()
+                    .map_err(|_| DecoderError::Custom("invalid ttl"))?;
+                Ok(Ttl(u32::from_be_bytes(bytes)))
+            }
+        }
+
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let mut extensions = Extensions::default();
+        extensions.insert(&Ttl(30));
+        // an extension of a type this node doesn't know of
+        extensions.insert_raw(0xffff, vec![1, 2, 3]);
+
+        let relay_init = RelayInit(
+            inr_enr.clone(),
+            NodeId::random(),
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            Some(extensions.clone()),
+        );
+        let decoded: RelayInit = Notification::rlp_decode(&relay_init.to_rlp())
+            .unwrap()
+            .into();
+        assert_eq!(relay_init, decoded);
+        assert_eq!(30, decoded.extension::<Ttl>().unwrap().unwrap().0);
+
+        let relay_msg: RelayMsg = RelayMsg(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            None,
+            Some(extensions),
+        );
+        let encoded = relay_msg.to_rlp();
+        // absent items before the extension list stand in as empty items
+        assert_eq!(7, Rlp::new(&encoded[1..]).item_count().unwrap());
+        let decoded: RelayMsg = Notification::rlp_decode(&encoded).unwrap().into();
+        assert_eq!(30, decoded.extension::<Ttl>().unwrap().unwrap().0);
+        assert_eq!(
+            Some(&[1, 2, 3][..]),
+            decoded.6.as_ref().unwrap().get_raw(0xffff)
+        );
+    }
+
+    #[test]
+    fn test_relay_init_into_relay_msg() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let attempt_id = [2u8; ATTEMPT_ID_LENGTH];
+        let mut extensions = Extensions::default();
+        extensions.insert_raw(7, vec![1]);
+        let relay_init: RelayInit = RelayInit(
+            inr_enr.clone(),
+            NodeId::random(),
+            nonce,
+            Some(attempt_id),
+            None,
+            Some(extensions),
+        );
+        let observed = "192.0.2.1:9000".parse().unwrap();
+        let relay_id = NodeId::random();
+        let mut added = Extensions::default();
+        added.insert_raw(7, vec![2]);
+        added.insert_raw(8, vec![3]);
+
+        let relay_msg = relay_init.clone().into_relay_msg(RelayExtras::default());
+        assert_eq!(
+            RelayMsg(
+                inr_enr.clone(),
+                nonce,
+                None,
+                None,
+                None,
+                None,
+                relay_init.5.clone()
+            ),
+            relay_msg
+        );
+
+        let extras = RelayExtras {
+            echo_attempt_id: true,
+            observed: Some(observed),
+            relay_id: Some(relay_id),
+            extensions: added.clone(),
+        };
+        assert_eq!(
+            RelayMsg(
+                inr_enr,
+                nonce,
+                Some(attempt_id),
+                None,
+                Some(observed),
+                Some(relay_id),
+                Some(added)
+            ),
+            relay_init.into_relay_msg(extras)
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_compressed_enr() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4")
+            .add_value("attnets", [0u8; 64].as_slice())
+            .build(&enr_key)
+            .unwrap();
+        let notif = RelayInit(
+            inr_enr,
+            NodeId::random(),
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+        );
+        let compress = WireConfig {
+            compress_enr: true,
+            ..Default::default()
+        };
+
+        let plain = notif.to_rlp();
+        let compressed = notif.to_rlp_with(&compress);
+        assert!(compressed.len() < plain.len());
+        for encoded in [&plain, &compressed] {
+            let decoded: RelayInit = Notification::rlp_decode(encoded).unwrap().into();
+            assert_eq!(notif, decoded);
+        }
+
+        // the size cap on ENRs applies to the decompressed ENR too
+        let limits = DecodeLimits {
+            max_enr_size: notif.0.size() - 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with(&compressed, &limits)
+        );
+
+        // an ENR that doesn't compress is sent as is
+        let notif: RelayMsg = RelayMsg(
+            EnrBuilder::new("v4").build(&enr_key).unwrap(),
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(notif.to_rlp(), notif.rlp_encode_with(&compress));
+    }
+
+    #[test]
+    fn test_encode_decode_punch_report() {
+        for notif in [
+            PunchReport([1u8; MESSAGE_NONCE_LENGTH], true, None, None),
+            PunchReport(
+                [2u8; MESSAGE_NONCE_LENGTH],
+                false,
+                Some([3; ATTEMPT_ID_LENGTH]),
+                None,
+            ),
+        ] {
+            let encoded = notif.to_rlp();
+            assert_eq!(10, encoded[0]);
+            let decoded: PunchReport = Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded)
+                .unwrap()
+                .into();
+            assert_eq!(notif, decoded);
+        }
+    }
+
+    #[test]
+    fn test_decode_legacy_numbering() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let notif: RelayMsg = RelayMsg(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        let legacy = WireConfig {
+            type_numbering: TypeNumbering::Legacy,
+            ..Default::default()
+        };
+        let encoded = notif.to_rlp_with(&legacy);
+        assert_eq!(1, encoded[0]);
+
+        let limits = DecodeLimits::default();
+        assert!(Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded).is_err());
+        let compat = WireConfig {
+            type_numbering: TypeNumbering::Compat,
+            ..Default::default()
+        };
+        let decoded = Notification::rlp_decode_with_wire(&encoded, &limits, &compat).unwrap();
+        assert_eq!(Notification::RelayMsg(notif), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_relay_abort() {
+        let notif = RelayAbort([1u8; MESSAGE_NONCE_LENGTH], ReasonCode::Busy, None);
+
+        let encoded_notif = notif.to_rlp();
+        let decoded_notif = Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded_notif)
+            .expect("Should decode");
+
+        assert_eq!(notif, decoded_notif.into());
+    }
+
+    #[test]
+    fn test_encode_decode_echo() {
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let notif = EchoAfter(
+            NodeId::random(),
+            nonce,
+            std::time::Duration::from_secs(30),
+            None,
+        );
+        let encoded = notif.to_rlp();
+        assert_eq!(11, encoded[0]);
+        let decoded: EchoAfter = Notification::rlp_decode(&encoded).unwrap().into();
+        assert_eq!(notif, decoded);
+
+        let notif = Echo(nonce, None);
+        let encoded = notif.to_rlp();
+        assert_eq!(12, encoded[0]);
+        let decoded: Echo = Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded)
+            .unwrap()
+            .into();
+        assert_eq!(notif, decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_other_nonce_length() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let notif = RelayInit(inr_enr, NodeId::random(), [1u8; 16], None, None, None);
+
+        let encoded_notif = notif.to_rlp();
+
+        assert_eq!(
+            notif,
+            Notification::<16>::rlp_decode(&encoded_notif)
+                .unwrap()
+                .into()
+        );
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded_notif)
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_other_node_id_length() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        // overlays with shorter node ids hold them left padded
+        let mut raw = [0u8; NODE_ID_LENGTH];
+        raw[NODE_ID_LENGTH - 20..].fill(7);
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let notif = RelayInit::<MESSAGE_NONCE_LENGTH, 20>(
+            inr_enr,
+            NodeId::from(raw),
+            nonce,
+            None,
+            None,
+            None,
+        );
+
+        let encoded_notif = notif.to_rlp();
+        let default_len =
+            RelayInit::<MESSAGE_NONCE_LENGTH>(notif.0.clone(), notif.1, nonce, None, None, None)
+                .to_rlp()
+                .len();
+        assert_eq!(default_len - (NODE_ID_LENGTH - 20), encoded_notif.len());
+        assert_eq!(
+            notif,
+            Notification::<MESSAGE_NONCE_LENGTH, 20>::rlp_decode(&encoded_notif)
+                .unwrap()
+                .into()
+        );
+
+        let notif =
+            EchoAfter::<MESSAGE_NONCE_LENGTH, 20>(notif.1, nonce, std::time::Duration::ZERO, None);
+        assert_eq!(
+            notif,
+            Notification::<MESSAGE_NONCE_LENGTH, 20>::rlp_decode(&notif.to_rlp())
+                .unwrap()
+                .into()
+        );
+        // a discv5 node id doesn't fit
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            Notification::<MESSAGE_NONCE_LENGTH, 20>::rlp_decode(
+                &EchoAfter::<MESSAGE_NONCE_LENGTH>(
+                    NodeId::random(),
+                    nonce,
+                    std::time::Duration::ZERO,
+                    None
+                )
+                .to_rlp()
+            )
+        );
+    }
+
+    #[test]
+    fn test_decode_size_caps() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let encoded = RelayMsg::<MESSAGE_NONCE_LENGTH>(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .rlp_encode();
+
+        assert_eq!(Ok(NotificationType::RelayMsg), peek_notif_type(&encoded));
+        assert!(peek_notif_type(&[0xff]).is_err());
+
+        let limits = DecodeLimits {
+            max_notification_size: encoded.len() - 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with(&encoded, &limits)
+        );
+        let limits = DecodeLimits {
+            max_enr_size: 16,
+            ..Default::default()
+        };
+        assert_eq!(
+            Err(DecoderError::Custom("ENR exceeds size cap")),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with(&encoded, &limits)
+        );
+    }
+
+    #[test]
+    fn test_encode_decode_padded() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let wire = WireConfig {
+            padding_bucket: Some(256),
+            ..Default::default()
+        };
+
+        let relay_init: RelayInit =
+            RelayInit(inr_enr.clone(), NodeId::random(), nonce, None, None, None);
+        let relay_msg: RelayMsg = RelayMsg(inr_enr, nonce, None, None, None, None, None);
+        let encoded_init = relay_init.to_rlp_with(&wire);
+        let encoded_msg = relay_msg.to_rlp_with(&wire);
+
+        assert_eq!(256, encoded_init.len());
+        assert_eq!(encoded_init.len(), encoded_msg.len());
+        assert_eq!(
+            relay_init,
+            Notification::rlp_decode(&encoded_init).unwrap().into()
+        );
+        assert_eq!(
+            relay_msg,
+            Notification::rlp_decode(&encoded_msg).unwrap().into()
+        );
+    }
+
+    #[test]
+    fn test_namespace_rejects_foreign_notifications() {
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let devnet = WireConfig {
+            namespace: Some(ProtocolNamespace::new("devnet")),
+            padding_bucket: Some(64),
+            ..Default::default()
+        };
+        let other = WireConfig {
+            namespace: Some(ProtocolNamespace::new("other")),
+            ..devnet
+        };
+        assert_ne!(devnet.namespace, other.namespace);
+        let report = PunchReport(nonce, true, None, None);
+        let encoded = report.to_rlp_with(&devnet);
+        let limits = DecodeLimits::default();
+
+        assert_eq!(64, encoded.len());
+        assert_eq!(
+            Notification::from(report.clone()),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with_wire(&encoded, &limits, &devnet)
+                .unwrap()
+        );
+        assert!(Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&encoded).is_err());
+        assert!(Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with_wire(
+            &encoded, &limits, &other
+        )
+        .is_err());
+        // notifications of no namespace are foreign too, and rejected before parsing
+        let mainnet = report.rlp_encode();
+        let list_end = mainnet.len();
+        assert_eq!(
+            Some(RejectReason::ForeignNamespace),
+            quick_reject_with(&mainnet, &limits, &devnet)
+        );
+        assert_eq!(None, quick_reject_with(&encoded, &limits, &devnet));
+
+        // the trailer is keyed over the notification, there is no constant tag to link
+        let other_report = PunchReport([2u8; MESSAGE_NONCE_LENGTH], true, None, None);
+        let other_encoded = other_report.to_rlp_with(&devnet);
+        assert_ne!(encoded[list_end..], other_encoded[list_end..]);
+
+        // attempt ids are derived from the namespace too
+        let devnet_ns = devnet.namespace.unwrap();
+        let attempt_id = devnet.attempt_id([9u8; ATTEMPT_ID_LENGTH]);
+        assert_eq!(attempt_id[..2], [9u8; 2]);
+        assert!(devnet_ns.is_attempt_id(&attempt_id));
+        let report = PunchReport(nonce, true, Some(attempt_id), None);
+        assert_eq!(
+            Notification::from(report.clone()),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with_wire(
+                &report.to_rlp_with(&devnet),
+                &limits,
+                &devnet
+            )
+            .unwrap()
+        );
+        let foreign_id = other.attempt_id([9u8; ATTEMPT_ID_LENGTH]);
+        assert!(!devnet_ns.is_attempt_id(&foreign_id));
+        let report = PunchReport(nonce, true, Some(foreign_id), None);
+        assert_eq!(
+            Err(DecoderError::Custom("foreign protocol namespace")),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode_with_wire(
+                &report.to_rlp_with(&devnet),
+                &limits,
+                &devnet
+            )
+        );
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4")
+            .add_value("attnets", [0u8; 64].as_slice())
+            .build(&enr_key)
+            .unwrap();
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let relay_init: RelayInit =
+            RelayInit(inr_enr.clone(), NodeId::random(), nonce, None, None, None);
+        let relay_msg: RelayMsg = RelayMsg(inr_enr, nonce, None, None, None, None, None);
+
+        for wire in [
+            WireConfig::default(),
+            WireConfig {
+                compress_enr: true,
+                ..Default::default()
+            },
+            WireConfig {
+                padding_bucket: Some(256),
+                ..Default::default()
+            },
+            WireConfig {
+                namespace: Some(ProtocolNamespace::new("devnet")),
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(
+                relay_init.to_rlp_with(&wire).len(),
+                relay_init.encoded_len(&wire)
+            );
+            assert_eq!(
+                relay_msg.to_rlp_with(&wire).len(),
+                relay_msg.encoded_len(&wire)
+            );
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_nested() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        let notif: Notification = RelayMsg(
+            inr_enr,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .into();
+
+        // a discv5-like envelope with a request id
+        let mut s = RlpStream::new_list(2);
+        s.append(&7u64);
+        s.append(&notif);
+        let encoded = s.out();
+
+        let rlp = Rlp::new(&encoded);
+        assert_eq!(7u64, rlp.val_at::<u64>(0).unwrap());
+        assert_eq!(notif, rlp.val_at::<Notification>(1).unwrap());
+    }
 }
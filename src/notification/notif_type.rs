@@ -0,0 +1,130 @@
+use crate::rlp_backend::DecoderError;
+use parse_display_derive::Display;
+
+/// How notification types are numbered on the wire. Early releases numbered RelayInit and
+/// RelayMsg from 0, the current numbering continues after the discv5 message types.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TypeNumbering {
+    /// RelayInit is 7, RelayMsg is 8, RelayAbort is 9, PunchReport is 10, EchoAfter is 11 and
+    /// Echo is 12.
+    #[default]
+    Current,
+    /// RelayInit is 0 and RelayMsg is 1. Later types postdate this numbering and keep their
+    /// current numbers.
+    Legacy,
+    /// Decodes both numberings, which don't overlap, and encodes the current one. For migrating
+    /// a network away from the legacy numbering.
+    Compat,
+}
+
+/// The type of a notification, the first byte of its encoding.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationType {
+    RelayInit,
+    RelayMsg,
+    RelayAbort,
+    PunchReport,
+    EchoAfter,
+    Echo,
+}
+
+impl NotificationType {
+    /// Returns the type the given byte stands for under `numbering`.
+    pub fn from_byte(msg_type: u8, numbering: TypeNumbering) -> Result<Self, DecoderError> {
+        let legacy = matches!(numbering, TypeNumbering::Legacy | TypeNumbering::Compat);
+        let current = matches!(numbering, TypeNumbering::Current | TypeNumbering::Compat);
+        match msg_type {
+            7 if current => Ok(NotificationType::RelayInit),
+            8 if current => Ok(NotificationType::RelayMsg),
+            0 if legacy => Ok(NotificationType::RelayInit),
+            1 if legacy => Ok(NotificationType::RelayMsg),
+            9 => Ok(NotificationType::RelayAbort),
+            10 => Ok(NotificationType::PunchReport),
+            11 => Ok(NotificationType::EchoAfter),
+            12 => Ok(NotificationType::Echo),
+            _ => Err(DecoderError::Custom("invalid notification type")),
+        }
+    }
+
+    /// Returns the byte standing for the type under `numbering`.
+    pub const fn to_byte(self, numbering: TypeNumbering) -> u8 {
+        match (self, numbering) {
+            (NotificationType::RelayInit, TypeNumbering::Legacy) => 0,
+            (NotificationType::RelayMsg, TypeNumbering::Legacy) => 1,
+            (NotificationType::RelayInit, _) => 7,
+            (NotificationType::RelayMsg, _) => 8,
+            (NotificationType::RelayAbort, _) => 9,
+            (NotificationType::PunchReport, _) => 10,
+            (NotificationType::EchoAfter, _) => 11,
+            (NotificationType::Echo, _) => 12,
+        }
+    }
+}
+
+/// Decodes the current numbering, see [`NotificationType::from_byte`] for the legacy one.
+impl TryFrom<u8> for NotificationType {
+    type Error = DecoderError;
+
+    fn try_from(msg_type: u8) -> Result<Self, Self::Error> {
+        Self::from_byte(msg_type, TypeNumbering::Current)
+    }
+}
+
+impl From<NotificationType> for u8 {
+    fn from(msg_type: NotificationType) -> Self {
+        msg_type.to_byte(TypeNumbering::Current)
+    }
+}
+
+/// Returns the type of an encoded notification without decoding it, so that garbage can be
+/// dropped before parsing any RLP.
+pub fn peek_notif_type(data: &[u8]) -> Result<NotificationType, DecoderError> {
+    peek_notif_type_with(data, TypeNumbering::Current)
+}
+
+/// Returns the type of an encoded notification under `numbering` without decoding it.
+pub fn peek_notif_type_with(
+    data: &[u8],
+    numbering: TypeNumbering,
+) -> Result<NotificationType, DecoderError> {
+    let Some(msg_type) = data.first() else {
+        return Err(DecoderError::RlpIsTooShort);
+    };
+    NotificationType::from_byte(*msg_type, numbering)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbering_modes() {
+        let types = [
+            NotificationType::RelayInit,
+            NotificationType::RelayMsg,
+            NotificationType::RelayAbort,
+            NotificationType::PunchReport,
+            NotificationType::EchoAfter,
+            NotificationType::Echo,
+        ];
+        for numbering in [TypeNumbering::Current, TypeNumbering::Legacy] {
+            for msg_type in types {
+                let byte = msg_type.to_byte(numbering);
+                assert_eq!(Ok(msg_type), NotificationType::from_byte(byte, numbering));
+                assert_eq!(
+                    Ok(msg_type),
+                    NotificationType::from_byte(byte, TypeNumbering::Compat)
+                );
+            }
+        }
+
+        // legacy numbers are rejected unless asked for
+        assert!(NotificationType::try_from(0).is_err());
+        assert!(NotificationType::from_byte(7, TypeNumbering::Legacy).is_err());
+        // compat encodes the current numbering
+        assert_eq!(
+            7,
+            NotificationType::RelayInit.to_byte(TypeNumbering::Compat)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use crate::rlp_backend::RlpStream;
+use bytes::BytesMut;
+use std::cell::RefCell;
+
+/// Capacity of the pooled buffers, enough for a [`crate::RelayMsg`] with a typical ENR.
+const POOLED_BUFFER_CAPACITY: usize = 320;
+/// Max number of streams pooled per thread.
+const MAX_POOLED_STREAMS: usize = 4;
+
+thread_local! {
+    static POOL: RefCell<Vec<RlpStream>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Encodes with `encode` into a pooled stream and appends the encoding to `out`. Neither the
+/// stream's buffer nor its list stack is allocated again, so re-encoding notifications on the
+/// relay path into a reused `out` doesn't allocate.
+pub(crate) fn encode_pooled(out: &mut Vec<u8>, encode: impl FnOnce(&mut RlpStream)) {
+    with_pooled(encode, |raw| out.extend_from_slice(raw))
+}
+
+/// Returns the length of the encoding by `encode`, encoded into a pooled stream.
+pub(crate) fn encoded_len_pooled(encode: impl FnOnce(&mut RlpStream)) -> usize {
+    with_pooled(encode, <[u8]>::len)
+}
+
+/// Encodes with `encode` into a pooled stream and reads the encoding with `read`.
+fn with_pooled<R>(encode: impl FnOnce(&mut RlpStream), read: impl FnOnce(&[u8]) -> R) -> R {
+    let mut s = POOL
+        .with(|pool| pool.borrow_mut().pop())
+        .unwrap_or_else(|| {
+            RlpStream::new_with_buffer(BytesMut::with_capacity(POOLED_BUFFER_CAPACITY))
+        });
+    encode(&mut s);
+    let res = read(s.as_raw());
+
+    // don't hold on to streams grown by unusually large notifications
+    if s.as_raw().len() > 4 * POOLED_BUFFER_CAPACITY {
+        return res;
+    }
+    s.clear();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED_STREAMS {
+            pool.push(s);
+        }
+    });
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+    };
+
+    /// Counts the allocations made on each thread.
+    struct CountingAlloc;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static GLOBAL: CountingAlloc = CountingAlloc;
+
+    fn allocations(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    fn encode(s: &mut RlpStream) {
+        s.begin_list(2).append(&1u8).append(&"nat_hp");
+    }
+
+    #[test]
+    fn test_pooled_stream_reused() {
+        let mut first = Vec::new();
+        encode_pooled(&mut first, encode);
+        let mut second = vec![0xff];
+        encode_pooled(&mut second, |s| {
+            s.append(&2u8);
+        });
+
+        let mut expected = RlpStream::new_list(2);
+        expected.append(&1u8).append(&"nat_hp");
+        assert_eq!(expected.out().to_vec(), first);
+        assert_eq!(vec![0xff, 2], second);
+        assert_eq!(1, POOL.with(|pool| pool.borrow().len()));
+    }
+
+    #[test]
+    fn test_pooled_encoding_doesnt_allocate() {
+        let mut out = Vec::with_capacity(POOLED_BUFFER_CAPACITY);
+        // warm up the pool of this thread
+        encode_pooled(&mut out, encode);
+
+        let pooled = allocations(|| {
+            for _ in 0..10 {
+                out.clear();
+                encode_pooled(&mut out, encode);
+                assert_eq!(9, encoded_len_pooled(encode));
+            }
+        });
+        let unpooled = allocations(|| {
+            for _ in 0..10 {
+                let mut s = RlpStream::new();
+                encode(&mut s);
+                out.clear();
+                out.extend_from_slice(s.as_raw());
+            }
+        });
+        assert_eq!(0, pooled);
+        assert!(unpooled >= 10);
+    }
+}
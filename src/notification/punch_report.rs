@@ -0,0 +1,102 @@
+use super::{append_optional, decode_attempt_id, decode_extensions, decode_nonce, optional_len};
+use crate::{
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpCodec, RlpStream},
+    AttemptId, Extensions, MessageNonce, Notification, NotificationType, WireConfig,
+    MESSAGE_NONCE_LENGTH,
+};
+use std::fmt;
+
+/// A notification sent from the initiator to the relay once an attempt the relay relayed
+/// resolved. Contains the nonce of the timed out request, whether the target was punched and
+/// optionally the attempt id and extensions, so that the relay gets feedback on its forwarding.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PunchReport<const N: usize = MESSAGE_NONCE_LENGTH>(
+    pub MessageNonce<N>,
+    pub bool,
+    pub Option<AttemptId>,
+    pub Option<Extensions>,
+);
+
+impl<const N: usize, const I: usize> From<Notification<N, I>> for PunchReport<N> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::PunchReport(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
+
+impl<const N: usize> PunchReport<N> {
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
+
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(24);
+        buf.push(NotificationType::PunchReport.to_byte(wire.type_numbering));
+        self.encode_rlp_to(&mut buf);
+        wire.pad(&mut buf);
+        buf
+    }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+}
+
+impl<const N: usize> Encodable for PunchReport<N> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let PunchReport(nonce, punched, attempt_id, extensions) = self;
+
+        let attempt_id = attempt_id.as_ref().map(|attempt_id| attempt_id as &[u8]);
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [
+            attempt_id.as_ref().map(|item| item as &dyn Encodable),
+            extensions.map(|item| item as &dyn Encodable),
+        ];
+
+        s.begin_list(2 + optional_len(&optional));
+        s.append(&(nonce as &[u8]));
+        s.append(punched);
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize> Decodable for PunchReport<N> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !(2..=4).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let nonce = decode_nonce(rlp, 0)?;
+        let punched = rlp.val_at::<bool>(1)?;
+        let attempt_id = decode_attempt_id(rlp, 2)?;
+        let extensions = decode_extensions(rlp, 3)?;
+
+        Ok(PunchReport(nonce, punched, attempt_id, extensions))
+    }
+}
+
+impl<const N: usize> fmt::Display for PunchReport<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nonce = hex::encode(self.0);
+        write!(
+            f,
+            "PunchReport: Nonce: 0x{}..{}, Punched: {}",
+            &nonce[0..2],
+            &nonce[nonce.len() - 2..],
+            self.1
+        )?;
+        if let Some(attempt_id) = self.2 {
+            write!(f, ", Attempt: 0x{}", hex::encode(attempt_id))?;
+        }
+        Ok(())
+    }
+}
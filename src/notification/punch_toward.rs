@@ -0,0 +1,31 @@
+use super::{decode_socket_addr, ExtensionCodec, ExtensionType, RlpSocketAddr};
+use crate::rlp_backend::{DecoderError, Rlp, RlpStream};
+use std::net::SocketAddr;
+
+/// An extension of a [`crate::RelayMsg`] telling the target which addresses of the initiator to
+/// punch toward first. Set by a relay that reaches the target over one address family, when the
+/// initiator has addresses of both, so that a relay on another family than the target's NAT
+/// doesn't leave the target guessing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PunchToward(pub Vec<SocketAddr>);
+
+impl ExtensionCodec for PunchToward {
+    const TYPE: ExtensionType = 1;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new_list(self.0.len());
+        for addr in self.0.iter() {
+            s.append(&RlpSocketAddr(*addr));
+        }
+        s.out().to_vec()
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(data);
+        let mut addrs = Vec::with_capacity(rlp.item_count()?);
+        for index in 0..rlp.item_count()? {
+            addrs.push(decode_socket_addr(&rlp, index)?.ok_or(DecoderError::RlpExpectedToBeList)?);
+        }
+        Ok(PunchToward(addrs))
+    }
+}
@@ -0,0 +1,110 @@
+use super::{DecodeLimits, NotificationType, WireConfig};
+use parse_display_derive::Display;
+
+/// Why [`quick_reject`] discarded a datagram.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+#[display(style = "kebab-case")]
+pub enum RejectReason {
+    /// Shorter than the smallest notification.
+    TooShort,
+    /// Longer than [`DecodeLimits::max_notification_size`].
+    TooLong,
+    /// The first byte is no notification type.
+    UnknownType,
+    /// The type isn't followed by an RLP list header.
+    MalformedHeader,
+    /// The RLP list header claims more bytes than the datagram holds.
+    Truncated,
+    /// The datagram lacks the trailer of [`WireConfig::namespace`].
+    ForeignNamespace,
+}
+
+/// Checks the type byte, the length and the RLP list header of a datagram without parsing any
+/// RLP items, so that a relay's socket loop can discard floods of garbage before handing
+/// datagrams to the full decoder. Returns `None` if the datagram may be a notification under the
+/// default [`DecodeLimits`] and [`WireConfig`].
+pub fn quick_reject(data: &[u8]) -> Option<RejectReason> {
+    quick_reject_with(data, &DecodeLimits::default(), &WireConfig::default())
+}
+
+/// Like [`quick_reject`], under the given size caps, type numbering and namespace.
+pub fn quick_reject_with(
+    data: &[u8],
+    limits: &DecodeLimits,
+    wire: &WireConfig,
+) -> Option<RejectReason> {
+    if data.len() < 3 {
+        return Some(RejectReason::TooShort);
+    }
+    if data.len() > limits.max_notification_size {
+        return Some(RejectReason::TooLong);
+    }
+    if NotificationType::from_byte(data[0], wire.type_numbering).is_err() {
+        return Some(RejectReason::UnknownType);
+    }
+    let (header_len, payload_len) = match data[1] {
+        prefix @ 0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+        prefix @ 0xf8..=0xff => {
+            let len_of_len = (prefix - 0xf7) as usize;
+            let Some(len_bytes) = data.get(2..2 + len_of_len) else {
+                return Some(RejectReason::Truncated);
+            };
+            // canonical long lengths have no leading zeros and don't fit a short header
+            if len_bytes[0] == 0 || len_of_len > std::mem::size_of::<usize>() {
+                return Some(RejectReason::MalformedHeader);
+            }
+            let payload_len = len_bytes
+                .iter()
+                .fold(0usize, |len, byte| len << 8 | *byte as usize);
+            if payload_len < 56 {
+                return Some(RejectReason::MalformedHeader);
+            }
+            (1 + len_of_len, payload_len)
+        }
+        _ => return Some(RejectReason::MalformedHeader),
+    };
+    let list_end = payload_len.saturating_add(1 + header_len);
+    if list_end > data.len() {
+        return Some(RejectReason::Truncated);
+    }
+    // without a namespace, anything after the list is padding, checked by the full decoder
+    if wire.namespace.is_some() && !wire.check_trailer(&data[..list_end], &data[list_end..]) {
+        return Some(RejectReason::ForeignNamespace);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PunchReport, ReasonCode, RelayAbort, MESSAGE_NONCE_LENGTH};
+
+    #[test]
+    fn test_quick_reject() {
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        let abort = RelayAbort(nonce, ReasonCode::Busy, None).rlp_encode();
+        let report = PunchReport(nonce, true, None, None).rlp_encode();
+        assert_eq!(None, quick_reject(&abort));
+        assert_eq!(None, quick_reject(&report));
+
+        assert_eq!(Some(RejectReason::TooShort), quick_reject(&abort[..2]));
+        assert_eq!(Some(RejectReason::TooLong), quick_reject(&[9; 1281]));
+        assert_eq!(Some(RejectReason::UnknownType), quick_reject(&[0, 0xc1, 0]));
+        assert_eq!(
+            Some(RejectReason::MalformedHeader),
+            quick_reject(&[9, 0x80, 0])
+        );
+        assert_eq!(
+            Some(RejectReason::MalformedHeader),
+            quick_reject(&[9, 0xf8, 0x01, 0])
+        );
+        assert_eq!(
+            Some(RejectReason::Truncated),
+            quick_reject(&abort[..abort.len() - 1])
+        );
+        assert_eq!(
+            Some(RejectReason::Truncated),
+            quick_reject(&[9, 0xf9, 0x01])
+        );
+    }
+}
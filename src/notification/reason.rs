@@ -0,0 +1,98 @@
+use crate::{rlp_backend::DecoderError, HolePunchError};
+use parse_display_derive::Display;
+use std::fmt::{Debug, Display};
+
+/// Why a peer won't take part in a hole punch attempt, as sent on the wire in a single byte. The
+/// numeric values are stable.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+#[display(style = "kebab-case")]
+pub enum ReasonCode {
+    /// The relay has no session with the target.
+    UnknownTarget = 1,
+    /// The initiator exceeded the relay's rate limit.
+    RateLimited = 2,
+    /// The relay's policy doesn't allow the attempt, for example because it's a replay.
+    PolicyDenied = 3,
+    /// The relay is shedding load.
+    Busy = 4,
+    /// The notification was malformed.
+    BadNotification = 5,
+    /// The initiator and the target share no address family, for example an IPv4-only initiator
+    /// and an IPv6-only target, so no hole can be punched between them.
+    AddressFamilyMismatch = 6,
+}
+
+impl From<ReasonCode> for u8 {
+    fn from(code: ReasonCode) -> Self {
+        code as u8
+    }
+}
+
+impl TryFrom<u8> for ReasonCode {
+    type Error = DecoderError;
+
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        match code {
+            1 => Ok(ReasonCode::UnknownTarget),
+            2 => Ok(ReasonCode::RateLimited),
+            3 => Ok(ReasonCode::PolicyDenied),
+            4 => Ok(ReasonCode::Busy),
+            5 => Ok(ReasonCode::BadNotification),
+            6 => Ok(ReasonCode::AddressFamilyMismatch),
+            _ => Err(DecoderError::Custom("invalid reason code")),
+        }
+    }
+}
+
+/// The reason code to send to the initiator for a failure of this node as relay, if the failure
+/// is one to tell the initiator about.
+impl<E: Debug + Display> TryFrom<&HolePunchError<E>> for ReasonCode {
+    type Error = ();
+
+    fn try_from(e: &HolePunchError<E>) -> Result<Self, Self::Error> {
+        match e {
+            HolePunchError::UnknownTarget(_) => Ok(ReasonCode::UnknownTarget),
+            HolePunchError::RateLimited => Ok(ReasonCode::RateLimited),
+            HolePunchError::Replayed | HolePunchError::Unsupported(_) => {
+                Ok(ReasonCode::PolicyDenied)
+            }
+            HolePunchError::Overloaded | HolePunchError::RelayQueueFull => Ok(ReasonCode::Busy),
+            HolePunchError::NotificationError(_) | HolePunchError::InitiatorUnreachable => {
+                Ok(ReasonCode::BadNotification)
+            }
+            HolePunchError::AddressFamilyMismatch(_) => Ok(ReasonCode::AddressFamilyMismatch),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A peer aborted a hole punch attempt of this node with the reason code.
+impl<E: Debug + Display> From<ReasonCode> for HolePunchError<E> {
+    fn from(code: ReasonCode) -> Self {
+        HolePunchError::Aborted(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reason_code_roundtrip() {
+        for code in [
+            ReasonCode::UnknownTarget,
+            ReasonCode::RateLimited,
+            ReasonCode::PolicyDenied,
+            ReasonCode::Busy,
+            ReasonCode::BadNotification,
+            ReasonCode::AddressFamilyMismatch,
+        ] {
+            assert_eq!(Ok(code), ReasonCode::try_from(u8::from(code)));
+        }
+        assert!(ReasonCode::try_from(0).is_err());
+        assert_eq!(
+            Ok(ReasonCode::Busy),
+            ReasonCode::try_from(&HolePunchError::<String>::RelayQueueFull)
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use super::{decode_nonce, ExtensionCodec, ExtensionType};
+use crate::{
+    rlp_backend::{DecoderError, Rlp, RlpStream},
+    MessageNonce, MESSAGE_NONCE_LENGTH,
+};
+
+/// Max number of nonces in [`RecentNonces`].
+pub const MAX_RECENT_NONCES: usize = 4;
+
+/// An extension of a [`crate::RelayInit`], forwarded in the [`crate::RelayMsg`], carrying the
+/// nonces of further requests to the target that timed out after the one the notification
+/// carries, oldest first. The initiator awaits a WHOAREYOU for any of them, and the target
+/// answers the most recent, the one the initiator most likely still has pending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentNonces<const N: usize = MESSAGE_NONCE_LENGTH>(pub Vec<MessageNonce<N>>);
+
+impl<const N: usize> RecentNonces<N> {
+    /// The most recent nonce, the one the target answers, if any.
+    pub fn most_recent(&self) -> Option<&MessageNonce<N>> {
+        self.0.last()
+    }
+}
+
+impl<const N: usize> ExtensionCodec for RecentNonces<N> {
+    const TYPE: ExtensionType = 3;
+
+    fn encode(&self) -> Vec<u8> {
+        let mut s = RlpStream::new_list(self.0.len());
+        for nonce in self.0.iter() {
+            s.append(&(nonce as &[u8]));
+        }
+        s.out().to_vec()
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecoderError> {
+        let rlp = Rlp::new(data);
+        let count = rlp.item_count()?;
+        if count > MAX_RECENT_NONCES {
+            return Err(DecoderError::Custom("too many nonces"));
+        }
+        let mut nonces = Vec::with_capacity(count);
+        for index in 0..count {
+            nonces.push(decode_nonce(&rlp, index)?);
+        }
+        Ok(RecentNonces(nonces))
+    }
+}
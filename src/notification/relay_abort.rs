@@ -0,0 +1,97 @@
+use super::{append_optional, decode_extensions, decode_nonce, optional_len};
+use crate::{
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpCodec, RlpStream},
+    ExtensionCodec, Extensions, MessageNonce, Notification, NotificationType, ReasonCode,
+    WireConfig, MESSAGE_NONCE_LENGTH,
+};
+use std::fmt;
+
+/// A notification sent from the relay to the initiator instead of relaying a [`crate::RelayInit`].
+/// Contains the nonce of the timed out request and why the attempt was aborted, so the initiator
+/// can move on to another relay without waiting out the timeout, and optionally extensions.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RelayAbort<const N: usize = MESSAGE_NONCE_LENGTH>(
+    pub MessageNonce<N>,
+    pub ReasonCode,
+    pub Option<Extensions>,
+);
+
+impl<const N: usize, const I: usize> From<Notification<N, I>> for RelayAbort<N> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::RelayAbort(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
+
+impl<const N: usize> RelayAbort<N> {
+    /// Decodes the extension of the codec's type, if present.
+    pub fn extension<T: ExtensionCodec>(&self) -> Option<Result<T, DecoderError>> {
+        self.2.as_ref()?.get()
+    }
+
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
+
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
+        let mut buf: Vec<u8> = Vec::with_capacity(16);
+        buf.push(NotificationType::RelayAbort.to_byte(wire.type_numbering));
+        self.encode_rlp_to(&mut buf);
+        wire.pad(&mut buf);
+        buf
+    }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+}
+
+impl<const N: usize> Encodable for RelayAbort<N> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let RelayAbort(nonce, reason, extensions) = self;
+
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [extensions.map(|item| item as &dyn Encodable)];
+
+        s.begin_list(2 + optional_len(&optional));
+        s.append(&(nonce as &[u8]));
+        s.append(&u8::from(*reason));
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize> Decodable for RelayAbort<N> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !(2..=3).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let nonce = decode_nonce(rlp, 0)?;
+        let reason = ReasonCode::try_from(rlp.val_at::<u8>(1)?)?;
+        let extensions = decode_extensions(rlp, 2)?;
+
+        Ok(RelayAbort(nonce, reason, extensions))
+    }
+}
+
+impl<const N: usize> fmt::Display for RelayAbort<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nonce = hex::encode(self.0);
+        write!(
+            f,
+            "RelayAbort: Nonce: 0x{}..{}, Reason: {}",
+            &nonce[0..2],
+            &nonce[nonce.len() - 2..],
+            self.1
+        )
+    }
+}
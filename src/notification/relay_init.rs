@@ -1,38 +1,185 @@
-use crate::{impl_from_variant_unwrap, Enr, MessageNonce, Notification, REALYINIT_MSG_TYPE};
+use super::{
+    append_enr, append_optional, decode_attempt_id, decode_candidates, decode_enr,
+    decode_extensions, decode_node_id, decode_nonce, optional_len,
+    pool::{encode_pooled, encoded_len_pooled},
+    RlpNodeId,
+};
+use crate::{
+    redact::{redaction, RedactedEnr},
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpStream},
+    AttemptId, Candidates, DecodeLimits, Enr, ExtensionCodec, Extensions, MessageNonce,
+    Notification, NotificationType, RedactedDisplay, Redaction, RelayMsg, WireConfig,
+    MESSAGE_NONCE_LENGTH, NODE_ID_LENGTH,
+};
 use enr::NodeId;
-use rlp::RlpStream;
-use std::fmt;
+use std::{fmt, net::SocketAddr};
 
 /// Nonce of request that triggered the initiation of this hole punching attempt.
-type NonceOfTimedOutMessage = MessageNonce;
+type NonceOfTimedOutMessage<const N: usize> = MessageNonce<N>;
 
 /// A notification sent from the initiator to the relay. Contains the enr of the initiator, the
-/// nonce of the timed out request and the node id of the target.
+/// nonce of the timed out request, the node id of the target and optionally the attempt id, the
+/// candidate addresses of the initiator and extensions.
 #[derive(Clone, PartialEq, Eq, Debug)]
-pub struct RelayInit(pub Enr, pub NodeId, pub NonceOfTimedOutMessage);
+pub struct RelayInit<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>(
+    pub Enr,
+    pub NodeId,
+    pub NonceOfTimedOutMessage<N>,
+    pub Option<AttemptId>,
+    pub Option<Candidates>,
+    pub Option<Extensions>,
+);
 
-impl_from_variant_unwrap!(, Notification, RelayInit, Notification::RelayInit);
+/// What a relay adds to a [`RelayInit`] when forwarding it to the target, see
+/// [`RelayInit::into_relay_msg`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelayExtras {
+    /// Echoes the attempt id of the initiator, if the target is expected to understand it.
+    pub echo_attempt_id: bool,
+    /// The socket the relay's session with the target is at.
+    pub observed: Option<SocketAddr>,
+    /// The node id of the relay.
+    pub relay_id: Option<NodeId>,
+    /// Extensions added to those of the initiator, replacing any of the same type.
+    pub extensions: Extensions,
+}
 
-impl RelayInit {
-    pub fn rlp_encode(self) -> Vec<u8> {
-        let RelayInit(initiator, target, nonce) = self;
+impl<const N: usize, const I: usize> From<Notification<N, I>> for RelayInit<N, I> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::RelayInit(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
+
+impl<const N: usize, const I: usize> RelayInit<N, I> {
+    /// Decodes the extension of the codec's type, if present.
+    pub fn extension<T: ExtensionCodec>(&self) -> Option<Result<T, DecoderError>> {
+        self.5.as_ref()?.get()
+    }
 
-        let mut s = RlpStream::new();
-        s.begin_list(3);
-        s.append(&initiator);
-        s.append(&(&target.raw() as &[u8]));
-        s.append(&(&nonce as &[u8]));
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
 
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::with_capacity(280);
-        buf.push(REALYINIT_MSG_TYPE);
-        buf.extend_from_slice(&s.out());
+        self.rlp_encode_to(&mut buf, wire);
         buf
     }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+
+    /// Converts the notification into the [`RelayMsg`] a relay forwards to the target. The target
+    /// id is dropped, the initiator's ENR, nonce, candidates and extensions are kept.
+    pub fn into_relay_msg(self, extras: RelayExtras) -> RelayMsg<N, I> {
+        let RelayInit(initiator, _, nonce, attempt_id, candidates, mut extensions) = self;
+        for ext in extras.extensions.iter() {
+            extensions
+                .get_or_insert_with(Extensions::default)
+                .insert_raw(ext.ty, ext.data.clone());
+        }
+        RelayMsg(
+            initiator,
+            nonce,
+            attempt_id.filter(|_| extras.echo_attempt_id),
+            candidates,
+            extras.observed,
+            extras.relay_id,
+            extensions,
+        )
+    }
+
+    /// Appends the notification, encoded according to the given wire format options, to `buf`.
+    /// Encodes through a pooled stream, so that relays re-encoding into a reused `buf` don't
+    /// allocate.
+    pub fn rlp_encode_to(&self, buf: &mut Vec<u8>, wire: &WireConfig) {
+        let start = buf.len();
+        buf.push(NotificationType::RelayInit.to_byte(wire.type_numbering));
+        encode_pooled(buf, |s| self.rlp_append_wire(s, wire));
+        wire.pad_from(buf, start);
+    }
+
+    /// The length of the notification encoded according to the given wire format options,
+    /// including its type and any padding, without allocating the encoding. Notifications must
+    /// fit in a single discv5 packet, which a large ENR may not allow.
+    pub fn encoded_len(&self, wire: &WireConfig) -> usize {
+        wire.padded_len(1 + encoded_len_pooled(|s| self.rlp_append_wire(s, wire)))
+    }
 }
 
-impl fmt::Display for RelayInit {
+impl<const N: usize, const I: usize> RelayInit<N, I> {
+    /// Appends the notification according to the given wire format options.
+    fn rlp_append_wire(&self, s: &mut RlpStream, wire: &WireConfig) {
+        let RelayInit(initiator, target, nonce, attempt_id, candidates, extensions) = self;
+
+        let attempt_id = attempt_id.as_ref().map(|attempt_id| attempt_id as &[u8]);
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [
+            attempt_id.as_ref().map(|item| item as &dyn Encodable),
+            candidates.as_ref().map(|item| item as &dyn Encodable),
+            extensions.map(|item| item as &dyn Encodable),
+        ];
+
+        s.begin_list(3 + optional_len(&optional));
+        append_enr(s, initiator, wire.compress_enr);
+        s.append(&RlpNodeId::<I>(*target));
+        s.append(&(nonce as &[u8]));
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize, const I: usize> Encodable for RelayInit<N, I> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_wire(s, &WireConfig::default());
+    }
+}
+
+impl<const N: usize, const I: usize> Decodable for RelayInit<N, I> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        Self::decode_with(rlp, &DecodeLimits::default())
+    }
+}
+
+impl<const N: usize, const I: usize> RelayInit<N, I> {
+    /// Decodes the notification, decompressing its ENR up to the size cap of `limits`.
+    pub(crate) fn decode_with(rlp: &Rlp<'_>, limits: &DecodeLimits) -> Result<Self, DecoderError> {
+        if !(3..=6).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let initiator = decode_enr(rlp, 0, limits.max_enr_size)?;
+        let tgt = decode_node_id::<I>(rlp, 1)?;
+        let nonce = decode_nonce(rlp, 2)?;
+        let attempt_id = decode_attempt_id(rlp, 3)?;
+        let candidates = decode_candidates(rlp, 4)?;
+        let extensions = decode_extensions(rlp, 5)?;
+
+        Ok(RelayInit(
+            initiator, tgt, nonce, attempt_id, candidates, extensions,
+        ))
+    }
+}
+
+impl<const N: usize, const I: usize> fmt::Display for RelayInit<N, I> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let initiator = &self.0;
+        self.fmt_redacted(f, redaction())
+    }
+}
+
+impl<const N: usize, const I: usize> RedactedDisplay for RelayInit<N, I> {
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>, redaction: Redaction) -> fmt::Result {
+        let initiator = RedactedEnr(&self.0, redaction);
         let tgt = hex::encode(self.1);
         let nonce = hex::encode(self.2);
         write!(
@@ -43,6 +190,20 @@ impl fmt::Display for RelayInit {
             &tgt[tgt.len() - 4..],
             &nonce[0..2],
             &nonce[nonce.len() - 2..]
-        )
+        )?;
+        if let Some(attempt_id) = self.3 {
+            write!(f, ", Attempt: 0x{}", hex::encode(attempt_id))?;
+        }
+        match &self.4 {
+            Some(candidates) if redaction == Redaction::Full => {
+                write!(f, ", Candidates: [{}]", candidates)?
+            }
+            Some(candidates) => write!(f, ", Candidates: {}", candidates.len())?,
+            None => {}
+        }
+        if let Some(extensions) = &self.5 {
+            write!(f, ", Extensions: [{}]", extensions)?;
+        }
+        Ok(())
     }
 }
@@ -1,37 +1,155 @@
-use crate::impl_from_variant_unwrap;
-use crate::{Enr, MessageNonce, Notification, REALYMSG_MSG_TYPE};
-use rlp::RlpStream;
-use std::fmt;
+use super::{
+    append_enr, append_optional, decode_attempt_id, decode_candidates, decode_enr,
+    decode_extensions, decode_nonce, decode_relay_id, decode_socket_addr, optional_len,
+    pool::{encode_pooled, encoded_len_pooled},
+    RlpNodeId, RlpSocketAddr,
+};
+use crate::{
+    redact::{redaction, RedactedEnr, RedactedSocket},
+    rlp_backend::{Decodable, DecoderError, Encodable, Rlp, RlpStream},
+    AttemptId, Candidates, DecodeLimits, Enr, ExtensionCodec, Extensions, MessageNonce, NodeId,
+    Notification, NotificationType, RedactedDisplay, Redaction, WireConfig, MESSAGE_NONCE_LENGTH,
+    NODE_ID_LENGTH,
+};
+use std::{fmt, net::SocketAddr};
 
 /// Nonce of request that triggered the initiation of this hole punching attempt.
-type NonceOfTimedOutMessage = MessageNonce;
+type NonceOfTimedOutMessage<const N: usize> = MessageNonce<N>;
 
-/// A notification sent from the initiator to the relay. Contains the enr of the initiator and the
-/// nonce of the timed out request.
+/// A notification sent from the relay to the target. Contains the enr of the initiator, the nonce
+/// of the timed out request and optionally the attempt id, the candidate addresses of the
+/// initiator, the socket the relay observes the target at, the node id of the relay and
+/// extensions.
 #[derive(Clone, PartialEq, Eq, Debug)]
-pub struct RelayMsg(pub Enr, pub NonceOfTimedOutMessage);
+pub struct RelayMsg<const N: usize = MESSAGE_NONCE_LENGTH, const I: usize = NODE_ID_LENGTH>(
+    pub Enr,
+    pub NonceOfTimedOutMessage<N>,
+    pub Option<AttemptId>,
+    pub Option<Candidates>,
+    pub Option<SocketAddr>,
+    pub Option<NodeId>,
+    pub Option<Extensions>,
+);
 
-impl_from_variant_unwrap!(, Notification, RelayMsg, Notification::RelayMsg);
+impl<const N: usize, const I: usize> From<Notification<N, I>> for RelayMsg<N, I> {
+    fn from(notif: Notification<N, I>) -> Self {
+        if let Notification::RelayMsg(v) = notif {
+            return v;
+        }
+        panic!("Bad impl of From")
+    }
+}
 
-impl RelayMsg {
-    pub fn rlp_encode(self) -> Vec<u8> {
-        let RelayMsg(initiator, nonce) = self;
+impl<const N: usize, const I: usize> RelayMsg<N, I> {
+    /// Decodes the extension of the codec's type, if present.
+    pub fn extension<T: ExtensionCodec>(&self) -> Option<Result<T, DecoderError>> {
+        self.6.as_ref()?.get()
+    }
 
-        let mut s = RlpStream::new();
-        s.begin_list(2);
-        s.append(&initiator);
-        s.append(&(&nonce as &[u8]));
+    /// Encodes the notification, prefixed with its type.
+    pub fn to_rlp(&self) -> Vec<u8> {
+        self.to_rlp_with(&WireConfig::default())
+    }
 
+    /// Encodes the notification according to the given wire format options.
+    pub fn to_rlp_with(&self, wire: &WireConfig) -> Vec<u8> {
         let mut buf: Vec<u8> = Vec::with_capacity(312);
-        buf.push(REALYMSG_MSG_TYPE);
-        buf.extend_from_slice(&s.out());
+        self.rlp_encode_to(&mut buf, wire);
         buf
     }
+
+    /// Consuming [`Self::to_rlp`].
+    pub fn rlp_encode(self) -> Vec<u8> {
+        self.to_rlp()
+    }
+
+    /// Consuming [`Self::to_rlp_with`].
+    pub fn rlp_encode_with(self, wire: &WireConfig) -> Vec<u8> {
+        self.to_rlp_with(wire)
+    }
+
+    /// Appends the notification, encoded according to the given wire format options, to `buf`.
+    /// Encodes through a pooled stream, so that relays re-encoding into a reused `buf` don't
+    /// allocate.
+    pub fn rlp_encode_to(&self, buf: &mut Vec<u8>, wire: &WireConfig) {
+        let start = buf.len();
+        buf.push(NotificationType::RelayMsg.to_byte(wire.type_numbering));
+        encode_pooled(buf, |s| self.rlp_append_wire(s, wire));
+        wire.pad_from(buf, start);
+    }
+
+    /// The length of the notification encoded according to the given wire format options,
+    /// including its type and any padding, see [`crate::RelayInit::encoded_len`].
+    pub fn encoded_len(&self, wire: &WireConfig) -> usize {
+        wire.padded_len(1 + encoded_len_pooled(|s| self.rlp_append_wire(s, wire)))
+    }
+}
+
+impl<const N: usize, const I: usize> RelayMsg<N, I> {
+    /// Appends the notification according to the given wire format options.
+    fn rlp_append_wire(&self, s: &mut RlpStream, wire: &WireConfig) {
+        let RelayMsg(initiator, nonce, attempt_id, candidates, observed, relay, extensions) = self;
+
+        let attempt_id = attempt_id.as_ref().map(|attempt_id| attempt_id as &[u8]);
+        let observed = observed.map(RlpSocketAddr);
+        let relay = relay.map(RlpNodeId::<I>);
+        let extensions = extensions.as_ref().filter(|ext| !ext.is_empty());
+        let optional = [
+            attempt_id.as_ref().map(|item| item as &dyn Encodable),
+            candidates.as_ref().map(|item| item as &dyn Encodable),
+            observed.as_ref().map(|item| item as &dyn Encodable),
+            relay.as_ref().map(|item| item as &dyn Encodable),
+            extensions.map(|item| item as &dyn Encodable),
+        ];
+
+        s.begin_list(2 + optional_len(&optional));
+        append_enr(s, initiator, wire.compress_enr);
+        s.append(&(nonce as &[u8]));
+        append_optional(s, &optional);
+    }
+}
+
+impl<const N: usize, const I: usize> Encodable for RelayMsg<N, I> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_wire(s, &WireConfig::default());
+    }
+}
+
+impl<const N: usize, const I: usize> Decodable for RelayMsg<N, I> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        Self::decode_with(rlp, &DecodeLimits::default())
+    }
+}
+
+impl<const N: usize, const I: usize> RelayMsg<N, I> {
+    /// Decodes the notification, decompressing its ENR up to the size cap of `limits`.
+    pub(crate) fn decode_with(rlp: &Rlp<'_>, limits: &DecodeLimits) -> Result<Self, DecoderError> {
+        if !(2..=7).contains(&rlp.item_count()?) {
+            return Err(DecoderError::RlpIncorrectListLen);
+        }
+        let initiator = decode_enr(rlp, 0, limits.max_enr_size)?;
+        let nonce = decode_nonce(rlp, 1)?;
+        let attempt_id = decode_attempt_id(rlp, 2)?;
+        let candidates = decode_candidates(rlp, 3)?;
+        let observed = decode_socket_addr(rlp, 4)?;
+        let relay = decode_relay_id::<I>(rlp, 5)?;
+        let extensions = decode_extensions(rlp, 6)?;
+
+        Ok(RelayMsg(
+            initiator, nonce, attempt_id, candidates, observed, relay, extensions,
+        ))
+    }
 }
 
-impl fmt::Display for RelayMsg {
+impl<const N: usize, const I: usize> fmt::Display for RelayMsg<N, I> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let initiator = &self.0;
+        self.fmt_redacted(f, redaction())
+    }
+}
+
+impl<const N: usize, const I: usize> RedactedDisplay for RelayMsg<N, I> {
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>, redaction: Redaction) -> fmt::Result {
+        let initiator = RedactedEnr(&self.0, redaction);
         let nonce = hex::encode(self.1);
         write!(
             f,
@@ -39,6 +157,32 @@ impl fmt::Display for RelayMsg {
             initiator,
             &nonce[0..2],
             &nonce[nonce.len() - 2..]
-        )
+        )?;
+        if let Some(attempt_id) = self.2 {
+            write!(f, ", Attempt: 0x{}", hex::encode(attempt_id))?;
+        }
+        match &self.3 {
+            Some(candidates) if redaction == Redaction::Full => {
+                write!(f, ", Candidates: [{}]", candidates)?
+            }
+            Some(candidates) => write!(f, ", Candidates: {}", candidates.len())?,
+            None => {}
+        }
+        if let Some(observed) = self.4 {
+            write!(f, ", Observed: {}", RedactedSocket(observed, redaction))?;
+        }
+        if let Some(relay) = self.5 {
+            let relay = hex::encode(relay.raw());
+            write!(
+                f,
+                ", Relay: 0x{}..{}",
+                &relay[0..4],
+                &relay[relay.len() - 4..]
+            )?;
+        }
+        if let Some(extensions) = &self.6 {
+            write!(f, ", Extensions: [{}]", extensions)?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,232 @@
+use crate::rlp_backend::DecoderError;
+
+const TAG_LITERAL: u8 = 0b00;
+const TAG_COPY_1: u8 = 0b01;
+const TAG_COPY_2: u8 = 0b10;
+const MIN_MATCH: usize = 4;
+const MAX_COPY_2_LEN: usize = 64;
+const HASH_BITS: u32 = 10;
+
+/// Compresses `input` to the raw snappy format, without framing. Greedy, emitting copies with 2
+/// byte offsets, which is plenty for an ENR and not worth pulling in a compression crate for.
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() + 8);
+    let mut len = input.len();
+    while len >= 0x80 {
+        out.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    out.push(len as u8);
+
+    let mut table = [usize::MAX; 1 << HASH_BITS];
+    let mut literal_start = 0;
+    let mut pos = 0;
+    while pos + MIN_MATCH <= input.len() {
+        let key = u32::from_le_bytes(input[pos..pos + MIN_MATCH].try_into().unwrap());
+        let slot = (key.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[slot];
+        table[slot] = pos;
+        if candidate == usize::MAX
+            || pos - candidate > u16::MAX as usize
+            || input[candidate..candidate + MIN_MATCH] != input[pos..pos + MIN_MATCH]
+        {
+            pos += 1;
+            continue;
+        }
+        let mut match_len = MIN_MATCH;
+        while pos + match_len < input.len()
+            && input[candidate + match_len] == input[pos + match_len]
+        {
+            match_len += 1;
+        }
+        emit_literal(&mut out, &input[literal_start..pos]);
+        let offset = pos - candidate;
+        let mut left = match_len;
+        while left > 0 {
+            let chunk = left.min(MAX_COPY_2_LEN);
+            out.push(TAG_COPY_2 | ((chunk - 1) as u8) << 2);
+            out.extend_from_slice(&(offset as u16).to_le_bytes());
+            left -= chunk;
+        }
+        pos += match_len;
+        literal_start = pos;
+    }
+    emit_literal(&mut out, &input[literal_start..]);
+    out
+}
+
+fn emit_literal(out: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    let n = literal.len() - 1;
+    if n < 60 {
+        out.push(TAG_LITERAL | (n as u8) << 2);
+    } else {
+        let len_bytes = (n.ilog2() / 8 + 1) as usize;
+        out.push(TAG_LITERAL | ((59 + len_bytes) as u8) << 2);
+        out.extend_from_slice(&n.to_le_bytes()[..len_bytes]);
+    }
+    out.extend_from_slice(literal);
+}
+
+/// Decompresses `input`, failing if the output would exceed `max_len` bytes.
+pub(crate) fn decompress(input: &[u8], max_len: usize) -> Result<Vec<u8>, DecoderError> {
+    let invalid = || DecoderError::Custom("invalid snappy data");
+    // the length is a varint of at most 5 bytes, fitting a u32
+    let mut pos = 0;
+    let mut len = 0usize;
+    loop {
+        let byte = *input.get(pos).ok_or_else(invalid)?;
+        len |= ((byte & 0x7f) as usize) << (7 * pos);
+        pos += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if pos == 5 {
+            return Err(invalid());
+        }
+    }
+    if len > max_len {
+        return Err(DecoderError::RlpIsTooBig);
+    }
+
+    let mut out = Vec::with_capacity(len);
+    while pos < input.len() {
+        let tag = input[pos];
+        pos += 1;
+        let (copy_len, offset) = match tag & 0b11 {
+            TAG_LITERAL => {
+                let mut n = (tag >> 2) as usize;
+                if n >= 60 {
+                    let len_bytes = n - 59;
+                    let bytes = input.get(pos..pos + len_bytes).ok_or_else(invalid)?;
+                    n = bytes
+                        .iter()
+                        .rev()
+                        .fold(0, |n, byte| n << 8 | *byte as usize);
+                    pos += len_bytes;
+                }
+                let literal = input.get(pos..pos + n + 1).ok_or_else(invalid)?;
+                if out.len() + literal.len() > len {
+                    return Err(invalid());
+                }
+                out.extend_from_slice(literal);
+                pos += n + 1;
+                continue;
+            }
+            TAG_COPY_1 => {
+                let byte = *input.get(pos).ok_or_else(invalid)?;
+                pos += 1;
+                (
+                    ((tag >> 2) & 0b111) as usize + 4,
+                    ((tag >> 5) as usize) << 8 | byte as usize,
+                )
+            }
+            TAG_COPY_2 => {
+                let bytes = input.get(pos..pos + 2).ok_or_else(invalid)?;
+                pos += 2;
+                (
+                    (tag >> 2) as usize + 1,
+                    u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+                )
+            }
+            // a copy with a 4 byte offset
+            _ => {
+                let bytes = input.get(pos..pos + 4).ok_or_else(invalid)?;
+                pos += 4;
+                (
+                    (tag >> 2) as usize + 1,
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+                )
+            }
+        };
+        if offset == 0 || offset > out.len() || out.len() + copy_len > len {
+            return Err(invalid());
+        }
+        // copies may overlap their own output
+        let start = out.len() - offset;
+        for i in 0..copy_len {
+            out.push(out[start + i]);
+        }
+    }
+    if out.len() != len {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snappy_round_trip() {
+        // a literal "a" followed by a 4 byte copy at offset 1, as emitted by other encoders
+        assert_eq!(
+            b"aaaaa".to_vec(),
+            decompress(&[0x05, 0x00, b'a', TAG_COPY_1, 0x01], 16).unwrap()
+        );
+
+        let repetitive = b"nat_hp nat_hp nat_hp ".repeat(20);
+        let compressed = compress(&repetitive);
+        assert!(compressed.len() < repetitive.len() / 4);
+        assert_eq!(repetitive, decompress(&compressed, 1024).unwrap());
+        let random = (0..300).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        assert_eq!(
+            random,
+            decompress(&compress(&random), random.len()).unwrap()
+        );
+
+        assert!(decompress(&compressed, 100).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1], 1024).is_err());
+    }
+
+    #[test]
+    fn test_malformed_input_rejected() {
+        let invalid = Err(DecoderError::Custom("invalid snappy data"));
+        // truncated varint
+        assert_eq!(invalid, decompress(&[], 16));
+        assert_eq!(invalid, decompress(&[0x80], 16));
+        // varint of more than 5 bytes
+        assert_eq!(
+            invalid,
+            decompress(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 16)
+        );
+        // a 5 byte varint is fine
+        assert_eq!(
+            Ok(b"a".to_vec()),
+            decompress(&[0x81, 0x80, 0x80, 0x80, 0x00, 0x00, b'a'], 16)
+        );
+        // copy at offset 0, or before the start of the output
+        assert_eq!(
+            invalid,
+            decompress(&[0x05, 0x00, b'a', TAG_COPY_1, 0x00], 16)
+        );
+        assert_eq!(
+            invalid,
+            decompress(&[0x05, 0x00, b'a', TAG_COPY_1, 0x02], 16)
+        );
+        assert_eq!(
+            invalid,
+            decompress(&[0x04, TAG_COPY_2 | 3 << 2, 0x01, 0x00], 16)
+        );
+        // literal past the end of the input, or of its length bytes
+        assert_eq!(invalid, decompress(&[0x03, 0x02 << 2, b'a', b'b'], 16));
+        assert_eq!(invalid, decompress(&[0x03, 61 << 2, 0x02], 16));
+        // copy with its offset cut off
+        assert_eq!(invalid, decompress(&[0x05, 0x00, b'a', TAG_COPY_2], 16));
+        // output shorter or longer than its declared length
+        assert_eq!(invalid, decompress(&[0x02, 0x00, b'a'], 16));
+        assert_eq!(invalid, decompress(&[0x01, 0x04, b'a', b'b'], 16));
+        assert_eq!(
+            invalid,
+            decompress(&[0x05, 0x00, b'a', TAG_COPY_1 | 1 << 2, 0x01], 16)
+        );
+        // declared length over the cap
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            decompress(&[0x11, 0x00, b'a'], 16)
+        );
+    }
+}
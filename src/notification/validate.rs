@@ -0,0 +1,214 @@
+use super::{
+    Candidates, Echo, EchoAfter, Enr, MessageNonce, Notification, PunchReport, RelayAbort,
+    RelayInit, RelayMsg, DEFAULT_MAX_ENR_SIZE,
+};
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+
+/// Why a notification failed [`Notification::validate`].
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("ENR signature doesn't verify")]
+    BadSignature,
+    #[error("ENR of {0} bytes exceeds the {1} byte limit")]
+    EnrTooLarge(usize, usize),
+    #[error("socket {0} can't be punched towards")]
+    BadSocket(SocketAddr),
+    #[error("nonce is all zeros")]
+    ZeroNonce,
+}
+
+/// The checks [`Notification::validate`] performs on top of decoding. Use one of the presets,
+/// [`Self::STRICT`], [`Self::STANDARD`] or [`Self::PERMISSIVE`], or mix checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// Verifies the signature of the ENR of the initiator. Decoding verifies it already, this
+    /// catches notifications built locally.
+    pub verify_enr_signature: bool,
+    /// Max size of the ENR of the initiator, RLP encoded and uncompressed. Unchecked if `None`.
+    pub max_enr_size: Option<usize>,
+    /// Rejects unspecified, multicast and broadcast sockets and port zero among the candidates
+    /// of the initiator and the socket observed by the relay.
+    pub reject_bad_sockets: bool,
+    /// Applies the socket checks to the sockets of the ENR of the initiator too, and rejects
+    /// loopback sockets.
+    pub strict_sockets: bool,
+    /// Rejects all zero nonces.
+    pub reject_zero_nonce: bool,
+}
+
+impl ValidationPolicy {
+    /// All checks, for relays exposed to untrusted peers.
+    pub const STRICT: Self = ValidationPolicy {
+        verify_enr_signature: true,
+        max_enr_size: Some(DEFAULT_MAX_ENR_SIZE),
+        reject_bad_sockets: true,
+        strict_sockets: true,
+        reject_zero_nonce: true,
+    };
+
+    /// The checks of [`Self::STRICT`] except those on loopback and ENR sockets, which local
+    /// test networks use.
+    pub const STANDARD: Self = ValidationPolicy {
+        verify_enr_signature: true,
+        max_enr_size: Some(DEFAULT_MAX_ENR_SIZE),
+        reject_bad_sockets: true,
+        strict_sockets: false,
+        reject_zero_nonce: true,
+    };
+
+    /// Only the ENR signature check.
+    pub const PERMISSIVE: Self = ValidationPolicy {
+        verify_enr_signature: true,
+        max_enr_size: None,
+        reject_bad_sockets: false,
+        strict_sockets: false,
+        reject_zero_nonce: false,
+    };
+
+    fn check_enr(&self, enr: &Enr) -> Result<(), ValidationError> {
+        if self.verify_enr_signature && !enr.verify() {
+            return Err(ValidationError::BadSignature);
+        }
+        if let Some(max) = self.max_enr_size {
+            if enr.size() > max {
+                return Err(ValidationError::EnrTooLarge(enr.size(), max));
+            }
+        }
+        if self.strict_sockets {
+            let sockets = enr
+                .udp4_socket()
+                .map(SocketAddr::V4)
+                .into_iter()
+                .chain(enr.udp6_socket().map(SocketAddr::V6));
+            for socket in sockets {
+                self.check_socket(&socket)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_candidates(&self, candidates: Option<&Candidates>) -> Result<(), ValidationError> {
+        for candidate in candidates.into_iter().flat_map(Candidates::iter) {
+            self.check_socket(&candidate.addr)?;
+        }
+        Ok(())
+    }
+
+    fn check_socket(&self, socket: &SocketAddr) -> Result<(), ValidationError> {
+        if !self.reject_bad_sockets && !self.strict_sockets {
+            return Ok(());
+        }
+        let ip = socket.ip();
+        let bad = socket.port() == 0
+            || ip.is_unspecified()
+            || ip.is_multicast()
+            || matches!(ip, IpAddr::V4(ip) if ip.is_broadcast())
+            || (self.strict_sockets && ip.is_loopback());
+        if bad {
+            return Err(ValidationError::BadSocket(*socket));
+        }
+        Ok(())
+    }
+
+    fn check_nonce<const N: usize>(&self, nonce: &MessageNonce<N>) -> Result<(), ValidationError> {
+        if self.reject_zero_nonce && nonce.iter().all(|byte| *byte == 0) {
+            return Err(ValidationError::ZeroNonce);
+        }
+        Ok(())
+    }
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl<const N: usize, const I: usize> Notification<N, I> {
+    /// Checks the notification against `policy`, beyond what decoding checks. The
+    /// [`crate::HolePunchService`] validates each notification it receives.
+    pub fn validate(&self, policy: &ValidationPolicy) -> Result<(), ValidationError> {
+        match self {
+            Notification::RelayInit(RelayInit(enr, _, nonce, _, candidates, _)) => {
+                policy.check_nonce(nonce)?;
+                policy.check_enr(enr)?;
+                policy.check_candidates(candidates.as_ref())
+            }
+            Notification::RelayMsg(RelayMsg(enr, nonce, _, candidates, observed, ..)) => {
+                policy.check_nonce(nonce)?;
+                policy.check_enr(enr)?;
+                policy.check_candidates(candidates.as_ref())?;
+                observed
+                    .as_ref()
+                    .map_or(Ok(()), |observed| policy.check_socket(observed))
+            }
+            Notification::RelayAbort(RelayAbort(nonce, ..))
+            | Notification::PunchReport(PunchReport(nonce, ..))
+            | Notification::EchoAfter(EchoAfter(_, nonce, ..))
+            | Notification::Echo(Echo(nonce, ..)) => policy.check_nonce(nonce),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Candidate, CandidateKind};
+    use enr::{CombinedKey, EnrBuilder, NodeId};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_validate_presets() {
+        let key = CombinedKey::generate_secp256k1();
+        let enr = EnrBuilder::new("v4")
+            .ip4(Ipv4Addr::LOCALHOST)
+            .udp4(9000)
+            .build(&key)
+            .unwrap();
+        let nonce = [1; 12];
+        let relay_init = |nonce, candidates| -> Notification {
+            Notification::from(RelayInit(
+                enr.clone(),
+                NodeId::random(),
+                nonce,
+                None,
+                candidates,
+                None,
+            ))
+        };
+
+        let notif = relay_init(nonce, None);
+        assert_eq!(Ok(()), notif.validate(&ValidationPolicy::STANDARD));
+        // the loopback ENR socket only passes below strict
+        assert_eq!(
+            Err(ValidationError::BadSocket(
+                "127.0.0.1:9000".parse().unwrap()
+            )),
+            notif.validate(&ValidationPolicy::STRICT)
+        );
+
+        let multicast: SocketAddr = "224.0.0.1:9000".parse().unwrap();
+        let candidates = Candidates::new(vec![Candidate::new(CandidateKind::Host, multicast)]);
+        let notif = relay_init(nonce, Some(candidates));
+        assert_eq!(
+            Err(ValidationError::BadSocket(multicast)),
+            notif.validate(&ValidationPolicy::STANDARD)
+        );
+        assert_eq!(Ok(()), notif.validate(&ValidationPolicy::PERMISSIVE));
+
+        let notif = relay_init([0; 12], None);
+        assert_eq!(
+            Err(ValidationError::ZeroNonce),
+            notif.validate(&ValidationPolicy::STANDARD)
+        );
+        let policy = ValidationPolicy {
+            max_enr_size: Some(16),
+            ..ValidationPolicy::PERMISSIVE
+        };
+        assert_eq!(
+            Err(ValidationError::EnrTooLarge(enr.size(), 16)),
+            relay_init(nonce, None).validate(&policy)
+        );
+    }
+}
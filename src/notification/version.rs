@@ -0,0 +1,63 @@
+use super::{ExtensionCodec, ExtensionType};
+use crate::{rlp_backend::DecoderError, WireConfig, HOLE_PUNCH_PROTOCOL_VERSION};
+use parse_display_derive::Display;
+
+/// A revision of the notification protocol, see [`HOLE_PUNCH_PROTOCOL_VERSION`]. Sent as an
+/// extension of the notifications to peers of revision 8 or later, so that the receiver learns
+/// the highest revision the sender speaks.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[display("v{0}")]
+pub struct ProtocolVersion(pub u8);
+
+impl ProtocolVersion {
+    /// The revision implemented by this crate.
+    pub const CURRENT: ProtocolVersion = ProtocolVersion(HOLE_PUNCH_PROTOCOL_VERSION);
+
+    /// Restricts the wire format options to those a peer speaking this revision understands.
+    pub fn restrict(self, wire: &WireConfig) -> WireConfig {
+        WireConfig {
+            attempt_ids: wire.attempt_ids && self.0 >= 2,
+            observed_addrs: wire.observed_addrs && self.0 >= 4,
+            compress_enr: wire.compress_enr && self.0 >= 5,
+            relay_ids: wire.relay_ids && self.0 >= 7,
+            punch_hints: wire.punch_hints && self.supports_extensions(),
+            ..*wire
+        }
+    }
+
+    /// Returns true if notifications may carry candidates, since revision 3.
+    pub fn supports_candidates(self) -> bool {
+        self.0 >= 3
+    }
+
+    /// Returns true if the [`crate::PunchReport`] is understood, since revision 6.
+    pub fn supports_punch_reports(self) -> bool {
+        self.0 >= 6
+    }
+
+    /// Returns true if notifications may carry extensions, since revision 8.
+    pub fn supports_extensions(self) -> bool {
+        self.0 >= 8
+    }
+
+    /// Returns true if the [`crate::EchoAfter`] and [`crate::Echo`] are understood, since
+    /// revision 9.
+    pub fn supports_echo(self) -> bool {
+        self.0 >= 9
+    }
+}
+
+impl ExtensionCodec for ProtocolVersion {
+    const TYPE: ExtensionType = 2;
+
+    fn encode(&self) -> Vec<u8> {
+        vec![self.0]
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, DecoderError> {
+        match data {
+            [version] => Ok(ProtocolVersion(*version)),
+            _ => Err(DecoderError::Custom("version is one byte")),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use super::{AttemptId, TypeNumbering, ATTEMPT_ID_LENGTH};
+
+/// Least number of bytes a [`ProtocolNamespace`] appends to notifications.
+pub const NAMESPACE_TAG_LENGTH: usize = 4;
+
+/// Number of bytes at the end of an attempt id that a [`ProtocolNamespace`] derives.
+const ATTEMPT_ID_CHECK_LENGTH: usize = 2;
+
+const FNV_OFFSET: u32 = 0x811c9dc5;
+
+/// A network the notifications are confined to, for example a private devnet, see
+/// [`WireConfig::namespace`]. The bytes after the RLP list of each notification, the tag and any
+/// padding, are keyed by the namespace over the notification, and so is the end of each attempt
+/// id. The trailer thus differs between notifications, and a notification from another network
+/// is dropped before its RLP is parsed. The keying is a cheap compatibility check, not a secret,
+/// anyone knowing the name of a namespace can tell its notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProtocolNamespace(pub [u8; NAMESPACE_TAG_LENGTH]);
+
+impl ProtocolNamespace {
+    /// Derives the key of the namespace from its name, with 32 bit FNV-1a.
+    pub fn new(name: &str) -> Self {
+        ProtocolNamespace(fnv1a(FNV_OFFSET, name.as_bytes()).to_be_bytes())
+    }
+
+    /// Derives an attempt id of the namespace from random bytes, keeping their start and keying
+    /// the end over it.
+    pub fn attempt_id(&self, random: AttemptId) -> AttemptId {
+        let head = ATTEMPT_ID_LENGTH - ATTEMPT_ID_CHECK_LENGTH;
+        let mut attempt_id = random;
+        let check = self.keyed(b"attempt id", &attempt_id[..head]).to_be_bytes();
+        attempt_id[head..].copy_from_slice(&check[..ATTEMPT_ID_CHECK_LENGTH]);
+        attempt_id
+    }
+
+    /// Returns true if `attempt_id` was derived by [`Self::attempt_id`].
+    pub fn is_attempt_id(&self, attempt_id: &[u8]) -> bool {
+        AttemptId::try_from(attempt_id).is_ok_and(|id| self.attempt_id(id) == id)
+    }
+
+    /// The bytes following the RLP list of the encoded notification `notif`.
+    fn trailer(&self, notif: &[u8]) -> impl Iterator<Item = u8> {
+        let seed = self.keyed(b"trailer", notif);
+        (0u32..).flat_map(move |block| fnv1a(seed, &block.to_be_bytes()).to_be_bytes())
+    }
+
+    fn keyed(&self, domain: &[u8], data: &[u8]) -> u32 {
+        [&self.0[..], domain, data]
+            .into_iter()
+            .fold(FNV_OFFSET, fnv1a)
+    }
+}
+
+/// Continues the 32 bit FNV-1a `hash` over `bytes`.
+fn fnv1a(hash: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Wire format options of the notifications, that peers must agree on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WireConfig {
+    /// Pads encoded notifications with zeros up to a multiple of the given number of bytes, so
+    /// that their length doesn't give away their type. Decoding strips padding regardless.
+    pub padding_bucket: Option<usize>,
+    /// Sends an [`crate::AttemptId`] along with each notification. Peers implementing protocol
+    /// revision 1 reject notifications carrying one, so only enable once all peers upgraded.
+    pub attempt_ids: bool,
+    /// As relay, appends the socket the target's session is at to the [`crate::RelayMsg`], so that
+    /// the target learns its reflexive address. Peers before protocol revision 4 reject
+    /// notifications carrying one.
+    pub observed_addrs: bool,
+    /// Compresses the ENR in notifications with snappy where that makes them shorter. Decoding
+    /// accepts compressed and uncompressed ENRs regardless, but peers before protocol revision 5
+    /// reject compressed ones.
+    pub compress_enr: bool,
+    /// As relay, appends the node id of this node to the [`crate::RelayMsg`], so that the target
+    /// can tell relays apart, see [`crate::HolePunchConfig::rate_limit_per_relay`]. Peers before
+    /// protocol revision 7 reject notifications carrying one.
+    pub relay_ids: bool,
+    /// As relay, tells the target which addresses of the initiator are of the address family the
+    /// relay reaches the target over, see [`crate::PunchToward`]. Peers before protocol revision
+    /// 8 reject notifications carrying extensions.
+    pub punch_hints: bool,
+    /// How notification types are numbered, for interop with peers of early releases.
+    pub type_numbering: TypeNumbering,
+    /// Confines the notifications to a network. Notifications lacking the trailer or attempt id
+    /// the namespace derives, such as those of another namespace or of none, are rejected on
+    /// decode.
+    pub namespace: Option<ProtocolNamespace>,
+}
+
+impl WireConfig {
+    /// Pads the encoded notification according to the config.
+    pub fn pad(&self, buf: &mut Vec<u8>) {
+        self.pad_from(buf, 0)
+    }
+
+    /// Pads the notification encoded at `start` of `buf` according to the config.
+    pub(crate) fn pad_from(&self, buf: &mut Vec<u8>, start: usize) {
+        let len = buf.len();
+        let padded_len = self.padded_len(len - start);
+        buf.resize(start + padded_len, 0);
+        let (notif, trailer) = buf[start..].split_at_mut(len - start);
+        self.fill_trailer(notif, trailer);
+    }
+
+    /// Fills the bytes after the RLP list of the encoded notification `notif`, with zeros or as
+    /// keyed by the namespace.
+    pub(crate) fn fill_trailer(&self, notif: &[u8], trailer: &mut [u8]) {
+        match self.namespace {
+            Some(namespace) => trailer
+                .iter_mut()
+                .zip(namespace.trailer(notif))
+                .for_each(|(byte, keyed)| *byte = keyed),
+            None => trailer.fill(0),
+        }
+    }
+
+    /// Returns true if the bytes after the RLP list of the encoded notification `notif` are as
+    /// [`Self::fill_trailer`] fills them.
+    pub(crate) fn check_trailer(&self, notif: &[u8], trailer: &[u8]) -> bool {
+        match self.namespace {
+            Some(namespace) => {
+                trailer.len() >= NAMESPACE_TAG_LENGTH
+                    && trailer
+                        .iter()
+                        .copied()
+                        .eq(namespace.trailer(notif).take(trailer.len()))
+            }
+            None => trailer.iter().all(|byte| *byte == 0),
+        }
+    }
+
+    /// Derives the attempt id of a new attempt from random bytes, in the namespace if any.
+    pub(crate) fn attempt_id(&self, random: AttemptId) -> AttemptId {
+        self.namespace
+            .map_or(random, |namespace| namespace.attempt_id(random))
+    }
+
+    /// The length of an encoded notification of `len` bytes once tagged and padded according to
+    /// the config.
+    pub(crate) fn padded_len(&self, len: usize) -> usize {
+        let len = len + self.namespace.map_or(0, |_| NAMESPACE_TAG_LENGTH);
+        match self.padding_bucket.filter(|bucket| *bucket > 0) {
+            Some(bucket) => len.div_ceil(bucket) * bucket,
+            None => len,
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{NatStatus, NodeId, Reachability, ReachabilityEntry, RelayScore};
+use std::{
+    fs, io,
+    net::SocketAddr,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// The learned hole punch state that is worth keeping across restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PersistedState {
+    pub nat_status: NatStatus,
+    pub relay_scores: Vec<(NodeId, RelayScore)>,
+    pub reachability: Vec<(NodeId, ReachabilityEntry)>,
+    /// The punched holes being kept alive, so that a process replacing this one, for example
+    /// for an upgrade, keeps them open.
+    pub holes: Vec<PersistedHole>,
+}
+
+/// A punched hole being kept alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PersistedHole {
+    pub dst: SocketAddr,
+    /// Wall clock time of the last packet sent or received on the hole, so that holes that
+    /// closed while no process kept them alive aren't restored.
+    pub last_activity: SystemTime,
+    /// The initiator the hole was punched for, if this node was the target.
+    pub initiator: Option<NodeId>,
+}
+
+/// Storage for the [`PersistedState`].
+pub trait StateStore {
+    type Error;
+    /// Stores the state, replacing any previously stored state.
+    fn save(&mut self, state: &PersistedState) -> Result<(), Self::Error>;
+    /// Loads the stored state, if any state has been stored.
+    fn load(&mut self) -> Result<Option<PersistedState>, Self::Error>;
+}
+
+#[derive(Debug, Error)]
+pub enum FileStateStoreError {
+    #[error("failed accessing state file, {0}")]
+    Io(#[from] io::Error),
+    #[error("malformed state file at line {line}, {reason}")]
+    Malformed { line: usize, reason: &'static str },
+}
+
+/// Stores the [`PersistedState`] in a flat text file with one record per line:
+///
+/// ```text
+/// nat <status>
+/// relay <node-id> <successes> <failures>
+/// reachability <node-id> <reachability> <unix-secs> [socket]
+/// hole <socket> <unix-millis> <initiator-node-id or ->
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileStateStore {
+    path: PathBuf,
+}
+
+impl FileStateStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStateStore { path: path.into() }
+    }
+
+    pub fn encode(state: &PersistedState) -> String {
+        let mut out = format!("nat {}\n", state.nat_status);
+        for (node_id, score) in state.relay_scores.iter() {
+            out.push_str(&format!(
+                "relay {} {} {}\n",
+                hex::encode(node_id.raw()),
+                score.successes,
+                score.failures
+            ));
+        }
+        for (node_id, entry) in state.reachability.iter() {
+            let secs = entry
+                .updated
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let socket = entry
+                .socket
+                .map_or(String::new(), |socket| format!(" {socket}"));
+            out.push_str(&format!(
+                "reachability {} {} {}{}\n",
+                hex::encode(node_id.raw()),
+                entry.reachability,
+                secs,
+                socket
+            ));
+        }
+        for hole in state.holes.iter() {
+            let millis = hole
+                .last_activity
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            let initiator = hole
+                .initiator
+                .map_or("-".to_string(), |node_id| hex::encode(node_id.raw()));
+            out.push_str(&format!("hole {} {} {}\n", hole.dst, millis, initiator));
+        }
+        out
+    }
+
+    pub fn decode(s: &str) -> Result<PersistedState, FileStateStoreError> {
+        let mut state = PersistedState::default();
+        for (index, line) in s.lines().enumerate() {
+            let malformed = |reason| FileStateStoreError::Malformed {
+                line: index + 1,
+                reason,
+            };
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields.as_slice() {
+                [] => {}
+                ["nat", status] => {
+                    state.nat_status = status.parse().map_err(malformed)?;
+                }
+                ["relay", node_id, successes, failures] => {
+                    let score = RelayScore {
+                        successes: successes.parse().map_err(|_| malformed("invalid count"))?,
+                        failures: failures.parse().map_err(|_| malformed("invalid count"))?,
+                    };
+                    state
+                        .relay_scores
+                        .push((parse_node_id(node_id).map_err(malformed)?, score));
+                }
+                ["reachability", node_id, reachability, secs, socket @ ..] if socket.len() <= 1 => {
+                    let secs = secs.parse().map_err(|_| malformed("invalid timestamp"))?;
+                    let socket = match socket.first() {
+                        Some(socket) => {
+                            Some(socket.parse().map_err(|_| malformed("invalid socket"))?)
+                        }
+                        None => None,
+                    };
+                    let entry = ReachabilityEntry {
+                        reachability: reachability.parse::<Reachability>().map_err(malformed)?,
+                        updated: UNIX_EPOCH + Duration::from_secs(secs),
+                        socket,
+                    };
+                    state
+                        .reachability
+                        .push((parse_node_id(node_id).map_err(malformed)?, entry));
+                }
+                ["hole", dst, millis, initiator] => {
+                    let millis = millis.parse().map_err(|_| malformed("invalid timestamp"))?;
+                    let initiator = match *initiator {
+                        "-" => None,
+                        node_id => Some(parse_node_id(node_id).map_err(malformed)?),
+                    };
+                    state.holes.push(PersistedHole {
+                        dst: dst.parse().map_err(|_| malformed("invalid socket"))?,
+                        last_activity: UNIX_EPOCH + Duration::from_millis(millis),
+                        initiator,
+                    });
+                }
+                _ => return Err(malformed("unknown record")),
+            }
+        }
+        Ok(state)
+    }
+}
+
+fn parse_node_id(s: &str) -> Result<NodeId, &'static str> {
+    let bytes = hex::decode(s).map_err(|_| "invalid node id")?;
+    if bytes.len() != 32 {
+        return Err("invalid node id");
+    }
+    NodeId::parse(&bytes)
+}
+
+impl PersistedState {
+    /// Deserializes state serialized with [`crate::HolePunchService::serialize_state`].
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, FileStateStoreError> {
+        let s = std::str::from_utf8(bytes).map_err(|_| FileStateStoreError::Malformed {
+            line: 0,
+            reason: "not utf-8",
+        })?;
+        FileStateStore::decode(s)
+    }
+}
+
+impl StateStore for FileStateStore {
+    type Error = FileStateStoreError;
+
+    fn save(&mut self, state: &PersistedState) -> Result<(), Self::Error> {
+        // write to a temporary file first so a crash doesn't leave a truncated state file
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, Self::encode(state))?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    fn load(&mut self) -> Result<Option<PersistedState>, Self::Error> {
+        match fs::read_to_string(&self.path) {
+            Ok(s) => Self::decode(&s).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_store_roundtrip() {
+        let path = std::env::temp_dir().join(format!(
+            "nat_hole_punch_state_{}",
+            hex::encode(NodeId::random().raw())
+        ));
+        let mut store = FileStateStore::new(&path);
+        assert_eq!(None, store.load().unwrap());
+
+        let state = PersistedState {
+            nat_status: NatStatus::BehindNat,
+            relay_scores: vec![(
+                NodeId::random(),
+                RelayScore {
+                    successes: 3,
+                    failures: 1,
+                },
+            )],
+            reachability: vec![
+                (
+                    NodeId::random(),
+                    ReachabilityEntry {
+                        reachability: Reachability::Punched,
+                        updated: UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+                        socket: Some("198.51.100.7:9000".parse().unwrap()),
+                    },
+                ),
+                (
+                    NodeId::random(),
+                    ReachabilityEntry {
+                        reachability: Reachability::Unreachable,
+                        updated: UNIX_EPOCH + Duration::from_secs(1_700_000_001),
+                        socket: None,
+                    },
+                ),
+            ],
+            holes: vec![
+                PersistedHole {
+                    dst: "[2001:db8::1]:9000".parse().unwrap(),
+                    last_activity: UNIX_EPOCH + Duration::from_millis(1_700_000_000_123),
+                    initiator: Some(NodeId::random()),
+                },
+                PersistedHole {
+                    dst: "192.0.2.1:9000".parse().unwrap(),
+                    last_activity: UNIX_EPOCH + Duration::from_millis(1_700_000_000_456),
+                    initiator: None,
+                },
+            ],
+        };
+        store.save(&state).unwrap();
+        let loaded = store.load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(state), loaded);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Enr;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt,
+    hash::{Hash, Hasher},
+    net::{IpAddr, SocketAddr},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+/// How the sockets and ENRs of peers are shown when notifications and node addresses are
+/// displayed, so that operators can publish logs without leaking the IPs of peers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Redaction {
+    /// Sockets and ENRs are shown in full.
+    #[default]
+    Full = 0,
+    /// Sockets are shown with the host part of the IP masked, ENRs by their truncated node id.
+    Truncated = 1,
+    /// Sockets and ENRs are shown as a short hash, so that log lines about the same peer can be
+    /// correlated. The hash is stable within a build but not keyed, so the IPv4 space can be
+    /// searched for a given hash.
+    Hashed = 2,
+}
+
+static REDACTION: AtomicU8 = AtomicU8::new(Redaction::Full as u8);
+
+/// Sets how the [`fmt::Display`] impls of [`crate::RelayInit`], [`crate::RelayMsg`] and
+/// [`crate::NodeAddress`] show sockets and ENRs, process wide. Set once by the application,
+/// services don't touch it. See [`Redacted`] for displaying a single value with a different
+/// redaction.
+pub fn set_redaction(redaction: Redaction) {
+    REDACTION.store(redaction as u8, Ordering::Relaxed);
+}
+
+/// The process wide redaction, see [`set_redaction`].
+pub fn redaction() -> Redaction {
+    match REDACTION.load(Ordering::Relaxed) {
+        1 => Redaction::Truncated,
+        2 => Redaction::Hashed,
+        _ => Redaction::Full,
+    }
+}
+
+/// A value that can be displayed with its sockets and ENRs redacted.
+pub trait RedactedDisplay {
+    fn fmt_redacted(&self, f: &mut fmt::Formatter<'_>, redaction: Redaction) -> fmt::Result;
+}
+
+/// Displays the wrapped value with the given redaction, regardless of the process wide one.
+#[derive(Debug, Clone, Copy)]
+pub struct Redacted<'a, T>(pub &'a T, pub Redaction);
+
+impl<T: RedactedDisplay> fmt::Display for Redacted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_redacted(f, self.1)
+    }
+}
+
+/// A socket displayed with the given redaction.
+pub(crate) struct RedactedSocket(pub SocketAddr, pub Redaction);
+
+impl fmt::Display for RedactedSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let RedactedSocket(socket, redaction) = self;
+        match redaction {
+            Redaction::Full => write!(f, "{}", socket),
+            Redaction::Truncated => match socket.ip() {
+                IpAddr::V4(ip) => write!(f, "{}.*:{}", ip.octets()[0], socket.port()),
+                IpAddr::V6(ip) => write!(f, "[{:x}:*]:{}", ip.segments()[0], socket.port()),
+            },
+            Redaction::Hashed => write!(f, "#{:08x}", short_hash(socket)),
+        }
+    }
+}
+
+/// An ENR displayed with the given redaction.
+pub(crate) struct RedactedEnr<'a>(pub &'a Enr, pub Redaction);
+
+impl fmt::Display for RedactedEnr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let RedactedEnr(enr, redaction) = self;
+        match redaction {
+            Redaction::Full => write!(f, "{}", enr),
+            Redaction::Truncated => {
+                let node_id = hex::encode(enr.node_id().raw());
+                write!(
+                    f,
+                    "ENR: NodeId: 0x{}..{}, Seq: {}",
+                    &node_id[0..4],
+                    &node_id[node_id.len() - 4..],
+                    enr.seq()
+                )
+            }
+            Redaction::Hashed => write!(f, "#{:08x}", short_hash(&enr.to_base64())),
+        }
+    }
+}
+
+fn short_hash(value: &impl Hash) -> u32 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeAddress, NodeId};
+
+    #[test]
+    fn test_redacted_node_address() {
+        let addr = NodeAddress::new("192.0.2.1:9000".parse().unwrap(), NodeId::from([0x11; 32]));
+
+        assert!(Redacted(&addr, Redaction::Full)
+            .to_string()
+            .contains("192.0.2.1:9000"));
+        let truncated = Redacted(&addr, Redaction::Truncated).to_string();
+        assert!(truncated.contains("192.*:9000"));
+        assert!(!truncated.contains("192.0.2.1"));
+        let hashed = Redacted(&addr, Redaction::Hashed).to_string();
+        assert!(!hashed.contains("9000"));
+        // the same socket hashes the same, so log lines can be correlated
+        assert_eq!(hashed, Redacted(&addr, Redaction::Hashed).to_string());
+    }
+}
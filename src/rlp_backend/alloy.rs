@@ -0,0 +1,493 @@
+//! The subset of the `rlp` crate's API this crate uses, implemented on `alloy-rlp`.
+
+use crate::Enr;
+use alloy_rlp::Header;
+use bytes::{BufMut, BytesMut};
+use std::str::FromStr;
+
+/// Error decoding RLP, with the variants of the `rlp` crate's error.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecoderError {
+    #[error("data has additional unexpected bytes")]
+    RlpIsTooBig,
+    #[error("data has too few bytes for valid RLP")]
+    RlpIsTooShort,
+    #[error("expected RLP list")]
+    RlpExpectedToBeList,
+    #[error("expected RLP data")]
+    RlpExpectedToBeData,
+    #[error("unexpected RLP list length")]
+    RlpIncorrectListLen,
+    #[error("data length has a leading zero")]
+    RlpDataLenWithZeroPrefix,
+    #[error("list length has a leading zero")]
+    RlpListLenWithZeroPrefix,
+    #[error("non-canonical encoding")]
+    RlpInvalidIndirection,
+    #[error("declared length is inconsistent with data specified")]
+    RlpInconsistentLengthAndData,
+    #[error("declared length is invalid and results in overflow")]
+    RlpInvalidLength,
+    #[error("{0}")]
+    Custom(&'static str),
+}
+
+impl From<alloy_rlp::Error> for DecoderError {
+    fn from(err: alloy_rlp::Error) -> Self {
+        match err {
+            alloy_rlp::Error::Overflow => DecoderError::RlpIsTooBig,
+            alloy_rlp::Error::LeadingZero => DecoderError::RlpInvalidIndirection,
+            alloy_rlp::Error::InputTooShort => DecoderError::RlpIsTooShort,
+            alloy_rlp::Error::NonCanonicalSingleByte | alloy_rlp::Error::NonCanonicalSize => {
+                DecoderError::RlpInvalidIndirection
+            }
+            alloy_rlp::Error::UnexpectedLength => DecoderError::RlpInconsistentLengthAndData,
+            alloy_rlp::Error::UnexpectedString => DecoderError::RlpExpectedToBeList,
+            alloy_rlp::Error::UnexpectedList => DecoderError::RlpExpectedToBeData,
+            alloy_rlp::Error::ListLengthMismatch { .. } => DecoderError::RlpIncorrectListLen,
+            alloy_rlp::Error::Custom(msg) => DecoderError::Custom(msg),
+        }
+    }
+}
+
+/// Length of the header and of the payload of an item.
+pub(crate) struct PayloadInfo {
+    pub header_len: usize,
+    pub value_len: usize,
+}
+
+/// A view on an RLP item.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Rlp<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Rlp<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Rlp { bytes }
+    }
+
+    pub fn as_raw(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self.bytes.first(), Some(0x80 | 0xc0))
+    }
+
+    pub fn is_list(&self) -> bool {
+        matches!(self.bytes.first(), Some(b) if *b >= 0xc0)
+    }
+
+    pub fn payload_info(&self) -> Result<PayloadInfo, DecoderError> {
+        let mut buf = self.bytes;
+        let header = Header::decode(&mut buf)?;
+        Ok(PayloadInfo {
+            header_len: self.bytes.len() - buf.len(),
+            value_len: header.payload_length,
+        })
+    }
+
+    /// The payload of the item, the payload of a list too like with the `rlp` crate.
+    pub fn data(&self) -> Result<&'a [u8], DecoderError> {
+        let info = self.payload_info()?;
+        Ok(&self.bytes[info.header_len..info.header_len + info.value_len])
+    }
+
+    pub fn item_count(&self) -> Result<usize, DecoderError> {
+        let mut count = 0;
+        let mut items = self.items()?;
+        while next_item(&mut items)?.is_some() {
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    pub fn at(&self, index: usize) -> Result<Rlp<'a>, DecoderError> {
+        let mut items = self.items()?;
+        for _ in 0..index {
+            next_item(&mut items)?.ok_or(DecoderError::RlpIsTooShort)?;
+        }
+        next_item(&mut items)?.ok_or(DecoderError::RlpIsTooShort)
+    }
+
+    pub fn iter(&self) -> RlpIterator<'a> {
+        RlpIterator {
+            items: self.items().unwrap_or_default(),
+        }
+    }
+
+    pub fn as_val<T: Decodable>(&self) -> Result<T, DecoderError> {
+        T::decode(self)
+    }
+
+    pub fn val_at<T: Decodable>(&self, index: usize) -> Result<T, DecoderError> {
+        T::decode(&self.at(index)?)
+    }
+
+    pub fn as_list<T: Decodable>(&self) -> Result<Vec<T>, DecoderError> {
+        let mut items = self.items()?;
+        let mut list = Vec::new();
+        while let Some(item) = next_item(&mut items)? {
+            list.push(T::decode(&item)?);
+        }
+        Ok(list)
+    }
+
+    /// The payload of a list, holding its items.
+    fn items(&self) -> Result<&'a [u8], DecoderError> {
+        if !self.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        self.data()
+    }
+
+    /// Decodes the whole item with `alloy-rlp`.
+    fn decode_alloy<T: alloy_rlp::Decodable>(&self) -> Result<T, DecoderError> {
+        let mut buf = self.bytes;
+        Ok(T::decode(&mut buf)?)
+    }
+}
+
+/// Splits the next item off the payload of a list.
+fn next_item<'a>(items: &mut &'a [u8]) -> Result<Option<Rlp<'a>>, DecoderError> {
+    if items.is_empty() {
+        return Ok(None);
+    }
+    let start = *items;
+    let header = Header::decode(items)?;
+    *items = &items[header.payload_length..];
+    Ok(Some(Rlp::new(&start[..start.len() - items.len()])))
+}
+
+/// Iterator over the items of a list, empty for data.
+pub(crate) struct RlpIterator<'a> {
+    items: &'a [u8],
+}
+
+impl<'a> Iterator for RlpIterator<'a> {
+    type Item = Rlp<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        next_item(&mut self.items).ok().flatten()
+    }
+}
+
+/// A list whose items are still being appended.
+struct UnfinishedList {
+    position: usize,
+    current: usize,
+    max: usize,
+}
+
+/// Appends items to an RLP encoding, inserting the header of a list once its last item is
+/// appended.
+#[derive(Default)]
+pub(crate) struct RlpStream {
+    buffer: BytesMut,
+    unfinished_lists: Vec<UnfinishedList>,
+    finished_list: bool,
+}
+
+impl RlpStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_list(len: usize) -> Self {
+        let mut s = Self::new();
+        s.begin_list(len);
+        s
+    }
+
+    pub fn new_with_buffer(buffer: BytesMut) -> Self {
+        RlpStream {
+            buffer,
+            unfinished_lists: Vec::with_capacity(16),
+            finished_list: false,
+        }
+    }
+
+    pub fn append<E: Encodable + ?Sized>(&mut self, value: &E) -> &mut Self {
+        self.finished_list = false;
+        value.rlp_append(self);
+        if !self.finished_list {
+            self.note_appended(1);
+        }
+        self
+    }
+
+    pub fn append_list<E: Encodable>(&mut self, values: &[E]) -> &mut Self {
+        self.begin_list(values.len());
+        for value in values {
+            self.append(value);
+        }
+        self
+    }
+
+    pub fn append_raw(&mut self, bytes: &[u8], item_count: usize) -> &mut Self {
+        self.buffer.extend_from_slice(bytes);
+        self.note_appended(item_count);
+        self
+    }
+
+    pub fn append_empty_data(&mut self) -> &mut Self {
+        self.buffer.put_u8(alloy_rlp::EMPTY_STRING_CODE);
+        self.note_appended(1);
+        self
+    }
+
+    pub fn begin_list(&mut self, len: usize) -> &mut Self {
+        self.finished_list = false;
+        if len == 0 {
+            self.buffer.put_u8(alloy_rlp::EMPTY_LIST_CODE);
+            self.note_appended(1);
+            self.finished_list = true;
+        } else {
+            self.unfinished_lists.push(UnfinishedList {
+                position: self.buffer.len(),
+                current: 0,
+                max: len,
+            });
+        }
+        self
+    }
+
+    pub fn encoder(&mut self) -> BasicEncoder<'_> {
+        BasicEncoder(&mut self.buffer)
+    }
+
+    pub fn as_raw(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.unfinished_lists.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.unfinished_lists.clear();
+        self.finished_list = false;
+    }
+
+    pub fn out(self) -> BytesMut {
+        assert!(
+            self.is_finished(),
+            "cannot return the encoding of an unfinished list"
+        );
+        self.buffer
+    }
+
+    fn note_appended(&mut self, inserted: usize) {
+        let Some(list) = self.unfinished_lists.last_mut() else {
+            return;
+        };
+        list.current += inserted;
+        assert!(
+            list.current <= list.max,
+            "cannot append more items than the list was begun with"
+        );
+        let should_finish = list.current == list.max;
+        if should_finish {
+            let position = list.position;
+            self.unfinished_lists.pop();
+            self.insert_list_header(position);
+            self.note_appended(1);
+        }
+        self.finished_list = should_finish;
+    }
+
+    /// Inserts the header of the list whose payload starts at `position`, shifting the payload.
+    fn insert_list_header(&mut self, position: usize) {
+        let header = Header {
+            list: true,
+            payload_length: self.buffer.len() - position,
+        };
+        let mut encoded = [0; 9];
+        let header_len = header.length();
+        header.encode(&mut &mut encoded[..]);
+        let payload_end = self.buffer.len();
+        self.buffer.resize(payload_end + header_len, 0);
+        self.buffer
+            .copy_within(position..payload_end, position + header_len);
+        self.buffer[position..position + header_len].copy_from_slice(&encoded[..header_len]);
+    }
+}
+
+/// Writes a string into a stream, without counting it as an appended item.
+pub(crate) struct BasicEncoder<'a>(&'a mut BytesMut);
+
+impl BasicEncoder<'_> {
+    pub fn encode_value(&mut self, value: &[u8]) {
+        alloy_rlp::Encodable::encode(value, self.0);
+    }
+}
+
+/// Appends an item to a stream, see [`RlpStream::append`].
+pub(crate) trait Encodable {
+    fn rlp_append(&self, s: &mut RlpStream);
+
+    fn rlp_bytes(&self) -> BytesMut {
+        let mut s = RlpStream::new();
+        self.rlp_append(&mut s);
+        s.out()
+    }
+}
+
+/// Decodes an item from a view on it.
+pub(crate) trait Decodable: Sized {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError>;
+}
+
+impl<T: Encodable + ?Sized> Encodable for &T {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        (**self).rlp_append(s)
+    }
+}
+
+/// Implements the shim traits for types `alloy-rlp` encodes the way the `rlp` crate does.
+macro_rules! impl_alloy {
+    ($($ty:ty),*) => {
+        $(
+            impl Encodable for $ty {
+                fn rlp_append(&self, s: &mut RlpStream) {
+                    alloy_rlp::Encodable::encode(self, &mut s.buffer);
+                }
+            }
+
+            impl Decodable for $ty {
+                fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+                    rlp.decode_alloy()
+                }
+            }
+        )*
+    };
+}
+
+impl_alloy!(u8, u16, u32, u64, bool, String);
+
+impl Encodable for [u8] {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        alloy_rlp::Encodable::encode(self, &mut s.buffer);
+    }
+}
+
+impl Encodable for str {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        alloy_rlp::Encodable::encode(self, &mut s.buffer);
+    }
+}
+
+impl Encodable for Vec<u8> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.as_slice().rlp_append(s)
+    }
+}
+
+impl Decodable for Vec<u8> {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        let mut buf = rlp.bytes;
+        Ok(Header::decode_bytes(&mut buf, false)?.to_vec())
+    }
+}
+
+/// An ENR is encoded as the list of its signature, sequence number and key-value pairs, the
+/// values already being RLP encoded.
+impl Encodable for Enr {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let pairs = self.iter().count();
+        s.begin_list(2 + 2 * pairs);
+        s.append(self.signature());
+        s.append(&self.seq());
+        for (key, value) in self.iter() {
+            s.append(key);
+            s.append_raw(value, 1);
+        }
+    }
+}
+
+/// The `enr` crate only decodes ENRs with the `rlp` crate, reachable through their base64 text
+/// form, which also verifies the signature.
+impl Decodable for Enr {
+    fn decode(rlp: &Rlp<'_>) -> Result<Self, DecoderError> {
+        if !rlp.is_list() {
+            return Err(DecoderError::RlpExpectedToBeList);
+        }
+        let info = rlp.payload_info()?;
+        let item = &rlp.bytes[..info.header_len + info.value_len];
+        Enr::from_str(&base64::encode_config(item, base64::URL_SAFE_NO_PAD))
+            .map_err(|_| DecoderError::Custom("invalid ENR"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_lists_match_rlp_crate_encoding() {
+        let mut s = RlpStream::new_list(3);
+        s.append(&1u8);
+        s.begin_list(2).append(&"cat").append(&vec![0u8; 60]);
+        s.begin_list(0);
+        let encoded = s.out();
+
+        assert_eq!(&[0xf8, 70, 0x01, 0xf8, 66, 0x83], &encoded[..6]);
+        assert_eq!(72, encoded.len());
+        let rlp = Rlp::new(&encoded);
+        assert_eq!(3, rlp.item_count().unwrap());
+        assert_eq!(1u8, rlp.val_at::<u8>(0).unwrap());
+        let inner = rlp.at(1).unwrap();
+        assert_eq!("cat", inner.val_at::<String>(0).unwrap());
+        assert_eq!(vec![0u8; 60], inner.val_at::<Vec<u8>>(1).unwrap());
+        assert!(rlp.at(2).unwrap().is_empty());
+        assert_eq!(Err(DecoderError::RlpIsTooShort), rlp.at(3).map(|_| ()));
+    }
+
+    #[test]
+    fn test_long_list_header_inserted() {
+        let mut s = RlpStream::new_list(1);
+        s.append(&vec![7u8; 100]);
+        let encoded = s.out();
+
+        assert_eq!(&[0xf8, 102, 0xb8, 100], &encoded[..4]);
+        assert_eq!(
+            vec![7u8; 100],
+            Rlp::new(&encoded).val_at::<Vec<u8>>(0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_non_canonical_integer_rejected() {
+        assert_eq!(
+            Err(DecoderError::RlpInvalidIndirection),
+            Rlp::new(&[0x82, 0, 1]).as_val::<u16>()
+        );
+        assert_eq!(
+            Err(DecoderError::RlpIsTooBig),
+            Rlp::new(&[0x83, 1, 0, 0]).as_val::<u16>()
+        );
+    }
+
+    #[test]
+    fn test_enr_encoding_matches_enr_crate() {
+        let key = enr::CombinedKey::generate_secp256k1();
+        let enr = enr::EnrBuilder::new("v4")
+            .ip4([192, 0, 2, 1].into())
+            .udp4(30303)
+            .build(&key)
+            .unwrap();
+        let base64 = enr.to_base64();
+        let expected =
+            base64::decode_config(&base64["enr:".len()..], base64::URL_SAFE_NO_PAD).unwrap();
+
+        let encoded = RlpStream::new().append(&enr).as_raw().to_vec();
+        assert_eq!(expected, encoded);
+        assert_eq!(enr, Rlp::new(&encoded).as_val::<Enr>().unwrap());
+        assert_eq!(
+            Err(DecoderError::RlpExpectedToBeList),
+            Rlp::new(&[0x80]).as_val::<Enr>()
+        );
+    }
+}
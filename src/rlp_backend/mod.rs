@@ -0,0 +1,58 @@
+//! The RLP implementation notifications and [`crate::NodeAddress`] are encoded and decoded with,
+//! selected at compile time by feature: the `rlp` crate, or `alloy-rlp` which takes precedence
+//! when both are enabled.
+
+#[cfg(not(any(feature = "rlp", feature = "alloy-rlp")))]
+compile_error!("no RLP backend selected, enable the `rlp` or the `alloy-rlp` feature");
+
+#[cfg(feature = "alloy-rlp")]
+mod alloy;
+
+#[cfg(feature = "alloy-rlp")]
+pub use alloy::DecoderError;
+#[cfg(feature = "alloy-rlp")]
+pub(crate) use alloy::{Decodable, Encodable, Rlp, RlpStream};
+#[cfg(not(feature = "alloy-rlp"))]
+pub use rlp::DecoderError;
+#[cfg(not(feature = "alloy-rlp"))]
+pub(crate) use rlp::{Decodable, Encodable, Rlp, RlpStream};
+
+/// Encoding and decoding of whole items through the selected backend.
+pub(crate) trait RlpCodec: Sized {
+    /// Appends the encoding of the item to `out`.
+    fn encode_rlp_to(&self, out: &mut Vec<u8>);
+
+    /// Decodes an item from `data`, which must hold exactly one item.
+    fn decode_rlp(data: &[u8]) -> Result<Self, DecoderError>;
+
+    /// Encodes the item.
+    fn encode_rlp(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode_rlp_to(&mut out);
+        out
+    }
+}
+
+#[cfg(not(feature = "alloy-rlp"))]
+impl<T: Encodable + Decodable> RlpCodec for T {
+    fn encode_rlp_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&rlp::encode(self));
+    }
+
+    fn decode_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        rlp::decode(data)
+    }
+}
+
+#[cfg(feature = "alloy-rlp")]
+impl<T: Encodable + Decodable> RlpCodec for T {
+    fn encode_rlp_to(&self, out: &mut Vec<u8>) {
+        let mut s = RlpStream::new();
+        s.append(self);
+        out.extend_from_slice(s.as_raw());
+    }
+
+    fn decode_rlp(data: &[u8]) -> Result<Self, DecoderError> {
+        Rlp::new(data).as_val()
+    }
+}
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use std::{future::Future, io, net::SocketAddr, time::Duration};
+
+/// A UDP socket of a [`Runtime`].
+#[async_trait]
+pub trait UdpSocket: Send + Sync {
+    /// Sends `buf` to `dst`.
+    async fn send_to(&self, buf: &[u8], dst: SocketAddr) -> io::Result<usize>;
+    /// Receives a datagram into `buf`, returns its length and source.
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// The async runtime the crate spawns tasks, sets timers and binds sockets on, so it doesn't
+/// hard-depend on any one runtime.
+#[async_trait]
+pub trait Runtime: Send + Sync + 'static {
+    type UdpSocket: UdpSocket;
+    /// Runs `fut` in the background.
+    fn spawn<F>(fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static;
+    /// Resolves after `duration`.
+    async fn sleep(duration: Duration);
+    /// Binds a UDP socket to `addr`.
+    async fn bind_udp(addr: SocketAddr) -> io::Result<Self::UdpSocket>;
+}
+
+/// The [tokio](https://tokio.rs) runtime. Must be used from within a tokio runtime context.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioRuntime;
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl UdpSocket for tokio::net::UdpSocket {
+    async fn send_to(&self, buf: &[u8], dst: SocketAddr) -> io::Result<usize> {
+        tokio::net::UdpSocket::send_to(self, buf, dst).await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        tokio::net::UdpSocket::recv_from(self, buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        tokio::net::UdpSocket::local_addr(self)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait]
+impl Runtime for TokioRuntime {
+    type UdpSocket = tokio::net::UdpSocket;
+
+    fn spawn<F>(fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        tokio::spawn(fut);
+    }
+
+    async fn sleep(duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+
+    async fn bind_udp(addr: SocketAddr) -> io::Result<Self::UdpSocket> {
+        tokio::net::UdpSocket::bind(addr).await
+    }
+}
+
+/// The [async-std](https://async.rs) runtime.
+#[cfg(feature = "async-std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsyncStdRuntime;
+
+#[cfg(feature = "async-std")]
+#[async_trait]
+impl UdpSocket for async_std::net::UdpSocket {
+    async fn send_to(&self, buf: &[u8], dst: SocketAddr) -> io::Result<usize> {
+        async_std::net::UdpSocket::send_to(self, buf, dst).await
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        async_std::net::UdpSocket::recv_from(self, buf).await
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        async_std::net::UdpSocket::local_addr(self)
+    }
+}
+
+#[cfg(feature = "async-std")]
+#[async_trait]
+impl Runtime for AsyncStdRuntime {
+    type UdpSocket = async_std::net::UdpSocket;
+
+    fn spawn<F>(fut: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        async_std::task::spawn(fut);
+    }
+
+    async fn sleep(duration: Duration) {
+        async_std::task::sleep(duration).await
+    }
+
+    async fn bind_udp(addr: SocketAddr) -> io::Result<Self::UdpSocket> {
+        async_std::net::UdpSocket::bind(addr).await
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokio_udp_roundtrip() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let a = TokioRuntime::bind_udp("127.0.0.1:0".parse().unwrap())
+                .await
+                .unwrap();
+            let b = TokioRuntime::bind_udp("127.0.0.1:0".parse().unwrap())
+                .await
+                .unwrap();
+            a.send_to(b"ping", b.local_addr().unwrap()).await.unwrap();
+            let mut buf = [0u8; 4];
+            let (len, src) = b.recv_from(&mut buf).await.unwrap();
+            assert_eq!(b"ping", &buf[..len]);
+            assert_eq!(a.local_addr().unwrap(), src);
+        });
+    }
+}
@@ -0,0 +1,263 @@
+use crate::Enr;
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+};
+use curve25519_dalek::{constants::X25519_BASEPOINT, montgomery::MontgomeryPoint, scalar::Scalar};
+use enr::{
+    ed25519_dalek,
+    k256::{
+        elliptic_curve::{sec1::ToEncodedPoint, AffineXCoordinate},
+        PublicKey, SecretKey,
+    },
+    CombinedKey, CombinedPublicKey,
+};
+use hkdf::Hkdf;
+use rand::RngCore;
+use sha2::Sha256;
+use thiserror::Error;
+
+/// Length of the compressed secp256k1 ephemeral key leading a box sealed to a secp256k1 ENR in
+/// bytes.
+pub const EPHEMERAL_KEY_LENGTH: usize = 33;
+/// Length of the X25519 ephemeral key leading a box sealed to an ed25519 ENR in bytes.
+pub const X25519_EPHEMERAL_KEY_LENGTH: usize = 32;
+/// Length of the Poly1305 tag closing a sealed box in bytes.
+pub const SEAL_TAG_LENGTH: usize = 16;
+/// Most bytes a sealed box adds to the sealed notification.
+pub const SEAL_OVERHEAD: usize = EPHEMERAL_KEY_LENGTH + SEAL_TAG_LENGTH;
+
+const KDF_INFO: &[u8] = b"nat-hole-punch sealed v2";
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SealError {
+    #[error("sealed box of {0} bytes is too short")]
+    TooShort(usize),
+    #[error("malformed or low order key")]
+    MalformedKey,
+    #[error("sealed box failed authentication")]
+    Unauthenticated,
+}
+
+/// Seals an encoded notification, for example a [`crate::RelayInit`] or [`crate::RelayMsg`], to
+/// the key of `recipient`'s ENR, so it stays confidential over carriers without discv5 sessions.
+/// The box is `ephemeral-key || ciphertext || tag`, encrypted with ChaCha20-Poly1305 under a key
+/// derived by HKDF-SHA256 from a Diffie-Hellman with a fresh ephemeral key. The exchange is
+/// X25519 for ed25519 ENRs, on the Montgomery form of their key. Most ENRs carry a secp256k1 key,
+/// which has no X25519 form, so boxes to them use an ECDH on secp256k1 instead.
+pub fn seal(recipient: &Enr, notif: &[u8]) -> Result<Vec<u8>, SealError> {
+    let mut rng = rand::thread_rng();
+    let (ephemeral_key, recipient_key, shared) = match recipient.public_key() {
+        CombinedPublicKey::Secp256k1(key) => {
+            let key = PublicKey::from(&key);
+            let ephemeral = SecretKey::random(&mut rng);
+            let shared = secp256k1_ecdh(&ephemeral, &key);
+            (sec1(&ephemeral.public_key()), sec1(&key), shared)
+        }
+        CombinedPublicKey::Ed25519(key) => {
+            let key = x25519_public(&key)?;
+            let mut ephemeral = [0u8; 32];
+            rng.fill_bytes(&mut ephemeral);
+            let ephemeral = Scalar::from_bits(clamp(ephemeral));
+            let shared = x25519(&key, &ephemeral)?;
+            let ephemeral_key = (X25519_BASEPOINT * ephemeral).to_bytes().to_vec();
+            (ephemeral_key, key.to_bytes().to_vec(), shared)
+        }
+    };
+
+    let (cipher, nonce) = cipher(&shared, &ephemeral_key, &recipient_key);
+    let payload = Payload {
+        msg: notif,
+        aad: &ephemeral_key,
+    };
+    let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .expect("notifications are far below the ChaCha20 length limit");
+    let mut sealed = ephemeral_key;
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Opens a box sealed by [`seal`] to the ENR of `key`. Returns the encoded notification.
+pub fn open(key: &CombinedKey, sealed: &[u8]) -> Result<Vec<u8>, SealError> {
+    let (ephemeral_key, recipient_key, shared) = match key {
+        CombinedKey::Secp256k1(signing_key) => {
+            let ephemeral_key = split_ephemeral_key(sealed, EPHEMERAL_KEY_LENGTH)?;
+            let ephemeral =
+                PublicKey::from_sec1_bytes(ephemeral_key).map_err(|_| SealError::MalformedKey)?;
+            let secret = SecretKey::from(signing_key);
+            let shared = secp256k1_ecdh(&secret, &ephemeral);
+            (ephemeral_key, sec1(&secret.public_key()), shared)
+        }
+        CombinedKey::Ed25519(keypair) => {
+            let ephemeral_key = split_ephemeral_key(sealed, X25519_EPHEMERAL_KEY_LENGTH)?;
+            let ephemeral = MontgomeryPoint(ephemeral_key.try_into().expect("split at length"));
+            // the scalar of an ed25519 key is the clamped lower half of its expanded secret
+            let expanded = ed25519_dalek::ExpandedSecretKey::from(&keypair.secret).to_bytes();
+            let secret = Scalar::from_bits(expanded[..32].try_into().expect("64 byte key"));
+            let shared = x25519(&ephemeral, &secret)?;
+            let recipient_key = x25519_public(&keypair.public)?.to_bytes().to_vec();
+            (ephemeral_key, recipient_key, shared)
+        }
+    };
+
+    let (cipher, nonce) = cipher(&shared, ephemeral_key, &recipient_key);
+    let payload = Payload {
+        msg: &sealed[ephemeral_key.len()..],
+        aad: ephemeral_key,
+    };
+    cipher
+        .decrypt(&nonce, payload)
+        .map_err(|_| SealError::Unauthenticated)
+}
+
+/// Returns the ephemeral key of `len` bytes leading `sealed`, checking a tag follows it.
+fn split_ephemeral_key(sealed: &[u8], len: usize) -> Result<&[u8], SealError> {
+    if sealed.len() < len + SEAL_TAG_LENGTH {
+        return Err(SealError::TooShort(sealed.len()));
+    }
+    Ok(&sealed[..len])
+}
+
+/// Derives the key and nonce of the box from the shared secret, salted with both public keys of
+/// the box. The key is fresh for each box, so is the nonce.
+fn cipher(
+    shared: &[u8; 32],
+    ephemeral_key: &[u8],
+    recipient_key: &[u8],
+) -> (ChaCha20Poly1305, Nonce) {
+    let salt = [ephemeral_key, recipient_key].concat();
+    let mut okm = [0u8; 44];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(KDF_INFO, &mut okm)
+        .expect("44 bytes is a valid HKDF-SHA256 output length");
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&okm[..32]));
+    (cipher, *Nonce::from_slice(&okm[32..]))
+}
+
+fn sec1(key: &PublicKey) -> Vec<u8> {
+    key.to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// The x coordinate of the ECDH of `secret` and `public` on secp256k1.
+fn secp256k1_ecdh(secret: &SecretKey, public: &PublicKey) -> [u8; 32] {
+    (public.to_projective() * *secret.to_nonzero_scalar())
+        .to_affine()
+        .x()
+        .into()
+}
+
+/// The Montgomery form of an ed25519 public key.
+fn x25519_public(key: &ed25519_dalek::PublicKey) -> Result<MontgomeryPoint, SealError> {
+    curve25519_dalek::edwards::CompressedEdwardsY(key.to_bytes())
+        .decompress()
+        .map(|point| point.to_montgomery())
+        .ok_or(SealError::MalformedKey)
+}
+
+/// X25519 of `secret` and `public`, rejecting low order points that zero the shared secret.
+fn x25519(public: &MontgomeryPoint, secret: &Scalar) -> Result<[u8; 32], SealError> {
+    let shared = (public * secret).to_bytes();
+    if shared == [0; 32] {
+        return Err(SealError::MalformedKey);
+    }
+    Ok(shared)
+}
+
+fn clamp(mut scalar: [u8; 32]) -> [u8; 32] {
+    scalar[0] &= 248;
+    scalar[31] &= 127;
+    scalar[31] |= 64;
+    scalar
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RelayInit, MESSAGE_NONCE_LENGTH};
+    use enr::EnrBuilder;
+
+    fn enr(key: &CombinedKey) -> Enr {
+        EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .build(key)
+            .unwrap()
+    }
+
+    fn notif(enr: &Enr) -> Vec<u8> {
+        RelayInit::<MESSAGE_NONCE_LENGTH>(
+            enr.clone(),
+            enr.node_id(),
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+        )
+        .rlp_encode()
+    }
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        for (key, other, key_len) in [
+            (
+                CombinedKey::generate_secp256k1(),
+                CombinedKey::generate_secp256k1(),
+                EPHEMERAL_KEY_LENGTH,
+            ),
+            (
+                CombinedKey::generate_ed25519(),
+                CombinedKey::generate_ed25519(),
+                X25519_EPHEMERAL_KEY_LENGTH,
+            ),
+        ] {
+            let enr = enr(&key);
+            let notif = notif(&enr);
+
+            let sealed = seal(&enr, &notif).unwrap();
+            assert_eq!(notif.len() + key_len + SEAL_TAG_LENGTH, sealed.len());
+            assert_eq!(Ok(notif.clone()), open(&key, &sealed));
+
+            let mut tampered = sealed.clone();
+            tampered[key_len] ^= 1;
+            assert_eq!(Err(SealError::Unauthenticated), open(&key, &tampered));
+            assert_eq!(Err(SealError::Unauthenticated), open(&other, &sealed));
+        }
+    }
+
+    #[test]
+    fn test_truncated_box_rejected() {
+        let key = CombinedKey::generate_secp256k1();
+        let sealed = seal(&enr(&key), &[0xc0]).unwrap();
+
+        let short = &sealed[..EPHEMERAL_KEY_LENGTH + SEAL_TAG_LENGTH - 1];
+        assert_eq!(Err(SealError::TooShort(short.len())), open(&key, short));
+        assert_eq!(Err(SealError::TooShort(0)), open(&key, &[]));
+        // cut into the tag
+        let cut = &sealed[..sealed.len() - 1];
+        assert_eq!(Err(SealError::Unauthenticated), open(&key, cut));
+
+        let key = CombinedKey::generate_ed25519();
+        let short = [0u8; X25519_EPHEMERAL_KEY_LENGTH + SEAL_TAG_LENGTH - 1];
+        assert_eq!(Err(SealError::TooShort(short.len())), open(&key, &short));
+    }
+
+    #[test]
+    fn test_malformed_ephemeral_key_rejected() {
+        let key = CombinedKey::generate_secp256k1();
+        let mut sealed = seal(&enr(&key), &[0xc0]).unwrap();
+        // not a sec1 prefix
+        sealed[0] = 0x01;
+        assert_eq!(Err(SealError::MalformedKey), open(&key, &sealed));
+        // an x coordinate off the curve
+        sealed[0] = 0x02;
+        sealed[1..EPHEMERAL_KEY_LENGTH].fill(0xff);
+        assert_eq!(Err(SealError::MalformedKey), open(&key, &sealed));
+
+        // the low order point zeroes the shared secret
+        let key = CombinedKey::generate_ed25519();
+        let mut sealed = seal(&enr(&key), &[0xc0]).unwrap();
+        sealed[..X25519_EPHEMERAL_KEY_LENGTH].fill(0);
+        assert_eq!(Err(SealError::MalformedKey), open(&key, &sealed));
+    }
+}
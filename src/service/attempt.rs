@@ -0,0 +1,469 @@
+use super::{DeadlineBudget, LruCache, PunchTimings};
+use crate::{clock::system_clock, AttemptId, MessageNonce, NodeId, ReasonCode, SharedClock};
+use futures::channel::oneshot;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Default time budget of a hole punch attempt, across all relays it tries.
+pub const DEFAULT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+/// Default max number of attempts in flight.
+pub const DEFAULT_ATTEMPT_CAPACITY: usize = 1024;
+/// Default max number of attempts in flight with [`FairScheduling`].
+pub const DEFAULT_PUNCH_PARALLELISM: usize = 32;
+/// Default max number of attempts in flight per target with [`FairScheduling`].
+pub const DEFAULT_PUNCH_PARALLELISM_PER_TARGET: usize = 1;
+
+/// Caps on the attempts in flight. Attempts beyond the caps wait in the registry and are sent
+/// round-robin across targets as attempts resolve, so that a burst of attempts, for example after
+/// a restart, doesn't starve targets queued behind a few busy ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FairScheduling {
+    /// Max number of attempts in flight.
+    pub parallelism: usize,
+    /// Max number of attempts in flight per target.
+    pub per_target: usize,
+}
+
+impl Default for FairScheduling {
+    fn default() -> Self {
+        FairScheduling {
+            parallelism: DEFAULT_PUNCH_PARALLELISM,
+            per_target: DEFAULT_PUNCH_PARALLELISM_PER_TARGET,
+        }
+    }
+}
+
+impl FairScheduling {
+    /// No caps, attempts are sent as soon as they are queued.
+    pub(crate) fn unlimited() -> Self {
+        FairScheduling {
+            parallelism: usize::MAX,
+            per_target: usize::MAX,
+        }
+    }
+}
+
+/// An attempt waiting in the registry to be sent, see [`AttemptRegistry::enqueue`].
+#[derive(Debug, Clone)]
+pub struct QueuedAttempt<I> {
+    pub nonce: MessageNonce,
+    /// The sessions with the relays to try, in order.
+    pub relays: Vec<I>,
+    /// The session with the target.
+    pub target: I,
+    /// The encoded [`crate::RelayInit`].
+    pub notif: Vec<u8>,
+    pub attempt_id: Option<AttemptId>,
+}
+
+/// A hole punch attempt initiated by this node.
+#[derive(Debug, Clone)]
+pub struct Attempt<I> {
+    /// The session with the node the [`crate::RelayInit`] was last sent to.
+    pub relay: I,
+    /// The sessions with the relays to try next if the current one doesn't get the attempt
+    /// through in time.
+    pub fallback_relays: VecDeque<I>,
+    /// The session with the target, that the timed out request was sent over.
+    pub target: I,
+    /// The encoded [`crate::RelayInit`], sent to each relay tried.
+    pub notif: Vec<u8>,
+    /// The id sent along with the [`crate::RelayInit`], if any.
+    pub attempt_id: Option<AttemptId>,
+    /// The time the attempt was initiated.
+    pub started: Instant,
+    /// The time the current relay was tried.
+    pub try_started: Instant,
+    /// The index of the current relay among all relays of the attempt.
+    pub try_index: usize,
+    /// The total number of relays of the attempt.
+    pub tries: usize,
+    /// The time budget of the attempt.
+    pub budget: DeadlineBudget,
+}
+
+impl<I> Attempt<I> {
+    /// The point in time the try over the current relay times out.
+    pub fn try_deadline(&self) -> Instant {
+        self.budget
+            .try_deadline(self.started, self.try_started, self.try_index, self.tries)
+    }
+
+    /// The point in time the budget of the attempt is spent.
+    pub fn deadline(&self) -> Instant {
+        self.budget.deadline(self.started)
+    }
+}
+
+/// An attempt initiated by this node whose target was punched, until the discv5 session with the
+/// target is confirmed, see [`super::HolePunchService::confirm_established`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunchedAttempt {
+    pub nonce: MessageNonce,
+    pub attempt_id: Option<AttemptId>,
+    /// The socket the WHOAREYOU from the target arrived from.
+    pub src: SocketAddr,
+    pub timings: PunchTimings,
+}
+
+/// The timer of an attempt fired.
+#[derive(Debug, Clone)]
+pub enum AttemptTimeout<I> {
+    /// The try over a relay timed out, the [`crate::RelayInit`] should be sent to the next relay.
+    Retry {
+        nonce: MessageNonce,
+        failed_relay: I,
+        next_relay: I,
+        notif: Vec<u8>,
+    },
+    /// The budget of the attempt is spent. The attempt is removed from the registry.
+    TimedOut {
+        nonce: MessageNonce,
+        attempt: Attempt<I>,
+    },
+}
+
+/// The hole punch attempts in flight initiated by this node, indexed by the nonce of the timed
+/// out request that triggered each of them.
+#[derive(Debug, Clone)]
+pub struct AttemptRegistry<I> {
+    attempts: LruCache<MessageNonce, Attempt<I>>,
+    budget: DeadlineBudget,
+    clock: SharedClock,
+    scheduling: FairScheduling,
+    /// Targets with queued attempts, in the order they are served.
+    queued: VecDeque<(NodeId, VecDeque<QueuedAttempt<I>>)>,
+    queued_len: usize,
+    /// The target of each attempt in flight sent from the queue.
+    targets: HashMap<MessageNonce, NodeId>,
+    in_flight_per_target: HashMap<NodeId, usize>,
+}
+
+impl<I> Default for AttemptRegistry<I> {
+    fn default() -> Self {
+        Self::new(DeadlineBudget::default())
+    }
+}
+
+impl<I> AttemptRegistry<I> {
+    pub fn new(budget: DeadlineBudget) -> Self {
+        Self::with_capacity(budget, DEFAULT_ATTEMPT_CAPACITY)
+    }
+
+    /// Creates a registry holding at most `capacity` attempts. Once full, the oldest attempt is
+    /// evicted to make room for a new one.
+    pub fn with_capacity(budget: DeadlineBudget, capacity: usize) -> Self {
+        AttemptRegistry {
+            attempts: LruCache::new(capacity, None),
+            budget,
+            clock: system_clock(),
+            scheduling: FairScheduling::unlimited(),
+            queued: VecDeque::new(),
+            queued_len: 0,
+            targets: HashMap::new(),
+            in_flight_per_target: HashMap::new(),
+        }
+    }
+
+    /// Caps the attempts in flight, see [`AttemptRegistry::enqueue`].
+    pub fn with_scheduling(mut self, scheduling: FairScheduling) -> Self {
+        self.scheduling = scheduling;
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl<I: Clone> AttemptRegistry<I> {
+    /// Registers a new attempt that was sent to the first of `relays`, the rest are tried in
+    /// order within the attempt's budget. Returns the attempt it displaced, either the attempt
+    /// previously registered under the same nonce or the oldest attempt if the registry was
+    /// full. Does nothing if `relays` is empty.
+    pub fn insert(
+        &mut self,
+        nonce: MessageNonce,
+        relays: impl IntoIterator<Item = I>,
+        target: I,
+        notif: Vec<u8>,
+        attempt_id: Option<AttemptId>,
+    ) -> Option<(MessageNonce, Attempt<I>)> {
+        let mut fallback_relays = relays.into_iter().collect::<VecDeque<_>>();
+        let relay = fallback_relays.pop_front()?;
+        let now = self.clock.now();
+        self.attempts.insert(
+            nonce,
+            Attempt {
+                relay,
+                tries: fallback_relays.len() + 1,
+                fallback_relays,
+                target,
+                notif,
+                attempt_id,
+                started: now,
+                try_started: now,
+                try_index: 0,
+                budget: self.budget,
+            },
+        )
+    }
+
+    /// Returns the attempt in flight for the given nonce.
+    pub fn get(&self, nonce: &MessageNonce) -> Option<&Attempt<I>> {
+        self.attempts.peek(nonce)
+    }
+
+    /// Removes the attempt for the given nonce, for example once the WHOAREYOU from the target
+    /// has been received.
+    pub fn remove(&mut self, nonce: &MessageNonce) -> Option<Attempt<I>> {
+        self.release(nonce);
+        self.attempts.remove(nonce)
+    }
+
+    /// Number of attempts in flight.
+    pub fn len(&self) -> usize {
+        self.attempts.len()
+    }
+
+    /// Returns true if no attempts are in flight.
+    pub fn is_empty(&self) -> bool {
+        self.attempts.is_empty()
+    }
+
+    /// Number of attempts evicted to stay within capacity.
+    pub fn evictions(&self) -> u64 {
+        self.attempts.evictions()
+    }
+
+    /// The point in time the next try times out, if any attempts are in flight.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.attempts.values().map(Attempt::try_deadline).min()
+    }
+
+    /// Moves the attempts whose try over the current relay has timed out at `now` on to the next
+    /// relay, and removes the attempts whose budget is spent.
+    pub fn poll_timed_out(&mut self, now: Instant) -> Vec<AttemptTimeout<I>> {
+        let mut timeouts = Vec::new();
+        let mut spent = Vec::new();
+        for (nonce, attempt) in self.attempts.iter_mut() {
+            if now < attempt.try_deadline() {
+                continue;
+            }
+            match attempt.fallback_relays.pop_front() {
+                Some(next_relay) if now < attempt.deadline() => {
+                    timeouts.push(Self::retry(*nonce, attempt, next_relay, now))
+                }
+                _ => spent.push(*nonce),
+            }
+        }
+        for nonce in spent {
+            if let Some(attempt) = self.remove(&nonce) {
+                timeouts.push(AttemptTimeout::TimedOut { nonce, attempt });
+            }
+        }
+        timeouts
+    }
+
+    /// Moves the attempt for the given nonce on to the next relay before the try over the
+    /// current relay times out, for example because the relay aborted it. Removes the attempt if
+    /// there is no next relay or its budget is spent.
+    pub fn skip(&mut self, nonce: &MessageNonce, now: Instant) -> Option<AttemptTimeout<I>> {
+        let attempt = self.attempts.get_mut(nonce)?;
+        match attempt.fallback_relays.pop_front() {
+            Some(next_relay) if now < attempt.deadline() => {
+                Some(Self::retry(*nonce, attempt, next_relay, now))
+            }
+            _ => self.remove(nonce).map(|attempt| AttemptTimeout::TimedOut {
+                nonce: *nonce,
+                attempt,
+            }),
+        }
+    }
+
+    /// Queues an attempt to `target_id` to be sent once the caps of the [`FairScheduling`] allow
+    /// it. Returns the attempt dropped to make room, the oldest attempt of the target with the
+    /// most attempts queued, if as many attempts are queued as can be in flight.
+    pub fn enqueue(
+        &mut self,
+        target_id: NodeId,
+        attempt: QueuedAttempt<I>,
+    ) -> Option<QueuedAttempt<I>> {
+        match self.queued.iter_mut().find(|(id, _)| *id == target_id) {
+            Some((_, attempts)) => attempts.push_back(attempt),
+            None => self
+                .queued
+                .push_back((target_id, VecDeque::from([attempt]))),
+        }
+        self.queued_len += 1;
+        if self.queued_len <= self.attempts.capacity() {
+            return None;
+        }
+        let busiest = self
+            .queued
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, attempts))| attempts.len())
+            .map(|(index, _)| index)?;
+        let dropped = self.queued[busiest].1.pop_front();
+        if self.queued[busiest].1.is_empty() {
+            self.queued.remove(busiest);
+        }
+        self.queued_len -= 1;
+        dropped
+    }
+
+    /// Takes the next queued attempt the caps allow sending, serving targets round-robin. Once
+    /// sent, the attempt is registered with [`AttemptRegistry::insert_dequeued`].
+    pub fn dequeue(&mut self) -> Option<(NodeId, QueuedAttempt<I>)> {
+        if self.attempts.len() >= self.scheduling.parallelism {
+            return None;
+        }
+        for _ in 0..self.queued.len() {
+            let (target_id, mut attempts) = self.queued.pop_front()?;
+            let in_flight = self
+                .in_flight_per_target
+                .get(&target_id)
+                .copied()
+                .unwrap_or_default();
+            if in_flight >= self.scheduling.per_target {
+                self.queued.push_back((target_id, attempts));
+                continue;
+            }
+            let attempt = attempts.pop_front();
+            if !attempts.is_empty() {
+                self.queued.push_back((target_id, attempts));
+            }
+            self.queued_len -= 1;
+            return attempt.map(|attempt| (target_id, attempt));
+        }
+        None
+    }
+
+    /// Registers an attempt taken from the queue that was sent, counting it towards the caps of
+    /// its target until it is removed. See [`AttemptRegistry::insert`].
+    pub fn insert_dequeued(
+        &mut self,
+        target_id: NodeId,
+        attempt: QueuedAttempt<I>,
+    ) -> Option<(MessageNonce, Attempt<I>)> {
+        let QueuedAttempt {
+            nonce,
+            relays,
+            target,
+            notif,
+            attempt_id,
+        } = attempt;
+        let displaced = self.insert(nonce, relays, target, notif, attempt_id);
+        if let Some((displaced, _)) = displaced.as_ref() {
+            self.release(displaced);
+        }
+        if self.attempts.contains_key(&nonce) {
+            self.targets.insert(nonce, target_id);
+            *self.in_flight_per_target.entry(target_id).or_default() += 1;
+        }
+        displaced
+    }
+
+    /// Removes all attempts in flight, for example when the service shuts down.
+    pub fn drain(&mut self) -> Vec<(MessageNonce, Attempt<I>)> {
+        let nonces = self
+            .attempts
+            .iter()
+            .map(|(nonce, _)| *nonce)
+            .collect::<Vec<_>>();
+        nonces
+            .into_iter()
+            .filter_map(|nonce| Some((nonce, self.remove(&nonce)?)))
+            .collect()
+    }
+
+    /// Removes all attempts waiting to be sent.
+    pub fn drain_queued(&mut self) -> Vec<QueuedAttempt<I>> {
+        self.queued_len = 0;
+        self.queued
+            .drain(..)
+            .flat_map(|(_, attempts)| attempts)
+            .collect()
+    }
+
+    /// Number of attempts waiting to be sent.
+    pub fn queued_len(&self) -> usize {
+        self.queued_len
+    }
+
+    fn release(&mut self, nonce: &MessageNonce) {
+        let Some(target_id) = self.targets.remove(nonce) else {
+            return;
+        };
+        if let Some(in_flight) = self.in_flight_per_target.get_mut(&target_id) {
+            *in_flight -= 1;
+            if *in_flight == 0 {
+                self.in_flight_per_target.remove(&target_id);
+            }
+        }
+    }
+
+    fn retry(
+        nonce: MessageNonce,
+        attempt: &mut Attempt<I>,
+        next_relay: I,
+        now: Instant,
+    ) -> AttemptTimeout<I> {
+        let failed_relay = std::mem::replace(&mut attempt.relay, next_relay.clone());
+        attempt.try_started = now;
+        attempt.try_index += 1;
+        AttemptTimeout::Retry {
+            nonce,
+            failed_relay,
+            next_relay,
+            notif: attempt.notif.clone(),
+        }
+    }
+}
+
+/// The outcome of a hole punch attempt initiated by this node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    /// The WHOAREYOU from the target arrived from `src`.
+    Punched { src: SocketAddr },
+    /// The attempt failed, for the reason the evidence observed by this node points to.
+    Failed(FailureReason),
+    /// The attempt was given up on by this node shutting down, see
+    /// [`super::HolePunchService::shutdown`].
+    Cancelled,
+}
+
+/// Why a hole punch attempt initiated by this node failed, as far as this node observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureReason {
+    /// The budget of the attempt was spent without the WHOAREYOU from the target arriving.
+    NoWhoareyou,
+    /// The last relay tried aborted the attempt, for the given reason.
+    RelayAborted(ReasonCode),
+    /// The attempt was given up on to make room for newer attempts.
+    Evicted,
+    /// The WHOAREYOU from the target arrived, but the discv5 session with the target failed to
+    /// be established through the punched hole, see [`super::ServiceCommand::SessionFailed`].
+    SessionFailed,
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FailureReason::NoWhoareyou => write!(f, "no-whoareyou"),
+            FailureReason::RelayAborted(reason) => write!(f, "relay-aborted: {reason}"),
+            FailureReason::Evicted => write!(f, "evicted"),
+            FailureReason::SessionFailed => write!(f, "session-failed"),
+        }
+    }
+}
+
+/// Resolves with the outcome of a hole punch attempt.
+pub type AttemptFuture = oneshot::Receiver<AttemptOutcome>;
@@ -0,0 +1,93 @@
+use super::AttemptOutcome;
+use crate::{AttemptId, FallbackStrategy, MessageNonce, NodeId, ReasonCode};
+use std::{collections::VecDeque, net::SocketAddr, time::Instant};
+
+/// Default max number of attempts kept in the [`AuditLog`].
+pub const DEFAULT_AUDIT_LOG_SIZE: usize = 128;
+
+/// A relay tried by an attempt initiated by this node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayTry {
+    pub relay: NodeId,
+    /// The time the [`crate::RelayInit`] was sent to the relay.
+    pub sent: Instant,
+    /// The reason the relay aborted the attempt for, if it did.
+    pub aborted: Option<ReasonCode>,
+    /// What the relay tried instead of relaying, if it aborted for lack of a session with the
+    /// target.
+    pub fallback: Option<FallbackStrategy>,
+}
+
+/// The history of an attempt initiated by this node, see
+/// [`super::HolePunchService::recent_attempts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttemptRecord {
+    pub nonce: MessageNonce,
+    pub attempt_id: Option<AttemptId>,
+    pub target: NodeId,
+    /// The time the attempt was initiated.
+    pub initiated: Instant,
+    /// The relays tried, in order.
+    pub relays: Vec<RelayTry>,
+    /// The time the WHOAREYOU from the target arrived and the socket it arrived from.
+    pub punched: Option<(Instant, SocketAddr)>,
+    /// The time the discv5 session with the target was confirmed, see
+    /// [`super::HolePunchService::confirm_established`].
+    pub established: Option<Instant>,
+    /// The time the attempt was resolved and its outcome.
+    pub resolved: Option<(Instant, AttemptOutcome)>,
+}
+
+/// The most recent attempts initiated by this node, oldest first. The oldest record is dropped to
+/// make room for a new one. Keeps no records if the size is zero.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    records: VecDeque<AttemptRecord>,
+    size: usize,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_AUDIT_LOG_SIZE)
+    }
+}
+
+impl AuditLog {
+    pub fn new(size: usize) -> Self {
+        AuditLog {
+            records: VecDeque::with_capacity(size.min(DEFAULT_AUDIT_LOG_SIZE)),
+            size,
+        }
+    }
+
+    /// Records a newly initiated attempt.
+    pub fn record(&mut self, record: AttemptRecord) {
+        if self.size == 0 {
+            return;
+        }
+        if self.records.len() == self.size {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// The record of the most recent attempt under `nonce`, if still kept.
+    pub fn get_mut(&mut self, nonce: &MessageNonce) -> Option<&mut AttemptRecord> {
+        self.records
+            .iter_mut()
+            .rev()
+            .find(|record| record.nonce == *nonce)
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &AttemptRecord> {
+        self.records.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{clock::system_clock, NodeId, SharedClock};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Default cap on the time no attempts are initiated to a target that attempts keep failing to.
+pub const DEFAULT_MAX_FAILURE_COOLDOWN: Duration = Duration::from_secs(600);
+
+/// The failures of attempts to a target since the last success.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Number of consecutive failures.
+    pub failures: u32,
+    /// The end of the cooldown started by the last failure.
+    pub until: Instant,
+}
+
+/// Backs off exponentially from targets that attempts keep failing to, so that unpunchable peers
+/// don't use up the goodwill of relays. The cooldown doubles with each consecutive failure, up to
+/// a cap.
+#[derive(Debug, Clone)]
+pub struct BackoffLedger {
+    base: Duration,
+    max: Duration,
+    targets: HashMap<NodeId, Backoff>,
+    clock: SharedClock,
+}
+
+impl BackoffLedger {
+    /// Creates a ledger starting at a cooldown of `base` after the first failure. No cooldown if
+    /// `base` is zero.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        BackoffLedger {
+            base,
+            max,
+            targets: HashMap::new(),
+            clock: system_clock(),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns true if `target` is cooling down after a failure.
+    pub fn is_backing_off(&self, target: &NodeId) -> bool {
+        let now = self.clock.now();
+        self.targets
+            .get(target)
+            .is_some_and(|backoff| now < backoff.until)
+    }
+
+    pub fn get(&self, target: &NodeId) -> Option<&Backoff> {
+        self.targets.get(target)
+    }
+
+    /// Records that an attempt to `target` failed. Returns the cooldown started.
+    pub fn record_failure(&mut self, target: NodeId) -> Duration {
+        if self.base.is_zero() {
+            return Duration::ZERO;
+        }
+        let now = self.clock.now();
+        let failures = self
+            .targets
+            .get(&target)
+            .map_or(0, |backoff| backoff.failures)
+            .saturating_add(1);
+        let cooldown = self
+            .base
+            .saturating_mul(2u32.saturating_pow(failures - 1))
+            .min(self.max);
+        self.targets.insert(
+            target,
+            Backoff {
+                failures,
+                until: now + cooldown,
+            },
+        );
+        cooldown
+    }
+
+    /// Forgets the failures of `target`, for example when an attempt to it succeeded or its ENR
+    /// was updated. Returns true if the target was backed off from.
+    pub fn clear(&mut self, target: &NodeId) -> bool {
+        self.targets.remove(target).is_some()
+    }
+
+    /// Forgets targets whose cooldown passed at least the max cooldown ago at `now`, giving them
+    /// a fresh start.
+    pub fn prune(&mut self, now: Instant) {
+        let max = self.max;
+        self.targets
+            .retain(|_, backoff| now.saturating_duration_since(backoff.until) < max);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::ManualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        let clock = ManualClock::default();
+        let mut ledger = BackoffLedger::new(Duration::from_secs(5), Duration::from_secs(30))
+            .with_clock(Arc::new(clock.clone()));
+        let target = NodeId::random();
+
+        let cooldowns = (0..5)
+            .map(|_| ledger.record_failure(target).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![5, 10, 20, 30, 30], cooldowns);
+        assert!(ledger.is_backing_off(&target));
+
+        clock.advance(Duration::from_secs(30));
+        assert!(!ledger.is_backing_off(&target));
+        assert_eq!(30, ledger.record_failure(target).as_secs());
+
+        assert!(ledger.clear(&target));
+        assert!(!ledger.is_backing_off(&target));
+        assert_eq!(5, ledger.record_failure(target).as_secs());
+    }
+}
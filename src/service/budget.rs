@@ -0,0 +1,102 @@
+use super::DEFAULT_ATTEMPT_TIMEOUT;
+use std::time::{Duration, Instant};
+
+/// How the time budget of an attempt is split across the relays it tries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SplitPolicy {
+    /// Each relay gets an equal share of the budget.
+    #[default]
+    Even,
+    /// Each relay gets half of what is left of the budget, favouring the first relays.
+    Halving,
+}
+
+/// The total time an attempt may take, across all relays it tries. The last relay tried gets
+/// whatever is left of the budget, so that the attempt times out exactly when the budget is
+/// spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineBudget {
+    pub total: Duration,
+    pub policy: SplitPolicy,
+}
+
+impl Default for DeadlineBudget {
+    fn default() -> Self {
+        DeadlineBudget {
+            total: DEFAULT_ATTEMPT_TIMEOUT,
+            policy: SplitPolicy::default(),
+        }
+    }
+}
+
+impl DeadlineBudget {
+    pub fn new(total: Duration, policy: SplitPolicy) -> Self {
+        DeadlineBudget { total, policy }
+    }
+
+    /// The point in time the budget of an attempt started at `started` is spent.
+    pub fn deadline(&self, started: Instant) -> Instant {
+        started + self.total
+    }
+
+    /// The point in time the try at index `try_index` of `tries`, started at `try_started`, of an
+    /// attempt started at `started` times out.
+    pub fn try_deadline(
+        &self,
+        started: Instant,
+        try_started: Instant,
+        try_index: usize,
+        tries: usize,
+    ) -> Instant {
+        let deadline = self.deadline(started);
+        if try_index + 1 >= tries {
+            return deadline;
+        }
+        let share = match self.policy {
+            SplitPolicy::Even => self.total / tries as u32,
+            SplitPolicy::Halving => deadline.saturating_duration_since(try_started) / 2,
+        };
+        deadline.min(try_started + share)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_try_ends_with_budget() {
+        let start = Instant::now();
+        let total = Duration::from_secs(3);
+        for policy in [SplitPolicy::Even, SplitPolicy::Halving] {
+            let budget = DeadlineBudget::new(total, policy);
+
+            let first = budget.try_deadline(start, start, 0, 3);
+            let second = budget.try_deadline(start, first, 1, 3);
+            let last = budget.try_deadline(start, second, 2, 3);
+
+            assert!(first < second && second < last);
+            assert_eq!(start + total, last);
+        }
+    }
+
+    #[test]
+    fn test_split_policies() {
+        let start = Instant::now();
+        let total = Duration::from_secs(4);
+
+        let even = DeadlineBudget::new(total, SplitPolicy::Even);
+        assert_eq!(
+            start + Duration::from_secs(1),
+            even.try_deadline(start, start, 0, 4)
+        );
+
+        let halving = DeadlineBudget::new(total, SplitPolicy::Halving);
+        let first = halving.try_deadline(start, start, 0, 4);
+        assert_eq!(start + Duration::from_secs(2), first);
+        assert_eq!(
+            start + Duration::from_secs(3),
+            halving.try_deadline(start, first, 1, 4)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::Candidate;
+use std::{cmp::Reverse, net::SocketAddr};
+
+/// A local candidate paired with a remote candidate of the same address family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CandidatePair {
+    /// The local end of the pair, `None` if the node has no candidates of its own and sends from
+    /// its discv5 socket.
+    pub local: Option<Candidate>,
+    pub remote: Candidate,
+    /// The ICE pair priority, higher pairs are probed first.
+    pub priority: u64,
+}
+
+/// The candidate pairs of a hole punch attempt in the order they are probed, like the checklist
+/// of ICE-lite. The initiator is the controlling side.
+#[derive(Debug, Clone, Default)]
+pub struct Checklist {
+    pairs: Vec<CandidatePair>,
+    next: usize,
+}
+
+impl Checklist {
+    /// Pairs each local candidate with each remote candidate of the same address family. With no
+    /// local candidates, the remote candidates are probed in their own priority order.
+    pub fn new(local: &[Candidate], remote: &[Candidate], controlling: bool) -> Self {
+        let mut pairs = Vec::new();
+        for remote in remote {
+            if local.is_empty() {
+                pairs.push(CandidatePair {
+                    local: None,
+                    remote: *remote,
+                    priority: pair_priority(remote.priority, remote.priority, controlling),
+                });
+            }
+            for local in local
+                .iter()
+                .filter(|local| local.addr.is_ipv4() == remote.addr.is_ipv4())
+            {
+                pairs.push(CandidatePair {
+                    local: Some(*local),
+                    remote: *remote,
+                    priority: pair_priority(local.priority, remote.priority, controlling),
+                });
+            }
+        }
+        // stable, so that equal pairs are probed in the order the remote listed them
+        pairs.sort_by_key(|pair| Reverse(pair.priority));
+        Checklist { pairs, next: 0 }
+    }
+
+    /// Returns the next pair to probe, if any are left.
+    pub fn next_pair(&mut self) -> Option<&CandidatePair> {
+        let pair = self.pairs.get(self.next)?;
+        self.next += 1;
+        Some(pair)
+    }
+
+    /// The distinct remote addresses of the pairs in the order they are probed.
+    pub fn remote_addrs(&self) -> Vec<SocketAddr> {
+        let mut addrs = Vec::with_capacity(self.pairs.len());
+        for pair in self.pairs.iter() {
+            if !addrs.contains(&pair.remote.addr) {
+                addrs.push(pair.remote.addr);
+            }
+        }
+        addrs
+    }
+
+    pub fn pairs(&self) -> &[CandidatePair] {
+        &self.pairs
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// The pair priority of RFC 8445, from the priorities of the local and remote candidate.
+fn pair_priority(local: u32, remote: u32, controlling: bool) -> u64 {
+    let (g, d) = if controlling {
+        (local, remote)
+    } else {
+        (remote, local)
+    };
+    (1 << 32) * u64::from(g.min(d)) + 2 * u64::from(g.max(d)) + u64::from(g > d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CandidateKind;
+
+    #[test]
+    fn test_pairs_probed_in_priority_order() {
+        let host = Candidate::new(CandidateKind::Host, "10.0.0.2:9000".parse().unwrap());
+        let relayed = Candidate::new(CandidateKind::Relayed, "198.51.100.1:4000".parse().unwrap());
+        let v6 = Candidate::new(CandidateKind::Host, "[2001:db8::2]:9000".parse().unwrap());
+        let remote_reflexive =
+            Candidate::new(CandidateKind::Reflexive, "192.0.2.1:9000".parse().unwrap());
+        let remote_host = Candidate::new(CandidateKind::Host, "10.0.0.1:9000".parse().unwrap());
+
+        let mut checklist = Checklist::new(
+            &[relayed, host, v6],
+            &[remote_reflexive, remote_host],
+            false,
+        );
+        // the IPv6 candidate has no remote counterpart
+        assert_eq!(4, checklist.len());
+        let first = *checklist.next_pair().unwrap();
+        assert_eq!((Some(host), remote_host), (first.local, first.remote));
+        assert_eq!(
+            vec![remote_host.addr, remote_reflexive.addr],
+            checklist.remote_addrs()
+        );
+        assert!(checklist
+            .pairs()
+            .windows(2)
+            .all(|pair| pair[0].priority >= pair[1].priority));
+        assert_eq!(relayed, checklist.pairs()[3].local.unwrap());
+    }
+}
@@ -0,0 +1,223 @@
+use super::{
+    BackToBack, BurstPacing, DistanceHeuristic, DropPolicy, FairScheduling, KeepalivePayload,
+    LoadMonitor, PunchPattern, PunchPayload, PunchPolicy, RelayConcurrency, SplitPolicy,
+    SubnetDiversity, DEFAULT_ATTEMPT_CAPACITY, DEFAULT_ATTEMPT_TIMEOUT, DEFAULT_AUDIT_LOG_SIZE,
+    DEFAULT_FAILURE_COOLDOWN, DEFAULT_FIREWALL_EVIDENCE, DEFAULT_INITIATION_RATE,
+    DEFAULT_MAX_ECHO_DELAY, DEFAULT_MAX_FAILURE_COOLDOWN, DEFAULT_MAX_SCHEDULED_ECHOES,
+    DEFAULT_PENDING_NONCE_TTL, DEFAULT_RATE_LIMIT_PER_INITIATOR, DEFAULT_RATE_LIMIT_PER_RELAY,
+    DEFAULT_RATE_LIMIT_TOTAL, DEFAULT_RATE_LIMIT_WINDOW, DEFAULT_REACHABILITY_CAPACITY,
+    DEFAULT_REBIND_THRESHOLD, DEFAULT_REBIND_WINDOW, DEFAULT_RELAY_BOOK_CAPACITY,
+    DEFAULT_RELAY_CANDIDATES_PER_TARGET, DEFAULT_RELAY_QUEUE_DEPTH, DEFAULT_RELAY_RATE,
+    DEFAULT_REPLAY_CACHE_CAPACITY, DEFAULT_REPLAY_TTL,
+};
+use crate::{
+    clock::system_clock, rng::thread_rng, Candidate, DecodeLimits, KeepalivePolicy, Lazy,
+    SharedClock, SharedRng, ValidationPolicy, WireConfig, DEFAULT_HOLE_PUNCH_LIFETIME,
+    DEFAULT_MAX_NOTIFICATION_SIZE,
+};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+/// Default capacity of the command channel of the [`super::HolePunchService`].
+pub const DEFAULT_COMMAND_BUFFER: usize = 256;
+
+/// Default time a relayed attempt served as target is remembered, to answer only the first of
+/// the [`crate::RelayMsg`]s an initiator fanning out over several relays causes.
+pub const DEFAULT_SERVED_ATTEMPT_TTL: Duration = Duration::from_secs(5);
+
+/// Max time between two ticks of the [`super::HolePunchService`] timers.
+pub const DEFAULT_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Configuration of the [`super::HolePunchService`].
+#[derive(Debug, Clone)]
+pub struct HolePunchConfig {
+    /// The time a punched hole stays open without traffic.
+    pub hole_lifetime: Duration,
+    /// The time punched holes to specific destinations stay open without traffic, for example
+    /// for known infrastructure peers behind CGNAT.
+    pub hole_lifetime_overrides: HashMap<SocketAddr, Duration>,
+    /// Holes expiring within this window of each other are kept alive in one batch.
+    pub keepalive_batch_window: Duration,
+    /// When punched holes are kept alive and when they are left to close, see
+    /// [`crate::Aggressive`], [`crate::Lazy`] and [`crate::ActivityBased`].
+    pub keepalive_policy: Arc<dyn KeepalivePolicy>,
+    /// Which targets of timed out requests are worth punching a hole to, see
+    /// [`super::DistanceHeuristic`] and [`super::AlwaysPunch`].
+    pub punch_policy: Arc<dyn PunchPolicy>,
+    /// The time an initiated attempt waits for a WHOAREYOU from the target, across all relays
+    /// it tries.
+    pub attempt_timeout: Duration,
+    /// Max number of attempts in flight, the oldest attempt is given up on to make room.
+    pub attempt_capacity: usize,
+    /// Caps on the attempts in flight, attempts beyond them wait and are sent round-robin across
+    /// targets. Attempts are sent right away if `None`.
+    pub attempt_scheduling: Option<FairScheduling>,
+    /// How the time budget of an attempt is split across the relays it tries.
+    pub attempt_budget_split: SplitPolicy,
+    /// Max number of initiated attempts kept in the audit log, see
+    /// [`super::HolePunchService::recent_attempts`]. No attempts are kept if zero.
+    pub audit_log_size: usize,
+    /// Longest delay an [`crate::EchoAfter`] from a peer is answered after.
+    pub max_echo_delay: Duration,
+    /// Max number of echoes owed to peers at a time, further probes are refused.
+    pub max_scheduled_echoes: usize,
+    /// The time the nonce of a timed out request is matched against incoming WHOAREYOUs, see
+    /// [`super::HolePunchService::match_whoareyou`].
+    pub pending_nonce_ttl: Duration,
+    /// The time a relayed notification is remembered to drop replays of it.
+    pub replay_ttl: Duration,
+    /// Sends a [`crate::PunchReport`] to the relay of each attempt initiated by this node once it
+    /// resolves. Disable to not tell relays which peers this node reached.
+    pub punch_reports: bool,
+    /// The time a relayed attempt served as target is remembered, to punch once for the
+    /// [`crate::RelayMsg`]s of the same attempt arriving over other relays.
+    pub served_attempt_ttl: Duration,
+    /// Max number of notifications remembered to drop replays of them.
+    pub replay_cache_capacity: usize,
+    /// Max number of peers whose reachability is remembered.
+    pub reachability_capacity: usize,
+    /// Keeps the socket the WHOAREYOU of each punched target arrived from as a hint for the
+    /// next handshake with it, see [`super::HolePunchService::take_socket_hint`].
+    pub socket_hints: bool,
+    /// Max number of targets relay candidates are remembered for, see
+    /// [`super::RelayCandidateBook`].
+    pub relay_book_capacity: usize,
+    /// Max number of relay candidates remembered per target.
+    pub relay_candidates_per_target: usize,
+    /// Punches a fresh hole through the last relay that punched one to a peer whose ENR sockets
+    /// changed, see [`super::HolePunchService::handle_peer_enr_update`].
+    pub repunch_on_enr_update: bool,
+    /// Window in which an external address change and failures of keep-alives or requests over
+    /// punched holes count as a NAT rebind, see [`super::RebindDetector`].
+    pub rebind_window: Duration,
+    /// Number of failures over punched holes that together with an external address change
+    /// within `rebind_window` indicate a NAT rebind. The address change alone suffices if zero.
+    pub rebind_failures: usize,
+    /// Relayed attempts served as target while a local socket is taken to be public before it is
+    /// taken to be firewalled, see [`crate::NatStatus::Firewalled`].
+    pub firewall_evidence: u32,
+    /// Max notifications served per initiator per rate limit window.
+    pub rate_limit_per_initiator: u32,
+    /// Max notifications served in total per rate limit window.
+    pub rate_limit_total: u32,
+    /// Max relayed notifications served per relay per rate limit window, as target. Only relays
+    /// telling their node id are limited, see [`crate::WireConfig::relay_ids`].
+    pub rate_limit_per_relay: u32,
+    /// Length of a rate limit window.
+    pub rate_limit_window: Duration,
+    /// Max attempts initiated by this node per second, beyond which initiating fails with
+    /// [`crate::HolePunchError::LocallyThrottled`].
+    pub initiation_rate: u32,
+    /// The time no attempts are initiated to a target after an attempt to it failed, doubled with
+    /// each consecutive failure. No cooldown if zero.
+    pub failure_cooldown: Duration,
+    /// Cap on the cooldown of a target that attempts keep failing to.
+    pub max_failure_cooldown: Duration,
+    /// How the target punches a hole for the initiator.
+    pub punch_pattern: PunchPattern,
+    /// How the initiator finishes a punch with packets to the target, none if `None`.
+    pub back_to_back: Option<BackToBack>,
+    /// The packet that punches a hole for the initiator.
+    pub punch_payload: PunchPayload,
+    /// The packet that keeps a punched hole open.
+    pub keepalive_payload: KeepalivePayload,
+    /// Shapes the bursts of punch and keepalive packets, unpaced if `None`.
+    pub burst_pacing: Option<BurstPacing>,
+    /// The subnets relays selected for one attempt must be spread over, see
+    /// [`super::HolePunchService::select_relays`]. Unconstrained if `None`.
+    pub relay_diversity: Option<SubnetDiversity>,
+    /// Max number of notifications waiting to be relayed.
+    pub relay_queue_depth: usize,
+    /// Which notification to drop when the relay queue is full.
+    pub relay_queue_drop_policy: DropPolicy,
+    /// Max number of notifications relayed per second.
+    pub relay_rate: u32,
+    /// Caps on the relay operations in flight, notifications beyond them wait in the relay
+    /// queue. Relay operations are only capped by `relay_rate` if `None`.
+    pub relay_concurrency: Option<RelayConcurrency>,
+    /// Capacity of the command channel.
+    pub command_buffer: usize,
+    /// Wire format options of sent notifications.
+    pub wire: WireConfig,
+    /// Size caps of received notifications.
+    pub decode_limits: DecodeLimits,
+    /// The checks received notifications pass before they are handled. The ENR signature isn't
+    /// checked again, decoding verified it.
+    pub validation: ValidationPolicy,
+    /// Max size of an encoded notification sent, so that it fits in a single discv5 packet.
+    pub max_packet_size: usize,
+    /// Queried before relaying, notifications are aborted while this node is overloaded.
+    pub load_monitor: Option<Arc<dyn LoadMonitor>>,
+    /// The local sockets this node binds, for example separate v4 and v6 sockets. Holes, punched
+    /// peers and NAT status are kept per socket, a hole belonging to the first socket of the
+    /// address family of the remote. If empty, the node is assumed to bind a single socket.
+    pub local_sockets: Vec<SocketAddr>,
+    /// Addresses other than the sockets of the local ENR this node may be reachable at, sent to
+    /// the target of initiated attempts. Peers before protocol revision 3 reject notifications
+    /// carrying candidates, so leave empty until all peers upgraded.
+    pub local_candidates: Vec<Candidate>,
+    /// The clock the timers of the service read, see [`crate::test_utils::ManualClock`] for
+    /// stepping time in tests.
+    pub clock: SharedClock,
+    /// The source nonces and attempt ids are drawn from, see [`crate::SeededRng`] for
+    /// reproducible tests.
+    pub rng: SharedRng,
+}
+
+impl Default for HolePunchConfig {
+    fn default() -> Self {
+        HolePunchConfig {
+            hole_lifetime: Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME),
+            hole_lifetime_overrides: HashMap::new(),
+            keepalive_batch_window: Duration::ZERO,
+            keepalive_policy: Arc::new(Lazy::default()),
+            punch_policy: Arc::new(DistanceHeuristic::default()),
+            attempt_timeout: DEFAULT_ATTEMPT_TIMEOUT,
+            attempt_capacity: DEFAULT_ATTEMPT_CAPACITY,
+            attempt_scheduling: None,
+            attempt_budget_split: SplitPolicy::default(),
+            audit_log_size: DEFAULT_AUDIT_LOG_SIZE,
+            max_echo_delay: DEFAULT_MAX_ECHO_DELAY,
+            max_scheduled_echoes: DEFAULT_MAX_SCHEDULED_ECHOES,
+            pending_nonce_ttl: DEFAULT_PENDING_NONCE_TTL,
+            replay_ttl: DEFAULT_REPLAY_TTL,
+            punch_reports: true,
+            served_attempt_ttl: DEFAULT_SERVED_ATTEMPT_TTL,
+            replay_cache_capacity: DEFAULT_REPLAY_CACHE_CAPACITY,
+            reachability_capacity: DEFAULT_REACHABILITY_CAPACITY,
+            socket_hints: false,
+            relay_book_capacity: DEFAULT_RELAY_BOOK_CAPACITY,
+            relay_candidates_per_target: DEFAULT_RELAY_CANDIDATES_PER_TARGET,
+            repunch_on_enr_update: false,
+            rebind_window: DEFAULT_REBIND_WINDOW,
+            rebind_failures: DEFAULT_REBIND_THRESHOLD,
+            firewall_evidence: DEFAULT_FIREWALL_EVIDENCE,
+            rate_limit_per_initiator: DEFAULT_RATE_LIMIT_PER_INITIATOR,
+            rate_limit_total: DEFAULT_RATE_LIMIT_TOTAL,
+            rate_limit_per_relay: DEFAULT_RATE_LIMIT_PER_RELAY,
+            rate_limit_window: DEFAULT_RATE_LIMIT_WINDOW,
+            initiation_rate: DEFAULT_INITIATION_RATE,
+            failure_cooldown: DEFAULT_FAILURE_COOLDOWN,
+            max_failure_cooldown: DEFAULT_MAX_FAILURE_COOLDOWN,
+            punch_pattern: PunchPattern::default(),
+            back_to_back: None,
+            punch_payload: PunchPayload::default(),
+            keepalive_payload: KeepalivePayload::default(),
+            burst_pacing: None,
+            relay_diversity: Some(SubnetDiversity::default()),
+            relay_queue_depth: DEFAULT_RELAY_QUEUE_DEPTH,
+            relay_queue_drop_policy: DropPolicy::default(),
+            relay_rate: DEFAULT_RELAY_RATE,
+            relay_concurrency: None,
+            command_buffer: DEFAULT_COMMAND_BUFFER,
+            wire: WireConfig::default(),
+            decode_limits: DecodeLimits::default(),
+            validation: ValidationPolicy::default(),
+            max_packet_size: DEFAULT_MAX_NOTIFICATION_SIZE,
+            load_monitor: None,
+            local_sockets: Vec::new(),
+            local_candidates: Vec::new(),
+            clock: system_clock(),
+            rng: thread_rng(),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{NodeAddress, RelayInit};
+use async_trait::async_trait;
+
+/// Asks the application before this node spends bandwidth relaying an attempt on behalf of
+/// others, for example for user consent or a quota check. Refused attempts are aborted with
+/// [`crate::ReasonCode::PolicyDenied`], so that the initiator moves on to another relay.
+#[async_trait]
+pub trait RelayConsent: Send + Sync {
+    /// Returns true if the [`RelayInit`] received from the initiator at `from` may be relayed.
+    async fn allow(&self, init: &RelayInit, from: &NodeAddress) -> bool;
+}
+
+/// Relays every attempt the rate limits and load allow, the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysAllow;
+
+#[async_trait]
+impl RelayConsent for AlwaysAllow {
+    async fn allow(&self, _init: &RelayInit, _from: &NodeAddress) -> bool {
+        true
+    }
+}
@@ -0,0 +1,65 @@
+use super::{ServiceCommand, Source};
+use crate::{rlp_backend::DecoderError, DecodeLimits, Notification, SessionKey, WireConfig};
+use futures::{channel::mpsc, SinkExt};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DispatchError {
+    #[error("error parsing notification, {0}")]
+    Decode(#[from] DecoderError),
+    #[error("service is shut down")]
+    Closed,
+}
+
+/// Decodes and validates notifications off the task running the
+/// [`super::HolePunchService`], so that a busy relay spreads the decoding, including the ENR
+/// signature checks, over a pool of workers. Only the decoded notification goes through the
+/// command channel, as [`ServiceCommand::Decoded`]. Each worker takes its own clone.
+#[derive(Debug)]
+pub struct NotificationDispatcher<I> {
+    commands: mpsc::Sender<ServiceCommand<I>>,
+    decode_limits: DecodeLimits,
+    wire: WireConfig,
+}
+
+impl<I> Clone for NotificationDispatcher<I> {
+    fn clone(&self) -> Self {
+        NotificationDispatcher {
+            commands: self.commands.clone(),
+            decode_limits: self.decode_limits,
+            wire: self.wire,
+        }
+    }
+}
+
+impl<I: SessionKey> NotificationDispatcher<I> {
+    /// Dispatches to the service at the other end of `commands`, see
+    /// [`super::HolePunchService::dispatcher`] for one matching its decode configuration.
+    pub fn new(
+        commands: mpsc::Sender<ServiceCommand<I>>,
+        decode_limits: DecodeLimits,
+        wire: WireConfig,
+    ) -> Self {
+        NotificationDispatcher {
+            commands,
+            decode_limits,
+            wire,
+        }
+    }
+
+    /// Decodes a notification under the configured limits and wire format.
+    pub fn decode(&self, decrypted_notif: &[u8]) -> Result<Notification, DecoderError> {
+        Notification::rlp_decode_with_wire(decrypted_notif, &self.decode_limits, &self.wire)
+    }
+
+    /// Decodes a notification received over the session `src` and hands it to the service.
+    /// Malformed notifications never reach the service.
+    pub async fn dispatch(&mut self, src: &I, decrypted_notif: &[u8]) -> Result<(), DispatchError> {
+        let notif = self.decode(decrypted_notif)?;
+        let src = Source::from_session(src);
+        self.commands
+            .send(ServiceCommand::Decoded { src, notif })
+            .await
+            .map_err(|_| DispatchError::Closed)
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{MessageNonce, NodeId};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Default longest delay an [`crate::EchoAfter`] is answered after.
+pub const DEFAULT_MAX_ECHO_DELAY: Duration = Duration::from_secs(600);
+/// Default max number of echoes waiting for their delay to pass.
+pub const DEFAULT_MAX_SCHEDULED_ECHOES: usize = 64;
+/// Time an echo is waited for past the delay of its probe, covering the round trip and the
+/// timers of the peer.
+pub const ECHO_GRACE: Duration = Duration::from_secs(5);
+
+/// An [`crate::Echo`] due to the sender of an [`crate::EchoAfter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledEcho {
+    pub due: Instant,
+    pub peer: NodeId,
+    pub nonce: MessageNonce,
+}
+
+/// The echoes this node owes to peers measuring the lifetime of their NAT mappings, capped so
+/// that peers can't pile up work on this node.
+#[derive(Debug, Clone)]
+pub struct ScheduledEchoes {
+    echoes: Vec<ScheduledEcho>,
+    capacity: usize,
+    max_delay: Duration,
+}
+
+impl Default for ScheduledEchoes {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SCHEDULED_ECHOES, DEFAULT_MAX_ECHO_DELAY)
+    }
+}
+
+impl ScheduledEchoes {
+    pub fn new(capacity: usize, max_delay: Duration) -> Self {
+        ScheduledEchoes {
+            echoes: Vec::new(),
+            capacity,
+            max_delay,
+        }
+    }
+
+    /// Schedules an echo to `peer` after `delay` from `now`. Returns false if the delay is longer
+    /// than the max delay, if `peer` already waits for an echo or if the cap is reached.
+    pub fn schedule(
+        &mut self,
+        now: Instant,
+        peer: NodeId,
+        nonce: MessageNonce,
+        delay: Duration,
+    ) -> bool {
+        if delay > self.max_delay
+            || self.echoes.len() >= self.capacity
+            || self.echoes.iter().any(|echo| echo.peer == peer)
+        {
+            return false;
+        }
+        self.echoes.push(ScheduledEcho {
+            due: now + delay,
+            peer,
+            nonce,
+        });
+        true
+    }
+
+    /// Removes and returns the echoes due at `now`.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<ScheduledEcho> {
+        let (due, pending) = self
+            .echoes
+            .drain(..)
+            .partition(|echo: &ScheduledEcho| echo.due <= now);
+        self.echoes = pending;
+        due
+    }
+
+    /// Drops all scheduled echoes.
+    pub fn clear(&mut self) {
+        self.echoes.clear();
+    }
+
+    /// The point in time the next echo is due, if any are scheduled.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.echoes.iter().map(|echo| echo.due).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.echoes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.echoes.is_empty()
+    }
+}
+
+/// An [`crate::EchoAfter`] sent by this node, waiting for its echo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoProbe {
+    pub peer: NodeId,
+    /// The silence the echo is sent after.
+    pub delay: Duration,
+    /// The point in time the echo is given up on.
+    pub deadline: Instant,
+}
+
+/// The probes of the lifetime of the NAT mappings of this node waiting for their echoes, indexed
+/// by nonce.
+#[derive(Debug, Clone, Default)]
+pub struct EchoProbes {
+    probes: HashMap<MessageNonce, EchoProbe>,
+}
+
+impl EchoProbes {
+    pub fn insert(&mut self, nonce: MessageNonce, probe: EchoProbe) {
+        self.probes.insert(nonce, probe);
+    }
+
+    /// Removes the probe the echo with `nonce` answers.
+    pub fn remove(&mut self, nonce: &MessageNonce) -> Option<EchoProbe> {
+        self.probes.remove(nonce)
+    }
+
+    /// Removes and returns the probes whose echo didn't arrive by `now`.
+    pub fn poll_missed(&mut self, now: Instant) -> Vec<(MessageNonce, EchoProbe)> {
+        let missed = self
+            .probes
+            .iter()
+            .filter(|(_, probe)| probe.deadline <= now)
+            .map(|(nonce, probe)| (*nonce, *probe))
+            .collect::<Vec<_>>();
+        for (nonce, _) in missed.iter() {
+            self.probes.remove(nonce);
+        }
+        missed
+    }
+
+    /// The point in time the next probe is given up on, if any are waiting.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.probes.values().map(|probe| probe.deadline).min()
+    }
+
+    pub fn len(&self) -> usize {
+        self.probes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.probes.is_empty()
+    }
+}
@@ -0,0 +1,140 @@
+use super::PunchedPeers;
+use crate::{KeepaliveScheduler, NatStatus};
+use std::net::SocketAddr;
+
+/// Default number of relayed attempts served while a socket is taken to be public before it is
+/// taken to be behind a stateful firewall, see [`NatStatus::Firewalled`].
+pub const DEFAULT_FIREWALL_EVIDENCE: u32 = 2;
+
+/// The hole state of one local socket. Each socket of a node binding several, like separate v4
+/// and v6 sockets or one per interface, is mapped by its NAT on its own.
+#[derive(Debug)]
+pub struct LocalEndpoint {
+    pub(super) socket: Option<SocketAddr>,
+    pub(super) keepalive: KeepaliveScheduler,
+    pub(super) punched_peers: PunchedPeers,
+    pub(super) nat_status: NatStatus,
+    /// Relayed attempts served while the socket was taken to be public, each a peer that failed
+    /// to reach it unsolicited.
+    pub(super) firewall_evidence: u32,
+}
+
+impl LocalEndpoint {
+    /// The local socket, `None` if the service wasn't told its sockets.
+    pub fn socket(&self) -> Option<SocketAddr> {
+        self.socket
+    }
+
+    /// The holes punched from the socket.
+    pub fn keepalive(&self) -> &KeepaliveScheduler {
+        &self.keepalive
+    }
+
+    /// The initiators holes were punched for from the socket as target, by reflexive socket.
+    pub fn punched_peers(&self) -> &PunchedPeers {
+        &self.punched_peers
+    }
+
+    /// What is known about the socket being behind NAT.
+    pub fn nat_status(&self) -> NatStatus {
+        self.nat_status
+    }
+
+    pub(super) fn set_nat_status(&mut self, nat_status: NatStatus) {
+        self.nat_status = nat_status;
+        self.firewall_evidence = 0;
+    }
+
+    /// Records that this node punched a hole from the socket as target of a relayed attempt. The
+    /// initiator's request timed out, so a socket taken to be public is likely firewalled.
+    pub(super) fn record_relayed_punch(&mut self, firewall_evidence: u32) {
+        if self.nat_status != NatStatus::Public {
+            return;
+        }
+        self.firewall_evidence += 1;
+        if self.firewall_evidence >= firewall_evidence {
+            self.nat_status = NatStatus::Firewalled;
+        }
+    }
+}
+
+/// The local sockets of the service. A hole belongs to the first socket of the address family of
+/// the remote, the way a node with separate v4 and v6 sockets sends.
+#[derive(Debug)]
+pub(super) struct LocalEndpoints {
+    endpoints: Vec<LocalEndpoint>,
+}
+
+impl LocalEndpoints {
+    /// Creates an endpoint for each of `sockets`, or a single one without a known socket if none
+    /// are given.
+    pub(super) fn new(
+        sockets: &[SocketAddr],
+        mut new_endpoint: impl FnMut(Option<SocketAddr>) -> LocalEndpoint,
+    ) -> Self {
+        let endpoints = if sockets.is_empty() {
+            vec![new_endpoint(None)]
+        } else {
+            sockets
+                .iter()
+                .map(|socket| new_endpoint(Some(*socket)))
+                .collect()
+        };
+        LocalEndpoints { endpoints }
+    }
+
+    fn position(&self, remote: &SocketAddr) -> usize {
+        self.endpoints
+            .iter()
+            .position(|endpoint| {
+                endpoint
+                    .socket
+                    .is_some_and(|socket| socket.is_ipv4() == remote.is_ipv4())
+            })
+            .unwrap_or_default()
+    }
+
+    /// The endpoint holes to `remote` are punched from.
+    pub(super) fn for_remote(&self, remote: &SocketAddr) -> &LocalEndpoint {
+        &self.endpoints[self.position(remote)]
+    }
+
+    pub(super) fn for_remote_mut(&mut self, remote: &SocketAddr) -> &mut LocalEndpoint {
+        let index = self.position(remote);
+        &mut self.endpoints[index]
+    }
+
+    /// The endpoint of the local `socket`.
+    pub(super) fn get_mut(&mut self, socket: &SocketAddr) -> Option<&mut LocalEndpoint> {
+        self.endpoints
+            .iter_mut()
+            .find(|endpoint| endpoint.socket == Some(*socket))
+    }
+
+    /// The endpoint of the first local socket.
+    pub(super) fn first(&self) -> &LocalEndpoint {
+        &self.endpoints[0]
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = &LocalEndpoint> {
+        self.endpoints.iter()
+    }
+
+    pub(super) fn iter_mut(&mut self) -> impl Iterator<Item = &mut LocalEndpoint> {
+        self.endpoints.iter_mut()
+    }
+
+    /// The endpoints of the address family of `addr`. That is all endpoints if the service wasn't
+    /// told its sockets.
+    pub(super) fn of_family_mut(
+        &mut self,
+        addr: &SocketAddr,
+    ) -> impl Iterator<Item = &mut LocalEndpoint> {
+        let is_ipv4 = addr.is_ipv4();
+        self.endpoints.iter_mut().filter(move |endpoint| {
+            endpoint
+                .socket
+                .is_none_or(|socket| socket.is_ipv4() == is_ipv4)
+        })
+    }
+}
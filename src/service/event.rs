@@ -0,0 +1,96 @@
+use super::{AttemptOutcome, FailureReason, PunchTimings};
+use crate::{AttemptId, MessageNonce, NodeId, ProtocolVersion};
+use std::{net::SocketAddr, time::Duration};
+
+/// Events of the [`super::HolePunchService`], see [`super::HolePunchService::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HolePunchEvent {
+    /// The local NAT rebooted or rebound. The given holes were re-punched in bulk, attempts
+    /// initiated by this node should be initiated again.
+    NatRebindDetected {
+        /// The new external address, if it is known.
+        external: Option<SocketAddr>,
+        holes: Vec<SocketAddr>,
+    },
+    /// An attempt initiated by this node failed, emitted along with
+    /// [`HolePunchEvent::AttemptResolved`] and for sessions failing through a punched hole.
+    Failed {
+        attempt_id: Option<AttemptId>,
+        nonce: MessageNonce,
+        reason: FailureReason,
+    },
+    /// An attempt initiated by this node resolved.
+    AttemptResolved {
+        attempt_id: Option<AttemptId>,
+        nonce: MessageNonce,
+        outcome: AttemptOutcome,
+    },
+    /// The target of an attempt initiated by this node is punched at `src`, after the
+    /// back-to-back packets to it if configured, see [`super::BackToBack`].
+    TargetPunched {
+        attempt_id: Option<AttemptId>,
+        nonce: MessageNonce,
+        src: SocketAddr,
+        timings: PunchTimings,
+    },
+    /// The discv5 session with the target of an attempt initiated by this node is established
+    /// through the hole at `src`, see [`super::HolePunchService::confirm_established`].
+    Established {
+        attempt_id: Option<AttemptId>,
+        nonce: MessageNonce,
+        target: NodeId,
+        src: SocketAddr,
+        /// The time from initiating the attempt to the WHOAREYOU from the target arriving.
+        time_to_punch: Duration,
+        /// The time from initiating the attempt to the session being established.
+        time_to_establish: Duration,
+        timings: PunchTimings,
+    },
+    /// This node relayed an attempt from `initiator` to `target`.
+    Relayed {
+        attempt_id: Option<AttemptId>,
+        initiator: NodeId,
+        target: NodeId,
+    },
+    /// The initiator of an attempt this node relayed reported whether the target was punched.
+    PunchReported {
+        attempt_id: Option<AttemptId>,
+        initiator: NodeId,
+        target: NodeId,
+        punched: bool,
+    },
+    /// This node, as target, punched holes towards the given paths of `initiator`.
+    Punched {
+        attempt_id: Option<AttemptId>,
+        initiator: NodeId,
+        paths: Vec<SocketAddr>,
+    },
+    /// The relay of an attempt this node is the target of reported the socket it observes this
+    /// node at, see [`crate::WireConfig::observed_addrs`].
+    ObservedByRelay {
+        attempt_id: Option<AttemptId>,
+        observed: SocketAddr,
+    },
+    /// The revision of the notification protocol spoken with `peer` is negotiated, or changed.
+    VersionNegotiated {
+        peer: NodeId,
+        version: ProtocolVersion,
+    },
+    /// The keep-alive policy gave up on the hole to `dst`, it is left to close, see
+    /// [`crate::KeepalivePolicy`].
+    HoleAbandoned { dst: SocketAddr },
+    /// The echo of a probe sent to `peer` arrived, the NAT mapping of this node towards the peer
+    /// outlived `silence`, see [`super::HolePunchService::probe_mapping_lifetime`].
+    EchoReceived {
+        peer: NodeId,
+        nonce: MessageNonce,
+        silence: Duration,
+    },
+    /// The echo of a probe sent to `peer` didn't arrive, the NAT mapping of this node towards the
+    /// peer likely closed within `silence`.
+    EchoMissed {
+        peer: NodeId,
+        nonce: MessageNonce,
+        silence: Duration,
+    },
+}
@@ -0,0 +1,12 @@
+use crate::{FallbackStrategy, NodeId, RelayInit};
+use async_trait::async_trait;
+
+/// Gives a relay an alternative when it has no session with the target of an attempt, so the
+/// encrypted [`crate::RelayMsg`] can't be sent. The attempt is still aborted with
+/// [`crate::ReasonCode::UnknownTarget`], reporting the strategy tried.
+#[async_trait]
+pub trait RelayFallback: Send + Sync {
+    /// Tries to reach `target` another way than relaying `init` to it, for example by pinging it
+    /// from its ENR in the routing table. Returns the strategy tried, `None` if there was none.
+    async fn try_fallback(&self, init: &RelayInit, target: NodeId) -> Option<FallbackStrategy>;
+}
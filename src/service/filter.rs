@@ -0,0 +1,72 @@
+use crate::{NodeId, Notification, ReasonCode, SessionKey};
+use std::{fmt, net::SocketAddr, sync::Arc};
+
+/// Decides the fate of an inbound notification, see
+/// [`super::HolePunchService::add_inbound_filter`].
+pub type InboundFilterFn = Arc<dyn Fn(&Notification, &Source) -> Verdict + Send + Sync>;
+
+/// The peer an inbound notification arrived from, as far as discv5 told the service.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Source {
+    pub node_id: Option<NodeId>,
+    pub socket_addr: Option<SocketAddr>,
+}
+
+impl Source {
+    /// The peer of the given session.
+    pub fn from_session<K: SessionKey>(session: &K) -> Self {
+        Source {
+            node_id: session.node_id(),
+            socket_addr: session.socket_addr(),
+        }
+    }
+}
+
+/// What an inbound filter decides about a notification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// Passes the notification on to the next filter, or to the handler.
+    Allow,
+    /// Drops the notification silently.
+    Drop,
+    /// Drops the notification and, if it's a [`crate::RelayInit`], tells the initiator why, so
+    /// that it can move on to another relay.
+    Abort(ReasonCode),
+}
+
+/// A chain of inbound filters, evaluated in the order they were added. The first filter not
+/// allowing a notification decides its fate, so that policies like allowlists or proof of work
+/// compose without forking the dispatcher.
+#[derive(Clone, Default)]
+pub struct InboundFilters {
+    filters: Vec<InboundFilterFn>,
+}
+
+impl InboundFilters {
+    pub fn push(&mut self, filter: InboundFilterFn) {
+        self.filters.push(filter);
+    }
+
+    /// Returns the verdict of the first filter not allowing the notification.
+    pub fn evaluate(&self, notif: &Notification, src: &Source) -> Verdict {
+        self.filters
+            .iter()
+            .map(|filter| filter(notif, src))
+            .find(|verdict| *verdict != Verdict::Allow)
+            .unwrap_or(Verdict::Allow)
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl fmt::Debug for InboundFilters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "InboundFilters({})", self.filters.len())
+    }
+}
@@ -0,0 +1,56 @@
+use super::{KeepalivePayload, PunchPayload};
+use crate::NodeId;
+use std::net::SocketAddr;
+
+/// What a datagram read from the discv5 socket is, see
+/// [`super::HolePunchService::classify_inbound`]. Each kind carries the initiator this node
+/// punched a hole for at the source socket, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InboundKind {
+    /// A packet keeping a hole open, empty or the configured [`KeepalivePayload`]. Dropped
+    /// after [`super::ServiceCommand::PacketReceived`].
+    Keepalive(Option<NodeId>),
+    /// The configured [`PunchPayload`] of a peer punching a hole towards this node. Dropped too.
+    PunchProbe(Option<NodeId>),
+    /// Any other packet, for discv5 to decode.
+    Discv5(Option<NodeId>),
+}
+
+impl InboundKind {
+    /// The initiator this node punched a hole for at the source socket, if any.
+    pub fn punched_peer(&self) -> Option<NodeId> {
+        match self {
+            InboundKind::Keepalive(peer)
+            | InboundKind::PunchProbe(peer)
+            | InboundKind::Discv5(peer) => *peer,
+        }
+    }
+
+    /// Returns true if the packet is for discv5.
+    pub fn is_discv5(&self) -> bool {
+        matches!(self, InboundKind::Discv5(_))
+    }
+}
+
+/// Returns true if `packet` from `src` is a keep-alive packet in the format of `payload`.
+pub(crate) fn is_keep_hole_punched_packet(
+    packet: &[u8],
+    src: SocketAddr,
+    payload: &KeepalivePayload,
+) -> bool {
+    match payload {
+        KeepalivePayload::Empty => packet.is_empty(),
+        KeepalivePayload::Template(template) => packet.is_empty() || packet == template,
+        // the peer builds its packets towards this node as this node does towards it
+        KeepalivePayload::Custom(build) => packet.is_empty() || packet == build(src),
+    }
+}
+
+/// Returns true if `packet` is a punch packet in the format of `payload`. WHOAREYOUs and punch
+/// packets built per initiator are left to discv5 and the application.
+pub(crate) fn is_punch_probe(packet: &[u8], payload: &PunchPayload) -> bool {
+    match payload {
+        PunchPayload::Template(template) => !packet.is_empty() && packet == template,
+        PunchPayload::WhoAreYou | PunchPayload::Custom(_) => false,
+    }
+}
@@ -0,0 +1,8 @@
+use std::fmt::Debug;
+
+/// Tells the relay path whether this node is under pressure, for example from CPU or bandwidth
+/// use, so that relay work is shed before it affects discv5 itself.
+pub trait LoadMonitor: Debug + Send + Sync {
+    /// Returns true if this node should stop relaying for now.
+    fn is_overloaded(&self) -> bool;
+}
@@ -0,0 +1,67 @@
+use crate::NodeId;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+/// Maps the target of a [`crate::RelayInit`] to the session the relay forwards the
+/// [`crate::RelayMsg`] over. Targets without a session are aborted with
+/// [`crate::ReasonCode::UnknownTarget`]. See [`SessionMap`] for an in-memory implementation.
+pub trait TargetLookup: Send {
+    /// A type in discv5 for indexing sessions.
+    type SessionIndex;
+    /// Returns the active session with `target`, if any.
+    fn lookup(&self, target: NodeId) -> Option<Self::SessionIndex>;
+}
+
+/// Active sessions by node id, for discv5 integrations that don't index their sessions by node
+/// id. Clones share the map, so the application keeps a clone up to date as sessions come and go
+/// while the relay reads another.
+#[derive(Debug)]
+pub struct SessionMap<I> {
+    sessions: Arc<RwLock<HashMap<NodeId, I>>>,
+}
+
+impl<I> Default for SessionMap<I> {
+    fn default() -> Self {
+        SessionMap {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+}
+
+impl<I> Clone for SessionMap<I> {
+    fn clone(&self) -> Self {
+        SessionMap {
+            sessions: self.sessions.clone(),
+        }
+    }
+}
+
+impl<I> SessionMap<I> {
+    /// Records the session with `peer`. Returns the session it replaces, if any.
+    pub fn insert(&self, peer: NodeId, session: I) -> Option<I> {
+        self.sessions.write().unwrap().insert(peer, session)
+    }
+
+    /// Forgets the session with `peer`, for example when it is dropped by discv5.
+    pub fn remove(&self, peer: &NodeId) -> Option<I> {
+        self.sessions.write().unwrap().remove(peer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.read().unwrap().is_empty()
+    }
+}
+
+impl<I: Clone + Send + Sync> TargetLookup for SessionMap<I> {
+    type SessionIndex = I;
+
+    fn lookup(&self, target: NodeId) -> Option<I> {
+        self.sessions.read().unwrap().get(&target).cloned()
+    }
+}
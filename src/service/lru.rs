@@ -0,0 +1,218 @@
+use crate::{clock::system_clock, SharedClock};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+struct LruEntry<V> {
+    value: V,
+    inserted: Instant,
+    /// Position in the recency order, higher is more recent.
+    seq: u64,
+}
+
+/// A map holding at most `capacity` entries, that evicts the least recently used entry to make
+/// room for a new one, so that caches stay bounded on memory constrained nodes. Entries live for
+/// the optional time to live after insertion.
+#[derive(Debug, Clone)]
+pub struct LruCache<K, V> {
+    entries: HashMap<K, LruEntry<V>>,
+    order: BTreeMap<u64, K>,
+    next_seq: u64,
+    capacity: usize,
+    ttl: Option<Duration>,
+    evictions: u64,
+    clock: SharedClock,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    pub fn new(capacity: usize, ttl: Option<Duration>) -> Self {
+        LruCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_seq: 0,
+            capacity,
+            ttl,
+            evictions: 0,
+            clock: system_clock(),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Inserts an entry as the most recently used one. Returns the entry it displaced, either
+    /// the previous entry under the same key or the least recently used entry if the cache was
+    /// full.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        let seq = self.bump_seq();
+        let entry = LruEntry {
+            value,
+            inserted: self.clock.now(),
+            seq,
+        };
+        self.order.insert(seq, key.clone());
+        if let Some(replaced) = self.entries.insert(key.clone(), entry) {
+            self.order.remove(&replaced.seq);
+            return Some((key, replaced.value));
+        }
+        if self.entries.len() <= self.capacity {
+            return None;
+        }
+        let (_, lru) = self.order.pop_first()?;
+        self.evictions += 1;
+        self.entries.remove(&lru).map(|entry| (lru, entry.value))
+    }
+
+    /// Returns the live entry under `key` and marks it as the most recently used one.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns the live entry under `key` mutably and marks it as the most recently used one.
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        if !self.is_live(key) {
+            return None;
+        }
+        let seq = self.bump_seq();
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.seq);
+        self.order.insert(seq, key.clone());
+        entry.seq = seq;
+        Some(&mut entry.value)
+    }
+
+    /// Returns the live entry under `key` without changing its recency.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        if !self.is_live(key) {
+            return None;
+        }
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Returns true if a live entry is under `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.is_live(key)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.seq);
+        Some(entry.value)
+    }
+
+    /// Iterates over the entries, including expired ones not yet pruned.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().map(|(key, entry)| (key, &entry.value))
+    }
+
+    /// Iterates mutably over the entries, without changing their recency.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.entries
+            .iter_mut()
+            .map(|(key, entry)| (key, &mut entry.value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.values().map(|entry| &entry.value)
+    }
+
+    /// Keeps only the entries for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|key, entry| {
+            let keep = f(key, &mut entry.value);
+            if !keep {
+                order.remove(&entry.seq);
+            }
+            keep
+        });
+    }
+
+    /// Drops the entries that outlived the time to live at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        if let Some(ttl) = self.ttl {
+            let order = &mut self.order;
+            self.entries.retain(|_, entry| {
+                let live = now.saturating_duration_since(entry.inserted) < ttl;
+                if !live {
+                    order.remove(&entry.seq);
+                }
+                live
+            });
+        }
+    }
+
+    /// Number of entries, including expired ones not yet pruned.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if the cache holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Max number of entries.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries evicted to make room for new ones.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    fn is_live(&self, key: &K) -> bool {
+        self.entries.get(key).is_some_and(|entry| {
+            self.ttl
+                .is_none_or(|ttl| self.clock.now().saturating_duration_since(entry.inserted) < ttl)
+        })
+    }
+
+    fn bump_seq(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::ManualClock, Clock};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2, None);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        // touching 1 makes 2 the least recently used
+        assert_eq!(Some(&"a"), cache.get(&1));
+
+        assert_eq!(Some((2, "b")), cache.insert(3, "c"));
+        // replacing an entry isn't an eviction
+        assert_eq!(Some((3, "c")), cache.insert(3, "d"));
+        assert_eq!(1, cache.evictions());
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let clock = ManualClock::default();
+        let ttl = Duration::from_secs(10);
+        let mut cache = LruCache::new(8, Some(ttl)).with_clock(Arc::new(clock.clone()));
+        cache.insert(1, ());
+
+        clock.advance(ttl);
+        assert!(!cache.contains_key(&1));
+        cache.prune(clock.now());
+        assert!(cache.is_empty());
+        assert_eq!(0, cache.evictions());
+    }
+}
@@ -0,0 +1,4324 @@
+use crate::{
+    local_versions, negotiate_version, rng::SourceRng, supported_versions, AddressVote, AttemptId,
+    Candidate, CandidateKind, Candidates, DecodeLimits, Echo, EchoAfter, Enr, Extensions,
+    FallbackStrategy, FileStateStore, HolePunchError, InitiatorError, KeepaliveScheduler,
+    MessageNonce, NatHolePunch, NatStatus, NodeAddress, NodeId, Notification, PersistedHole,
+    PersistedState, ProtocolVersion, PunchReport, PunchToward, ReasonCode, RecentNonces,
+    RelayAbort, RelayError, RelayExtras, RelayInit, RelayMsg, Runtime, SessionKey, SharedClock,
+    SharedRng, StateStore, TargetError, ValidationPolicy, WireConfig, MAX_RECENT_NONCES,
+    MIN_HOLE_PUNCH_PROTOCOL_VERSION,
+};
+use async_trait::async_trait;
+use futures::{
+    channel::{mpsc, oneshot},
+    future::{self, Either},
+    StreamExt,
+};
+use rand::Rng;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::Instrument;
+
+mod attempt;
+mod audit;
+mod backoff;
+mod budget;
+mod checklist;
+mod config;
+mod consent;
+mod dispatch;
+mod echo;
+mod endpoint;
+mod event;
+mod fallback;
+mod filter;
+mod inbound;
+mod load;
+mod lookup;
+mod lru;
+mod pacing;
+mod payload;
+mod pending;
+mod punch;
+mod punch_policy;
+mod punched;
+mod race;
+mod rate_limit;
+mod reachability;
+mod rebind;
+mod relay_book;
+mod relay_queue;
+mod relay_score;
+mod replay;
+mod sink;
+mod stats;
+mod timings;
+
+pub use attempt::{
+    Attempt, AttemptFuture, AttemptOutcome, AttemptRegistry, AttemptTimeout, FailureReason,
+    FairScheduling, PunchedAttempt, QueuedAttempt, DEFAULT_ATTEMPT_CAPACITY,
+    DEFAULT_ATTEMPT_TIMEOUT, DEFAULT_PUNCH_PARALLELISM, DEFAULT_PUNCH_PARALLELISM_PER_TARGET,
+};
+pub use audit::{AttemptRecord, AuditLog, RelayTry, DEFAULT_AUDIT_LOG_SIZE};
+pub use backoff::{Backoff, BackoffLedger, DEFAULT_MAX_FAILURE_COOLDOWN};
+pub use budget::{DeadlineBudget, SplitPolicy};
+pub use checklist::{CandidatePair, Checklist};
+pub use config::{
+    HolePunchConfig, DEFAULT_COMMAND_BUFFER, DEFAULT_SERVED_ATTEMPT_TTL, DEFAULT_TICK_INTERVAL,
+};
+pub use consent::{AlwaysAllow, RelayConsent};
+pub use dispatch::{DispatchError, NotificationDispatcher};
+pub use echo::{
+    EchoProbe, EchoProbes, ScheduledEcho, ScheduledEchoes, DEFAULT_MAX_ECHO_DELAY,
+    DEFAULT_MAX_SCHEDULED_ECHOES, ECHO_GRACE,
+};
+pub use endpoint::{LocalEndpoint, DEFAULT_FIREWALL_EVIDENCE};
+pub use event::HolePunchEvent;
+pub use fallback::RelayFallback;
+pub use filter::{InboundFilterFn, InboundFilters, Source, Verdict};
+pub use inbound::InboundKind;
+pub use load::LoadMonitor;
+pub use lookup::{SessionMap, TargetLookup};
+pub use lru::LruCache;
+pub use pacing::{BurstPacing, Pacer, DEFAULT_PACING_BURST_CAP, DEFAULT_PACING_RATE};
+pub use payload::{KeepalivePayload, KeepalivePayloadFn, PunchPayload, PunchPayloadFn};
+pub use pending::{PendingNonceTable, DEFAULT_PENDING_NONCE_TTL};
+pub use punch::{
+    BackToBack, FinishingPunch, FinishingPunches, PunchPattern, ScheduledPunch, ScheduledPunches,
+    DEFAULT_PUNCH_SPACING,
+};
+pub use punch_policy::{
+    AlwaysPunch, DistanceHeuristic, PunchContext, PunchPolicy, DEFAULT_PUNCH_MAX_DISTANCE,
+};
+pub use punched::{PunchedPeer, PunchedPeers};
+pub use race::PathRaces;
+pub use rate_limit::{
+    InitiationThrottle, RateLimiter, DEFAULT_FAILURE_COOLDOWN, DEFAULT_INITIATION_RATE,
+    DEFAULT_RATE_LIMIT_PER_INITIATOR, DEFAULT_RATE_LIMIT_PER_RELAY, DEFAULT_RATE_LIMIT_TOTAL,
+    DEFAULT_RATE_LIMIT_WINDOW,
+};
+pub use reachability::{
+    Reachability, ReachabilityCache, ReachabilityEntry, DEFAULT_REACHABILITY_CAPACITY,
+};
+pub use rebind::{RebindDetector, DEFAULT_REBIND_THRESHOLD, DEFAULT_REBIND_WINDOW};
+pub use relay_book::{
+    RelayCandidateBook, DEFAULT_RELAY_BOOK_CAPACITY, DEFAULT_RELAY_CANDIDATES_PER_TARGET,
+};
+pub use relay_queue::{
+    DropPolicy, RelayConcurrency, RelayQueue, RelayQueueMetrics, DEFAULT_RELAY_CONCURRENCY,
+    DEFAULT_RELAY_CONCURRENCY_PER_INITIATOR, DEFAULT_RELAY_QUEUE_DEPTH, DEFAULT_RELAY_RATE,
+};
+pub use relay_score::{
+    RelayScore, RelayScores, SubnetDiversity, DEFAULT_RELAY_SUBNET_V4, DEFAULT_RELAY_SUBNET_V6,
+};
+pub use replay::{ReplayCache, DEFAULT_REPLAY_CACHE_CAPACITY, DEFAULT_REPLAY_TTL};
+pub use sink::NotificationSink;
+
+use endpoint::LocalEndpoints;
+pub use stats::{CacheEvictions, FailureCounts, Stats};
+pub use timings::{LatencyHistogram, PunchTimings, LATENCY_BUCKETS_MS};
+
+use stats::StatsRecorder;
+
+/// Input to the [`HolePunchService`] from discv5.
+#[derive(Debug)]
+pub enum ServiceCommand<I> {
+    /// A request to `target` timed out, see [`NatHolePunch::on_request_time_out`].
+    RequestTimedOut {
+        relay: I,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        target: I,
+    },
+    /// A request to `target` timed out. The relays are tried in order within the time budget of
+    /// the attempt, and its outcome is sent on `outcome`, see [`HolePunchService::initiate`].
+    Initiate {
+        relays: Vec<I>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        target: I,
+        outcome: oneshot::Sender<AttemptOutcome>,
+    },
+    /// Several requests to `target` timed out, see [`HolePunchService::initiate_for_nonces`].
+    InitiateForNonces {
+        relays: Vec<I>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        more_nonces: Vec<MessageNonce>,
+        target: I,
+        outcome: oneshot::Sender<AttemptOutcome>,
+    },
+    /// The application wants a hole to `target` punched through the relay `via`, see
+    /// [`NatHolePunch::initiate_punch`]. The outcome of the attempt is sent on `outcome`.
+    InitiatePunch {
+        target: NodeId,
+        via: I,
+        outcome: oneshot::Sender<AttemptOutcome>,
+    },
+    /// A decrypted notification was received, see [`NatHolePunch::on_notification`].
+    Notification(Vec<u8>),
+    /// A decrypted notification was received over the session `src`, which the inbound filters
+    /// get to see, see [`HolePunchService::add_inbound_filter`].
+    NotificationFrom { src: I, notif: Vec<u8> },
+    /// A notification received from `src` was decoded off the service task, see
+    /// [`NotificationDispatcher`].
+    Decoded { src: Source, notif: Notification },
+    /// A WHOAREYOU wrapping the nonce of a timed out request was received from `src`, meaning the
+    /// hole punch attempt for that request succeeded.
+    WhoAreYouReceived {
+        nonce: MessageNonce,
+        src: SocketAddr,
+    },
+    /// The discv5 session with the target of the attempt for `nonce` failed to be established
+    /// after the WHOAREYOU from the target arrived, see [`FailureReason::SessionFailed`].
+    SessionFailed { nonce: MessageNonce },
+    /// The discv5 session with `target` is established, see
+    /// [`HolePunchService::confirm_established`].
+    Established { target: NodeId },
+    /// A packet was sent to the given peer.
+    PacketSent(SocketAddr),
+    /// A packet was received from the given peer.
+    PacketReceived(SocketAddr),
+    /// The peer `voter` at `voter_ip` observed the local node at `observed`. Once the vote has a
+    /// majority, the NAT status is probed at the majority address.
+    AddressObserved {
+        voter: NodeId,
+        voter_ip: IpAddr,
+        observed: SocketAddr,
+    },
+    /// Measures whether the NAT mapping of this node towards `peer` outlives `silence`, see
+    /// [`HolePunchService::probe_mapping_lifetime`].
+    ProbeMappingLifetime { peer: NodeId, silence: Duration },
+    /// The ENR of `peer` was updated to a higher sequence number, see
+    /// [`HolePunchService::handle_peer_enr_update`].
+    PeerEnrUpdated {
+        peer: NodeId,
+        old: Box<Enr>,
+        new: Box<Enr>,
+    },
+    /// The peer at the session `from` returned the nodes `contains` in a NODES response, making
+    /// it a relay candidate for them, see [`RelayCandidateBook`].
+    NodesResponse { from: I, contains: Vec<NodeId> },
+    /// Shuts the service down, see [`HolePunchService::shutdown`]. [`HolePunchService::run`] and
+    /// [`HolePunchService::run_timed`] return once it is handled.
+    Shutdown { notify_relays: bool },
+    /// Drives the timers of the service. Should be sent at least every
+    /// [`DEFAULT_TICK_INTERVAL`], unless the service is run with [`HolePunchService::run_timed`].
+    Tick,
+}
+
+/// A batteries-included implementation of [`NatHolePunch`]. Owns the registry of attempts in
+/// flight, the replay cache and rate limiter for relaying, the keep-alive scheduler for punched
+/// holes and the scores of used relays. Sends packets over a [`NotificationSink`].
+pub struct HolePunchService<S: NotificationSink> {
+    sink: S,
+    commands: mpsc::Receiver<ServiceCommand<S::SessionIndex>>,
+    attempts: AttemptRegistry<S::SessionIndex>,
+    pending_nonces: PendingNonceTable<S::SessionIndex>,
+    replay_cache: ReplayCache,
+    /// The initiator, target and attempt id of the attempts relayed by this node, by nonce.
+    relayed: LruCache<MessageNonce, (NodeId, NodeId, Option<AttemptId>)>,
+    /// The attempts of other nodes punched for as target, by initiator and nonce.
+    served_attempts: LruCache<(NodeId, MessageNonce), ()>,
+    punch_reports: bool,
+    rate_limiter: RateLimiter,
+    /// Limits the relayed notifications served per relay, as target.
+    relay_rate_limiter: RateLimiter,
+    initiation_throttle: InitiationThrottle,
+    relay_queue: RelayQueue,
+    endpoints: LocalEndpoints,
+    relay_scores: RelayScores<NodeId>,
+    relay_diversity: Option<SubnetDiversity>,
+    reachability: ReachabilityCache,
+    /// The revision of the notification protocol negotiated with each peer.
+    peer_versions: LruCache<NodeId, ProtocolVersion>,
+    address_vote: AddressVote,
+    path_races: PathRaces,
+    punch_pattern: PunchPattern,
+    punch_payload: PunchPayload,
+    keepalive_payload: KeepalivePayload,
+    scheduled_punches: ScheduledPunches,
+    back_to_back: Option<BackToBack>,
+    finishing_punches: FinishingPunches,
+    /// The attempts initiated by this node whose target was punched, by target, until the session
+    /// with the target is established.
+    punched_attempts: LruCache<NodeId, PunchedAttempt>,
+    /// The relay of the last attempt initiated by this node that punched each target.
+    last_relays: LruCache<NodeId, NodeId>,
+    /// The socket the WHOAREYOU of each punched target arrived from, until the next handshake
+    /// with it, if [`HolePunchConfig::socket_hints`] is set.
+    socket_hints: Option<LruCache<NodeId, SocketAddr>>,
+    /// The primary nonce of the attempts initiated for several timed out requests, by the nonces
+    /// of the later requests, see [`RecentNonces`].
+    nonce_aliases: LruCache<MessageNonce, MessageNonce>,
+    relay_book: RelayCandidateBook<S::SessionIndex>,
+    repunch_on_enr_update: bool,
+    firewall_evidence: u32,
+    /// Spaces the packets of bursts, none if `None`.
+    pacer: Option<Pacer>,
+    stats: StatsRecorder,
+    outcomes: HashMap<MessageNonce, oneshot::Sender<AttemptOutcome>>,
+    rebind_detector: RebindDetector,
+    subscribers: Vec<mpsc::UnboundedSender<HolePunchEvent>>,
+    wire: WireConfig,
+    decode_limits: DecodeLimits,
+    validation: ValidationPolicy,
+    max_packet_size: usize,
+    load_monitor: Option<Arc<dyn LoadMonitor>>,
+    local_candidates: Vec<Candidate>,
+    inbound_filters: InboundFilters,
+    /// Finds the sessions with the targets of relayed attempts, the sink's sessions if `None`.
+    target_lookup: Option<Box<dyn TargetLookup<SessionIndex = S::SessionIndex>>>,
+    relay_consent: Box<dyn RelayConsent>,
+    /// Tried for targets without a session, none if `None`.
+    relay_fallback: Option<Box<dyn RelayFallback>>,
+    punch_policy: Arc<dyn PunchPolicy>,
+    shut_down: bool,
+    audit_log: AuditLog,
+    /// The echoes owed to peers probing their NAT mappings.
+    scheduled_echoes: ScheduledEchoes,
+    /// The probes of the NAT mappings of this node waiting for their echoes.
+    echo_probes: EchoProbes,
+    clock: SharedClock,
+    rng: SharedRng,
+}
+
+impl<S: NotificationSink> HolePunchService<S> {
+    /// Creates a new service and the sender half of its command channel.
+    pub fn new(
+        sink: S,
+        config: HolePunchConfig,
+    ) -> (Self, mpsc::Sender<ServiceCommand<S::SessionIndex>>) {
+        let (tx, rx) = mpsc::channel(config.command_buffer);
+        let clock = config.clock;
+        let rng = config.rng;
+        let endpoints = LocalEndpoints::new(&config.local_sockets, |socket| {
+            let mut keepalive = KeepaliveScheduler::new(config.hole_lifetime)
+                .with_policy(config.keepalive_policy.clone())
+                .with_clock(clock.clone());
+            keepalive.set_batch_window(config.keepalive_batch_window);
+            for (dst, lifetime) in config.hole_lifetime_overrides.iter() {
+                keepalive.set_lifetime(*dst, *lifetime);
+            }
+            LocalEndpoint {
+                socket,
+                keepalive,
+                punched_peers: PunchedPeers::default().with_clock(clock.clone()),
+                nat_status: NatStatus::default(),
+                firewall_evidence: 0,
+            }
+        });
+        let service = HolePunchService {
+            sink,
+            commands: rx,
+            attempts: AttemptRegistry::with_capacity(
+                DeadlineBudget::new(config.attempt_timeout, config.attempt_budget_split),
+                config.attempt_capacity,
+            )
+            .with_scheduling(
+                config
+                    .attempt_scheduling
+                    .unwrap_or(FairScheduling::unlimited()),
+            )
+            .with_clock(clock.clone()),
+            pending_nonces: PendingNonceTable::new(config.pending_nonce_ttl)
+                .with_clock(clock.clone()),
+            replay_cache: ReplayCache::with_capacity(
+                config.replay_ttl,
+                config.replay_cache_capacity,
+            )
+            .with_clock(clock.clone()),
+            relayed: LruCache::new(
+                config.replay_cache_capacity,
+                Some(config.replay_ttl.max(config.attempt_timeout)),
+            )
+            .with_clock(clock.clone()),
+            served_attempts: LruCache::new(
+                config.replay_cache_capacity,
+                Some(config.served_attempt_ttl),
+            )
+            .with_clock(clock.clone()),
+            punch_reports: config.punch_reports,
+            rate_limiter: RateLimiter::new(
+                config.rate_limit_per_initiator,
+                config.rate_limit_total,
+                config.rate_limit_window,
+            )
+            .with_clock(clock.clone()),
+            // relays are only limited individually, the total is limited by `rate_limiter`
+            relay_rate_limiter: RateLimiter::new(
+                config.rate_limit_per_relay,
+                u32::MAX,
+                config.rate_limit_window,
+            )
+            .with_clock(clock.clone()),
+            initiation_throttle: InitiationThrottle::new(
+                config.initiation_rate,
+                BackoffLedger::new(config.failure_cooldown, config.max_failure_cooldown),
+            )
+            .with_clock(clock.clone()),
+            relay_queue: {
+                let queue = RelayQueue::new(
+                    config.relay_queue_depth,
+                    config.relay_queue_drop_policy,
+                    config.relay_rate,
+                )
+                .with_clock(clock.clone());
+                match config.relay_concurrency {
+                    Some(concurrency) => {
+                        queue.with_concurrency(concurrency, config.attempt_timeout)
+                    }
+                    None => queue,
+                }
+            },
+            endpoints,
+            relay_scores: RelayScores::default(),
+            relay_diversity: config.relay_diversity,
+            reachability: ReachabilityCache::with_capacity(config.reachability_capacity),
+            peer_versions: LruCache::new(config.reachability_capacity, None)
+                .with_clock(clock.clone()),
+            address_vote: AddressVote::default()
+                .with_clock(clock.clone())
+                .with_rng(rng.clone()),
+            path_races: PathRaces::new(config.attempt_timeout).with_clock(clock.clone()),
+            punch_pattern: config.punch_pattern,
+            punch_payload: config.punch_payload,
+            keepalive_payload: config.keepalive_payload,
+            scheduled_punches: ScheduledPunches::default(),
+            back_to_back: config.back_to_back,
+            finishing_punches: FinishingPunches::default(),
+            punched_attempts: LruCache::new(config.attempt_capacity, Some(config.attempt_timeout))
+                .with_clock(clock.clone()),
+            last_relays: LruCache::new(config.reachability_capacity, None)
+                .with_clock(clock.clone()),
+            socket_hints: config.socket_hints.then(|| {
+                LruCache::new(config.reachability_capacity, Some(config.hole_lifetime))
+                    .with_clock(clock.clone())
+            }),
+            nonce_aliases: LruCache::new(
+                config.attempt_capacity.saturating_mul(MAX_RECENT_NONCES),
+                Some(config.pending_nonce_ttl),
+            )
+            .with_clock(clock.clone()),
+            relay_book: RelayCandidateBook::new(
+                config.relay_book_capacity,
+                config.relay_candidates_per_target,
+            )
+            .with_clock(clock.clone()),
+            repunch_on_enr_update: config.repunch_on_enr_update,
+            firewall_evidence: config.firewall_evidence,
+            pacer: config
+                .burst_pacing
+                .map(|pacing| Pacer::new(pacing).with_clock(clock.clone())),
+            stats: StatsRecorder::default(),
+            outcomes: HashMap::new(),
+            rebind_detector: RebindDetector::new(config.rebind_window, config.rebind_failures),
+            subscribers: Vec::new(),
+            wire: config.wire,
+            decode_limits: config.decode_limits,
+            validation: config.validation,
+            max_packet_size: config.max_packet_size,
+            load_monitor: config.load_monitor,
+            local_candidates: config.local_candidates,
+            inbound_filters: InboundFilters::default(),
+            target_lookup: None,
+            relay_consent: Box::new(AlwaysAllow),
+            relay_fallback: None,
+            punch_policy: config.punch_policy,
+            shut_down: false,
+            audit_log: AuditLog::new(config.audit_log_size),
+            scheduled_echoes: ScheduledEchoes::new(
+                config.max_scheduled_echoes,
+                config.max_echo_delay,
+            ),
+            echo_probes: EchoProbes::default(),
+            clock,
+            rng,
+        };
+        (service, tx)
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+
+    pub fn sink_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    pub fn attempts(&self) -> &AttemptRegistry<S::SessionIndex> {
+        &self.attempts
+    }
+
+    /// The initiators this node punched holes for as target, by reflexive socket, from its first
+    /// local socket. See [`Self::endpoints`] for nodes binding several.
+    pub fn punched_peers(&self) -> &PunchedPeers {
+        self.endpoints.first().punched_peers()
+    }
+
+    /// Triages a datagram read from the discv5 socket at `src`, so that read loops hand only
+    /// discv5 packets on to discv5.
+    pub fn classify_inbound(&self, packet: &[u8], src: SocketAddr) -> InboundKind {
+        let peer = self
+            .endpoints
+            .for_remote(&src)
+            .punched_peers()
+            .get(&src)
+            .map(|punched| punched.initiator);
+        if inbound::is_keep_hole_punched_packet(packet, src, &self.keepalive_payload) {
+            InboundKind::Keepalive(peer)
+        } else if inbound::is_punch_probe(packet, &self.punch_payload) {
+            InboundKind::PunchProbe(peer)
+        } else {
+            InboundKind::Discv5(peer)
+        }
+    }
+
+    /// A dispatcher decoding notifications under the limits and wire format of this service and
+    /// sending them to it over `commands`, the sender returned by [`Self::new`].
+    pub fn dispatcher(
+        &self,
+        commands: mpsc::Sender<ServiceCommand<S::SessionIndex>>,
+    ) -> NotificationDispatcher<S::SessionIndex> {
+        NotificationDispatcher::new(commands, self.decode_limits, self.wire)
+    }
+
+    /// The hole state of each local socket, see [`HolePunchConfig::local_sockets`].
+    pub fn endpoints(&self) -> impl Iterator<Item = &LocalEndpoint> {
+        self.endpoints.iter()
+    }
+
+    /// The hole state of the local socket holes to `remote` are punched from.
+    pub fn endpoint(&self, remote: &SocketAddr) -> &LocalEndpoint {
+        self.endpoints.for_remote(remote)
+    }
+
+    /// Returns a stream of the events of the service.
+    pub fn subscribe(&mut self) -> mpsc::UnboundedReceiver<HolePunchEvent> {
+        let (tx, rx) = mpsc::unbounded();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Sends an event to all subscribers, forgetting the ones that lost interest.
+    fn emit(&mut self, event: HolePunchEvent) {
+        self.subscribers
+            .retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+    }
+
+    /// Appends a filter to the chain evaluated on each inbound notification before it's handled.
+    /// The first filter not allowing a notification decides its fate, see [`Verdict`].
+    pub fn add_inbound_filter(
+        &mut self,
+        filter: impl Fn(&Notification, &Source) -> Verdict + Send + Sync + 'static,
+    ) {
+        self.inbound_filters.push(Arc::new(filter));
+    }
+
+    /// Looks up the sessions with the targets of attempts this node relays in `lookup` instead of
+    /// asking the sink, see [`NotificationSink::session`].
+    pub fn set_target_lookup(
+        &mut self,
+        lookup: impl TargetLookup<SessionIndex = S::SessionIndex> + 'static,
+    ) {
+        self.target_lookup = Some(Box::new(lookup));
+    }
+
+    /// Asks `consent` before relaying each attempt, instead of relaying all the rate limits and
+    /// load allow.
+    pub fn set_relay_consent(&mut self, consent: impl RelayConsent + 'static) {
+        self.relay_consent = Box::new(consent);
+    }
+
+    /// Tries `fallback` for the targets of relayed attempts this node has no session with,
+    /// instead of only aborting them.
+    pub fn set_relay_fallback(&mut self, fallback: impl RelayFallback + 'static) {
+        self.relay_fallback = Some(Box::new(fallback));
+    }
+
+    /// Matches the nonce of a received WHOAREYOU to the timed out request that this node initiated
+    /// a hole punch attempt for. Returns the session with the target the request was sent over.
+    pub fn match_whoareyou(&self, nonce: &MessageNonce) -> Option<&S::SessionIndex> {
+        self.pending_nonces.get(nonce)
+    }
+
+    /// The holes punched from the first local socket. See [`Self::endpoints`] for nodes binding
+    /// several.
+    pub fn keepalive(&self) -> &KeepaliveScheduler {
+        self.endpoints.first().keepalive()
+    }
+
+    /// Overrides the lifetime of the hole to `dst`, for example as learned from measuring the
+    /// path at runtime. See [`KeepaliveScheduler::set_lifetime`].
+    pub fn set_hole_lifetime(&mut self, dst: SocketAddr, lifetime: Duration) -> Option<Duration> {
+        self.endpoints
+            .for_remote_mut(&dst)
+            .keepalive
+            .set_lifetime(dst, lifetime)
+    }
+
+    /// The scores of relays, by node id.
+    pub fn relay_scores(&self) -> &RelayScores<NodeId> {
+        &self.relay_scores
+    }
+
+    /// Selects up to `k` of the sessions with relays to fan an attempt out to, best scoring
+    /// first and spread over subnets as configured, see [`HolePunchConfig::relay_diversity`].
+    pub fn select_relays(
+        &self,
+        candidates: impl IntoIterator<Item = S::SessionIndex>,
+        k: usize,
+    ) -> Vec<S::SessionIndex> {
+        self.relay_scores
+            .select_diverse(candidates, k, self.relay_diversity.as_ref(), |session| {
+                let ip = session.socket_addr().map(|socket| socket.ip());
+                (self.sink.node_id(session), ip)
+            })
+    }
+
+    /// The targets attempts are held off from after failing, see [`BackoffLedger`].
+    pub fn backoff(&self) -> &BackoffLedger {
+        self.initiation_throttle.backoff()
+    }
+
+    /// Forgets the failed attempts to `target`, so that attempts to it aren't held off anymore,
+    /// for example when its ENR was updated. Returns true if attempts to it were held off.
+    pub fn clear_backoff(&mut self, target: &NodeId) -> bool {
+        self.initiation_throttle.backoff_mut().clear(target)
+    }
+
+    /// The peers known to have passed each target to this node, tried as relays after those
+    /// given when initiating an attempt.
+    pub fn relay_book(&self) -> &RelayCandidateBook<S::SessionIndex> {
+        &self.relay_book
+    }
+
+    /// The relay candidates, for recording NODES responses directly or forgetting relays whose
+    /// session dropped.
+    pub fn relay_book_mut(&mut self) -> &mut RelayCandidateBook<S::SessionIndex> {
+        &mut self.relay_book
+    }
+
+    pub fn reachability(&self) -> &ReachabilityCache {
+        &self.reachability
+    }
+
+    /// The reachability cache, for sharing it with other hole punching sources, for example
+    /// libp2p's DCUtR, see the `libp2p` feature.
+    pub fn reachability_mut(&mut self) -> &mut ReachabilityCache {
+        &mut self.reachability
+    }
+
+    /// Records that the WHOAREYOU of the target of the attempt `nonce` arrived from `src`, the
+    /// target's reflexive socket as seen from this node, which its ENR may not advertise.
+    /// Called on each [`ServiceCommand::WhoAreYouReceived`], and for integrations learning the
+    /// source later. Returns the target, `None` if the attempt is unknown.
+    pub fn record_punch_source(&mut self, nonce: MessageNonce, src: SocketAddr) -> Option<NodeId> {
+        let nonce = self.nonce_aliases.peek(&nonce).copied().unwrap_or(nonce);
+        let target = match self.attempts.get(&nonce) {
+            Some(attempt) => self.sink.node_id(&attempt.target),
+            None => self
+                .punched_attempts
+                .iter()
+                .find(|(_, punched)| punched.nonce == nonce)
+                .map(|(target, _)| *target)?,
+        };
+        self.reachability
+            .insert_at(target, Reachability::Punched, src);
+        if let Some(hints) = self.socket_hints.as_mut() {
+            hints.insert(target, src);
+        }
+        Some(target)
+    }
+
+    /// Takes the socket `peer` was last punched at, to contact it at in the next handshake
+    /// instead of the socket of its ENR, see [`HolePunchConfig::socket_hints`].
+    pub fn take_socket_hint(&mut self, peer: &NodeId) -> Option<SocketAddr> {
+        self.socket_hints.as_mut()?.remove(peer)
+    }
+
+    /// The revision of the notification protocol negotiated with `peer`, if any yet.
+    pub fn protocol_version(&self, peer: &NodeId) -> Option<ProtocolVersion> {
+        self.peer_versions.peek(peer).copied()
+    }
+
+    pub fn address_vote(&self) -> &AddressVote {
+        &self.address_vote
+    }
+
+    /// The most recent attempts initiated by this node, oldest first, for diagnosing why a peer
+    /// can't be reached. See [`HolePunchConfig::audit_log_size`].
+    pub fn recent_attempts(&self) -> impl DoubleEndedIterator<Item = &AttemptRecord> {
+        self.audit_log.iter()
+    }
+
+    /// What is known about the first local socket being behind NAT. See [`Self::endpoints`] for
+    /// nodes binding several.
+    pub fn nat_status(&self) -> NatStatus {
+        self.endpoints.first().nat_status()
+    }
+
+    /// Sets what is known about the local node being behind NAT, for example from
+    /// [`crate::is_behind_nat`], for all local sockets.
+    pub fn set_nat_status(&mut self, nat_status: NatStatus) {
+        for endpoint in self.endpoints.iter_mut() {
+            endpoint.set_nat_status(nat_status);
+        }
+    }
+
+    /// Sets what is known about the local `socket` being behind NAT. Returns false if the service
+    /// has no such socket.
+    pub fn set_nat_status_of(&mut self, socket: &SocketAddr, nat_status: NatStatus) -> bool {
+        let Some(endpoint) = self.endpoints.get_mut(socket) else {
+            return false;
+        };
+        endpoint.set_nat_status(nat_status);
+        true
+    }
+
+    /// Returns the learned state worth keeping across restarts.
+    pub fn persisted_state(&self) -> PersistedState {
+        PersistedState {
+            nat_status: self.nat_status(),
+            relay_scores: self
+                .relay_scores
+                .iter()
+                .map(|(node_id, score)| (*node_id, *score))
+                .collect(),
+            reachability: self
+                .reachability
+                .iter()
+                .map(|(node_id, entry)| (*node_id, *entry))
+                .collect(),
+            holes: self.persisted_holes(),
+        }
+    }
+
+    fn persisted_holes(&self) -> Vec<PersistedHole> {
+        let (now, wall_now) = (self.clock.now(), SystemTime::now());
+        self.endpoints
+            .iter()
+            .flat_map(|endpoint| {
+                endpoint.keepalive.holes().filter_map(move |dst| {
+                    let last_activity = endpoint.keepalive.hole(dst)?.last_activity;
+                    let idle = now.saturating_duration_since(last_activity);
+                    Some(PersistedHole {
+                        dst: *dst,
+                        last_activity: wall_now.checked_sub(idle).unwrap_or(wall_now),
+                        initiator: endpoint.punched_peers.get(dst).map(|peer| peer.initiator),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Restores state learned before a restart. Punched holes that have been idle for their
+    /// lifetime since are assumed closed and aren't restored.
+    pub fn restore_state(&mut self, state: PersistedState) {
+        self.set_nat_status(state.nat_status);
+        for (node_id, score) in state.relay_scores {
+            self.relay_scores.insert(node_id, score);
+        }
+        for (node_id, entry) in state.reachability {
+            self.reachability.insert_entry(node_id, entry);
+        }
+        let wall_now = SystemTime::now();
+        for hole in state.holes {
+            let idle = wall_now
+                .duration_since(hole.last_activity)
+                .unwrap_or_default();
+            let endpoint = self.endpoints.for_remote_mut(&hole.dst);
+            if idle >= endpoint.keepalive.lifetime(&hole.dst) {
+                continue;
+            }
+            endpoint.keepalive.insert_idle(hole.dst, idle);
+            if let Some(initiator) = hole.initiator {
+                endpoint.punched_peers.insert(hole.dst, initiator);
+            }
+        }
+    }
+
+    /// Serializes the state worth handing over to a process replacing this one, for example
+    /// across an upgrade, including the punched holes so that the new process resumes keeping
+    /// them alive right away. Restore with [`PersistedState::deserialize`] and
+    /// [`Self::restore_state`].
+    pub fn serialize_state(&self) -> Vec<u8> {
+        FileStateStore::encode(&self.persisted_state()).into_bytes()
+    }
+
+    /// Saves the learned state to the store.
+    pub fn save_state<T: StateStore>(&self, store: &mut T) -> Result<(), T::Error> {
+        store.save(&self.persisted_state())
+    }
+
+    /// Loads state learned before a restart from the store, if any was stored.
+    pub fn load_state<T: StateStore>(&mut self, store: &mut T) -> Result<(), T::Error> {
+        if let Some(state) = store.load()? {
+            self.restore_state(state);
+        }
+        Ok(())
+    }
+
+    /// Returns the metrics of the queue of notifications waiting to be relayed.
+    pub fn relay_queue_metrics(&self) -> RelayQueueMetrics {
+        self.relay_queue.metrics()
+    }
+
+    /// Returns a snapshot of the hole punch statistics.
+    pub fn stats(&self) -> Stats {
+        let cache_evictions = CacheEvictions {
+            replay: self.replay_cache.evictions(),
+            attempts: self.attempts.evictions(),
+            reachability: self.reachability.evictions(),
+        };
+        let holes = self
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.keepalive.len())
+            .sum();
+        self.stats.snapshot(holes, cache_evictions)
+    }
+
+    /// Handles commands until all senders of the command channel are dropped.
+    pub async fn run(mut self) {
+        while let Some(command) = self.commands.next().await {
+            if let Err(e) = self.handle_command(command).await {
+                tracing::debug!("hole punch service failed handling command, {}", e);
+            }
+            if self.shut_down {
+                return;
+            }
+        }
+    }
+
+    /// Runs the service, firing the timers of the service itself on the configured clock so no
+    /// [`ServiceCommand::Tick`] needs to be sent.
+    pub async fn run_timed(mut self) {
+        let clock = self.clock.clone();
+        let mut sleep = None;
+        loop {
+            let timeout = self
+                .next_timeout()
+                .map_or(DEFAULT_TICK_INTERVAL, |timeout| {
+                    timeout.min(DEFAULT_TICK_INTERVAL)
+                });
+            let deadline = clock.now() + timeout;
+            // keep sleeping towards the current deadline unless a timer fires earlier
+            let (current, timer) = match sleep.take() {
+                Some((current, timer)) if current <= deadline => (current, timer),
+                _ => (deadline, clock.sleep_until(deadline)),
+            };
+            let command = match future::select(self.commands.next(), timer).await {
+                Either::Left((Some(command), timer)) => {
+                    sleep = Some((current, timer));
+                    command
+                }
+                Either::Left((None, _)) => return,
+                Either::Right(_) => ServiceCommand::Tick,
+            };
+            if let Err(e) = self.handle_command(command).await {
+                tracing::debug!("hole punch service failed handling command, {}", e);
+            }
+            if self.shut_down {
+                return;
+            }
+        }
+    }
+
+    /// Shuts the service down. Attempts in flight and queued are cancelled, resolving with
+    /// [`AttemptOutcome::Cancelled`], and with `notify_relays` the relays of the attempts in
+    /// flight get a [`PunchReport`] of not punched if punch reports are enabled. Back-to-back
+    /// punches are cut short, repeated WHOAREYOUs dropped and holes no longer kept alive. The
+    /// event channels are closed once the final events are sent.
+    pub async fn shutdown(&mut self, notify_relays: bool) -> Result<(), HolePunchError<S::Error>> {
+        let mut res = Ok(());
+        for punch in self.finishing_punches.drain() {
+            self.finish_punch(punch.nonce, punch.attempt_id, punch.dst, punch.timings);
+        }
+        for (nonce, attempt) in self.attempts.drain() {
+            if notify_relays {
+                if let Err(e) = self
+                    .report_punch(&attempt.relay, nonce, attempt.attempt_id, false)
+                    .await
+                {
+                    res = Err(e);
+                }
+            }
+            self.resolve(nonce, attempt.attempt_id, AttemptOutcome::Cancelled);
+        }
+        for queued in self.attempts.drain_queued() {
+            self.resolve(queued.nonce, queued.attempt_id, AttemptOutcome::Cancelled);
+        }
+        for (_, tx) in self.outcomes.drain() {
+            _ = tx.send(AttemptOutcome::Cancelled);
+        }
+        self.scheduled_punches = ScheduledPunches::default();
+        self.scheduled_echoes.clear();
+        self.echo_probes = EchoProbes::default();
+        self.punched_attempts.retain(|_, _| false);
+        for endpoint in self.endpoints.iter_mut() {
+            let holes = endpoint.keepalive.holes().copied().collect::<Vec<_>>();
+            for dst in holes {
+                endpoint.keepalive.remove(&dst);
+            }
+        }
+        // receivers get the events sent so far, then the end of the stream
+        for tx in self.subscribers.drain(..) {
+            tx.close_channel();
+        }
+        self.shut_down = true;
+        res
+    }
+
+    /// Returns true if the service was shut down, see [`Self::shutdown`].
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// Spawns [`Self::run_timed`] on the runtime `R`. The service should be configured with a
+    /// clock sleeping on the runtime, like [`crate::TokioClock`] on tokio.
+    pub fn spawn<R: Runtime>(self)
+    where
+        S: 'static,
+    {
+        R::spawn(self.run_timed())
+    }
+
+    /// Handles a single command.
+    pub async fn handle_command(
+        &mut self,
+        command: ServiceCommand<S::SessionIndex>,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let res = match command {
+            ServiceCommand::RequestTimedOut {
+                relay,
+                local_enr,
+                nonce,
+                target,
+            } => {
+                // lookups time out to NATed peers all the time, only requests over holes count
+                if self.over_hole(&target) {
+                    self.rebind_detector.record_failure(self.clock.now());
+                }
+                self.on_request_time_out(relay, local_enr, nonce, target)
+                    .await
+            }
+            ServiceCommand::Initiate {
+                relays,
+                local_enr,
+                nonce,
+                target,
+                outcome,
+            } => {
+                self.initiate_timed_out(relays, local_enr, nonce, Vec::new(), target, Some(outcome))
+                    .await
+            }
+            ServiceCommand::InitiateForNonces {
+                relays,
+                local_enr,
+                nonce,
+                more_nonces,
+                target,
+                outcome,
+            } => {
+                self.initiate_timed_out(
+                    relays,
+                    local_enr,
+                    nonce,
+                    more_nonces,
+                    target,
+                    Some(outcome),
+                )
+                .await
+            }
+            ServiceCommand::InitiatePunch {
+                target,
+                via,
+                outcome,
+            } => self
+                .initiate_punch_with(target, via, Some(outcome))
+                .await
+                .map(|_| ()),
+            ServiceCommand::Notification(notif) => self.on_notification(&notif).await,
+            ServiceCommand::NotificationFrom { src, notif } => {
+                self.on_notification_from(&notif, &Source::from_session(&src), |_| ())
+                    .await
+            }
+            ServiceCommand::Decoded { src, notif } => self.on_decoded(notif, &src).await,
+            ServiceCommand::WhoAreYouReceived { nonce, src } => self.on_whoareyou(nonce, src).await,
+            ServiceCommand::PacketSent(dst) => {
+                self.endpoints
+                    .for_remote_mut(&dst)
+                    .keepalive
+                    .touch_on_send(&dst);
+                Ok(())
+            }
+            ServiceCommand::PacketReceived(src) => {
+                self.endpoints
+                    .for_remote_mut(&src)
+                    .keepalive
+                    .touch_on_recv(&src);
+                // the initiator answered over one of the raced paths, drop the others
+                for path in self.path_races.resolve(&src).unwrap_or_default() {
+                    let endpoint = self.endpoints.for_remote_mut(&path);
+                    endpoint.keepalive.remove(&path);
+                    endpoint.punched_peers.remove(&path);
+                }
+                Ok(())
+            }
+            ServiceCommand::AddressObserved {
+                voter,
+                voter_ip,
+                observed,
+            } => {
+                self.on_address_observed(voter, voter_ip, observed);
+                Ok(())
+            }
+            ServiceCommand::SessionFailed { nonce } => {
+                self.on_session_failed(nonce);
+                Ok(())
+            }
+            ServiceCommand::Established { target } => {
+                self.confirm_established(target);
+                Ok(())
+            }
+            ServiceCommand::ProbeMappingLifetime { peer, silence } => {
+                self.probe_mapping_lifetime(peer, silence).await.map(|_| ())
+            }
+            ServiceCommand::PeerEnrUpdated { peer, old, new } => self
+                .handle_peer_enr_update(peer, &old, &new)
+                .await
+                .map(|_| ()),
+            ServiceCommand::NodesResponse { from, contains } => {
+                self.relay_book.record_nodes_response(from, &contains);
+                Ok(())
+            }
+            ServiceCommand::Shutdown { notify_relays } => self.shutdown(notify_relays).await,
+            ServiceCommand::Tick => self.on_tick(self.clock.now()).await,
+        };
+        if let Err(ref e) = res {
+            self.stats.punches_failed.record(e);
+        }
+        res
+    }
+
+    /// Initiates a hole punch attempt for the timed out request to `target`. The relays are tried
+    /// in order, each given a share of the attempt's time budget. The returned future resolves
+    /// with [`AttemptOutcome::Failed`] when the budget is spent.
+    pub async fn initiate(
+        &mut self,
+        relays: Vec<S::SessionIndex>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        target: S::SessionIndex,
+    ) -> Result<AttemptFuture, HolePunchError<S::Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.initiate_timed_out(relays, local_enr, nonce, Vec::new(), target, Some(tx))
+            .await?;
+        Ok(rx)
+    }
+
+    /// Like [`Self::initiate`], for the request with `nonce` and the requests to `target` that
+    /// timed out after it with `more_nonces`, oldest first. The WHOAREYOU of the target may carry
+    /// any of them, see [`RecentNonces`], the most recent [`MAX_RECENT_NONCES`] are sent.
+    pub async fn initiate_for_nonces(
+        &mut self,
+        relays: Vec<S::SessionIndex>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        more_nonces: Vec<MessageNonce>,
+        target: S::SessionIndex,
+    ) -> Result<AttemptFuture, HolePunchError<S::Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.initiate_timed_out(relays, local_enr, nonce, more_nonces, target, Some(tx))
+            .await?;
+        Ok(rx)
+    }
+
+    /// Initiates a hole punch attempt to `target` through `via` that isn't triggered by a timed out
+    /// request, under a fresh nonce.
+    async fn initiate_punch_with(
+        &mut self,
+        target: NodeId,
+        via: S::SessionIndex,
+        outcome: Option<oneshot::Sender<AttemptOutcome>>,
+    ) -> Result<MessageNonce, HolePunchError<S::Error>> {
+        let Some(target_session) = self.sink.session(&target) else {
+            return Err(HolePunchError::UnknownTarget(target));
+        };
+        let nonce = SourceRng(&*self.rng).gen::<MessageNonce>();
+        let local_enr = self.sink.local_enr();
+        self.initiate_with(
+            vec![via],
+            local_enr,
+            nonce,
+            Vec::new(),
+            target_session,
+            outcome,
+        )
+        .await?;
+        Ok(nonce)
+    }
+
+    /// Initiates an attempt for timed out requests to `target`, unless the [`PunchPolicy`] deems
+    /// the target not worth a hole.
+    async fn initiate_timed_out(
+        &mut self,
+        relays: Vec<S::SessionIndex>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        more_nonces: Vec<MessageNonce>,
+        target: S::SessionIndex,
+        outcome: Option<oneshot::Sender<AttemptOutcome>>,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let target_id = self.sink.node_id(&target);
+        let context = PunchContext {
+            distance: PunchContext::log2_distance(&local_enr.node_id(), &target_id),
+            table_candidate: self.sink.is_table_candidate(&target_id),
+            relay_candidates: self.relay_book.candidates_for(&target_id).len(),
+            last_reached: self
+                .reachability
+                .get(&target_id)
+                .map(|entry| entry.reachability),
+        };
+        if !self.punch_policy.should_punch(&target_id, &context) {
+            return Err(InitiatorError::Declined(target_id).into());
+        }
+        self.initiate_with(relays, local_enr, nonce, more_nonces, target, outcome)
+            .await
+    }
+
+    async fn initiate_with(
+        &mut self,
+        relays: Vec<S::SessionIndex>,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        mut more_nonces: Vec<MessageNonce>,
+        target: S::SessionIndex,
+        outcome: Option<oneshot::Sender<AttemptOutcome>>,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let target_id = self.sink.node_id(&target);
+        // fall back to the peers that passed the target to this node
+        let mut relays = relays;
+        for candidate in self.relay_book.candidates_for(&target_id) {
+            if !relays.contains(&candidate) && candidate != target {
+                relays.push(candidate);
+            }
+        }
+        let Some(first_relay) = relays.first() else {
+            return Err(InitiatorError::NoRelay.into());
+        };
+        if !self.sink.supports_hole_punch(&target) {
+            return Err(InitiatorError::Unsupported(target_id).into());
+        }
+        if !self.initiation_throttle.allow(target_id) {
+            return Err(InitiatorError::LocallyThrottled.into());
+        }
+        let first_relay_id = self.sink.node_id(first_relay);
+        let relays = relays
+            .into_iter()
+            .filter(|relay| self.sink.supports_hole_punch(relay))
+            .collect::<Vec<_>>();
+        if relays.is_empty() {
+            return Err(InitiatorError::Unsupported(first_relay_id).into());
+        }
+        let version = self.session_version(&relays[0]);
+        let wire = version.map_or(self.wire, |version| version.restrict(&self.wire));
+        let attempt_id = wire
+            .attempt_ids
+            .then(|| wire.attempt_id(SourceRng(&*self.rng).gen::<AttemptId>()));
+        let candidates = (!self.local_candidates.is_empty()
+            && version.is_none_or(ProtocolVersion::supports_candidates))
+        .then(|| Candidates::new(self.local_candidates.clone()));
+        let mut extensions = version
+            .filter(|version| version.supports_extensions())
+            .map(|_| {
+                let mut extensions = Extensions::default();
+                extensions.insert(&ProtocolVersion::CURRENT);
+                extensions
+            });
+        if !version.is_none_or(ProtocolVersion::supports_extensions) {
+            more_nonces.clear();
+        }
+        more_nonces.drain(..more_nonces.len().saturating_sub(MAX_RECENT_NONCES));
+        if !more_nonces.is_empty() {
+            extensions
+                .get_or_insert_with(Extensions::default)
+                .insert(&RecentNonces(more_nonces.clone()));
+        }
+        let notif: RelayInit = RelayInit(
+            local_enr, target_id, nonce, attempt_id, candidates, extensions,
+        );
+        let notif = notif.rlp_encode_with(&wire);
+        self.check_packet_size(&notif)
+            .map_err(InitiatorError::PacketTooLarge)?;
+        if let Some(outcome) = outcome {
+            self.outcomes.insert(nonce, outcome);
+        }
+        for more_nonce in more_nonces {
+            self.pending_nonces.insert(more_nonce, target.clone());
+            self.nonce_aliases.insert(more_nonce, nonce);
+        }
+        self.audit_log.record(AttemptRecord {
+            nonce,
+            attempt_id,
+            target: target_id,
+            initiated: self.clock.now(),
+            relays: Vec::new(),
+            punched: None,
+            established: None,
+            resolved: None,
+        });
+        let queued = QueuedAttempt {
+            nonce,
+            relays,
+            target,
+            notif,
+            attempt_id,
+        };
+        if let Some(dropped) = self.attempts.enqueue(target_id, queued) {
+            self.stats.punches_failed.timed_out += 1;
+            let outcome = AttemptOutcome::Failed(FailureReason::Evicted);
+            self.resolve(dropped.nonce, dropped.attempt_id, outcome);
+        }
+        self.send_queued_attempts().await
+    }
+
+    /// Fails with the length of the encoded notification if it doesn't fit in a packet of the
+    /// configured max size.
+    fn check_packet_size(&self, notif: &[u8]) -> Result<(), usize> {
+        if notif.len() > self.max_packet_size {
+            return Err(notif.len());
+        }
+        Ok(())
+    }
+
+    /// Sends the queued attempts the [`FairScheduling`] of the registry allows in flight. Without
+    /// scheduling configured, that is the attempt just queued.
+    async fn send_queued_attempts(&mut self) -> Result<(), HolePunchError<S::Error>> {
+        let mut res = Ok(());
+        while let Some((target_id, queued)) = self.attempts.dequeue() {
+            let sent = self
+                .sink
+                .send_notification(&queued.relays[0], queued.notif.clone())
+                .instrument(attempt_span(queued.attempt_id))
+                .await;
+            if let Err(e) = sent {
+                // dropping the sender tells a waiting caller the attempt failed
+                self.outcomes.remove(&queued.nonce);
+                res = Err(InitiatorError::RelayInitSendFailed(e).into());
+                continue;
+            }
+            let nonce = queued.nonce;
+            self.record_relay_try(&nonce, &queued.relays[0]);
+            self.pending_nonces.insert(nonce, queued.target.clone());
+            if let Some((evicted, attempt)) = self
+                .attempts
+                .insert_dequeued(target_id, queued)
+                .filter(|(displaced, _)| *displaced != nonce)
+            {
+                // the registry is full, give up on the oldest attempt
+                self.stats.punches_failed.timed_out += 1;
+                let outcome = AttemptOutcome::Failed(FailureReason::Evicted);
+                self.resolve(evicted, attempt.attempt_id, outcome);
+            }
+            self.stats.punches_initiated += 1;
+        }
+        res
+    }
+
+    fn record_relay_try(&mut self, nonce: &MessageNonce, relay: &S::SessionIndex) {
+        let relay = self.sink.node_id(relay);
+        let sent = self.clock.now();
+        if let Some(record) = self.audit_log.get_mut(nonce) {
+            record.relays.push(RelayTry {
+                relay,
+                sent,
+                aborted: None,
+                fallback: None,
+            });
+        }
+    }
+
+    /// The punched WHOAREYOU from the target of an attempt initiated by this node arrived. With
+    /// [`BackToBack`] configured, the punch is finished by packets to the target first.
+    async fn on_whoareyou(
+        &mut self,
+        nonce: MessageNonce,
+        src: SocketAddr,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        // the target may answer a later request than the one the attempt is for
+        let nonce = self.nonce_aliases.peek(&nonce).copied().unwrap_or(nonce);
+        if self.record_punch_source(nonce, src).is_none() {
+            return Ok(());
+        }
+        let Some(attempt) = self.attempts.remove(&nonce) else {
+            return Ok(());
+        };
+        let timings = PunchTimings {
+            initiated: attempt.started,
+            relay_sent: attempt.try_started,
+            whoareyou_received: self.clock.now(),
+            established: None,
+        };
+        self.stats.record_success(&timings);
+        self.relay_scores
+            .record_success(self.sink.node_id(&attempt.relay));
+        self.last_relays.insert(
+            self.sink.node_id(&attempt.target),
+            self.sink.node_id(&attempt.relay),
+        );
+        self.initiation_throttle
+            .backoff_mut()
+            .clear(&self.sink.node_id(&attempt.target));
+        self.endpoints.for_remote_mut(&src).keepalive.insert(src);
+        if let Some(record) = self.audit_log.get_mut(&nonce) {
+            record.punched = Some((self.clock.now(), src));
+        }
+        self.punched_attempts.insert(
+            self.sink.node_id(&attempt.target),
+            PunchedAttempt {
+                nonce,
+                attempt_id: attempt.attempt_id,
+                src,
+                timings,
+            },
+        );
+        let mut res = self
+            .report_punch(&attempt.relay, nonce, attempt.attempt_id, true)
+            .await;
+        let Some(pattern) = self.back_to_back.filter(|pattern| pattern.packets > 0) else {
+            self.finish_punch(nonce, attempt.attempt_id, src, timings);
+            return res;
+        };
+        if let Err(e) = self.send_keepalive_packet(src).await {
+            res = Err(HolePunchError::InitiatorError(e));
+        }
+        self.endpoints
+            .for_remote_mut(&src)
+            .keepalive
+            .touch_on_send(&src);
+        if pattern.packets > 1 {
+            self.finishing_punches.insert(FinishingPunch {
+                due: self.clock.now() + pattern.spacing,
+                dst: src,
+                nonce,
+                attempt_id: attempt.attempt_id,
+                remaining: pattern.packets - 1,
+                timings,
+            });
+        } else {
+            self.finish_punch(nonce, attempt.attempt_id, src, timings);
+        }
+        res
+    }
+
+    /// Reports the target of an attempt initiated by this node as punched.
+    fn finish_punch(
+        &mut self,
+        nonce: MessageNonce,
+        attempt_id: Option<AttemptId>,
+        src: SocketAddr,
+        timings: PunchTimings,
+    ) {
+        self.emit(HolePunchEvent::TargetPunched {
+            attempt_id,
+            nonce,
+            src,
+            timings,
+        });
+        self.resolve(nonce, attempt_id, AttemptOutcome::Punched { src });
+    }
+
+    /// Moves the holes to `peer` from the sockets of its `old` ENR to those of its `new` ENR of the
+    /// same family, for example after its NAT mapped it to a new reflexive socket, and forgets its
+    /// reachability. With [`HolePunchConfig::repunch_on_enr_update`], punches a fresh hole
+    /// through the last relay that punched one to the peer. Returns the nonce of that attempt.
+    pub async fn handle_peer_enr_update(
+        &mut self,
+        peer: NodeId,
+        old: &Enr,
+        new: &Enr,
+    ) -> Result<Option<MessageNonce>, HolePunchError<S::Error>> {
+        let old_addrs = enr_udp_sockets(old);
+        let new_addrs = enr_udp_sockets(new);
+        if old_addrs == new_addrs {
+            return Ok(None);
+        }
+        let mut migrated = false;
+        for stale in old_addrs.iter().filter(|addr| !new_addrs.contains(addr)) {
+            let Some(fresh) = new_addrs
+                .iter()
+                .find(|addr| addr.is_ipv4() == stale.is_ipv4() && !old_addrs.contains(addr))
+            else {
+                continue;
+            };
+            let endpoint = self.endpoints.for_remote_mut(stale);
+            if endpoint.keepalive.remove(stale) {
+                endpoint.keepalive.insert(*fresh);
+                migrated = true;
+            }
+            if endpoint
+                .punched_peers
+                .get(stale)
+                .is_some_and(|punched| punched.initiator == peer)
+            {
+                endpoint.punched_peers.remove(stale);
+                endpoint.punched_peers.insert(*fresh, peer);
+            }
+        }
+        self.reachability.remove(&peer);
+        tracing::debug!("ENR of {} updated, migrated holes: {}", peer, migrated);
+        if !self.repunch_on_enr_update {
+            return Ok(None);
+        }
+        let Some(via) = self
+            .last_relays
+            .peek(&peer)
+            .and_then(|relay| self.sink.session(relay))
+        else {
+            return Ok(None);
+        };
+        self.initiate_punch_with(peer, via, None).await.map(Some)
+    }
+
+    /// Asks `peer` to echo after `silence`, to measure whether the NAT mapping of this node towards
+    /// the peer outlives it. Emits [`HolePunchEvent::EchoReceived`] or
+    /// [`HolePunchEvent::EchoMissed`]. Nothing should be sent to the peer meanwhile, including
+    /// keep-alives, or the mapping is refreshed. Returns the nonce of the probe.
+    pub async fn probe_mapping_lifetime(
+        &mut self,
+        peer: NodeId,
+        silence: Duration,
+    ) -> Result<MessageNonce, HolePunchError<S::Error>> {
+        let Some(session) = self.sink.session(&peer) else {
+            return Err(HolePunchError::UnknownTarget(peer));
+        };
+        let understood = self
+            .session_version(&session)
+            .is_none_or(ProtocolVersion::supports_echo);
+        if !understood || !self.sink.supports_hole_punch(&session) {
+            return Err(HolePunchError::Unsupported(peer));
+        }
+        let nonce = SourceRng(&*self.rng).gen::<MessageNonce>();
+        let local_id = self.sink.local_enr().node_id();
+        let notif: EchoAfter = EchoAfter(local_id, nonce, silence, None);
+        let notif = notif.rlp_encode_with(&self.wire);
+        self.sink
+            .send_notification(&session, notif)
+            .await
+            .map_err(HolePunchError::EchoError)?;
+        let probe = EchoProbe {
+            peer,
+            delay: silence,
+            deadline: self.clock.now() + silence + ECHO_GRACE,
+        };
+        self.echo_probes.insert(nonce, probe);
+        Ok(nonce)
+    }
+
+    /// Confirms that the discv5 session with `target` is established. Completes the attempt that
+    /// punched the target, cutting short its back-to-back packets, keeps the hole alive and emits
+    /// [`HolePunchEvent::Established`]. Repeated WHOAREYOUs to `target` as initiator are dropped.
+    /// Returns false if no attempt punched the target.
+    pub fn confirm_established(&mut self, target: NodeId) -> bool {
+        self.scheduled_punches.cancel(&target);
+        let Some(punched) = self.punched_attempts.remove(&target) else {
+            return false;
+        };
+        let src = punched.src;
+        if self.finishing_punches.remove(&src).is_some() {
+            self.finish_punch(punched.nonce, punched.attempt_id, src, punched.timings);
+        }
+        let keepalive = &mut self.endpoints.for_remote_mut(&src).keepalive;
+        if !keepalive.contains(&src) {
+            keepalive.insert(src);
+        }
+        let now = self.clock.now();
+        if let Some(record) = self.audit_log.get_mut(&punched.nonce) {
+            record.established = Some(now);
+        }
+        let timings = PunchTimings {
+            established: Some(now),
+            ..punched.timings
+        };
+        self.stats.record_established(&timings);
+        self.emit(HolePunchEvent::Established {
+            attempt_id: punched.attempt_id,
+            nonce: punched.nonce,
+            target,
+            src,
+            time_to_punch: timings.time_to_punch(),
+            time_to_establish: now.saturating_duration_since(timings.initiated),
+            timings,
+        });
+        true
+    }
+
+    /// The discv5 session with the target of a punched attempt failed. The target is marked
+    /// unreachable if the nonce of the attempt is still remembered.
+    fn on_session_failed(&mut self, nonce: MessageNonce) {
+        self.stats.punches_failed.session_failed += 1;
+        self.punched_attempts
+            .retain(|_, punched| punched.nonce != nonce);
+        if let Some(target) = self.pending_nonces.get(&nonce) {
+            self.reachability
+                .insert(self.sink.node_id(target), Reachability::Unreachable);
+        }
+        self.emit(HolePunchEvent::Failed {
+            attempt_id: None,
+            nonce,
+            reason: FailureReason::SessionFailed,
+        });
+    }
+
+    fn on_address_observed(&mut self, voter: NodeId, voter_ip: IpAddr, observed: SocketAddr) {
+        let majority = self.address_vote.majority();
+        self.address_vote.vote(voter, voter_ip, observed);
+        let new_majority = self.address_vote.majority();
+        if new_majority != majority {
+            if let Some(probe) = self.address_vote.probe_nat() {
+                // the vote is on the mapping of the sockets of the family the peers reached
+                for endpoint in self.endpoints.of_family_mut(&observed) {
+                    endpoint.set_nat_status(probe.into());
+                }
+            }
+        }
+        if let Some(external) = new_majority {
+            self.rebind_detector
+                .record_external(external, self.clock.now());
+        }
+    }
+
+    /// Sends the outcome of an attempt to whoever is awaiting it.
+    fn resolve(
+        &mut self,
+        nonce: MessageNonce,
+        attempt_id: Option<AttemptId>,
+        outcome: AttemptOutcome,
+    ) {
+        if let Some(tx) = self.outcomes.remove(&nonce) {
+            // the receiver may have lost interest
+            _ = tx.send(outcome);
+        }
+        if let Some(record) = self.audit_log.get_mut(&nonce) {
+            record.resolved = Some((self.clock.now(), outcome));
+        }
+        if let AttemptOutcome::Failed(reason) = outcome {
+            self.emit(HolePunchEvent::Failed {
+                attempt_id,
+                nonce,
+                reason,
+            });
+        }
+        self.emit(HolePunchEvent::AttemptResolved {
+            attempt_id,
+            nonce,
+            outcome,
+        });
+    }
+
+    /// Relays the queued notifications the rate cap allows at `now`. Returns the error of relaying
+    /// the `incoming` notification, identified by initiator and nonce, if it was relayed. The
+    /// errors of relaying other notifications are only counted.
+    async fn process_relay_queue(
+        &mut self,
+        now: Instant,
+        incoming: Option<(NodeId, MessageNonce)>,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let mut res = Ok(());
+        while let Some(notif) = self.relay_queue.pop(now) {
+            let id = (notif.0.node_id(), notif.2);
+            let span = attempt_span(notif.3);
+            if let Err(e) = self
+                .relay(notif)
+                .instrument(span)
+                .await
+                .map_err(HolePunchError::from)
+            {
+                if Some(id) == incoming {
+                    res = Err(e);
+                } else {
+                    tracing::debug!("failed relaying queued notification, {}", e);
+                    self.stats.punches_failed.record(&e);
+                }
+            }
+        }
+        res
+    }
+
+    /// Decodes a notification from `src` and hands it to its handler, unless an inbound filter
+    /// rejects it. Shows the decoded notification to `inspect` first, see
+    /// [`NatHolePunch::handle_notification`].
+    async fn on_notification_from(
+        &mut self,
+        decrypted_notif: &[u8],
+        src: &Source,
+        inspect: impl FnOnce(&Notification) + Send,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let notif =
+            Notification::rlp_decode_with_wire(decrypted_notif, &self.decode_limits, &self.wire)?;
+        inspect(&notif);
+        self.on_decoded(notif, src).await
+    }
+
+    /// Hands a decoded notification from `src` to its handler, unless it fails validation or an
+    /// inbound filter rejects it.
+    async fn on_decoded(
+        &mut self,
+        notif: Notification,
+        src: &Source,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        // decoding verified the ENR signature, on the dispatcher's task if one is used
+        let policy = ValidationPolicy {
+            verify_enr_signature: false,
+            ..self.validation
+        };
+        notif.validate(&policy)?;
+        if let Notification::EchoAfter(EchoAfter(sender, ..)) = &notif {
+            // echoes go to the claimed sender, who must be the peer the probe came from
+            if src.node_id.is_some_and(|node_id| node_id != *sender) {
+                return Err(HolePunchError::Filtered);
+            }
+        }
+        match self.inbound_filters.evaluate(&notif, src) {
+            Verdict::Allow => {}
+            Verdict::Drop => return Err(HolePunchError::Filtered),
+            Verdict::Abort(reason) => {
+                // only the initiator of a relay init is around to hear about it
+                if let Notification::RelayInit(RelayInit(initiator, _, nonce, ..)) = &notif {
+                    self.send_relay_abort(initiator.node_id(), *nonce, reason)
+                        .await
+                        .map_err(HolePunchError::RelayError)?;
+                }
+                return Err(HolePunchError::Filtered);
+            }
+        }
+        match notif {
+            Notification::RelayInit(notif) => {
+                let span = attempt_span(notif.3);
+                self.on_relay_init(notif).instrument(span).await?
+            }
+            Notification::RelayMsg(notif) => {
+                let span = attempt_span(notif.2);
+                self.on_relay_msg(notif).instrument(span).await?
+            }
+            Notification::RelayAbort(notif) => self.on_relay_abort(notif).await?,
+            Notification::PunchReport(notif) => {
+                self.on_punch_report_from(notif, src.node_id).await?
+            }
+            Notification::EchoAfter(notif) => self.on_echo_after(notif).await?,
+            Notification::Echo(notif) => self.on_echo(notif).await?,
+        }
+        Ok(())
+    }
+
+    /// Counts the report on an attempt this node relayed, if `reporter` is the initiator of the
+    /// attempt. The target and other peers knowing the nonce can't release the relay slot of the
+    /// attempt or skew the relay's scores. Reports from unknown peers are dropped.
+    async fn on_punch_report_from(
+        &mut self,
+        notif: PunchReport,
+        reporter: Option<NodeId>,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let PunchReport(nonce, punched, attempt_id, _) = notif;
+        // only count reports on attempts this node relayed, and each once
+        let Some((initiator, target, relayed_attempt_id)) = self.relayed.peek(&nonce).copied()
+        else {
+            return Ok(());
+        };
+        if reporter != Some(initiator) || attempt_id != relayed_attempt_id {
+            return Err(HolePunchError::Filtered);
+        }
+        self.relayed.remove(&nonce);
+        // the relay operation is over, a notification waiting for its slot may go out
+        self.relay_queue.finish(&nonce);
+        if punched {
+            self.stats.relayed_punched += 1;
+        } else {
+            self.stats.relayed_failed += 1;
+        }
+        self.emit(HolePunchEvent::PunchReported {
+            attempt_id,
+            initiator,
+            target,
+            punched,
+        });
+        self.process_relay_queue(self.clock.now(), None).await
+    }
+
+    /// Tells the initiator of a relayed attempt that this node aborted it, if there is a session
+    /// with the initiator.
+    async fn send_relay_abort(
+        &mut self,
+        initiator: NodeId,
+        nonce: MessageNonce,
+        reason: ReasonCode,
+    ) -> Result<(), S::Error> {
+        self.send_relay_abort_with(initiator, nonce, reason, None)
+            .await
+    }
+
+    /// Like [`Self::send_relay_abort`], with the given extensions if the initiator decodes them.
+    async fn send_relay_abort_with(
+        &mut self,
+        initiator: NodeId,
+        nonce: MessageNonce,
+        reason: ReasonCode,
+        extensions: Option<Extensions>,
+    ) -> Result<(), S::Error> {
+        let Some(session) = self.sink.session(&initiator) else {
+            return Ok(());
+        };
+        let extensions = extensions.filter(|_| {
+            self.session_version(&session)
+                .is_none_or(ProtocolVersion::supports_extensions)
+        });
+        let notif = RelayAbort(nonce, reason, extensions).rlp_encode_with(&self.wire);
+        self.sink.send_notification(&session, notif).await
+    }
+
+    /// Tells the relay of an attempt initiated by this node how it resolved, if configured to.
+    async fn report_punch(
+        &mut self,
+        relay: &S::SessionIndex,
+        nonce: MessageNonce,
+        attempt_id: Option<AttemptId>,
+        punched: bool,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        let understood = self
+            .session_version(relay)
+            .is_none_or(ProtocolVersion::supports_punch_reports);
+        if !self.punch_reports || !understood {
+            return Ok(());
+        }
+        let notif = PunchReport(nonce, punched, attempt_id, None).rlp_encode_with(&self.wire);
+        self.sink
+            .send_notification(relay, notif)
+            .await
+            .map_err(HolePunchError::InitiatorError)
+    }
+
+    /// Learns the revisions of the notification protocol `peer` speaks, from the version it sent
+    /// or else from its ENR, and negotiates the highest mutual one.
+    fn learn_version(
+        &mut self,
+        peer: NodeId,
+        sent: Option<ProtocolVersion>,
+        enr: Option<&Enr>,
+    ) -> Option<ProtocolVersion> {
+        let remote = match (sent, enr) {
+            (Some(sent), _) => (MIN_HOLE_PUNCH_PROTOCOL_VERSION..=sent.0).collect(),
+            (None, Some(enr)) => supported_versions(enr),
+            (None, None) => return self.peer_versions.peek(&peer).copied(),
+        };
+        let version = negotiate_version(&local_versions(), &remote)?;
+        if self.peer_versions.peek(&peer) != Some(&version) {
+            self.peer_versions.insert(peer, version);
+            self.emit(HolePunchEvent::VersionNegotiated { peer, version });
+        }
+        Some(version)
+    }
+
+    /// The revision negotiated with the peer in `session`, negotiated from its ENR if there is
+    /// none yet.
+    fn session_version(&mut self, session: &S::SessionIndex) -> Option<ProtocolVersion> {
+        let peer = self.sink.node_id(session);
+        if let Some(version) = self.peer_versions.get(&peer) {
+            return Some(*version);
+        }
+        let enr = self.sink.enr(session);
+        self.learn_version(peer, None, enr.as_ref())
+    }
+
+    /// Sends a [`RelayMsg`] to the target of the [`RelayInit`].
+    async fn relay(&mut self, notif: RelayInit) -> Result<(), RelayError<S::Error>> {
+        let RelayInit(ref initiator, target, nonce, attempt_id, ref candidates, _) = notif;
+        let target_session = match self.target_lookup.as_ref() {
+            Some(lookup) => lookup.lookup(target),
+            None => self.sink.session(&target),
+        };
+        let Some(target_session) = target_session else {
+            let fallback = match self.relay_fallback.as_ref() {
+                Some(fallback) => fallback.try_fallback(&notif, target).await,
+                None => None,
+            };
+            if let Some(strategy) = fallback {
+                // the initiator learns whether retrying over this node may pay off
+                let mut extensions = Extensions::default();
+                extensions.insert(&strategy);
+                let reason = ReasonCode::UnknownTarget;
+                let initiator_id = initiator.node_id();
+                if let Err(e) = self
+                    .send_relay_abort_with(initiator_id, nonce, reason, Some(extensions))
+                    .await
+                {
+                    tracing::debug!("failed sending relay abort, {}", e);
+                }
+            }
+            return Err(RelayError::UnknownTarget(target));
+        };
+        if !self.sink.supports_hole_punch(&target_session) {
+            return Err(RelayError::Unsupported(target));
+        }
+        let initiator_id = initiator.node_id();
+        let mut target_addrs = self
+            .sink
+            .enr(&target_session)
+            .map(|enr| enr_udp_sockets(&enr))
+            .unwrap_or_default();
+        target_addrs.extend(target_session.socket_addr());
+        if !shares_address_family(initiator, candidates.as_ref(), &target_addrs) {
+            // the initiator fails fast instead of waiting out the try over this relay
+            let reason = ReasonCode::AddressFamilyMismatch;
+            if let Err(e) = self.send_relay_abort(initiator_id, nonce, reason).await {
+                tracing::debug!("failed sending relay abort, {}", e);
+            }
+            return Err(RelayError::AddressFamilyMismatch(target));
+        }
+        let sent_version = notif.extension::<ProtocolVersion>().and_then(Result::ok);
+        self.learn_version(initiator_id, sent_version, Some(initiator));
+        let version = self.session_version(&target_session);
+        let wire = version.map_or(self.wire, |version| version.restrict(&self.wire));
+        let mut extras = RelayExtras {
+            // only echo the attempt id if the target is expected to understand it
+            echo_attempt_id: wire.attempt_ids,
+            observed: wire
+                .observed_addrs
+                .then(|| target_session.socket_addr())
+                .flatten(),
+            relay_id: wire.relay_ids.then(|| self.sink.local_enr().node_id()),
+            extensions: Extensions::default(),
+        };
+        let hint = target_session
+            .socket_addr()
+            .filter(|_| wire.punch_hints)
+            .and_then(|target| punch_toward(initiator, candidates.as_ref(), target));
+        if let Some(hint) = hint {
+            extras.extensions.insert(&hint);
+        }
+        if version.is_some_and(ProtocolVersion::supports_extensions) {
+            extras.extensions.insert(&ProtocolVersion::CURRENT);
+        }
+        // extensions are forwarded as is, the target decodes the types it knows of
+        let mut msg = notif.into_relay_msg(extras);
+        if let Some(version) = version {
+            // unless the target is known not to decode them at all
+            if !version.supports_candidates() {
+                msg.3 = None;
+            }
+            if !version.supports_extensions() {
+                msg.6 = None;
+            }
+        }
+        let notif = msg.rlp_encode_with(&wire);
+        self.check_packet_size(&notif)
+            .map_err(RelayError::PacketTooLarge)?;
+        self.sink
+            .send_notification(&target_session, notif)
+            .await
+            .map_err(RelayError::RelayMsgSendFailed)?;
+        self.stats.punches_relayed += 1;
+        self.relayed
+            .insert(nonce, (initiator_id, target, attempt_id));
+        self.relay_queue
+            .start(initiator_id, nonce, self.clock.now());
+        self.emit(HolePunchEvent::Relayed {
+            attempt_id,
+            initiator: initiator_id,
+            target,
+        });
+        Ok(())
+    }
+
+    /// Tries the next relay of an attempt, or gives up on the attempt for `reason`.
+    async fn on_attempt_timeout(
+        &mut self,
+        timeout: AttemptTimeout<S::SessionIndex>,
+        reason: FailureReason,
+    ) -> Result<(), HolePunchError<S::Error>> {
+        match timeout {
+            AttemptTimeout::Retry {
+                nonce,
+                failed_relay,
+                next_relay,
+                notif,
+            } => {
+                self.relay_scores
+                    .record_failure(self.sink.node_id(&failed_relay));
+                self.record_relay_try(&nonce, &next_relay);
+                self.sink
+                    .send_notification(&next_relay, notif)
+                    .await
+                    .map_err(HolePunchError::InitiatorError)
+            }
+            AttemptTimeout::TimedOut { nonce, attempt } => {
+                // aborts are counted as they arrive
+                if reason == FailureReason::NoWhoareyou {
+                    self.stats.punches_failed.timed_out += 1;
+                }
+                self.initiation_throttle
+                    .backoff_mut()
+                    .record_failure(self.sink.node_id(&attempt.target));
+                self.relay_scores
+                    .record_failure(self.sink.node_id(&attempt.relay));
+                self.reachability.insert(
+                    self.sink.node_id(&attempt.target),
+                    Reachability::Unreachable,
+                );
+                self.resolve(nonce, attempt.attempt_id, AttemptOutcome::Failed(reason));
+                self.report_punch(&attempt.relay, nonce, attempt.attempt_id, false)
+                    .await
+            }
+        }
+    }
+
+    async fn on_tick(&mut self, now: Instant) -> Result<(), HolePunchError<S::Error>> {
+        let mut res = self.process_relay_queue(now, None).await;
+        for timeout in self.attempts.poll_timed_out(now) {
+            let reason = FailureReason::NoWhoareyou;
+            if let Err(e) = self.on_attempt_timeout(timeout, reason).await {
+                res = Err(e);
+            }
+        }
+        if let Err(e) = self.send_queued_attempts().await {
+            res = Err(e);
+        }
+        self.replay_cache.prune(now);
+        self.pending_nonces.prune(now);
+        self.nonce_aliases.prune(now);
+        self.served_attempts.prune(now);
+        if let Some(hints) = self.socket_hints.as_mut() {
+            hints.prune(now);
+        }
+        self.rate_limiter.prune(now);
+        self.relay_rate_limiter.prune(now);
+        self.initiation_throttle.prune(now);
+        self.path_races.prune(now);
+        self.address_vote.prune(now);
+
+        for punch in self.scheduled_punches.poll_due(now) {
+            if let Err(e) = self
+                .send_punch_packet(punch.dst, &punch.initiator, punch.nonce)
+                .await
+            {
+                res = Err(HolePunchError::TargetError(e));
+            }
+        }
+
+        for echo in self.scheduled_echoes.poll_due(now) {
+            // the peer may have gone meanwhile
+            let Some(session) = self.sink.session(&echo.peer) else {
+                continue;
+            };
+            let notif = Echo(echo.nonce, None).rlp_encode_with(&self.wire);
+            if let Err(e) = self.sink.send_notification(&session, notif).await {
+                res = Err(HolePunchError::EchoError(e));
+            }
+        }
+        for (nonce, probe) in self.echo_probes.poll_missed(now) {
+            self.emit(HolePunchEvent::EchoMissed {
+                peer: probe.peer,
+                nonce,
+                silence: probe.delay,
+            });
+        }
+
+        for mut punch in self.finishing_punches.poll_due(now) {
+            // a lost packet is made up for by the next one
+            if let Err(e) = self.send_keepalive_packet(punch.dst).await {
+                res = Err(HolePunchError::InitiatorError(e));
+            }
+            self.endpoints
+                .for_remote_mut(&punch.dst)
+                .keepalive
+                .touch_on_send(&punch.dst);
+            punch.remaining -= 1;
+            if punch.remaining == 0 {
+                self.finish_punch(punch.nonce, punch.attempt_id, punch.dst, punch.timings);
+            } else {
+                punch.due = now + self.back_to_back.unwrap_or_default().spacing;
+                self.finishing_punches.insert(punch);
+            }
+        }
+
+        let expired = self
+            .endpoints
+            .iter_mut()
+            .flat_map(|endpoint| endpoint.keepalive.poll_expired(now))
+            .collect::<Vec<_>>();
+        for dst in expired {
+            if let Err(e) = self.on_hole_punch_expired(dst).await {
+                self.rebind_detector.record_failure(now);
+                res = Err(e);
+            }
+        }
+        let mut given_up = Vec::new();
+        for endpoint in self.endpoints.iter_mut() {
+            for dst in endpoint.keepalive.drain_given_up() {
+                endpoint.punched_peers.remove(&dst);
+                given_up.push(dst);
+            }
+        }
+        for dst in given_up {
+            self.rebind_detector.record_failure(now);
+            self.emit(HolePunchEvent::HoleAbandoned { dst });
+        }
+
+        if self.rebind_detector.poll(now) {
+            if let Err(e) = self.on_nat_rebind().await {
+                res = Err(e);
+            }
+        }
+        res
+    }
+
+    /// Returns true if the peer in `session` is reached over a hole this node keeps alive, or
+    /// punched for it as target.
+    fn over_hole(&self, session: &S::SessionIndex) -> bool {
+        let mut sockets = self
+            .sink
+            .enr(session)
+            .map(|enr| enr_udp_sockets(&enr))
+            .unwrap_or_default();
+        sockets.extend(session.socket_addr());
+        if sockets
+            .iter()
+            .any(|socket| self.endpoints.for_remote(socket).keepalive.contains(socket))
+        {
+            return true;
+        }
+        let node_id = self.sink.node_id(session);
+        self.endpoints.iter().any(|endpoint| {
+            endpoint
+                .punched_peers
+                .iter()
+                .any(|(_, peer)| peer.initiator == node_id)
+        })
+    }
+
+    /// Re-punches all holes at once after the local NAT rebound, from the new mapping.
+    async fn on_nat_rebind(&mut self) -> Result<(), HolePunchError<S::Error>> {
+        let holes = self
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| endpoint.keepalive.holes().copied())
+            .collect::<Vec<_>>();
+        tracing::debug!("NAT rebind detected, re-punching {} holes", holes.len());
+        let mut res = Ok(());
+        for dst in holes.iter() {
+            self.endpoints
+                .for_remote_mut(dst)
+                .keepalive
+                .touch_on_send(dst);
+            if let Err(e) = self.on_hole_punch_expired(*dst).await {
+                res = Err(e);
+            }
+        }
+        self.emit(HolePunchEvent::NatRebindDetected {
+            external: self.rebind_detector.external(),
+            holes,
+        });
+        res
+    }
+
+    /// Punches a hole for the initiator at `dst` according to the configured [`PunchPattern`].
+    async fn punch(
+        &mut self,
+        dst: SocketAddr,
+        initiator: &Enr,
+        nonce: MessageNonce,
+    ) -> Result<(), S::Error> {
+        for _ in 0..self.punch_pattern.leading_empty_packets {
+            self.send_keepalive_packet(dst).await?;
+        }
+        self.send_punch_packet(dst, initiator, nonce).await?;
+        self.scheduled_punches.schedule(
+            &self.punch_pattern,
+            self.clock.now(),
+            dst,
+            initiator,
+            nonce,
+        );
+        Ok(())
+    }
+
+    /// Waits until the configured [`BurstPacing`] allows sending another packet.
+    async fn pace(&mut self) {
+        if let Some(send_at) = self.pacer.as_mut().and_then(Pacer::reserve) {
+            self.clock.sleep_until(send_at).await;
+        }
+    }
+
+    /// Sends the configured [`PunchPayload`] to the initiator at `dst`.
+    async fn send_punch_packet(
+        &mut self,
+        dst: SocketAddr,
+        initiator: &Enr,
+        nonce: MessageNonce,
+    ) -> Result<(), S::Error> {
+        self.pace().await;
+        match &self.punch_payload {
+            PunchPayload::WhoAreYou => self.sink.send_whoareyou(dst, initiator, nonce).await,
+            PunchPayload::Template(packet) => {
+                let packet = packet.clone();
+                self.sink.send_packet(dst, packet).await
+            }
+            PunchPayload::Custom(build) => {
+                let packet = build(dst, initiator, nonce);
+                self.sink.send_packet(dst, packet).await
+            }
+        }
+    }
+
+    /// Sends the configured [`KeepalivePayload`] to `dst`.
+    async fn send_keepalive_packet(&mut self, dst: SocketAddr) -> Result<(), S::Error> {
+        self.pace().await;
+        match &self.keepalive_payload {
+            KeepalivePayload::Empty => self.sink.send_empty_packet(dst).await,
+            KeepalivePayload::Template(packet) => {
+                let packet = packet.clone();
+                self.sink.send_packet(dst, packet).await
+            }
+            KeepalivePayload::Custom(build) => {
+                let packet = build(dst);
+                self.sink.send_packet(dst, packet).await
+            }
+        }
+    }
+
+    /// The time until the next timer of the service fires, if any.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        let keepalive = self
+            .endpoints
+            .iter()
+            .filter_map(|endpoint| endpoint.keepalive.next_deadline())
+            .min();
+        [
+            keepalive,
+            self.attempts.next_deadline(),
+            self.scheduled_punches.next_deadline(),
+            self.finishing_punches.next_deadline(),
+            self.scheduled_echoes.next_deadline(),
+            self.echo_probes.next_deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map(|deadline| deadline.saturating_duration_since(self.clock.now()))
+    }
+}
+
+/// The tracing span of the hole punch attempt with the given id, for correlating the logs of the
+/// initiator, relay and target.
+fn attempt_span(attempt_id: Option<AttemptId>) -> tracing::Span {
+    tracing::debug_span!(
+        "hole_punch",
+        attempt_id = attempt_id.map(hex::encode).as_deref()
+    )
+}
+
+/// Returns the UDP sockets of the ENR, IPv4 first.
+pub(crate) fn enr_udp_sockets(enr: &Enr) -> Vec<SocketAddr> {
+    NodeAddress::all_from_enr(enr)
+        .into_iter()
+        .map(|addr| addr.socket_addr)
+        .collect()
+}
+
+/// The addresses of the initiator, its candidates and the sockets of its ENR.
+fn initiator_addrs(initiator: &Enr, candidates: Option<&Candidates>) -> Vec<SocketAddr> {
+    let mut addrs = candidates
+        .map(|c| c.iter().map(|candidate| candidate.addr).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for addr in enr_udp_sockets(initiator) {
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+/// Returns false if none of the addresses of the initiator is of the family of any of the known
+/// addresses of the target. True if the addresses of either are unknown.
+pub(crate) fn shares_address_family(
+    initiator: &Enr,
+    candidates: Option<&Candidates>,
+    target: &[SocketAddr],
+) -> bool {
+    let addrs = initiator_addrs(initiator, candidates);
+    addrs.is_empty()
+        || target.is_empty()
+        || addrs
+            .iter()
+            .any(|addr| target.iter().any(|t| addr.is_ipv4() == t.is_ipv4()))
+}
+
+/// The addresses of the initiator, its candidates and the sockets of its ENR, of the family the
+/// relay reaches the target at `target` over. `None` if that doesn't narrow down the addresses.
+pub(crate) fn punch_toward(
+    initiator: &Enr,
+    candidates: Option<&Candidates>,
+    target: SocketAddr,
+) -> Option<PunchToward> {
+    let addrs = initiator_addrs(initiator, candidates);
+    let toward = addrs
+        .iter()
+        .filter(|addr| addr.is_ipv4() == target.is_ipv4())
+        .copied()
+        .collect::<Vec<_>>();
+    (!toward.is_empty() && toward.len() < addrs.len()).then_some(PunchToward(toward))
+}
+
+#[async_trait]
+impl<S: NotificationSink> NatHolePunch for HolePunchService<S> {
+    type SessionIndex = S::SessionIndex;
+    type Discv5Error = S::Error;
+
+    async fn on_request_time_out(
+        &mut self,
+        relay: Self::SessionIndex,
+        local_enr: Enr,
+        timed_out_message_nonce: MessageNonce,
+        target_session_index: Self::SessionIndex,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        self.initiate_timed_out(
+            vec![relay],
+            local_enr,
+            timed_out_message_nonce,
+            Vec::new(),
+            target_session_index,
+            None,
+        )
+        .await
+    }
+
+    async fn initiate_punch(
+        &mut self,
+        target: NodeId,
+        via: Self::SessionIndex,
+    ) -> Result<MessageNonce, HolePunchError<Self::Discv5Error>> {
+        self.initiate_punch_with(target, via, None).await
+    }
+
+    async fn handle_notification<F>(
+        &mut self,
+        decrypted_notif: &[u8],
+        inspect: F,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>>
+    where
+        F: FnOnce(&Notification) + Send,
+    {
+        self.on_notification_from(decrypted_notif, &Source::default(), inspect)
+            .await
+    }
+
+    async fn on_relay_init(
+        &mut self,
+        notif: RelayInit,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        let id = (notif.0.node_id(), notif.2);
+        if !self.rate_limiter.allow(id.0) {
+            return Err(RelayError::RateLimited.into());
+        }
+        if !self.replay_cache.insert(id.0, id.1) {
+            return Err(RelayError::Replayed.into());
+        }
+        if let Some(monitor) = self.load_monitor.as_ref() {
+            if monitor.is_overloaded() {
+                // tell the initiator so it can move on to another relay right away
+                self.send_relay_abort(id.0, id.1, ReasonCode::Busy)
+                    .await
+                    .map_err(HolePunchError::RelayError)?;
+                return Err(RelayError::Overloaded.into());
+            }
+        }
+        // the socket of the session the notification came over, else the one the ENR advertises
+        let from = self
+            .sink
+            .session(&id.0)
+            .and_then(|session| session.socket_addr())
+            .map(|socket| NodeAddress::new(socket, id.0))
+            .or_else(|| NodeAddress::from_enr(&notif.0));
+        let allowed = match from {
+            Some(from) => self.relay_consent.allow(&notif, &from).await,
+            None => false,
+        };
+        if !allowed {
+            self.send_relay_abort(id.0, id.1, ReasonCode::PolicyDenied)
+                .await
+                .map_err(HolePunchError::RelayError)?;
+            return Err(RelayError::ConsentDenied.into());
+        }
+        if let Some(dropped) = self.relay_queue.push(notif) {
+            if (dropped.0.node_id(), dropped.2) == id {
+                return Err(RelayError::QueueFull.into());
+            }
+        }
+        self.process_relay_queue(self.clock.now(), Some(id)).await
+    }
+
+    async fn on_relay_msg(
+        &mut self,
+        notif: RelayMsg,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        let hint = notif
+            .extension::<PunchToward>()
+            .and_then(Result::ok)
+            .unwrap_or(PunchToward(Vec::new()));
+        let relay_version = notif.extension::<ProtocolVersion>().and_then(Result::ok);
+        // answer the most recent of the initiator's timed out requests
+        let recent_nonce = notif
+            .extension::<RecentNonces>()
+            .and_then(Result::ok)
+            .and_then(|nonces| nonces.most_recent().copied());
+        let RelayMsg(initiator, nonce, attempt_id, candidates, observed, relay, _) = notif;
+        if let Some(relay) = relay {
+            if !self.relay_rate_limiter.allow(relay) {
+                return Err(TargetError::RateLimited.into());
+            }
+            if relay_version.is_some() {
+                self.learn_version(relay, relay_version, None);
+            }
+        }
+        // an initiator fanning out over several relays gets punched for once
+        let served = (initiator.node_id(), nonce);
+        if self.served_attempts.contains_key(&served) {
+            self.stats.duplicate_relay_msgs += 1;
+            return Ok(());
+        }
+        self.learn_version(initiator.node_id(), None, Some(&initiator));
+        if let Some(observed) = observed {
+            self.emit(HolePunchEvent::ObservedByRelay {
+                attempt_id,
+                observed,
+            });
+        }
+        // the sockets of the ENR are candidates too, unless the initiator listed them
+        let mut remote = candidates.map(|c| c.as_ref().to_vec()).unwrap_or_default();
+        for addr in enr_udp_sockets(&initiator) {
+            if !remote.iter().any(|candidate| candidate.addr == addr) {
+                remote.push(Candidate::new(CandidateKind::Reflexive, addr));
+            }
+        }
+        let mut paths = Checklist::new(&self.local_candidates, &remote, false).remote_addrs();
+        // the relay's hint goes first, stable to keep the priority order otherwise
+        paths.sort_by_key(|addr| !hint.0.contains(addr));
+        if paths.is_empty() {
+            return Err(TargetError::InitiatorUnreachable.into());
+        }
+        // race the candidates of the initiator in priority order, any of them may be NATed
+        let mut punched = Vec::with_capacity(paths.len());
+        let mut res = Ok(());
+        for dst in paths {
+            match self
+                .punch(dst, &initiator, recent_nonce.unwrap_or(nonce))
+                .await
+            {
+                // the WHOAREYOU punched a hole for the initiator in this node's NAT
+                Ok(()) => punched.push(dst),
+                Err(e) => res = Err(TargetError::WhoAreYouSendFailed(e).into()),
+            }
+        }
+        if punched.is_empty() {
+            return res;
+        }
+        self.served_attempts.insert(served, ());
+        for dst in punched.iter() {
+            let endpoint = self.endpoints.for_remote_mut(dst);
+            endpoint.keepalive.insert(*dst);
+            endpoint.punched_peers.insert(*dst, initiator.node_id());
+        }
+        // the attempt counts once, towards the socket of the preferred path
+        let endpoint = self.endpoints.for_remote_mut(&punched[0]);
+        endpoint.record_relayed_punch(self.firewall_evidence);
+        match endpoint.nat_status {
+            NatStatus::BehindNat | NatStatus::BehindCgnat | NatStatus::SecondaryInterface => {
+                self.stats.target_punches_nat += 1
+            }
+            NatStatus::Firewalled => self.stats.target_punches_firewall += 1,
+            NatStatus::Unknown | NatStatus::Public => {}
+        }
+        self.emit(HolePunchEvent::Punched {
+            attempt_id,
+            initiator: initiator.node_id(),
+            paths: punched.clone(),
+        });
+        self.path_races.start(initiator.node_id(), punched);
+        Ok(())
+    }
+
+    async fn on_relay_abort(
+        &mut self,
+        notif: RelayAbort,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        let fallback = notif.extension::<FallbackStrategy>().and_then(Result::ok);
+        let RelayAbort(nonce, reason, _) = notif;
+        tracing::debug!("relay aborted hole punch attempt, {}", reason);
+        self.stats.punches_failed.aborted += 1;
+        let record = self.audit_log.get_mut(&nonce);
+        if let Some(relay_try) = record.and_then(|record| record.relays.last_mut()) {
+            relay_try.aborted = Some(reason);
+            relay_try.fallback = fallback;
+        }
+        let timeout = if reason == ReasonCode::AddressFamilyMismatch {
+            // no other relay bridges the address families either
+            self.attempts
+                .remove(&nonce)
+                .map(|attempt| AttemptTimeout::TimedOut { nonce, attempt })
+        } else {
+            self.attempts.skip(&nonce, self.clock.now())
+        };
+        match timeout {
+            Some(timeout) => {
+                let reason = FailureReason::RelayAborted(reason);
+                self.on_attempt_timeout(timeout, reason).await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The reporter is unknown, so the report is dropped. Pass reports in with their session,
+    /// see [`ServiceCommand::NotificationFrom`].
+    async fn on_punch_report(
+        &mut self,
+        notif: PunchReport,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        self.on_punch_report_from(notif, None).await
+    }
+
+    async fn on_echo_after(
+        &mut self,
+        notif: EchoAfter,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        let EchoAfter(peer, nonce, delay, _) = notif;
+        if !self.rate_limiter.allow(peer) {
+            return Err(HolePunchError::RateLimited);
+        }
+        if !self
+            .scheduled_echoes
+            .schedule(self.clock.now(), peer, nonce, delay)
+        {
+            return Err(HolePunchError::EchoRefused);
+        }
+        Ok(())
+    }
+
+    async fn on_echo(&mut self, notif: Echo) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        let Echo(nonce, _) = notif;
+        if let Some(probe) = self.echo_probes.remove(&nonce) {
+            self.emit(HolePunchEvent::EchoReceived {
+                peer: probe.peer,
+                nonce,
+                silence: probe.delay,
+            });
+        }
+        Ok(())
+    }
+
+    async fn on_hole_punch_expired(
+        &mut self,
+        dst: SocketAddr,
+    ) -> Result<(), HolePunchError<Self::Discv5Error>> {
+        self.send_keepalive_packet(dst)
+            .await
+            .map_err(HolePunchError::KeepaliveError)?;
+        self.stats.keepalives_sent += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::ManualClock, Clock, SeededRng, ATTEMPT_ID_LENGTH, DEFAULT_HOLE_PUNCH_LIFETIME,
+        HOLE_PUNCH_ENR_KEY, MESSAGE_NONCE_LENGTH,
+    };
+    use enr::{CombinedKey, EnrBuilder};
+    use futures::{executor::block_on, FutureExt};
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::Arc,
+    };
+
+    /// Records the packets sent by the service. Sessions are indexed by node id.
+    #[derive(Default)]
+    struct RecordingSink {
+        sessions: HashMap<NodeId, ()>,
+        unsupported: HashSet<NodeId>,
+        notifications: Vec<(NodeId, Vec<u8>)>,
+        whoareyous: Vec<(SocketAddr, MessageNonce)>,
+        empty_packets: Vec<SocketAddr>,
+        packets: Vec<(SocketAddr, Vec<u8>)>,
+        enrs: HashMap<NodeId, Enr>,
+        /// Peers the routing table has no room for, all others are table candidates.
+        full_buckets: HashSet<NodeId>,
+    }
+
+    #[async_trait]
+    impl NotificationSink for RecordingSink {
+        type SessionIndex = NodeId;
+        type Error = String;
+
+        fn local_enr(&self) -> Enr {
+            relay_init(NodeId::random()).0
+        }
+
+        fn node_id(&self, session: &NodeId) -> NodeId {
+            *session
+        }
+
+        fn session(&self, node_id: &NodeId) -> Option<NodeId> {
+            self.sessions.get(node_id).map(|_| *node_id)
+        }
+
+        fn supports_hole_punch(&self, session: &NodeId) -> bool {
+            !self.unsupported.contains(session)
+        }
+
+        fn enr(&self, session: &NodeId) -> Option<Enr> {
+            self.enrs.get(session).cloned()
+        }
+
+        fn is_table_candidate(&self, node_id: &NodeId) -> Option<bool> {
+            Some(!self.full_buckets.contains(node_id))
+        }
+
+        async fn send_notification(
+            &mut self,
+            session: &NodeId,
+            notif: Vec<u8>,
+        ) -> Result<(), String> {
+            self.notifications.push((*session, notif));
+            Ok(())
+        }
+
+        async fn send_whoareyou(
+            &mut self,
+            dst: SocketAddr,
+            _initiator: &Enr,
+            nonce: MessageNonce,
+        ) -> Result<(), String> {
+            self.whoareyous.push((dst, nonce));
+            Ok(())
+        }
+
+        async fn send_empty_packet(&mut self, dst: SocketAddr) -> Result<(), String> {
+            self.empty_packets.push(dst);
+            Ok(())
+        }
+
+        async fn send_packet(&mut self, dst: SocketAddr, packet: Vec<u8>) -> Result<(), String> {
+            self.packets.push((dst, packet));
+            Ok(())
+        }
+    }
+
+    fn relay_init(target: NodeId) -> RelayInit {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let inr_enr = EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .ip6("2001:db8::1".parse().unwrap())
+            .udp6(9000)
+            .build(&enr_key)
+            .unwrap();
+        RelayInit(
+            inr_enr,
+            target,
+            [1u8; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_relay_forwards_relay_msg_once() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+
+        let notif = relay_init(target);
+        let encoded = notif.to_rlp();
+
+        block_on(service.handle_command(ServiceCommand::Notification(encoded.clone())))
+            .expect("should relay");
+        let replay = block_on(service.handle_command(ServiceCommand::Notification(encoded)));
+        assert!(matches!(replay, Err(HolePunchError::Replayed)));
+
+        let stats = service.stats();
+        assert_eq!(1, stats.punches_relayed);
+        assert_eq!(1, stats.punches_failed.replayed);
+        assert_eq!(1, stats.punches_failed.total());
+
+        let sent = &service.sink().notifications;
+        assert_eq!(1, sent.len());
+        assert_eq!(target, sent[0].0);
+        let RelayInit(initiator, _, nonce, ..) = notif;
+        assert_eq!(
+            Notification::RelayMsg(RelayMsg(initiator, nonce, None, None, None, None, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_relay_msg_restricted_to_negotiated_version() {
+        let key = CombinedKey::generate_secp256k1();
+        let mut target_enr = EnrBuilder::new("v4").build(&key).unwrap();
+        target_enr.insert(HOLE_PUNCH_ENR_KEY, &[3u8], &key).unwrap();
+        let target = target_enr.node_id();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        sink.enrs.insert(target, target_enr);
+        let config = HolePunchConfig {
+            wire: WireConfig {
+                observed_addrs: true,
+                relay_ids: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(sink, config);
+        let mut events = service.subscribe();
+        let mut extensions = Extensions::default();
+        extensions.insert(&ProtocolVersion::CURRENT);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(target);
+        let initiator_id = initiator.node_id();
+
+        let notif = RelayInit(
+            initiator.clone(),
+            target,
+            nonce,
+            None,
+            None,
+            Some(extensions),
+        );
+        block_on(service.on_relay_init(notif)).unwrap();
+
+        assert_eq!(
+            Some(ProtocolVersion::CURRENT),
+            service.protocol_version(&initiator_id)
+        );
+        assert_eq!(Some(ProtocolVersion(3)), service.protocol_version(&target));
+        assert_eq!(
+            HolePunchEvent::VersionNegotiated {
+                peer: initiator_id,
+                version: ProtocolVersion::CURRENT
+            },
+            events.try_recv().unwrap()
+        );
+        // no extensions, relay id or observed socket the target can't decode
+        let sent = &service.sink().notifications;
+        assert_eq!(
+            Notification::RelayMsg(RelayMsg(initiator, nonce, None, None, None, None, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        );
+    }
+
+    #[derive(Debug)]
+    struct Overloaded;
+
+    impl LoadMonitor for Overloaded {
+        fn is_overloaded(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_overloaded_relay_aborts() {
+        let target = NodeId::random();
+        let notif = relay_init(target);
+        let initiator = notif.0.node_id();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        sink.sessions.insert(initiator, ());
+        let config = HolePunchConfig {
+            load_monitor: Some(Arc::new(Overloaded)),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(sink, config);
+
+        let res = block_on(service.on_relay_init(notif.clone()));
+
+        assert!(matches!(res, Err(HolePunchError::Overloaded)));
+        let sent = &service.sink().notifications;
+        assert_eq!(1, sent.len());
+        assert_eq!(initiator, sent[0].0);
+        assert_eq!(
+            Notification::RelayAbort(RelayAbort(notif.2, ReasonCode::Busy, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        );
+    }
+
+    struct DenyInitiator(NodeId);
+
+    #[async_trait::async_trait]
+    impl RelayConsent for DenyInitiator {
+        async fn allow(&self, _init: &RelayInit, from: &NodeAddress) -> bool {
+            from.node_id != self.0
+        }
+    }
+
+    #[test]
+    fn test_relay_asks_consent() {
+        let target = NodeId::random();
+        let denied = relay_init(target);
+        let allowed = relay_init(target);
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        sink.sessions.insert(denied.0.node_id(), ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        service.set_relay_consent(DenyInitiator(denied.0.node_id()));
+
+        let res = block_on(service.on_relay_init(denied.clone()));
+
+        assert!(matches!(res, Err(HolePunchError::Filtered)));
+        let sent = &service.sink().notifications;
+        assert_eq!(denied.0.node_id(), sent[0].0);
+        assert_eq!(
+            Notification::RelayAbort(RelayAbort(denied.2, ReasonCode::PolicyDenied, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        );
+
+        block_on(service.on_relay_init(allowed)).unwrap();
+        assert_eq!(target, service.sink().notifications[1].0);
+    }
+
+    struct WakeupPings(Arc<std::sync::Mutex<Vec<NodeId>>>);
+
+    #[async_trait::async_trait]
+    impl RelayFallback for WakeupPings {
+        async fn try_fallback(
+            &self,
+            _init: &RelayInit,
+            target: NodeId,
+        ) -> Option<FallbackStrategy> {
+            self.0.lock().unwrap().push(target);
+            Some(FallbackStrategy::WakeupPing)
+        }
+    }
+
+    #[test]
+    fn test_relay_reports_fallback_for_unknown_target() {
+        let target = NodeId::random();
+        let init = relay_init(target);
+        let initiator = init.0.node_id();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(initiator, ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let pings = Arc::default();
+        service.set_relay_fallback(WakeupPings(Arc::clone(&pings)));
+
+        let res = block_on(service.on_relay_init(init.clone()));
+
+        assert!(matches!(res, Err(HolePunchError::UnknownTarget(id)) if id == target));
+        assert_eq!(vec![target], *pings.lock().unwrap());
+        let sent = &service.sink().notifications;
+        assert_eq!(initiator, sent[0].0);
+        let Notification::RelayAbort(abort) =
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        else {
+            panic!("expected a relay abort");
+        };
+        assert_eq!((init.2, ReasonCode::UnknownTarget), (abort.0, abort.1));
+        assert_eq!(
+            Some(Ok(FallbackStrategy::WakeupPing)),
+            abort.extension::<FallbackStrategy>()
+        );
+    }
+
+    #[test]
+    fn test_relay_aborts_address_family_mismatch() {
+        let key = CombinedKey::generate_secp256k1();
+        let target_enr = EnrBuilder::new("v4")
+            .ip6("2001:db8::2".parse().unwrap())
+            .udp6(9000)
+            .build(&key)
+            .unwrap();
+        let target = target_enr.node_id();
+        let key = CombinedKey::generate_secp256k1();
+        let initiator_enr = EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .build(&key)
+            .unwrap();
+        let initiator = initiator_enr.node_id();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        sink.sessions.insert(initiator, ());
+        sink.enrs.insert(target, target_enr);
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+
+        let notif = RelayInit(initiator_enr, target, nonce, None, None, None);
+        let res = block_on(service.on_relay_init(notif));
+
+        assert!(matches!(res, Err(HolePunchError::AddressFamilyMismatch(id)) if id == target));
+        let sent = &service.sink().notifications;
+        assert_eq!(1, sent.len());
+        assert_eq!(initiator, sent[0].0);
+        assert_eq!(
+            Notification::RelayAbort(RelayAbort(nonce, ReasonCode::AddressFamilyMismatch, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(&sent[0].1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_initiator_moves_on_after_abort() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relays = vec![NodeId::random(), NodeId::random()];
+
+        let _outcome =
+            block_on(service.initiate(relays.clone(), local_enr, nonce, target)).unwrap();
+        block_on(service.on_relay_abort(RelayAbort(nonce, ReasonCode::Busy, None))).unwrap();
+
+        assert_eq!(relays[1], service.sink().notifications[1].0);
+        assert_eq!(relays[1], service.attempts().get(&nonce).unwrap().relay);
+    }
+
+    #[test]
+    fn test_initiator_falls_back_to_relay_book() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let (given, harvested) = (NodeId::random(), NodeId::random());
+        let command = ServiceCommand::NodesResponse {
+            from: harvested,
+            contains: vec![target],
+        };
+        block_on(service.handle_command(command)).unwrap();
+        assert_eq!(
+            vec![harvested],
+            service.relay_book().candidates_for(&target)
+        );
+
+        let _outcome = block_on(service.initiate(vec![given], local_enr, nonce, target)).unwrap();
+        block_on(service.on_relay_abort(RelayAbort(nonce, ReasonCode::Busy, None))).unwrap();
+
+        assert_eq!(given, service.sink().notifications[0].0);
+        assert_eq!(harvested, service.sink().notifications[1].0);
+    }
+
+    #[test]
+    fn test_peer_enr_update_migrates_holes() {
+        let key = CombinedKey::generate_secp256k1();
+        let mut target_enr = EnrBuilder::new("v4")
+            .ip4("198.51.100.7".parse().unwrap())
+            .udp4(9000)
+            .build(&key)
+            .unwrap();
+        let old_enr = target_enr.clone();
+        let target = target_enr.node_id();
+        let relay = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        sink.sessions.insert(relay, ());
+        let config = HolePunchConfig {
+            repunch_on_enr_update: true,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(sink, config);
+        let RelayInit(local_enr, _, nonce, ..) = relay_init(target);
+        let stale: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let fresh: SocketAddr = "198.51.100.7:9001".parse().unwrap();
+
+        let _outcome = block_on(service.initiate(vec![relay], local_enr, nonce, target)).unwrap();
+        block_on(service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src: stale }))
+            .unwrap();
+        assert!(service.endpoint(&stale).keepalive().contains(&stale));
+
+        target_enr.set_udp4(9001, &key).unwrap();
+        let repunch = block_on(service.handle_peer_enr_update(target, &old_enr, &target_enr))
+            .unwrap()
+            .expect("should re-punch through the last relay");
+
+        let keepalive = service.endpoint(&fresh).keepalive();
+        assert!(keepalive.contains(&fresh));
+        assert!(!keepalive.contains(&stale));
+        assert!(service.reachability().get(&target).is_none());
+        assert!(service.attempts().get(&repunch).is_some());
+        let sent = &service.sink().notifications;
+        assert_eq!(relay, sent.last().unwrap().0);
+    }
+
+    #[test]
+    fn test_punch_source_learned_from_whoareyou() {
+        let config = HolePunchConfig {
+            socket_hints: true,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let rebound: SocketAddr = "198.51.100.7:9001".parse().unwrap();
+
+        assert_eq!(None, service.record_punch_source(nonce, src));
+        let _outcome =
+            block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target)).unwrap();
+        block_on(service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src })).unwrap();
+        let entry = service.reachability().get(&target).unwrap();
+        assert_eq!(
+            (Reachability::Punched, Some(src)),
+            (entry.reachability, entry.socket)
+        );
+
+        // a later WHOAREYOU of the punched attempt moves the hint
+        assert_eq!(Some(target), service.record_punch_source(nonce, rebound));
+        assert_eq!(Some(rebound), service.take_socket_hint(&target));
+        assert_eq!(None, service.take_socket_hint(&target));
+        assert_eq!(
+            Some(rebound),
+            service.reachability().get(&target).unwrap().socket
+        );
+    }
+
+    #[test]
+    fn test_recent_attempts_record_phases() {
+        let clock = ManualClock::default();
+        let config = HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            audit_log_size: 1,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relays = vec![NodeId::random(), NodeId::random()];
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let initiated = clock.now();
+        let step = Duration::from_millis(100);
+
+        let _outcome =
+            block_on(service.initiate(relays.clone(), local_enr.clone(), nonce, target)).unwrap();
+        clock.advance(step);
+        block_on(service.on_relay_abort(RelayAbort(nonce, ReasonCode::Busy, None))).unwrap();
+        clock.advance(step);
+        block_on(service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src })).unwrap();
+        clock.advance(step);
+        assert!(service.confirm_established(target));
+
+        let records = service.recent_attempts().collect::<Vec<_>>();
+        assert_eq!(
+            vec![&AttemptRecord {
+                nonce,
+                attempt_id: None,
+                target,
+                initiated,
+                relays: vec![
+                    RelayTry {
+                        relay: relays[0],
+                        sent: initiated,
+                        aborted: Some(ReasonCode::Busy),
+                        fallback: None,
+                    },
+                    RelayTry {
+                        relay: relays[1],
+                        sent: initiated + step,
+                        aborted: None,
+                        fallback: None,
+                    },
+                ],
+                punched: Some((initiated + 2 * step, src)),
+                established: Some(initiated + 3 * step),
+                resolved: Some((initiated + 2 * step, AttemptOutcome::Punched { src })),
+            }],
+            records
+        );
+
+        // the oldest record makes room
+        let other_nonce = [2u8; MESSAGE_NONCE_LENGTH];
+        let _outcome = block_on(service.initiate(relays, local_enr, other_nonce, target)).unwrap();
+        let nonces = service
+            .recent_attempts()
+            .map(|record| record.nonce)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![other_nonce], nonces);
+    }
+
+    #[test]
+    fn test_failure_reasons() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let mut events = service.subscribe();
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+
+        let mut outcome =
+            block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target)).unwrap();
+        block_on(service.on_relay_abort(RelayAbort(nonce, ReasonCode::UnknownTarget, None)))
+            .unwrap();
+
+        let reason = FailureReason::RelayAborted(ReasonCode::UnknownTarget);
+        assert_eq!(Ok(Some(AttemptOutcome::Failed(reason))), outcome.try_recv());
+        assert_eq!(
+            HolePunchEvent::Failed {
+                attempt_id: None,
+                nonce,
+                reason
+            },
+            events.try_recv().unwrap()
+        );
+        assert_eq!(1, service.stats().punches_failed.total());
+
+        // a punched target whose session then fails is marked unreachable
+        block_on(service.handle_command(ServiceCommand::SessionFailed { nonce })).unwrap();
+        let _attempt_resolved = events.try_recv().unwrap();
+        assert_eq!(
+            HolePunchEvent::Failed {
+                attempt_id: None,
+                nonce,
+                reason: FailureReason::SessionFailed
+            },
+            events.try_recv().unwrap()
+        );
+        assert_eq!(1, service.stats().punches_failed.session_failed);
+        assert_eq!(
+            Some(Reachability::Unreachable),
+            service
+                .reachability()
+                .get(&target)
+                .map(|entry| entry.reachability)
+        );
+    }
+
+    /// Times out a request to each of `targets`, and has peers vote on the external addresses
+    /// `externals` one after the other, ticking after each. Returns the number of empty packets
+    /// sent after each tick.
+    fn time_out_and_rebind(
+        service: &mut HolePunchService<RecordingSink>,
+        targets: &[NodeId],
+        externals: &[&str],
+    ) -> Vec<usize> {
+        let RelayInit(local_enr, ..) = relay_init(NodeId::random());
+        for (i, target) in targets.iter().enumerate() {
+            let command = ServiceCommand::RequestTimedOut {
+                relay: NodeId::random(),
+                local_enr: local_enr.clone(),
+                nonce: [i as u8; MESSAGE_NONCE_LENGTH],
+                target: *target,
+            };
+            _ = block_on(service.handle_command(command));
+        }
+        let voters = ["10.0.0.1", "10.0.1.1", "10.0.2.1"].map(|ip| (NodeId::random(), ip));
+        let mut sent = Vec::new();
+        for external in externals {
+            for (voter, voter_ip) in voters {
+                service.on_address_observed(
+                    voter,
+                    voter_ip.parse().unwrap(),
+                    external.parse().unwrap(),
+                );
+            }
+            block_on(service.on_tick(Instant::now())).unwrap();
+            sent.push(service.sink().empty_packets.len());
+        }
+        sent
+    }
+
+    #[test]
+    fn test_rebind_repunches_holes() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let mut events = service.subscribe();
+        let hole: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let peer = NodeId::random();
+        let endpoint = service.endpoints.for_remote_mut(&hole);
+        endpoint.keepalive.insert(hole);
+        endpoint.punched_peers.insert(hole, peer);
+
+        let externals = ["198.51.100.1:9000", "198.51.100.1:9001"];
+        let sent = time_out_and_rebind(&mut service, &[peer, peer], &externals);
+
+        assert_eq!(vec![0, 1], sent);
+        assert_eq!(vec![hole], service.sink().empty_packets);
+        assert_eq!(
+            HolePunchEvent::NatRebindDetected {
+                external: Some("198.51.100.1:9001".parse().unwrap()),
+                holes: vec![hole],
+            },
+            events.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_rebind_ignores_lookup_timeouts() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let hole: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        service
+            .endpoints
+            .for_remote_mut(&hole)
+            .keepalive
+            .insert(hole);
+
+        // timeouts to peers not behind holes don't count, however many
+        let targets = [(); 8].map(|_| NodeId::random());
+        let externals = ["198.51.100.1:9000", "198.51.100.1:9001"];
+        let sent = time_out_and_rebind(&mut service, &targets, &externals);
+        assert_eq!(vec![0, 0], sent);
+
+        // nor does an address change alone
+        let sent = time_out_and_rebind(&mut service, &[], &["198.51.100.1:9002"]);
+        assert_eq!(vec![0], sent);
+    }
+
+    #[test]
+    fn test_relay_unknown_target() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let target = NodeId::random();
+
+        let res = block_on(service.on_relay_init(relay_init(target)));
+
+        assert!(matches!(res, Err(HolePunchError::UnknownTarget(id)) if id == target));
+    }
+
+    #[test]
+    fn test_relay_looks_up_target() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let sessions = SessionMap::default();
+        service.set_target_lookup(sessions.clone());
+        let target = NodeId::random();
+
+        sessions.insert(target, target);
+        block_on(service.on_relay_init(relay_init(target))).unwrap();
+        assert_eq!(target, service.sink().notifications[0].0);
+
+        sessions.remove(&target);
+        let res = block_on(service.on_relay_init(relay_init(target)));
+        assert!(matches!(res, Err(HolePunchError::UnknownTarget(id)) if id == target));
+    }
+
+    #[test]
+    fn test_target_races_address_families() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+
+        let initiator_id = initiator.node_id();
+        block_on(service.on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None)))
+            .unwrap();
+
+        assert_eq!(vec![(v4, nonce), (v6, nonce)], service.sink().whoareyous);
+        assert!(service.keepalive().contains(&v4));
+        assert!(service.keepalive().contains(&v6));
+        assert_eq!(
+            Some(initiator_id),
+            service.punched_peers().get(&v4).map(|peer| peer.initiator)
+        );
+
+        // the initiator answers over IPv6, the IPv4 hole isn't kept open
+        block_on(service.handle_command(ServiceCommand::PacketReceived(v6))).unwrap();
+
+        assert!(!service.keepalive().contains(&v4));
+        assert!(service.keepalive().contains(&v6));
+        assert!(service.punched_peers().get(&v4).is_none());
+        assert!(service.punched_peers().get(&v6).is_some());
+    }
+
+    #[test]
+    fn test_holes_kept_per_local_socket() {
+        let local_v4: SocketAddr = "10.0.0.2:9000".parse().unwrap();
+        let local_v6: SocketAddr = "[fd00::2]:9000".parse().unwrap();
+        let config = HolePunchConfig {
+            local_sockets: vec![local_v4, local_v6],
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+
+        block_on(service.on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None)))
+            .unwrap();
+        assert!(service.set_nat_status_of(&local_v6, NatStatus::Public));
+
+        let endpoint_v4 = service.endpoint(&v4);
+        assert_eq!(Some(local_v4), endpoint_v4.socket());
+        assert_eq!(
+            vec![&v4],
+            endpoint_v4.keepalive().holes().collect::<Vec<_>>()
+        );
+        assert!(endpoint_v4.punched_peers().get(&v4).is_some());
+        assert_eq!(NatStatus::default(), endpoint_v4.nat_status());
+        let endpoint_v6 = service.endpoint(&v6);
+        assert_eq!(Some(local_v6), endpoint_v6.socket());
+        assert_eq!(
+            vec![&v6],
+            endpoint_v6.keepalive().holes().collect::<Vec<_>>()
+        );
+        assert!(endpoint_v6.punched_peers().get(&v4).is_none());
+        assert_eq!(NatStatus::Public, endpoint_v6.nat_status());
+        assert_eq!(2, service.stats().active_holes);
+    }
+
+    #[test]
+    fn test_target_probes_candidates_in_priority_order() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let host: SocketAddr = "10.0.0.1:9000".parse().unwrap();
+        let relayed: SocketAddr = "198.51.100.1:4000".parse().unwrap();
+        let candidates = Candidates::new(vec![
+            Candidate::new(CandidateKind::Relayed, relayed),
+            Candidate::new(CandidateKind::Host, host),
+        ]);
+
+        block_on(service.on_relay_msg(RelayMsg(
+            initiator,
+            nonce,
+            None,
+            Some(candidates),
+            None,
+            None,
+            None,
+        )))
+        .unwrap();
+
+        // the sockets of the ENR rank between the host and relayed candidates
+        let probed = service
+            .sink()
+            .whoareyous
+            .iter()
+            .map(|(dst, _)| *dst)
+            .collect::<Vec<_>>();
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+        assert_eq!(vec![host, v4, v6, relayed], probed);
+    }
+
+    #[test]
+    fn test_initiator_sends_local_candidates() {
+        let relay = NodeId::random();
+        let candidate = Candidate::new(CandidateKind::Host, "10.0.0.2:9000".parse().unwrap());
+        let config = HolePunchConfig {
+            local_candidates: vec![candidate],
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+
+        block_on(service.on_request_time_out(relay, local_enr, nonce, target)).unwrap();
+
+        let sent = &service.sink().notifications[0].1;
+        let notif: RelayInit = Notification::rlp_decode(sent).unwrap().into();
+        let RelayInit(.., candidates, _) = notif;
+        assert_eq!(
+            Some(&[candidate][..]),
+            candidates.as_ref().map(|c| c.as_ref())
+        );
+    }
+
+    #[test]
+    fn test_target_learns_socket_observed_by_relay() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let mut events = service.subscribe();
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let observed: SocketAddr = "203.0.113.5:41000".parse().unwrap();
+
+        let notif = RelayMsg(initiator, nonce, None, None, Some(observed), None, None);
+        block_on(service.on_relay_msg(notif)).unwrap();
+
+        assert_eq!(
+            HolePunchEvent::ObservedByRelay {
+                attempt_id: None,
+                observed
+            },
+            events.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_punch_toward_family_of_target() {
+        let RelayInit(initiator, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+
+        // a relay reached over IPv4 by the initiator, reaching the target over IPv6
+        let target: SocketAddr = "[2001:db8::2]:9000".parse().unwrap();
+        assert_eq!(
+            Some(PunchToward(vec![v6])),
+            punch_toward(&initiator, None, target)
+        );
+        // and the reverse
+        let target: SocketAddr = "198.51.100.2:9000".parse().unwrap();
+        let host = Candidate::new(CandidateKind::Host, "10.0.0.1:9000".parse().unwrap());
+        assert_eq!(
+            Some(PunchToward(vec![host.addr, v4])),
+            punch_toward(&initiator, Some(&Candidates::new(vec![host])), target)
+        );
+
+        // nothing to narrow down for an initiator of a single family
+        let enr_key = CombinedKey::generate_secp256k1();
+        let v4_only = EnrBuilder::new("v4")
+            .ip4("192.0.2.1".parse().unwrap())
+            .udp4(9000)
+            .build(&enr_key)
+            .unwrap();
+        assert_eq!(None, punch_toward(&v4_only, None, target));
+    }
+
+    #[test]
+    fn test_target_punches_toward_hint_first() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+        let mut extensions = Extensions::default();
+        extensions.insert(&PunchToward(vec![v6]));
+
+        let notif = RelayMsg(initiator, nonce, None, None, None, None, Some(extensions));
+        block_on(service.on_relay_msg(notif)).unwrap();
+
+        assert_eq!(vec![(v6, nonce), (v4, nonce)], service.sink().whoareyous);
+    }
+
+    #[test]
+    fn test_target_rate_limits_per_relay() {
+        let config = HolePunchConfig {
+            rate_limit_per_relay: 1,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let abusive = NodeId::random();
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let relay_msg = |relay| RelayMsg(initiator.clone(), nonce, None, None, None, relay, None);
+
+        block_on(service.on_relay_msg(relay_msg(Some(abusive)))).unwrap();
+        assert!(matches!(
+            block_on(service.on_relay_msg(relay_msg(Some(abusive)))),
+            Err(HolePunchError::RateLimited)
+        ));
+        // other relays, and relays not telling their node id, are served
+        block_on(service.on_relay_msg(relay_msg(Some(NodeId::random())))).unwrap();
+        block_on(service.on_relay_msg(relay_msg(None))).unwrap();
+    }
+
+    #[test]
+    fn test_inbound_filters_drop_and_abort() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let denied = relay_init(target);
+        let denied_id = denied.0.node_id();
+        service.sink_mut().sessions.insert(denied_id, ());
+        let banned: SocketAddr = "203.0.113.9:9000".parse().unwrap();
+
+        service.add_inbound_filter(move |_, src| match src.socket_addr {
+            Some(addr) if addr == banned => Verdict::Drop,
+            _ => Verdict::Allow,
+        });
+        service.add_inbound_filter(move |notif, _| match notif {
+            Notification::RelayInit(RelayInit(initiator, ..))
+                if initiator.node_id() == denied_id =>
+            {
+                Verdict::Abort(ReasonCode::PolicyDenied)
+            }
+            _ => Verdict::Allow,
+        });
+
+        // dropped silently
+        let notif = relay_init(target).rlp_encode();
+        let dropped = block_on(service.handle_command(
+            ServiceCommand::<NodeId>::NotificationFrom {
+                src: NodeId::random(),
+                notif: notif.clone(),
+            },
+        ));
+        assert!(dropped.is_ok(), "node id sessions carry no socket");
+        let res = block_on(service.on_notification_from(
+            &relay_init(target).rlp_encode(),
+            &Source {
+                node_id: None,
+                socket_addr: Some(banned),
+            },
+            |_| (),
+        ));
+        assert!(matches!(res, Err(HolePunchError::Filtered)));
+
+        // aborted towards the initiator
+        let nonce = denied.2;
+        let res =
+            block_on(service.handle_command(ServiceCommand::Notification(denied.rlp_encode())));
+        assert!(matches!(res, Err(HolePunchError::Filtered)));
+        let (to, abort) = service.sink().notifications.last().unwrap();
+        assert_eq!(denied_id, *to);
+        assert_eq!(
+            Notification::RelayAbort(RelayAbort(nonce, ReasonCode::PolicyDenied, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(abort).unwrap()
+        );
+        assert_eq!(1, service.stats().punches_failed.filtered);
+        assert_eq!(1, service.stats().punches_relayed);
+    }
+
+    #[test]
+    fn test_punch_report_round_trip() {
+        let relay = NodeId::random();
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut relay_service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let (mut initiator_service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, _, nonce, ..) = relay_init(target);
+        let initiator = local_enr.node_id();
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+
+        block_on(initiator_service.on_request_time_out(relay, local_enr, nonce, target)).unwrap();
+        let relay_init = initiator_service.sink().notifications[0].1.clone();
+        block_on(relay_service.handle_command(ServiceCommand::Notification(relay_init))).unwrap();
+        block_on(
+            initiator_service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src }),
+        )
+        .unwrap();
+
+        let (to, report) = initiator_service.sink().notifications[1].clone();
+        assert_eq!(relay, to);
+        let mut events = relay_service.subscribe();
+        // only the initiator reports on its attempt, with the attempt id the relay saw
+        let forged = PunchReport(nonce, false, Some([9; ATTEMPT_ID_LENGTH]), None).rlp_encode();
+        for (src, notif) in [
+            (target, report.clone()),
+            (NodeId::random(), report.clone()),
+            (initiator, forged),
+        ] {
+            let command = ServiceCommand::NotificationFrom { src, notif };
+            assert!(matches!(
+                block_on(relay_service.handle_command(command)),
+                Err(HolePunchError::Filtered)
+            ));
+        }
+        assert!(matches!(
+            block_on(relay_service.handle_command(ServiceCommand::Notification(report.clone()))),
+            Err(HolePunchError::Filtered)
+        ));
+        assert!(events.try_recv().is_err());
+        for _ in 0..2 {
+            // a repeated report isn't counted twice
+            let command = ServiceCommand::NotificationFrom {
+                src: initiator,
+                notif: report.clone(),
+            };
+            block_on(relay_service.handle_command(command)).unwrap();
+        }
+        assert_eq!(
+            HolePunchEvent::PunchReported {
+                attempt_id: None,
+                initiator,
+                target,
+                punched: true,
+            },
+            events.try_recv().unwrap()
+        );
+        assert_eq!(1, relay_service.stats().relayed_punched);
+        assert_eq!(0, relay_service.stats().relayed_failed);
+    }
+
+    #[test]
+    fn test_relay_concurrency_per_initiator() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let config = HolePunchConfig {
+            relay_concurrency: Some(RelayConcurrency {
+                max_in_flight: 8,
+                per_initiator: 1,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(sink, config);
+        let first = relay_init(target);
+        let mut second = first.clone();
+        second.2 = [2; MESSAGE_NONCE_LENGTH];
+
+        block_on(service.on_relay_init(first.clone())).unwrap();
+        block_on(service.on_relay_init(second)).unwrap();
+        // the second attempt of the initiator waits for the first to be reported on
+        assert_eq!(1, service.stats().punches_relayed);
+        assert_eq!(1, service.relay_queue_metrics().depth);
+        assert_eq!(1, service.relay_queue_metrics().in_flight);
+
+        let report = PunchReport(first.2, true, None, None);
+        block_on(service.on_punch_report_from(report, Some(first.0.node_id()))).unwrap();
+        assert_eq!(2, service.stats().punches_relayed);
+        assert_eq!(0, service.relay_queue_metrics().depth);
+    }
+
+    #[test]
+    fn test_attempt_retries_relays_within_budget() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relays = vec![NodeId::random(), NodeId::random()];
+
+        let mut outcome =
+            block_on(service.initiate(relays.clone(), local_enr, nonce, target)).unwrap();
+        let started = service.attempts().get(&nonce).unwrap().started;
+
+        // first relay gets half of the budget
+        block_on(service.on_tick(started + DEFAULT_ATTEMPT_TIMEOUT / 2)).unwrap();
+        let sent_to = service
+            .sink()
+            .notifications
+            .iter()
+            .map(|(relay, _)| *relay)
+            .collect::<Vec<_>>();
+        assert_eq!(relays, sent_to);
+        assert_eq!(Ok(None), outcome.try_recv());
+
+        block_on(service.on_tick(started + DEFAULT_ATTEMPT_TIMEOUT)).unwrap();
+        assert_eq!(
+            Ok(Some(AttemptOutcome::Failed(FailureReason::NoWhoareyou))),
+            outcome.try_recv()
+        );
+        assert!(service.attempts().is_empty());
+        // a late WHOAREYOU is still matched
+        assert_eq!(Some(&target), service.match_whoareyou(&nonce));
+    }
+
+    #[test]
+    fn test_handle_notification_shows_decoded() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let notif = relay_init(target);
+
+        let expected = Notification::RelayInit(notif.clone());
+
+        let mut inspected = false;
+        block_on(service.handle_notification(&notif.rlp_encode(), |decoded| {
+            inspected = decoded == &expected
+        }))
+        .unwrap();
+
+        assert!(inspected);
+        assert_eq!(1, service.sink().notifications.len());
+    }
+
+    #[test]
+    fn test_attempt_id_is_echoed_and_surfaced() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let config = HolePunchConfig {
+            wire: WireConfig {
+                attempt_ids: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(sink, config);
+        let mut events = service.subscribe();
+        let attempt_id = Some([7u8; ATTEMPT_ID_LENGTH]);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(target);
+        let notif: RelayInit = RelayInit(initiator.clone(), target, nonce, attempt_id, None, None);
+        let notif = notif.rlp_encode();
+
+        block_on(service.handle_command(ServiceCommand::Notification(notif))).unwrap();
+
+        let relayed: RelayMsg = Notification::rlp_decode(&service.sink().notifications[0].1)
+            .unwrap()
+            .into();
+        assert_eq!(attempt_id, relayed.2);
+        assert_eq!(
+            HolePunchEvent::Relayed {
+                attempt_id,
+                initiator: initiator.node_id(),
+                target,
+            },
+            events.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_attempt_for_several_timed_out_requests() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relay = NodeId::random();
+        let more_nonces = vec![[2u8; MESSAGE_NONCE_LENGTH], [3u8; MESSAGE_NONCE_LENGTH]];
+
+        let _outcome = block_on(service.initiate_for_nonces(
+            vec![relay],
+            local_enr,
+            nonce,
+            more_nonces.clone(),
+            target,
+        ))
+        .unwrap();
+        let sent: RelayInit = Notification::rlp_decode(&service.sink().notifications[0].1)
+            .unwrap()
+            .into();
+        assert_eq!(
+            Some(RecentNonces(more_nonces.clone())),
+            sent.extension().and_then(Result::ok)
+        );
+
+        // the target answers the most recent request
+        let (mut target_service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let msg = sent.into_relay_msg(RelayExtras::default());
+        block_on(target_service.on_relay_msg(msg)).unwrap();
+        assert_eq!(more_nonces[1], target_service.sink().whoareyous[0].1);
+
+        // which resolves the attempt for the first
+        assert!(service.match_whoareyou(&more_nonces[1]).is_some());
+        let src = "192.0.2.2:9000".parse().unwrap();
+        let command = ServiceCommand::WhoAreYouReceived {
+            nonce: more_nonces[1],
+            src,
+        };
+        block_on(service.handle_command(command)).unwrap();
+        assert!(service.attempts().get(&nonce).is_none());
+    }
+
+    #[test]
+    fn test_initiate_punch_without_timed_out_request() {
+        let target = NodeId::random();
+        let relay = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+
+        let nonce = block_on(service.initiate_punch(target, relay)).unwrap();
+
+        let (sent_to, notif) = &service.sink().notifications[0];
+        assert_eq!(relay, *sent_to);
+        let RelayInit(_, sent_target, sent_nonce, ..) =
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(notif)
+                .unwrap()
+                .into();
+        assert_eq!((target, nonce), (sent_target, sent_nonce));
+        assert_eq!(Some(&target), service.match_whoareyou(&nonce));
+
+        let unknown = NodeId::random();
+        let res = block_on(service.initiate_punch(unknown, relay));
+        assert!(matches!(res, Err(HolePunchError::UnknownTarget(id)) if id == unknown));
+    }
+
+    #[test]
+    fn test_seeded_rng_replays_attempts() {
+        let target = NodeId::random();
+        let relay = NodeId::random();
+        let initiate = |seed| -> (MessageNonce, Option<AttemptId>) {
+            let mut sink = RecordingSink::default();
+            sink.sessions.insert(target, ());
+            let config = HolePunchConfig {
+                wire: WireConfig {
+                    attempt_ids: true,
+                    ..Default::default()
+                },
+                rng: Arc::new(SeededRng::new(seed)),
+                ..Default::default()
+            };
+            let (mut service, _tx) = HolePunchService::new(sink, config);
+            block_on(service.initiate_punch(target, relay)).unwrap();
+            let RelayInit(_, _, nonce, attempt_id, ..) =
+                Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(
+                    &service.sink().notifications[0].1,
+                )
+                .unwrap()
+                .into();
+            (nonce, attempt_id)
+        };
+
+        assert_eq!(initiate(1), initiate(1));
+        assert_ne!(initiate(1), initiate(2));
+    }
+
+    #[test]
+    fn test_full_attempt_registry_evicts_oldest() {
+        let config = HolePunchConfig {
+            attempt_capacity: 1,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relays = vec![NodeId::random()];
+
+        let mut oldest =
+            block_on(service.initiate(relays.clone(), local_enr.clone(), nonce, target)).unwrap();
+        let _newest =
+            block_on(service.initiate(relays, local_enr, [2u8; MESSAGE_NONCE_LENGTH], target))
+                .unwrap();
+
+        assert_eq!(
+            Ok(Some(AttemptOutcome::Failed(FailureReason::Evicted))),
+            oldest.try_recv()
+        );
+        assert_eq!(1, service.attempts().len());
+        assert_eq!(1, service.stats().cache_evictions.attempts);
+    }
+
+    #[test]
+    fn test_fair_scheduling_round_robins_targets() {
+        let config = HolePunchConfig {
+            attempt_scheduling: Some(FairScheduling {
+                parallelism: 2,
+                per_target: 1,
+            }),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, ..) = relay_init(NodeId::random());
+        let (busy, other) = (NodeId::random(), NodeId::random());
+        let relay = NodeId::random();
+
+        // a burst of attempts to one target queued ahead of an attempt to another
+        for (i, target) in [busy, busy, busy, other].into_iter().enumerate() {
+            let nonce = [i as u8; MESSAGE_NONCE_LENGTH];
+            block_on(service.on_request_time_out(relay, local_enr.clone(), nonce, target)).unwrap();
+        }
+
+        let nonce = |i: u8| [i; MESSAGE_NONCE_LENGTH];
+        let in_flight = |service: &HolePunchService<RecordingSink>| {
+            (0..4)
+                .filter(|i| service.attempts().get(&nonce(*i)).is_some())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![0, 3], in_flight(&service));
+        assert_eq!(2, service.attempts().queued_len());
+
+        // the next attempt to the busy target is sent once its first one resolves
+        let src = "192.0.2.1:9000".parse().unwrap();
+        block_on(service.on_whoareyou(nonce(0), src)).unwrap();
+        block_on(service.handle_command(ServiceCommand::Tick)).unwrap();
+        assert_eq!(vec![1, 3], in_flight(&service));
+        assert_eq!(1, service.attempts().queued_len());
+        assert_eq!(3, service.stats().punches_initiated);
+    }
+
+    #[test]
+    fn test_shutdown_cancels_attempts() {
+        let config = HolePunchConfig {
+            attempt_scheduling: Some(FairScheduling {
+                parallelism: 1,
+                per_target: 1,
+            }),
+            ..Default::default()
+        };
+        let (mut service, mut tx) = HolePunchService::new(RecordingSink::default(), config);
+        let mut events = service.subscribe();
+        let RelayInit(local_enr, ..) = relay_init(NodeId::random());
+        let relay = NodeId::random();
+        let hole = "192.0.2.1:9000".parse().unwrap();
+        service
+            .endpoints
+            .for_remote_mut(&hole)
+            .keepalive
+            .insert(hole);
+
+        let mut outcomes = [1, 2].map(|i| {
+            let nonce = [i; MESSAGE_NONCE_LENGTH];
+            let target = NodeId::random();
+            block_on(service.initiate(vec![relay], local_enr.clone(), nonce, target)).unwrap()
+        });
+        assert_eq!(1, service.attempts().queued_len());
+
+        block_on(service.handle_command(ServiceCommand::Shutdown {
+            notify_relays: true,
+        }))
+        .unwrap();
+
+        for outcome in outcomes.iter_mut() {
+            assert_eq!(Ok(Some(AttemptOutcome::Cancelled)), outcome.try_recv());
+        }
+        assert!(service.attempts().is_empty() && service.attempts().queued_len() == 0);
+        assert!(!service.keepalive().contains(&hole));
+        let (reported, report) = service.sink().notifications.last().unwrap();
+        assert_eq!(relay, *reported);
+        assert_eq!(
+            Notification::PunchReport(PunchReport([1; MESSAGE_NONCE_LENGTH], false, None, None)),
+            Notification::<MESSAGE_NONCE_LENGTH>::rlp_decode(report).unwrap()
+        );
+        // the events sent before shutting down are flushed, then the channel ends
+        let resolved = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, HolePunchEvent::AttemptResolved { .. }))
+            .count();
+        assert_eq!(2, resolved);
+        assert_eq!(Err(mpsc::TryRecvError::Closed), events.try_recv());
+
+        // the run loop returns once shut down
+        assert!(service.is_shut_down());
+        tx.try_send(ServiceCommand::Shutdown {
+            notify_relays: false,
+        })
+        .unwrap();
+        block_on(service.run());
+    }
+
+    #[test]
+    fn test_state_handed_over_keeps_holes() {
+        let (mut old, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let (hole, initiated) = (
+            "192.0.2.1:9000".parse().unwrap(),
+            "198.51.100.1:9000".parse().unwrap(),
+        );
+        let initiator = NodeId::random();
+        old.endpoints.for_remote_mut(&hole).keepalive.insert(hole);
+        old.endpoints
+            .for_remote_mut(&hole)
+            .punched_peers
+            .insert(hole, initiator);
+        old.endpoints
+            .for_remote_mut(&initiated)
+            .keepalive
+            .insert(initiated);
+        old.reachability.insert(initiator, Reachability::Punched);
+
+        let state = PersistedState::deserialize(&old.serialize_state()).unwrap();
+        let (mut new, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        new.restore_state(state.clone());
+
+        assert!(new.keepalive().contains(&hole));
+        assert!(new.keepalive().contains(&initiated));
+        assert_eq!(
+            Some(initiator),
+            new.punched_peers().get(&hole).map(|peer| peer.initiator)
+        );
+        assert!(new.punched_peers().get(&initiated).is_none());
+        assert!(new.reachability().get(&initiator).is_some());
+
+        // holes idle for their lifetime closed while no process kept them alive
+        let (mut late, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let mut stale = state;
+        for hole in stale.holes.iter_mut() {
+            hole.last_activity -= Duration::from_secs(DEFAULT_HOLE_PUNCH_LIFETIME);
+        }
+        late.restore_state(stale);
+        assert!(late.keepalive().is_empty());
+    }
+
+    #[test]
+    fn test_packet_too_large_not_sent() {
+        let config = HolePunchConfig {
+            max_packet_size: 100,
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+
+        let res = block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target));
+        assert!(matches!(res, Err(HolePunchError::PacketTooLarge(len)) if len > 100));
+        assert!(service.sink().notifications.is_empty());
+        assert!(service.attempts().is_empty());
+    }
+
+    #[test]
+    fn test_punch_policy_declines_targets() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let relay = NodeId::random();
+        service.sink_mut().full_buckets.insert(target);
+
+        let command = ServiceCommand::RequestTimedOut {
+            relay,
+            local_enr,
+            nonce,
+            target,
+        };
+        assert!(matches!(
+            block_on(service.handle_command(command)),
+            Err(HolePunchError::Declined(declined)) if declined == target
+        ));
+        assert!(service.sink().notifications.is_empty());
+        assert_eq!(1, service.stats().punches_failed.declined);
+
+        // the application asking for a hole explicitly overrides the policy
+        service.sink_mut().sessions.insert(target, ());
+        block_on(service.initiate_punch(target, relay)).unwrap();
+        assert_eq!(relay, service.sink().notifications[0].0);
+    }
+
+    #[test]
+    fn test_initiations_throttled_locally() {
+        let clock = ManualClock::default();
+        let config = HolePunchConfig {
+            initiation_rate: 2,
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, failing, ..) = relay_init(NodeId::random());
+        let relay = NodeId::random();
+        let initiate = |service: &mut HolePunchService<RecordingSink>, target, i| {
+            let nonce = [i; MESSAGE_NONCE_LENGTH];
+            let command = ServiceCommand::RequestTimedOut {
+                relay,
+                local_enr: local_enr.clone(),
+                nonce,
+                target,
+            };
+            block_on(service.handle_command(command))
+        };
+
+        initiate(&mut service, failing, 0).unwrap();
+        initiate(&mut service, NodeId::random(), 1).unwrap();
+        assert!(matches!(
+            initiate(&mut service, NodeId::random(), 2),
+            Err(HolePunchError::LocallyThrottled)
+        ));
+
+        // the failed target cools down after the rate limit window passed
+        clock.advance(DEFAULT_ATTEMPT_TIMEOUT);
+        block_on(service.handle_command(ServiceCommand::Tick)).unwrap();
+        assert!(matches!(
+            initiate(&mut service, failing, 3),
+            Err(HolePunchError::LocallyThrottled)
+        ));
+        initiate(&mut service, NodeId::random(), 4).unwrap();
+        // new information about the target ends the backoff early
+        assert!(service.clear_backoff(&failing));
+        initiate(&mut service, failing, 5).unwrap();
+        assert_eq!(2, service.stats().punches_failed.locally_throttled);
+    }
+
+    #[test]
+    fn test_timers_follow_manual_clock() {
+        let clock = ManualClock::default();
+        let config = HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+
+        let mut outcome =
+            block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target)).unwrap();
+        assert_eq!(Some(DEFAULT_ATTEMPT_TIMEOUT), service.next_timeout());
+
+        clock.advance(DEFAULT_ATTEMPT_TIMEOUT);
+        block_on(service.handle_command(ServiceCommand::Tick)).unwrap();
+        assert_eq!(
+            Ok(Some(AttemptOutcome::Failed(FailureReason::NoWhoareyou))),
+            outcome.try_recv()
+        );
+
+        clock.advance(DEFAULT_PENDING_NONCE_TTL);
+        assert_eq!(None, service.match_whoareyou(&nonce));
+    }
+
+    #[test]
+    fn test_initiator_finishes_punch_back_to_back() {
+        let clock = ManualClock::default();
+        let spacing = Duration::from_millis(100);
+        let config = HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            back_to_back: Some(BackToBack {
+                packets: 2,
+                spacing,
+            }),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let mut events = service.subscribe();
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let start = clock.now();
+
+        let mut outcome =
+            block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target)).unwrap();
+        block_on(service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src })).unwrap();
+
+        // the first packet goes out right away, the target isn't reported punched yet
+        assert_eq!(vec![src], service.sink().empty_packets);
+        assert_eq!(Ok(None), outcome.try_recv());
+        assert_eq!(Some(spacing), service.next_timeout());
+
+        clock.advance(spacing);
+        block_on(service.handle_command(ServiceCommand::Tick)).unwrap();
+        assert_eq!(vec![src, src], service.sink().empty_packets);
+        assert_eq!(
+            HolePunchEvent::TargetPunched {
+                attempt_id: None,
+                nonce,
+                src,
+                timings: PunchTimings {
+                    initiated: start,
+                    relay_sent: start,
+                    whoareyou_received: start,
+                    established: None,
+                },
+            },
+            events.try_recv().unwrap()
+        );
+        assert_eq!(
+            Ok(Some(AttemptOutcome::Punched { src })),
+            outcome.try_recv()
+        );
+    }
+
+    #[test]
+    fn test_echo_probe_measures_mapping() {
+        let clock = ManualClock::default();
+        let config = || HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let responder_id = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(responder_id, ());
+        let (mut requester, _tx) = HolePunchService::new(sink, config());
+        let (mut responder, _tx) = HolePunchService::new(RecordingSink::default(), config());
+        let mut events = requester.subscribe();
+        let silence = Duration::from_secs(30);
+
+        let nonce = block_on(requester.probe_mapping_lifetime(responder_id, silence)).unwrap();
+        let echo_after = requester.sink().notifications[0].1.clone();
+        let EchoAfter(requester_id, ..): EchoAfter =
+            Notification::rlp_decode(&echo_after).unwrap().into();
+        responder.sink.sessions.insert(requester_id, ());
+        block_on(responder.handle_command(ServiceCommand::Notification(echo_after))).unwrap();
+
+        // the echo waits out the silence
+        clock.advance(silence / 2);
+        block_on(responder.on_tick(clock.now())).unwrap();
+        assert!(responder.sink().notifications.is_empty());
+        clock.advance(silence / 2);
+        block_on(responder.on_tick(clock.now())).unwrap();
+        let (sent_to, echo) = responder.sink().notifications[0].clone();
+        assert_eq!(requester_id, sent_to);
+
+        block_on(requester.handle_command(ServiceCommand::Notification(echo))).unwrap();
+        assert_eq!(
+            HolePunchEvent::EchoReceived {
+                peer: responder_id,
+                nonce,
+                silence,
+            },
+            events.try_recv().unwrap()
+        );
+
+        // no echo arrives
+        let nonce = block_on(requester.probe_mapping_lifetime(responder_id, silence)).unwrap();
+        clock.advance(silence + ECHO_GRACE);
+        block_on(requester.on_tick(clock.now())).unwrap();
+        assert_eq!(
+            HolePunchEvent::EchoMissed {
+                peer: responder_id,
+                nonce,
+                silence,
+            },
+            events.try_recv().unwrap()
+        );
+
+        let too_long = EchoAfter(requester_id, nonce, DEFAULT_MAX_ECHO_DELAY * 2, None);
+        let res = block_on(responder.on_echo_after(too_long));
+        assert!(matches!(res, Err(HolePunchError::EchoRefused)));
+    }
+
+    #[test]
+    fn test_confirm_established_completes_attempt() {
+        let clock = ManualClock::default();
+        let spacing = Duration::from_millis(100);
+        let config = HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            back_to_back: Some(BackToBack {
+                packets: 3,
+                spacing,
+            }),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let mut events = service.subscribe();
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let start = clock.now();
+
+        let mut outcome =
+            block_on(service.initiate(vec![NodeId::random()], local_enr, nonce, target)).unwrap();
+        clock.advance(spacing);
+        block_on(service.handle_command(ServiceCommand::WhoAreYouReceived { nonce, src })).unwrap();
+        clock.advance(spacing / 2);
+        block_on(service.handle_command(ServiceCommand::Established { target })).unwrap();
+
+        // the remaining back-to-back packets are dropped
+        assert!(service.finishing_punches.is_empty());
+        assert_eq!(vec![src], service.sink().empty_packets);
+        assert_eq!(
+            Ok(Some(AttemptOutcome::Punched { src })),
+            outcome.try_recv()
+        );
+        assert!(service.keepalive().contains(&src));
+        let established = std::iter::from_fn(|| events.try_recv().ok())
+            .find(|event| matches!(event, HolePunchEvent::Established { .. }));
+        assert_eq!(
+            Some(HolePunchEvent::Established {
+                attempt_id: None,
+                nonce,
+                target,
+                src,
+                time_to_punch: spacing,
+                time_to_establish: spacing + spacing / 2,
+                timings: PunchTimings {
+                    initiated: start,
+                    relay_sent: start,
+                    whoareyou_received: start + spacing,
+                    established: Some(start + spacing + spacing / 2),
+                },
+            }),
+            established
+        );
+        let stats = service.stats();
+        assert_eq!(1, stats.relay_latency.count());
+        assert_eq!(spacing, stats.relay_latency.sum());
+        assert_eq!(1, stats.time_to_establish.count());
+        assert!(!service.confirm_established(target));
+    }
+
+    #[test]
+    fn test_initiate_skips_unsupported_peers() {
+        let unsupported_relay = NodeId::random();
+        let relay = NodeId::random();
+        let unsupported_target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.unsupported.insert(unsupported_relay);
+        sink.unsupported.insert(unsupported_target);
+        let (mut service, _tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let RelayInit(local_enr, target, nonce, ..) = relay_init(NodeId::random());
+
+        let res =
+            block_on(service.initiate(vec![relay], local_enr.clone(), nonce, unsupported_target));
+        assert!(matches!(res, Err(HolePunchError::Unsupported(id)) if id == unsupported_target));
+        let res =
+            block_on(service.initiate(vec![unsupported_relay], local_enr.clone(), nonce, target));
+        assert!(matches!(res, Err(HolePunchError::Unsupported(id)) if id == unsupported_relay));
+
+        let _outcome =
+            block_on(service.initiate(vec![unsupported_relay, relay], local_enr, nonce, target))
+                .unwrap();
+        assert_eq!(relay, service.sink().notifications[0].0);
+        assert_eq!(1, service.attempts().get(&nonce).unwrap().tries);
+    }
+
+    #[test]
+    fn test_target_repeats_whoareyou() {
+        let config = HolePunchConfig {
+            punch_pattern: PunchPattern {
+                repeats: 2,
+                spacing: Duration::from_millis(100),
+                leading_empty_packets: 1,
+            },
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let v6: SocketAddr = "[2001:db8::1]:9000".parse().unwrap();
+
+        block_on(service.on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None)))
+            .unwrap();
+        assert_eq!(vec![v4, v6], service.sink().empty_packets);
+        assert_eq!(2, service.sink().whoareyous.len());
+
+        let now = Instant::now();
+        block_on(service.on_tick(now + Duration::from_millis(100))).unwrap();
+        assert_eq!(4, service.sink().whoareyous.len());
+        block_on(service.on_tick(now + Duration::from_millis(200))).unwrap();
+        assert_eq!(6, service.sink().whoareyous.len());
+        assert!(service.next_timeout().is_some());
+    }
+
+    #[test]
+    fn test_target_punches_once_per_attempt() {
+        let clock = ManualClock::default();
+        let config = HolePunchConfig {
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let relayed = |relay| {
+            RelayMsg(
+                initiator.clone(),
+                nonce,
+                None,
+                None,
+                None,
+                Some(relay),
+                None,
+            )
+        };
+
+        // the initiator fanned out over three relays
+        for _ in 0..3 {
+            block_on(service.on_relay_msg(relayed(NodeId::random()))).unwrap();
+        }
+        assert_eq!(2, service.sink().whoareyous.len());
+        assert_eq!(2, service.stats().duplicate_relay_msgs);
+
+        // a later attempt with the same nonce is served again
+        clock.advance(DEFAULT_SERVED_ATTEMPT_TTL);
+        block_on(service.on_relay_msg(relayed(NodeId::random()))).unwrap();
+        assert_eq!(4, service.sink().whoareyous.len());
+    }
+
+    #[test]
+    fn test_punch_bursts_paced() {
+        let clock = ManualClock::default();
+        let config = HolePunchConfig {
+            punch_pattern: PunchPattern {
+                leading_empty_packets: 1,
+                ..Default::default()
+            },
+            burst_pacing: Some(BurstPacing {
+                packets_per_ms: 1,
+                burst_cap: 2,
+            }),
+            clock: Arc::new(clock.clone()),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let start = clock.now();
+
+        // an empty packet and a WHOAREYOU to each of the two paths, two past the burst cap
+        let mut punch = service
+            .on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None))
+            .boxed();
+        let res = loop {
+            if let Some(res) = (&mut punch).now_or_never() {
+                break res;
+            }
+            clock.advance(Duration::from_millis(1));
+        };
+        drop(punch);
+        res.unwrap();
+
+        assert_eq!(Duration::from_millis(2), clock.now() - start);
+        assert_eq!(2, service.sink().empty_packets.len());
+        assert_eq!(2, service.sink().whoareyous.len());
+    }
+
+    #[test]
+    fn test_target_punches_with_opaque_payloads() {
+        let config = HolePunchConfig {
+            punch_pattern: PunchPattern {
+                leading_empty_packets: 1,
+                ..Default::default()
+            },
+            punch_payload: PunchPayload::Custom(Arc::new(|_, _, nonce| nonce.to_vec())),
+            keepalive_payload: KeepalivePayload::Template(vec![0xc0]),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+
+        block_on(service.on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None)))
+            .unwrap();
+        block_on(service.on_hole_punch_expired(v4)).unwrap();
+
+        let sink = service.sink();
+        assert!(sink.whoareyous.is_empty());
+        assert!(sink.empty_packets.is_empty());
+        assert_eq!((v4, vec![0xc0]), sink.packets[0]);
+        assert_eq!((v4, nonce.to_vec()), sink.packets[1]);
+        assert_eq!((v4, vec![0xc0]), *sink.packets.last().unwrap());
+    }
+
+    #[test]
+    fn test_relayed_punches_reveal_firewall() {
+        let (mut service, _tx) =
+            HolePunchService::new(RecordingSink::default(), HolePunchConfig::default());
+        service.set_nat_status(NatStatus::Public);
+
+        for _ in 0..DEFAULT_FIREWALL_EVIDENCE {
+            let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+            let notif = RelayMsg(initiator, nonce, None, None, None, None, None);
+            block_on(service.on_relay_msg(notif)).unwrap();
+        }
+        assert_eq!(NatStatus::Firewalled, service.nat_status());
+        let stats = service.stats();
+        assert_eq!(1, stats.target_punches_firewall);
+        assert_eq!(0, stats.target_punches_nat);
+
+        service.set_nat_status(NatStatus::BehindNat);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let notif = RelayMsg(initiator, nonce, None, None, None, None, None);
+        block_on(service.on_relay_msg(notif)).unwrap();
+        assert_eq!(1, service.stats().target_punches_nat);
+    }
+
+    #[test]
+    fn test_dispatcher_decodes_off_service() {
+        let target = NodeId::random();
+        let mut sink = RecordingSink::default();
+        sink.sessions.insert(target, ());
+        let (mut service, tx) = HolePunchService::new(sink, HolePunchConfig::default());
+        let mut dispatcher = service.dispatcher(tx);
+        let notif = relay_init(target);
+        let initiator = notif.0.node_id();
+
+        let res = block_on(dispatcher.dispatch(&initiator, &[0xff; 8]));
+        assert!(matches!(res, Err(DispatchError::Decode(_))));
+
+        block_on(dispatcher.dispatch(&initiator, &notif.to_rlp())).unwrap();
+        let command = block_on(service.commands.next()).unwrap();
+        assert!(matches!(
+            &command,
+            ServiceCommand::Decoded { src, notif: Notification::RelayInit(_) }
+                if src.node_id == Some(initiator)
+        ));
+        block_on(service.handle_command(command)).unwrap();
+        assert_eq!(1, service.stats().punches_relayed);
+    }
+
+    #[test]
+    fn test_classify_inbound() {
+        let config = HolePunchConfig {
+            punch_payload: PunchPayload::Template(vec![0xaa]),
+            keepalive_payload: KeepalivePayload::Template(vec![0xc0]),
+            ..Default::default()
+        };
+        let (mut service, _tx) = HolePunchService::new(RecordingSink::default(), config);
+        let RelayInit(initiator, _, nonce, ..) = relay_init(NodeId::random());
+        let initiator_id = initiator.node_id();
+        let v4: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let other: SocketAddr = "198.51.100.1:9000".parse().unwrap();
+
+        block_on(service.on_relay_msg(RelayMsg(initiator, nonce, None, None, None, None, None)))
+            .unwrap();
+
+        let peer = Some(initiator_id);
+        assert_eq!(
+            InboundKind::Keepalive(peer),
+            service.classify_inbound(&[], v4)
+        );
+        assert_eq!(
+            InboundKind::Keepalive(peer),
+            service.classify_inbound(&[0xc0], v4)
+        );
+        assert_eq!(
+            InboundKind::PunchProbe(None),
+            service.classify_inbound(&[0xaa], other)
+        );
+        assert_eq!(
+            InboundKind::Discv5(peer),
+            service.classify_inbound(&[0u8; 63], v4)
+        );
+        assert_eq!(
+            InboundKind::Discv5(None),
+            service.classify_inbound(&[0u8; 63], other)
+        );
+    }
+}
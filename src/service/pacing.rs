@@ -0,0 +1,109 @@
+use crate::{clock::system_clock, SharedClock};
+use std::time::{Duration, Instant};
+
+/// Default max number of packets of a burst sent per millisecond.
+pub const DEFAULT_PACING_RATE: u32 = 1;
+/// Default max number of packets of a burst sent back-to-back before pacing sets in.
+pub const DEFAULT_PACING_BURST_CAP: u32 = 8;
+
+/// Shapes the packet bursts of the service, like the leading empty packets and WHOAREYOUs of a
+/// punch raced to several paths or the re-punch of all holes after a NAT rebind. Dozens of
+/// packets sent within microseconds may trip host firewalls or the flood protection of NATs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BurstPacing {
+    /// Max packets sent per millisecond once the burst cap is used up.
+    pub packets_per_ms: u32,
+    /// Max packets sent back-to-back, refilled at `packets_per_ms`.
+    pub burst_cap: u32,
+}
+
+impl Default for BurstPacing {
+    fn default() -> Self {
+        BurstPacing {
+            packets_per_ms: DEFAULT_PACING_RATE,
+            burst_cap: DEFAULT_PACING_BURST_CAP,
+        }
+    }
+}
+
+impl BurstPacing {
+    /// The time between packets once the burst cap is used up.
+    fn interval(&self) -> Duration {
+        Duration::from_millis(1) / self.packets_per_ms.max(1)
+    }
+}
+
+/// Spaces packets according to a [`BurstPacing`], as a token bucket holding `burst_cap` packets.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    pacing: BurstPacing,
+    /// The time the bucket is full again, given the packets sent so far.
+    full_at: Instant,
+    clock: SharedClock,
+}
+
+impl Pacer {
+    pub fn new(pacing: BurstPacing) -> Self {
+        let clock = system_clock();
+        Pacer {
+            pacing,
+            full_at: clock.now(),
+            clock,
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.full_at = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    pub fn pacing(&self) -> BurstPacing {
+        self.pacing
+    }
+
+    /// Reserves a packet. Returns the time it may be sent at, `None` if right away.
+    pub fn reserve(&mut self) -> Option<Instant> {
+        let now = self.clock.now();
+        let interval = self.pacing.interval();
+        let window = interval * self.pacing.burst_cap.max(1);
+        let full_at = self.full_at.max(now);
+        // the packet may go once the bucket has room for it
+        let send_at = (full_at + interval).checked_sub(window).unwrap_or(now);
+        self.full_at = full_at + interval;
+        (send_at > now).then_some(send_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::ManualClock, Clock};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_pacer_caps_burst() {
+        let clock = ManualClock::default();
+        let mut pacer = Pacer::new(BurstPacing {
+            packets_per_ms: 2,
+            burst_cap: 3,
+        })
+        .with_clock(Arc::new(clock.clone()));
+        let start = clock.now();
+        let half_ms = Duration::from_micros(500);
+
+        for _ in 0..3 {
+            assert_eq!(None, pacer.reserve());
+        }
+        assert_eq!(Some(start + half_ms), pacer.reserve());
+        assert_eq!(Some(start + 2 * half_ms), pacer.reserve());
+
+        // the bucket refills while idle
+        clock.advance(Duration::from_millis(10));
+        for _ in 0..3 {
+            assert_eq!(None, pacer.reserve());
+        }
+        assert!(pacer.reserve().is_some());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{Enr, MessageNonce};
+use std::{fmt, net::SocketAddr, sync::Arc};
+
+/// Builds the packet that punches a hole for the initiator at the given socket.
+pub type PunchPayloadFn = Arc<dyn Fn(SocketAddr, &Enr, MessageNonce) -> Vec<u8> + Send + Sync>;
+/// Builds the packet that keeps the hole to the given socket open.
+pub type KeepalivePayloadFn = Arc<dyn Fn(SocketAddr) -> Vec<u8> + Send + Sync>;
+
+/// The packet the target sends to punch a hole for the initiator. Protocols other than discv5
+/// riding the punched hole, for example QUIC which requires the first inbound packet to be a
+/// valid Initial, supply their own.
+#[derive(Clone, Default)]
+pub enum PunchPayload {
+    /// A WHOAREYOU, see [`super::NotificationSink::send_whoareyou`].
+    #[default]
+    WhoAreYou,
+    /// The same opaque packet to every initiator.
+    Template(Vec<u8>),
+    /// An opaque packet built per initiator.
+    Custom(PunchPayloadFn),
+}
+
+impl fmt::Debug for PunchPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PunchPayload::WhoAreYou => write!(f, "WhoAreYou"),
+            PunchPayload::Template(packet) => write!(f, "Template(0x{})", hex::encode(packet)),
+            PunchPayload::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// The packet sent to keep a punched hole open, and to open the local NAT before punching.
+#[derive(Clone, Default)]
+pub enum KeepalivePayload {
+    /// An empty packet, see [`super::NotificationSink::send_empty_packet`].
+    #[default]
+    Empty,
+    /// The same opaque packet to every peer.
+    Template(Vec<u8>),
+    /// An opaque packet built per peer.
+    Custom(KeepalivePayloadFn),
+}
+
+impl fmt::Debug for KeepalivePayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeepalivePayload::Empty => write!(f, "Empty"),
+            KeepalivePayload::Template(packet) => {
+                write!(f, "Template(0x{})", hex::encode(packet))
+            }
+            KeepalivePayload::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
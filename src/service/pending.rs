@@ -0,0 +1,139 @@
+use crate::{clock::system_clock, MessageNonce, SharedClock};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Default time the nonce of a timed out request is matched against incoming WHOAREYOUs.
+pub const DEFAULT_PENDING_NONCE_TTL: Duration = Duration::from_secs(10);
+
+/// The nonces of the timed out requests that this node initiated hole punch attempts for,
+/// mapped to the session with the target each request was sent over. Outlives the attempts, so
+/// that repeated and late WHOAREYOUs from the target are still matched.
+#[derive(Debug, Clone)]
+pub struct PendingNonceTable<I> {
+    pending: HashMap<MessageNonce, (I, Instant)>,
+    ttl: Duration,
+    clock: SharedClock,
+}
+
+impl<I> Default for PendingNonceTable<I> {
+    fn default() -> Self {
+        Self::new(DEFAULT_PENDING_NONCE_TTL)
+    }
+}
+
+impl<I> PendingNonceTable<I> {
+    pub fn new(ttl: Duration) -> Self {
+        PendingNonceTable {
+            pending: HashMap::new(),
+            ttl,
+            clock: system_clock(),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records that the request with the given nonce to `target` timed out. Returns the target
+    /// previously recorded under the same nonce, if any.
+    pub fn insert(&mut self, nonce: MessageNonce, target: I) -> Option<I> {
+        self.pending
+            .insert(nonce, (target, self.clock.now()))
+            .map(|(target, _)| target)
+    }
+
+    /// Returns the session with the target of the timed out request with the given nonce, if a
+    /// WHOAREYOU with the nonce is expected.
+    pub fn get(&self, nonce: &MessageNonce) -> Option<&I> {
+        let (target, inserted) = self.pending.get(nonce)?;
+        (self.clock.now().saturating_duration_since(*inserted) < self.ttl).then_some(target)
+    }
+
+    /// Stops expecting a WHOAREYOU with the given nonce.
+    pub fn remove(&mut self, nonce: &MessageNonce) -> Option<I> {
+        self.pending.remove(nonce).map(|(target, _)| target)
+    }
+
+    /// Number of nonces expected in a WHOAREYOU.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns true if no WHOAREYOUs are expected.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Forgets the nonces that have outlived the time to live at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        let ttl = self.ttl;
+        self.pending
+            .retain(|_, (_, inserted)| now.saturating_duration_since(*inserted) < ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::ManualClock, Clock, MESSAGE_NONCE_LENGTH};
+    use std::sync::Arc;
+
+    fn table(clock: &ManualClock) -> PendingNonceTable<u8> {
+        PendingNonceTable::new(Duration::from_secs(10)).with_clock(Arc::new(clock.clone()))
+    }
+
+    #[test]
+    fn test_get_expires_after_ttl() {
+        let clock = ManualClock::default();
+        let mut table = table(&clock);
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+        table.insert(nonce, 7);
+
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(Some(&7), table.get(&nonce));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(None, table.get(&nonce));
+        // expired, but kept until pruned
+        assert_eq!(1, table.len());
+    }
+
+    #[test]
+    fn test_prune_removes_expired() {
+        let clock = ManualClock::default();
+        let mut table = table(&clock);
+        let (old, new) = ([1u8; MESSAGE_NONCE_LENGTH], [2u8; MESSAGE_NONCE_LENGTH]);
+        table.insert(old, 1);
+        clock.advance(Duration::from_secs(5));
+        table.insert(new, 2);
+
+        clock.advance(Duration::from_secs(5));
+        table.prune(clock.now());
+        assert_eq!(1, table.len());
+        assert_eq!(None, table.remove(&old));
+        assert_eq!(Some(&2), table.get(&new));
+
+        clock.advance(Duration::from_secs(5));
+        table.prune(clock.now());
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_insert_returns_displaced_target() {
+        let clock = ManualClock::default();
+        let mut table = table(&clock);
+        let nonce = [1u8; MESSAGE_NONCE_LENGTH];
+
+        assert_eq!(None, table.insert(nonce, 1));
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(Some(1), table.insert(nonce, 2));
+        // the replacement restarts the time to live
+        clock.advance(Duration::from_secs(9));
+        assert_eq!(Some(&2), table.get(&nonce));
+        assert_eq!(Some(2), table.remove(&nonce));
+        assert!(table.is_empty());
+    }
+}
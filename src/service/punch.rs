@@ -0,0 +1,179 @@
+use super::PunchTimings;
+use crate::{AttemptId, Enr, MessageNonce, NodeId};
+use std::{
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Default spacing between repeated WHOAREYOUs.
+pub const DEFAULT_PUNCH_SPACING: Duration = Duration::from_millis(200);
+
+/// How the target punches a hole for the initiator. Port-restricted cone NATs may need several
+/// packets to the initiator before accepting inbound traffic from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunchPattern {
+    /// The number of times the WHOAREYOU is repeated after the first one.
+    pub repeats: u8,
+    /// The time between repeated WHOAREYOUs.
+    pub spacing: Duration,
+    /// The number of empty packets sent to the initiator before the first WHOAREYOU.
+    pub leading_empty_packets: u8,
+}
+
+impl Default for PunchPattern {
+    fn default() -> Self {
+        PunchPattern {
+            repeats: 0,
+            spacing: DEFAULT_PUNCH_SPACING,
+            leading_empty_packets: 0,
+        }
+    }
+}
+
+/// How the initiator finishes a punch once the WHOAREYOU from the target arrived. Port-restricted
+/// NATs in front of the target may only accept the initiator after a packet from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackToBack {
+    /// The number of packets sent to the target, the first one right away.
+    pub packets: u8,
+    /// The time between the packets.
+    pub spacing: Duration,
+}
+
+impl Default for BackToBack {
+    fn default() -> Self {
+        BackToBack {
+            packets: 3,
+            spacing: DEFAULT_PUNCH_SPACING,
+        }
+    }
+}
+
+/// The next packet of a back-to-back punch due to be sent to the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinishingPunch {
+    pub due: Instant,
+    pub dst: SocketAddr,
+    pub nonce: MessageNonce,
+    pub attempt_id: Option<AttemptId>,
+    /// The number of packets left to send, including this one.
+    pub remaining: u8,
+    pub timings: PunchTimings,
+}
+
+/// The back-to-back punches in progress, see [`BackToBack`].
+#[derive(Debug, Clone, Default)]
+pub struct FinishingPunches {
+    punches: Vec<FinishingPunch>,
+}
+
+impl FinishingPunches {
+    pub fn insert(&mut self, punch: FinishingPunch) {
+        self.punches.push(punch);
+    }
+
+    /// Removes and returns the punches due at `now`.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<FinishingPunch> {
+        let (due, pending) = self
+            .punches
+            .drain(..)
+            .partition(|punch: &FinishingPunch| punch.due <= now);
+        self.punches = pending;
+        due
+    }
+
+    /// The point in time the next packet is due, if any punches are in progress.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.punches.iter().map(|punch| punch.due).min()
+    }
+
+    /// Removes and returns all punches in progress.
+    pub fn drain(&mut self) -> Vec<FinishingPunch> {
+        std::mem::take(&mut self.punches)
+    }
+
+    /// Removes and returns the punch to `dst`, if one is in progress.
+    pub fn remove(&mut self, dst: &SocketAddr) -> Option<FinishingPunch> {
+        let index = self.punches.iter().position(|punch| punch.dst == *dst)?;
+        Some(self.punches.swap_remove(index))
+    }
+
+    /// Number of punches in progress.
+    pub fn len(&self) -> usize {
+        self.punches.len()
+    }
+
+    /// Returns true if no punches are in progress.
+    pub fn is_empty(&self) -> bool {
+        self.punches.is_empty()
+    }
+}
+
+/// A repeated WHOAREYOU due to be sent.
+#[derive(Debug, Clone)]
+pub struct ScheduledPunch {
+    pub due: Instant,
+    pub dst: SocketAddr,
+    pub initiator: Enr,
+    pub nonce: MessageNonce,
+}
+
+/// The repeated WHOAREYOUs of the [`PunchPattern`] waiting to be sent.
+#[derive(Debug, Clone, Default)]
+pub struct ScheduledPunches {
+    punches: Vec<ScheduledPunch>,
+}
+
+impl ScheduledPunches {
+    /// Schedules the repeats of a WHOAREYOU sent to `dst` at `now` according to the pattern.
+    pub fn schedule(
+        &mut self,
+        pattern: &PunchPattern,
+        now: Instant,
+        dst: SocketAddr,
+        initiator: &Enr,
+        nonce: MessageNonce,
+    ) {
+        for repeat in 1..=pattern.repeats as u32 {
+            self.punches.push(ScheduledPunch {
+                due: now + pattern.spacing * repeat,
+                dst,
+                initiator: initiator.clone(),
+                nonce,
+            });
+        }
+    }
+
+    /// Removes and returns the punches due at `now`.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<ScheduledPunch> {
+        let (due, pending) = self
+            .punches
+            .drain(..)
+            .partition(|punch: &ScheduledPunch| punch.due <= now);
+        self.punches = pending;
+        due
+    }
+
+    /// The point in time the next punch is due, if any are scheduled.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.punches.iter().map(|punch| punch.due).min()
+    }
+
+    /// Drops the punches scheduled for `initiator`. Returns the number of punches dropped.
+    pub fn cancel(&mut self, initiator: &NodeId) -> usize {
+        let len = self.punches.len();
+        self.punches
+            .retain(|punch| punch.initiator.node_id() != *initiator);
+        len - self.punches.len()
+    }
+
+    /// Number of scheduled punches.
+    pub fn len(&self) -> usize {
+        self.punches.len()
+    }
+
+    /// Returns true if no punches are scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.punches.is_empty()
+    }
+}
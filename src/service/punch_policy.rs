@@ -0,0 +1,133 @@
+use super::Reachability;
+use crate::NodeId;
+use std::fmt::Debug;
+
+/// Default max log2 distance of targets punched for by [`DistanceHeuristic`]. The three furthest
+/// buckets hold seven eighths of the network, so they are full of peers reachable without
+/// punching.
+pub const DEFAULT_PUNCH_MAX_DISTANCE: u64 = 253;
+
+/// What is known about the target of a timed out request, see [`PunchPolicy`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PunchContext {
+    /// The log2 distance of the target from the local node, the bucket of the routing table it
+    /// goes in. `None` if the target is the local node.
+    pub distance: Option<u64>,
+    /// Whether the routing table has room for the target, if the sink knows, see
+    /// [`super::NotificationSink::is_table_candidate`].
+    pub table_candidate: Option<bool>,
+    /// Number of peers that passed the target to this node in a NODES response, see
+    /// [`super::RelayCandidateBook`].
+    pub relay_candidates: usize,
+    /// How the target was last reached, if ever, see [`super::ReachabilityCache`].
+    pub last_reached: Option<Reachability>,
+}
+
+impl PunchContext {
+    /// The log2 distance between two node ids, `None` if they are equal.
+    pub fn log2_distance(a: &NodeId, b: &NodeId) -> Option<u64> {
+        let (a, b) = (a.raw(), b.raw());
+        let leading_zeros = a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| a ^ b)
+            .position(|byte| byte != 0)
+            .map(|index| index as u32 * 8 + (a[index] ^ b[index]).leading_zeros())?;
+        Some(u64::from(256 - leading_zeros))
+    }
+}
+
+/// Decides which targets of timed out requests are worth punching a hole to. Attempts the
+/// application asks for explicitly, see [`crate::NatHolePunch::initiate_punch`], aren't subject to
+/// it.
+pub trait PunchPolicy: Debug + Send + Sync {
+    /// Returns true if a hole should be punched to `target`.
+    fn should_punch(&self, target: &NodeId, context: &PunchContext) -> bool;
+}
+
+/// Punches for every timed out request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysPunch;
+
+impl PunchPolicy for AlwaysPunch {
+    fn should_punch(&self, _target: &NodeId, _context: &PunchContext) -> bool {
+        true
+    }
+}
+
+/// The default policy. Follows the routing table if the sink knows whether it has room for the
+/// target, and punches to peers reached before regardless of their distance. Otherwise only
+/// punches to targets within `max_distance`, in the sparse buckets close to the local node.
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceHeuristic {
+    pub max_distance: u64,
+}
+
+impl Default for DistanceHeuristic {
+    fn default() -> Self {
+        DistanceHeuristic {
+            max_distance: DEFAULT_PUNCH_MAX_DISTANCE,
+        }
+    }
+}
+
+impl PunchPolicy for DistanceHeuristic {
+    fn should_punch(&self, _target: &NodeId, context: &PunchContext) -> bool {
+        if let Some(table_candidate) = context.table_candidate {
+            return table_candidate;
+        }
+        if matches!(
+            context.last_reached,
+            Some(Reachability::Direct | Reachability::Punched)
+        ) {
+            return true;
+        }
+        context
+            .distance
+            .is_some_and(|distance| distance <= self.max_distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_heuristic() {
+        let local = NodeId::new(&[0; 32]);
+        let mut far = [0; 32];
+        far[0] = 0x80;
+        let mut close = [0; 32];
+        close[31] = 1;
+        assert_eq!(
+            Some(256),
+            PunchContext::log2_distance(&local, &NodeId::new(&far))
+        );
+        assert_eq!(
+            Some(1),
+            PunchContext::log2_distance(&local, &NodeId::new(&close))
+        );
+        assert_eq!(None, PunchContext::log2_distance(&local, &local));
+
+        let policy = DistanceHeuristic::default();
+        let target = NodeId::random();
+        let context = |distance| PunchContext {
+            distance: Some(distance),
+            ..Default::default()
+        };
+        assert!(policy.should_punch(&target, &context(DEFAULT_PUNCH_MAX_DISTANCE)));
+        assert!(!policy.should_punch(&target, &context(256)));
+        // a peer reached before is worth a hole wherever it is
+        let reached = PunchContext {
+            last_reached: Some(Reachability::Punched),
+            ..context(256)
+        };
+        assert!(policy.should_punch(&target, &reached));
+        // the routing table has the last word
+        let full_bucket = PunchContext {
+            table_candidate: Some(false),
+            ..reached
+        };
+        assert!(!policy.should_punch(&target, &full_bucket));
+    }
+}
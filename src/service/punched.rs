@@ -0,0 +1,127 @@
+use crate::{clock::system_clock, NodeId, SharedClock};
+use std::{collections::HashMap, net::SocketAddr, time::Instant};
+
+/// An initiator this node punched a hole for as target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunchedPeer {
+    pub initiator: NodeId,
+    /// The time the hole was punched.
+    pub established: Instant,
+}
+
+/// The initiators this node punched holes for, indexed by their reflexive socket, so that the
+/// packet demultiplexer can associate packets arriving through a punched hole with the session
+/// the punch set up.
+#[derive(Debug, Clone)]
+pub struct PunchedPeers {
+    peers: HashMap<SocketAddr, PunchedPeer>,
+    clock: SharedClock,
+}
+
+impl Default for PunchedPeers {
+    fn default() -> Self {
+        PunchedPeers {
+            peers: HashMap::new(),
+            clock: system_clock(),
+        }
+    }
+}
+
+impl PunchedPeers {
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Records that a hole was punched for `initiator` at its reflexive socket `src`. Returns the
+    /// peer previously recorded at the socket, if any.
+    pub fn insert(&mut self, src: SocketAddr, initiator: NodeId) -> Option<PunchedPeer> {
+        self.peers.insert(
+            src,
+            PunchedPeer {
+                initiator,
+                established: self.clock.now(),
+            },
+        )
+    }
+
+    /// Returns the initiator a hole was punched for at `src`.
+    pub fn get(&self, src: &SocketAddr) -> Option<&PunchedPeer> {
+        self.peers.get(src)
+    }
+
+    pub fn remove(&mut self, src: &SocketAddr) -> Option<PunchedPeer> {
+        self.peers.remove(src)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&SocketAddr, &PunchedPeer)> {
+        self.peers.iter()
+    }
+
+    /// Number of punched peers.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Returns true if no peers are punched.
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::ManualClock, Clock};
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_insert_replace_remove() {
+        let clock = ManualClock::default();
+        let mut peers = PunchedPeers::default().with_clock(Arc::new(clock.clone()));
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let other: SocketAddr = "198.51.100.8:9000".parse().unwrap();
+        let (first, second) = (NodeId::random(), NodeId::random());
+
+        assert_eq!(None, peers.insert(src, first));
+        assert_eq!(None, peers.insert(other, second));
+        assert_eq!(2, peers.len());
+        assert_eq!(Some(first), peers.get(&src).map(|peer| peer.initiator));
+
+        // another initiator punching from the same socket replaces the first
+        let replaced = peers.insert(src, second).unwrap();
+        assert_eq!(first, replaced.initiator);
+        assert_eq!(Some(second), peers.get(&src).map(|peer| peer.initiator));
+        assert_eq!(2, peers.len());
+
+        assert_eq!(Some(second), peers.remove(&src).map(|peer| peer.initiator));
+        assert_eq!(None, peers.get(&src));
+        assert_eq!(None, peers.remove(&src));
+        assert_eq!(
+            vec![&other],
+            peers.iter().map(|(src, _)| src).collect::<Vec<_>>()
+        );
+        peers.remove(&other);
+        assert!(peers.is_empty());
+    }
+
+    #[test]
+    fn test_established_from_clock() {
+        let clock = ManualClock::default();
+        let mut peers = PunchedPeers::default().with_clock(Arc::new(clock.clone()));
+        let src: SocketAddr = "198.51.100.7:9000".parse().unwrap();
+        let start = clock.now();
+
+        peers.insert(src, NodeId::random());
+        assert_eq!(start, peers.get(&src).unwrap().established);
+
+        clock.advance(Duration::from_secs(30));
+        let replaced = peers.insert(src, NodeId::random()).unwrap();
+        assert_eq!(start, replaced.established);
+        assert_eq!(
+            start + Duration::from_secs(30),
+            peers.get(&src).unwrap().established
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{clock::system_clock, NodeId, SharedClock};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// The holes punched towards the different address families of one initiator, of which only the
+/// one the initiator answers over is kept.
+#[derive(Debug, Clone)]
+struct Race {
+    paths: Vec<SocketAddr>,
+    started: Instant,
+}
+
+/// Tracks the hole punch paths raced towards initiators with both an IPv4 and an IPv6 socket.
+/// Races are indexed by their paths, so resolving on a received packet is a lookup.
+#[derive(Debug, Clone)]
+pub struct PathRaces {
+    races: HashMap<NodeId, Race>,
+    by_path: HashMap<SocketAddr, NodeId>,
+    timeout: Duration,
+    clock: SharedClock,
+}
+
+impl PathRaces {
+    pub fn new(timeout: Duration) -> Self {
+        PathRaces {
+            races: HashMap::new(),
+            by_path: HashMap::new(),
+            timeout,
+            clock: system_clock(),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Starts racing the given paths to `initiator`, replacing any race to it. A single path is
+    /// no race and isn't tracked. A path already raced to another initiator is raced to this one.
+    pub fn start(&mut self, initiator: NodeId, paths: Vec<SocketAddr>) {
+        if paths.len() < 2 {
+            return;
+        }
+        if let Some(race) = self.races.remove(&initiator) {
+            self.unindex(&initiator, &race);
+        }
+        for path in &paths {
+            self.by_path.insert(*path, initiator);
+        }
+        self.races.insert(
+            initiator,
+            Race {
+                paths,
+                started: self.clock.now(),
+            },
+        );
+    }
+
+    /// Resolves the race that `src` is a path of, if any, returning the losing paths.
+    pub fn resolve(&mut self, src: &SocketAddr) -> Option<Vec<SocketAddr>> {
+        let initiator = self.by_path.get(src).copied()?;
+        let race = self.races.remove(&initiator)?;
+        self.unindex(&initiator, &race);
+        Some(race.paths.into_iter().filter(|path| path != src).collect())
+    }
+
+    /// Number of races in progress.
+    pub fn len(&self) -> usize {
+        self.races.len()
+    }
+
+    /// Returns true if no races are in progress.
+    pub fn is_empty(&self) -> bool {
+        self.races.is_empty()
+    }
+
+    /// Forgets the races that haven't resolved within the timeout at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        let timeout = self.timeout;
+        let by_path = &mut self.by_path;
+        self.races.retain(|initiator, race| {
+            let live = now.saturating_duration_since(race.started) < timeout;
+            if !live {
+                Self::unindex_from(by_path, initiator, race);
+            }
+            live
+        });
+    }
+
+    fn unindex(&mut self, initiator: &NodeId, race: &Race) {
+        Self::unindex_from(&mut self.by_path, initiator, race)
+    }
+
+    /// Removes the paths of the race to `initiator` from the index, leaving those since raced to
+    /// another initiator.
+    fn unindex_from(by_path: &mut HashMap<SocketAddr, NodeId>, initiator: &NodeId, race: &Race) {
+        for path in &race.paths {
+            if by_path.get(path) == Some(initiator) {
+                by_path.remove(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_utils::ManualClock, Clock};
+    use std::sync::Arc;
+
+    fn paths(port: u16) -> Vec<SocketAddr> {
+        vec![
+            SocketAddr::from(([198, 51, 100, 7], port)),
+            SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, 7], port)),
+        ]
+    }
+
+    #[test]
+    fn test_resolve_returns_losing_paths() {
+        let mut races = PathRaces::new(Duration::from_secs(5));
+        let (initiator, other) = (NodeId::random(), NodeId::random());
+        races.start(initiator, paths(9000));
+        races.start(other, paths(9001));
+        // a single path is no race
+        races.start(NodeId::random(), paths(9002)[..1].to_vec());
+        assert_eq!(2, races.len());
+
+        assert_eq!(Some(vec![paths(9000)[0]]), races.resolve(&paths(9000)[1]));
+        assert_eq!(1, races.len());
+        // the race is over, late packets over the losing path resolve nothing
+        assert_eq!(None, races.resolve(&paths(9000)[0]));
+        assert_eq!(None, races.resolve(&paths(9002)[0]));
+        assert_eq!(Some(vec![paths(9001)[1]]), races.resolve(&paths(9001)[0]));
+        assert!(races.is_empty());
+        assert!(races.by_path.is_empty());
+    }
+
+    #[test]
+    fn test_restart_replaces_race() {
+        let mut races = PathRaces::new(Duration::from_secs(5));
+        let (initiator, other) = (NodeId::random(), NodeId::random());
+        races.start(initiator, paths(9000));
+        races.start(initiator, paths(9001));
+        assert_eq!(None, races.resolve(&paths(9000)[0]));
+
+        // a path raced to another initiator since stays indexed under it
+        races.start(other, vec![paths(9001)[0], paths(9002)[0]]);
+        assert_eq!(Some(vec![paths(9001)[0]]), races.resolve(&paths(9001)[1]));
+        assert_eq!(Some(vec![paths(9002)[0]]), races.resolve(&paths(9001)[0]));
+        assert!(races.by_path.is_empty());
+    }
+
+    #[test]
+    fn test_prune_forgets_unresolved_races() {
+        let clock = ManualClock::default();
+        let mut races = PathRaces::new(Duration::from_secs(5)).with_clock(Arc::new(clock.clone()));
+        races.start(NodeId::random(), paths(9000));
+        clock.advance(Duration::from_secs(3));
+        races.start(NodeId::random(), paths(9001));
+
+        clock.advance(Duration::from_secs(2));
+        races.prune(clock.now());
+        assert_eq!(1, races.len());
+        assert_eq!(None, races.resolve(&paths(9000)[0]));
+        assert_eq!(2, races.by_path.len());
+
+        clock.advance(Duration::from_secs(3));
+        races.prune(clock.now());
+        assert!(races.is_empty());
+        assert!(races.by_path.is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use super::{BackoffLedger, DEFAULT_MAX_FAILURE_COOLDOWN};
+use crate::{clock::system_clock, NodeId, SharedClock};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Default max number of notifications served per initiator per rate limit window.
+pub const DEFAULT_RATE_LIMIT_PER_INITIATOR: u32 = 4;
+/// Default max number of relayed notifications served per relay per rate limit window, as
+/// target.
+pub const DEFAULT_RATE_LIMIT_PER_RELAY: u32 = 4;
+/// Default max number of notifications served in total per rate limit window.
+pub const DEFAULT_RATE_LIMIT_TOTAL: u32 = 64;
+/// Default length of a rate limit window.
+pub const DEFAULT_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+/// Default max number of attempts initiated by this node per second.
+pub const DEFAULT_INITIATION_RATE: u32 = 16;
+/// Default time no attempts are initiated to a target after an attempt to it failed, doubled with
+/// each consecutive failure.
+pub const DEFAULT_FAILURE_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// Counts the notifications served in the current window.
+#[derive(Debug, Clone, Copy)]
+struct Window {
+    start: Instant,
+    count: u32,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Window {
+            start: now,
+            count: 0,
+        }
+    }
+
+    /// Increments the count if the limit isn't reached, starting a new window if the current one
+    /// has passed.
+    fn try_increment(&mut self, now: Instant, len: Duration, limit: u32) -> bool {
+        if now.saturating_duration_since(self.start) >= len {
+            *self = Window::new(now);
+        }
+        if self.count >= limit {
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+}
+
+/// Fixed window rate limiter for notifications, limiting both the notifications served per
+/// initiator and in total.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    per_initiator_limit: u32,
+    total_limit: u32,
+    window_len: Duration,
+    per_initiator: HashMap<NodeId, Window>,
+    total: Window,
+    clock: SharedClock,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_RATE_LIMIT_PER_INITIATOR,
+            DEFAULT_RATE_LIMIT_TOTAL,
+            DEFAULT_RATE_LIMIT_WINDOW,
+        )
+    }
+}
+
+impl RateLimiter {
+    pub fn new(per_initiator_limit: u32, total_limit: u32, window_len: Duration) -> Self {
+        let clock = system_clock();
+        RateLimiter {
+            per_initiator_limit,
+            total_limit,
+            window_len,
+            per_initiator: HashMap::new(),
+            total: Window::new(clock.now()),
+            clock,
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.total = Window::new(clock.now());
+        self.clock = clock;
+        self
+    }
+
+    /// Returns true and counts the notification if serving a notification from `initiator` is
+    /// within limits.
+    pub fn allow(&mut self, initiator: NodeId) -> bool {
+        let now = self.clock.now();
+        let window = self
+            .per_initiator
+            .entry(initiator)
+            .or_insert_with(|| Window::new(now));
+        if !window.try_increment(now, self.window_len, self.per_initiator_limit) {
+            return false;
+        }
+        if !self
+            .total
+            .try_increment(now, self.window_len, self.total_limit)
+        {
+            // don't charge the initiator for a notification that isn't served
+            window.count -= 1;
+            return false;
+        }
+        true
+    }
+
+    /// Forgets initiators whose window has passed at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        let window_len = self.window_len;
+        self.per_initiator
+            .retain(|_, window| now.saturating_duration_since(window.start) < window_len);
+    }
+}
+
+/// Throttles the attempts initiated by this node, so that a buggy query loop can't hammer
+/// relays. Caps the attempts initiated per second and backs off from targets that attempts keep
+/// failing to, see [`BackoffLedger`].
+#[derive(Debug, Clone)]
+pub struct InitiationThrottle {
+    rate: u32,
+    window: Window,
+    backoff: BackoffLedger,
+    clock: SharedClock,
+}
+
+impl Default for InitiationThrottle {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_INITIATION_RATE,
+            BackoffLedger::new(DEFAULT_FAILURE_COOLDOWN, DEFAULT_MAX_FAILURE_COOLDOWN),
+        )
+    }
+}
+
+impl InitiationThrottle {
+    pub fn new(rate: u32, backoff: BackoffLedger) -> Self {
+        let clock = system_clock();
+        InitiationThrottle {
+            rate,
+            window: Window::new(clock.now()),
+            backoff,
+            clock,
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.window = Window::new(clock.now());
+        self.backoff = self.backoff.with_clock(clock.clone());
+        self.clock = clock;
+        self
+    }
+
+    /// Returns true and counts the attempt if initiating an attempt to `target` is within limits.
+    pub fn allow(&mut self, target: NodeId) -> bool {
+        if self.backoff.is_backing_off(&target) {
+            return false;
+        }
+        self.window
+            .try_increment(self.clock.now(), DEFAULT_RATE_LIMIT_WINDOW, self.rate)
+    }
+
+    pub fn backoff(&self) -> &BackoffLedger {
+        &self.backoff
+    }
+
+    pub fn backoff_mut(&mut self) -> &mut BackoffLedger {
+        &mut self.backoff
+    }
+
+    /// Forgets targets backed off from long enough at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        self.backoff.prune(now);
+    }
+}
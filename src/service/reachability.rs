@@ -0,0 +1,128 @@
+use super::LruCache;
+use crate::{NodeId, SessionKey};
+use std::{fmt, net::SocketAddr, str::FromStr, time::SystemTime};
+
+/// Default max number of peers with known reachability.
+pub const DEFAULT_REACHABILITY_CAPACITY: usize = 4096;
+
+/// How a peer was last reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reachability {
+    /// The peer was reached without punching a hole.
+    Direct,
+    /// The peer was reached through a punched hole.
+    Punched,
+    /// Punching a hole to the peer failed.
+    Unreachable,
+}
+
+impl fmt::Display for Reachability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Reachability::Direct => "direct",
+            Reachability::Punched => "punched",
+            Reachability::Unreachable => "unreachable",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl FromStr for Reachability {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "direct" => Ok(Reachability::Direct),
+            "punched" => Ok(Reachability::Punched),
+            "unreachable" => Ok(Reachability::Unreachable),
+            _ => Err("invalid reachability"),
+        }
+    }
+}
+
+/// The last known reachability of a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReachabilityEntry {
+    pub reachability: Reachability,
+    /// Wall clock time of the observation, so that entries stay meaningful across restarts.
+    pub updated: SystemTime,
+    /// The socket the peer was reached at, if known.
+    pub socket: Option<SocketAddr>,
+}
+
+/// The last known reachability of peers, indexed by any [`SessionKey`]. Once full, the peers
+/// observed least recently are forgotten.
+#[derive(Debug, Clone)]
+pub struct ReachabilityCache<K: SessionKey = NodeId> {
+    entries: LruCache<K, ReachabilityEntry>,
+}
+
+impl<K: SessionKey> Default for ReachabilityCache<K> {
+    fn default() -> Self {
+        Self::with_capacity(DEFAULT_REACHABILITY_CAPACITY)
+    }
+}
+
+impl<K: SessionKey> ReachabilityCache<K> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        ReachabilityCache {
+            entries: LruCache::new(capacity, None),
+        }
+    }
+
+    /// Records the reachability of a peer observed now.
+    pub fn insert(&mut self, peer: K, reachability: Reachability) {
+        self.insert_entry(
+            peer,
+            ReachabilityEntry {
+                reachability,
+                updated: SystemTime::now(),
+                socket: None,
+            },
+        )
+    }
+
+    /// Records the reachability of a peer observed now at `socket`.
+    pub fn insert_at(&mut self, peer: K, reachability: Reachability, socket: SocketAddr) {
+        self.insert_entry(
+            peer,
+            ReachabilityEntry {
+                reachability,
+                updated: SystemTime::now(),
+                socket: Some(socket),
+            },
+        )
+    }
+
+    /// Records an observation of the reachability of a peer.
+    pub fn insert_entry(&mut self, peer: K, entry: ReachabilityEntry) {
+        self.entries.insert(peer, entry);
+    }
+
+    pub fn get(&self, peer: &K) -> Option<&ReachabilityEntry> {
+        self.entries.peek(peer)
+    }
+
+    pub fn remove(&mut self, peer: &K) -> Option<ReachabilityEntry> {
+        self.entries.remove(peer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &ReachabilityEntry)> {
+        self.entries.iter()
+    }
+
+    /// Number of peers with known reachability.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no peers have known reachability.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of peers forgotten to stay within capacity.
+    pub fn evictions(&self) -> u64 {
+        self.entries.evictions()
+    }
+}
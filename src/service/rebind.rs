@@ -0,0 +1,141 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// Default window in which an external address change and failures on holes count as
+/// correlated.
+pub const DEFAULT_REBIND_WINDOW: Duration = Duration::from_secs(60);
+/// Default number of failures on holes that together with an external address change indicate
+/// the NAT rebound.
+pub const DEFAULT_REBIND_THRESHOLD: usize = 2;
+
+/// Detects that the local NAT rebooted or rebound, killing all holes at once. The external
+/// address changes, and keep-alives or requests to peers behind punched holes fail within the
+/// same window. Failures alone, like a burst of lookup timeouts to NATed peers, don't indicate
+/// a rebind.
+#[derive(Debug, Clone)]
+pub struct RebindDetector {
+    window: Duration,
+    threshold: usize,
+    failures: VecDeque<Instant>,
+    external: Option<SocketAddr>,
+    external_changed: Option<Instant>,
+}
+
+impl Default for RebindDetector {
+    fn default() -> Self {
+        Self::new(DEFAULT_REBIND_WINDOW, DEFAULT_REBIND_THRESHOLD)
+    }
+}
+
+impl RebindDetector {
+    /// Detects a rebind once the external address changed and `threshold` failures on holes
+    /// occurred within `window`. The address change alone suffices if `threshold` is zero.
+    pub fn new(window: Duration, threshold: usize) -> Self {
+        RebindDetector {
+            window,
+            threshold,
+            failures: VecDeque::new(),
+            external: None,
+            external_changed: None,
+        }
+    }
+
+    /// A keep-alive or request over a punched hole failed at `now`.
+    pub fn record_failure(&mut self, now: Instant) {
+        self.failures.push_back(now);
+    }
+
+    /// The external address of the local node is `external` at `now`, as learned from peer
+    /// votes or a STUN server.
+    pub fn record_external(&mut self, external: SocketAddr, now: Instant) {
+        if self.external.is_some_and(|known| known != external) {
+            self.external_changed = Some(now);
+        }
+        self.external = Some(external);
+    }
+
+    /// The last known external address.
+    pub fn external(&self) -> Option<SocketAddr> {
+        self.external
+    }
+
+    /// Returns true if the NAT rebound since the last time a rebind was detected. Resets the
+    /// detector.
+    pub fn poll(&mut self, now: Instant) -> bool {
+        let expired = |at: &Instant| now.saturating_duration_since(*at) >= self.window;
+        while self.failures.front().is_some_and(expired) {
+            self.failures.pop_front();
+        }
+        if self.external_changed.as_ref().is_some_and(expired) {
+            self.external_changed = None;
+        }
+        if self.external_changed.is_some() && self.failures.len() >= self.threshold {
+            self.external_changed = None;
+            self.failures.clear();
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_alone_not_detected() {
+        let mut detector = RebindDetector::new(Duration::from_secs(1), 3);
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            detector.record_failure(now);
+        }
+        assert!(!detector.poll(now));
+
+        // an address change learned after the failures expired isn't correlated with them
+        detector.record_external("198.51.100.1:9000".parse().unwrap(), now);
+        detector.record_external(
+            "198.51.100.1:9001".parse().unwrap(),
+            now + Duration::from_secs(2),
+        );
+        assert!(!detector.poll(now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_address_change_with_failures_detected() {
+        let mut detector = RebindDetector::new(Duration::from_secs(1), 2);
+        let now = Instant::now();
+
+        detector.record_external("198.51.100.1:9000".parse().unwrap(), now);
+        assert!(!detector.poll(now));
+        detector.record_external("198.51.100.1:9001".parse().unwrap(), now);
+        // the address change alone isn't enough
+        assert!(!detector.poll(now));
+
+        detector.record_failure(now + Duration::from_millis(200));
+        assert!(!detector.poll(now + Duration::from_millis(200)));
+        detector.record_failure(now + Duration::from_millis(400));
+        assert!(detector.poll(now + Duration::from_millis(500)));
+        assert!(!detector.poll(now + Duration::from_millis(500)));
+
+        // the address change expires like failures do
+        detector.record_external("198.51.100.1:9002".parse().unwrap(), now);
+        detector.record_failure(now + Duration::from_secs(1));
+        detector.record_failure(now + Duration::from_secs(1));
+        assert!(!detector.poll(now + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_zero_threshold_detects_address_change() {
+        let mut detector = RebindDetector::new(Duration::from_secs(1), 0);
+        let now = Instant::now();
+
+        detector.record_external("198.51.100.1:9000".parse().unwrap(), now);
+        assert!(!detector.poll(now));
+        detector.record_external("198.51.100.1:9001".parse().unwrap(), now);
+        assert!(detector.poll(now));
+    }
+}
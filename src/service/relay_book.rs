@@ -0,0 +1,109 @@
+use super::LruCache;
+use crate::{NodeId, SharedClock};
+use std::hash::Hash;
+
+/// Default max number of targets relay candidates are remembered for.
+pub const DEFAULT_RELAY_BOOK_CAPACITY: usize = 1024;
+/// Default max number of relay candidates remembered per target.
+pub const DEFAULT_RELAY_CANDIDATES_PER_TARGET: usize = 3;
+
+/// The peers that passed each target to this node in a NODES response, and so have a session
+/// with the target to relay over. Filled from the responses to lookups, see
+/// [`super::ServiceCommand::NodesResponse`], and consumed when initiating attempts, which try the
+/// relays given by the caller first and then these.
+#[derive(Debug, Clone)]
+pub struct RelayCandidateBook<I> {
+    /// Most recent responder first.
+    candidates: LruCache<NodeId, Vec<I>>,
+    per_target: usize,
+}
+
+impl<I: Clone + Eq + Hash> Default for RelayCandidateBook<I> {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_RELAY_BOOK_CAPACITY,
+            DEFAULT_RELAY_CANDIDATES_PER_TARGET,
+        )
+    }
+}
+
+impl<I: Clone + Eq + Hash> RelayCandidateBook<I> {
+    pub fn new(capacity: usize, per_target: usize) -> Self {
+        RelayCandidateBook {
+            candidates: LruCache::new(capacity, None),
+            per_target,
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.candidates = self.candidates.with_clock(clock);
+        self
+    }
+
+    /// Records that the peer at the session `from` returned the given nodes in a NODES response.
+    /// `from` becomes the first candidate of each, displacing the least recent one if a target
+    /// has [`Self::new`]'s `per_target` candidates already.
+    pub fn record_nodes_response(&mut self, from: I, contains: &[NodeId]) {
+        for target in contains {
+            let mut relays = self.candidates.remove(target).unwrap_or_default();
+            relays.retain(|relay| *relay != from);
+            relays.insert(0, from.clone());
+            relays.truncate(self.per_target);
+            self.candidates.insert(*target, relays);
+        }
+    }
+
+    /// The sessions with the peers that most recently passed `target` to this node, most recent
+    /// first.
+    pub fn candidates_for(&self, target: &NodeId) -> Vec<I> {
+        self.candidates.peek(target).cloned().unwrap_or_default()
+    }
+
+    /// Forgets the relay at the session `relay`, for example once the session is dropped.
+    pub fn remove_relay(&mut self, relay: &I) {
+        self.candidates.retain(|_, relays| {
+            relays.retain(|candidate| candidate != relay);
+            !relays.is_empty()
+        });
+    }
+
+    /// Number of targets relay candidates are known for.
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    /// Returns true if no relay candidates are known.
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_candidates_recency_ordered_and_bounded() {
+        let mut book = RelayCandidateBook::new(2, 2);
+        let (target, other, third) = (NodeId::random(), NodeId::random(), NodeId::random());
+
+        book.record_nodes_response(1u8, &[target, other]);
+        book.record_nodes_response(2, &[target]);
+        assert_eq!(vec![2, 1], book.candidates_for(&target));
+        // a repeated response moves the responder to the front, the oldest falls out
+        book.record_nodes_response(1, &[target]);
+        book.record_nodes_response(3, &[target]);
+        assert_eq!(vec![3, 1], book.candidates_for(&target));
+
+        // the least recently passed target is forgotten
+        book.record_nodes_response(3, &[third]);
+        assert!(book.candidates_for(&other).is_empty());
+        assert_eq!(2, book.len());
+
+        book.remove_relay(&3);
+        assert_eq!(vec![1], book.candidates_for(&target));
+        assert!(book.candidates_for(&third).is_empty());
+        assert_eq!(1, book.len());
+    }
+}
@@ -0,0 +1,463 @@
+use crate::{clock::system_clock, MessageNonce, NodeId, RelayInit, SharedClock};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Default max number of [`RelayInit`]s waiting to be relayed.
+pub const DEFAULT_RELAY_QUEUE_DEPTH: usize = 64;
+/// Default max number of [`RelayInit`]s relayed per second.
+pub const DEFAULT_RELAY_RATE: u32 = 32;
+/// Default max number of relay operations in flight with [`RelayConcurrency`].
+pub const DEFAULT_RELAY_CONCURRENCY: usize = 64;
+/// Default max number of relay operations in flight per initiator with [`RelayConcurrency`].
+pub const DEFAULT_RELAY_CONCURRENCY_PER_INITIATOR: usize = 4;
+
+/// Caps on the relay operations in flight. An operation is in flight from relaying a
+/// [`RelayInit`] until the initiator reports on the attempt or the attempt times out.
+/// Notifications beyond the caps wait in the queue, and the initiator with the fewest operations
+/// in flight relative to its weight is served first, so one busy initiator can't take all slots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelayConcurrency {
+    /// Max number of relay operations in flight.
+    pub max_in_flight: usize,
+    /// Max number of relay operations in flight per initiator of weight 1.
+    pub per_initiator: usize,
+    /// The weight of initiators, 1 for those not listed. An initiator of weight `w` gets `w`
+    /// times the share of slots, and `w` times `per_initiator`.
+    pub weights: HashMap<NodeId, u32>,
+}
+
+impl Default for RelayConcurrency {
+    fn default() -> Self {
+        RelayConcurrency {
+            max_in_flight: DEFAULT_RELAY_CONCURRENCY,
+            per_initiator: DEFAULT_RELAY_CONCURRENCY_PER_INITIATOR,
+            weights: HashMap::new(),
+        }
+    }
+}
+
+impl RelayConcurrency {
+    /// Gives `initiator` the given weight.
+    pub fn with_weight(mut self, initiator: NodeId, weight: u32) -> Self {
+        self.weights.insert(initiator, weight);
+        self
+    }
+
+    /// The weight of `initiator`, at least 1.
+    pub fn weight(&self, initiator: &NodeId) -> usize {
+        self.weights
+            .get(initiator)
+            .map_or(1, |weight| *weight.max(&1) as usize)
+    }
+}
+
+/// Which notification to drop when the relay queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drops the notification that has waited the longest.
+    #[default]
+    DropOldest,
+    /// Drops the incoming notification.
+    DropNewest,
+    /// Drops the oldest notification of the initiator with the most notifications queued, so a
+    /// single initiator can't crowd out the others.
+    PerInitiatorFair,
+}
+
+/// Metrics of the relay queue.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayQueueMetrics {
+    /// Notifications currently queued.
+    pub depth: usize,
+    /// Notifications queued in total.
+    pub enqueued: u64,
+    /// Notifications dropped in total because the queue was full.
+    pub dropped: u64,
+    /// Notifications taken from the queue to be relayed in total.
+    pub processed: u64,
+    /// Relay operations currently in flight, only tracked with [`RelayConcurrency`].
+    pub in_flight: usize,
+}
+
+/// A bounded queue of [`RelayInit`]s waiting to be relayed, drained at a capped rate so that a
+/// burst of notifications isn't amplified instantaneously.
+#[derive(Debug, Clone)]
+pub struct RelayQueue {
+    queue: VecDeque<RelayInit>,
+    depth: usize,
+    policy: DropPolicy,
+    rate: u32,
+    tokens: f64,
+    last_refill: Instant,
+    metrics: RelayQueueMetrics,
+    concurrency: Option<RelayConcurrency>,
+    /// The time after which a relay operation is assumed finished.
+    op_timeout: Duration,
+    /// The initiator and deadline of each relay operation in flight, by nonce.
+    in_flight: HashMap<MessageNonce, (NodeId, Instant)>,
+    in_flight_per_initiator: HashMap<NodeId, usize>,
+}
+
+impl Default for RelayQueue {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_RELAY_QUEUE_DEPTH,
+            DropPolicy::default(),
+            DEFAULT_RELAY_RATE,
+        )
+    }
+}
+
+impl RelayQueue {
+    pub fn new(depth: usize, policy: DropPolicy, rate: u32) -> Self {
+        RelayQueue {
+            queue: VecDeque::with_capacity(depth),
+            depth,
+            policy,
+            rate,
+            tokens: rate as f64,
+            last_refill: system_clock().now(),
+            metrics: RelayQueueMetrics::default(),
+            concurrency: None,
+            op_timeout: Duration::ZERO,
+            in_flight: HashMap::new(),
+            in_flight_per_initiator: HashMap::new(),
+        }
+    }
+
+    /// Starts refilling the rate at the time of `clock` instead of the system clock. The queue
+    /// is given the time on each call after.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.last_refill = clock.now();
+        self
+    }
+
+    /// Caps the relay operations in flight, see [`RelayConcurrency`]. Operations are started with
+    /// [`Self::start`] and finished with [`Self::finish`] or once `op_timeout` passes, the time
+    /// an attempt is given by its initiator.
+    pub fn with_concurrency(mut self, concurrency: RelayConcurrency, op_timeout: Duration) -> Self {
+        self.concurrency = Some(concurrency);
+        self.op_timeout = op_timeout;
+        self
+    }
+
+    /// Queues a notification. Returns the notification dropped to make room for it, or the
+    /// notification itself if it was dropped, if the queue is full.
+    pub fn push(&mut self, notif: RelayInit) -> Option<RelayInit> {
+        if self.depth == 0 {
+            // there is nothing to make room in
+            self.metrics.dropped += 1;
+            return Some(notif);
+        }
+        let dropped = if self.queue.len() < self.depth {
+            None
+        } else {
+            match self.policy {
+                DropPolicy::DropOldest => self.queue.pop_front(),
+                DropPolicy::DropNewest => {
+                    self.metrics.dropped += 1;
+                    return Some(notif);
+                }
+                DropPolicy::PerInitiatorFair => self.drop_from_busiest_initiator(&notif),
+            }
+        };
+        if dropped.is_some() {
+            self.metrics.dropped += 1;
+        }
+        self.queue.push_back(notif);
+        self.metrics.enqueued += 1;
+        self.metrics.depth = self.queue.len();
+        dropped
+    }
+
+    fn drop_from_busiest_initiator(&mut self, incoming: &RelayInit) -> Option<RelayInit> {
+        let mut queued = HashMap::new();
+        for notif in self.queue.iter() {
+            *queued.entry(notif.0.node_id()).or_insert(0usize) += 1;
+        }
+        // the incoming notification counts towards its initiator's share
+        *queued.entry(incoming.0.node_id()).or_insert(0) += 1;
+        let (busiest, _) = queued.into_iter().max_by_key(|(_, count)| *count)?;
+        let index = self
+            .queue
+            .iter()
+            .position(|notif| notif.0.node_id() == busiest);
+        match index {
+            Some(index) => self.queue.remove(index),
+            // the incoming notification's initiator is the busiest and has nothing queued, i.e.
+            // the queue has no capacity at all
+            None => self.queue.pop_front(),
+        }
+    }
+
+    /// Takes the next notification to relay, if the rate cap, and the concurrency caps if any,
+    /// allow it at `now`.
+    pub fn pop(&mut self, now: Instant) -> Option<RelayInit> {
+        self.refill(now);
+        self.expire(now);
+        if self.tokens < 1.0 {
+            return None;
+        }
+        let notif = match self.concurrency.as_ref() {
+            Some(concurrency) => {
+                if self.in_flight.len() >= concurrency.max_in_flight {
+                    return None;
+                }
+                let index = self.least_served(concurrency)?;
+                self.queue.remove(index)?
+            }
+            None => self.queue.pop_front()?,
+        };
+        self.tokens -= 1.0;
+        self.metrics.processed += 1;
+        self.metrics.depth = self.queue.len();
+        Some(notif)
+    }
+
+    /// The index of the longest waiting notification of the initiator with the fewest operations
+    /// in flight relative to its weight, among the initiators below their cap.
+    fn least_served(&self, concurrency: &RelayConcurrency) -> Option<usize> {
+        self.queue
+            .iter()
+            .map(|notif| {
+                let initiator = notif.0.node_id();
+                let in_flight = self
+                    .in_flight_per_initiator
+                    .get(&initiator)
+                    .copied()
+                    .unwrap_or_default();
+                (in_flight, concurrency.weight(&initiator))
+            })
+            .enumerate()
+            .filter(|(_, (in_flight, weight))| {
+                *in_flight < concurrency.per_initiator.saturating_mul(*weight)
+            })
+            // in_flight / weight compared without division
+            .min_by(
+                |(a, (a_in_flight, a_weight)), (b, (b_in_flight, b_weight))| {
+                    (a_in_flight * b_weight)
+                        .cmp(&(b_in_flight * a_weight))
+                        .then(a.cmp(b))
+                },
+            )
+            .map(|(index, _)| index)
+    }
+
+    /// Counts the relay operation of the attempt `nonce` of `initiator`, relayed at `now`,
+    /// towards the caps until it is finished or times out. Does nothing without
+    /// [`RelayConcurrency`].
+    pub fn start(&mut self, initiator: NodeId, nonce: MessageNonce, now: Instant) {
+        if self.concurrency.is_none() {
+            return;
+        }
+        let deadline = now + self.op_timeout;
+        if let Some((previous, _)) = self.in_flight.insert(nonce, (initiator, deadline)) {
+            self.release(&previous);
+        }
+        *self.in_flight_per_initiator.entry(initiator).or_default() += 1;
+        self.metrics.in_flight = self.in_flight.len();
+    }
+
+    /// Finishes the relay operation of the attempt `nonce`, freeing its slot.
+    pub fn finish(&mut self, nonce: &MessageNonce) {
+        if let Some((initiator, _)) = self.in_flight.remove(nonce) {
+            self.release(&initiator);
+            self.metrics.in_flight = self.in_flight.len();
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let expired = self
+            .in_flight
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(nonce, _)| *nonce)
+            .collect::<Vec<_>>();
+        for nonce in expired {
+            self.finish(&nonce);
+        }
+    }
+
+    fn release(&mut self, initiator: &NodeId) {
+        if let Some(count) = self.in_flight_per_initiator.get_mut(initiator) {
+            *count -= 1;
+            if *count == 0 {
+                self.in_flight_per_initiator.remove(initiator);
+            }
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() * self.rate as f64).min(self.rate as f64);
+        self.last_refill = now;
+    }
+
+    /// Number of queued notifications.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns true if no notifications are queued.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn metrics(&self) -> RelayQueueMetrics {
+        self.metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NodeId, MESSAGE_NONCE_LENGTH};
+    use enr::{CombinedKey, EnrBuilder};
+
+    fn relay_init(enr_key: &CombinedKey, nonce: u8) -> RelayInit {
+        let enr = EnrBuilder::new("v4").build(enr_key).unwrap();
+        RelayInit(
+            enr,
+            NodeId::random(),
+            [nonce; MESSAGE_NONCE_LENGTH],
+            None,
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_per_initiator_fair_drops_busiest() {
+        let hyperactive = CombinedKey::generate_secp256k1();
+        let other = CombinedKey::generate_secp256k1();
+        let newcomer = CombinedKey::generate_secp256k1();
+        let mut queue = RelayQueue::new(3, DropPolicy::PerInitiatorFair, 1);
+
+        queue.push(relay_init(&hyperactive, 1));
+        queue.push(relay_init(&other, 2));
+        queue.push(relay_init(&hyperactive, 3));
+        let dropped = queue.push(relay_init(&newcomer, 4)).unwrap();
+
+        assert_eq!([1u8; MESSAGE_NONCE_LENGTH], dropped.2);
+        assert_eq!(1, queue.metrics().dropped);
+        assert_eq!(3, queue.metrics().depth);
+    }
+
+    #[test]
+    fn test_zero_depth_queues_nothing() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        for policy in [
+            DropPolicy::DropOldest,
+            DropPolicy::DropNewest,
+            DropPolicy::PerInitiatorFair,
+        ] {
+            let mut queue = RelayQueue::new(0, policy, 1);
+            let dropped = queue.push(relay_init(&enr_key, 1)).unwrap();
+            assert_eq!([1u8; MESSAGE_NONCE_LENGTH], dropped.2);
+            assert!(queue.is_empty());
+            assert_eq!(1, queue.metrics().dropped);
+        }
+    }
+
+    #[test]
+    fn test_rate_capped() {
+        let enr_key = CombinedKey::generate_secp256k1();
+        let mut queue = RelayQueue::new(4, DropPolicy::DropNewest, 2);
+        for nonce in 0..5 {
+            queue.push(relay_init(&enr_key, nonce));
+        }
+        let now = Instant::now();
+
+        assert!(queue.pop(now).is_some());
+        assert!(queue.pop(now).is_some());
+        assert!(queue.pop(now).is_none());
+        assert!(queue.pop(now + Duration::from_secs(1)).is_some());
+
+        let metrics = queue.metrics();
+        assert_eq!(
+            (4, 1, 3, 1),
+            (
+                metrics.enqueued,
+                metrics.dropped,
+                metrics.processed,
+                metrics.depth
+            )
+        );
+    }
+
+    #[test]
+    fn test_concurrency_fair_across_initiators() {
+        let hyperactive = CombinedKey::generate_secp256k1();
+        let other = CombinedKey::generate_secp256k1();
+        let concurrency = RelayConcurrency {
+            max_in_flight: 3,
+            per_initiator: 2,
+            ..Default::default()
+        };
+        let timeout = Duration::from_secs(2);
+        let mut queue =
+            RelayQueue::new(8, DropPolicy::DropNewest, 100).with_concurrency(concurrency, timeout);
+        for nonce in 0..4 {
+            queue.push(relay_init(&hyperactive, nonce));
+        }
+        queue.push(relay_init(&other, 4));
+        let now = Instant::now();
+        let deadline = now + timeout;
+        let pop = |queue: &mut RelayQueue, now| {
+            let notif = queue.pop(now)?;
+            queue.start(notif.0.node_id(), notif.2, now);
+            Some(notif.2[0])
+        };
+
+        // the other initiator is served before the second operation of the hyperactive one
+        assert_eq!(Some(0), pop(&mut queue, now));
+        assert_eq!(Some(4), pop(&mut queue, now));
+        assert_eq!(Some(1), pop(&mut queue, now));
+        // the global cap is reached
+        assert_eq!(None, pop(&mut queue, now));
+        queue.finish(&[4; MESSAGE_NONCE_LENGTH]);
+        // the hyperactive initiator is at its own cap
+        assert_eq!(None, pop(&mut queue, now));
+        assert_eq!(2, queue.metrics().in_flight);
+
+        // operations past their deadline free their slots
+        assert_eq!(Some(2), pop(&mut queue, deadline));
+        assert_eq!(Some(3), pop(&mut queue, deadline));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_concurrency_weighted() {
+        let heavy = CombinedKey::generate_secp256k1();
+        let light = CombinedKey::generate_secp256k1();
+        let heavy_id = EnrBuilder::new("v4").build(&heavy).unwrap().node_id();
+        let concurrency = RelayConcurrency {
+            max_in_flight: 8,
+            per_initiator: 1,
+            ..Default::default()
+        }
+        .with_weight(heavy_id, 3);
+        let timeout = Duration::from_secs(2);
+        let mut queue =
+            RelayQueue::new(8, DropPolicy::DropNewest, 100).with_concurrency(concurrency, timeout);
+        for nonce in 0..2 {
+            queue.push(relay_init(&light, nonce));
+        }
+        for nonce in 2..6 {
+            queue.push(relay_init(&heavy, nonce));
+        }
+        let now = Instant::now();
+        let mut served = Vec::new();
+        while let Some(notif) = queue.pop(now) {
+            queue.start(notif.0.node_id(), notif.2, now);
+            served.push(notif.2[0]);
+        }
+
+        // the heavy initiator gets three slots to the one of the light initiator
+        assert_eq!(vec![0, 2, 3, 4], served);
+        assert_eq!(2, queue.len());
+    }
+}
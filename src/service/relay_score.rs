@@ -0,0 +1,194 @@
+use std::{collections::HashMap, hash::Hash, net::IpAddr};
+
+/// Default prefix length of the IPv4 subnets relays of one attempt are spread over.
+pub const DEFAULT_RELAY_SUBNET_V4: u8 = 24;
+/// Default prefix length of the IPv6 subnets relays of one attempt are spread over.
+pub const DEFAULT_RELAY_SUBNET_V6: u8 = 48;
+
+/// Requires the relays chosen for one attempt to be in different subnets, as relays in the same
+/// subnet are likely run by one operator on one uplink and give no redundancy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubnetDiversity {
+    /// Prefix length of the IPv4 subnets two relays mustn't share.
+    pub v4_prefix: u8,
+    /// Prefix length of the IPv6 subnets two relays mustn't share.
+    pub v6_prefix: u8,
+}
+
+impl Default for SubnetDiversity {
+    fn default() -> Self {
+        SubnetDiversity {
+            v4_prefix: DEFAULT_RELAY_SUBNET_V4,
+            v6_prefix: DEFAULT_RELAY_SUBNET_V6,
+        }
+    }
+}
+
+impl SubnetDiversity {
+    /// Returns true if the addresses are in the same subnet. Addresses of different families
+    /// never are.
+    pub fn same_subnet(&self, a: IpAddr, b: IpAddr) -> bool {
+        match (a, b) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => {
+                let mask = u32::MAX.checked_shl(32 - self.v4_prefix.min(32) as u32);
+                let mask = mask.unwrap_or(0);
+                u32::from(a) & mask == u32::from(b) & mask
+            }
+            (IpAddr::V6(a), IpAddr::V6(b)) => {
+                let mask = u128::MAX.checked_shl(128 - self.v6_prefix.min(128) as u32);
+                let mask = mask.unwrap_or(0);
+                u128::from(a) & mask == u128::from(b) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The outcomes of the hole punch attempts a relay has been used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayScore {
+    pub successes: u32,
+    pub failures: u32,
+}
+
+impl RelayScore {
+    /// The estimated probability that an attempt through the relay succeeds. Relays without
+    /// history score 0.5.
+    pub fn value(&self) -> f64 {
+        (self.successes as f64 + 1.0) / (self.successes as f64 + self.failures as f64 + 2.0)
+    }
+}
+
+/// Scores relays by the outcomes of the hole punch attempts they have been used for.
+#[derive(Debug, Clone)]
+pub struct RelayScores<I> {
+    scores: HashMap<I, RelayScore>,
+}
+
+impl<I> Default for RelayScores<I> {
+    fn default() -> Self {
+        RelayScores {
+            scores: HashMap::new(),
+        }
+    }
+}
+
+impl<I: Eq + Hash + Clone> RelayScores<I> {
+    /// Records that an attempt through `relay` succeeded.
+    pub fn record_success(&mut self, relay: I) {
+        self.scores.entry(relay).or_default().successes += 1;
+    }
+
+    /// Records that an attempt through `relay` failed.
+    pub fn record_failure(&mut self, relay: I) {
+        self.scores.entry(relay).or_default().failures += 1;
+    }
+
+    /// Returns the score of `relay`.
+    pub fn get(&self, relay: &I) -> RelayScore {
+        self.scores.get(relay).copied().unwrap_or_default()
+    }
+
+    /// Returns the best scoring relay of the candidates.
+    pub fn best<'a>(&self, candidates: impl IntoIterator<Item = &'a I>) -> Option<&'a I>
+    where
+        I: 'a,
+    {
+        candidates.into_iter().max_by(|a, b| {
+            self.get(a)
+                .value()
+                .partial_cmp(&self.get(b).value())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    }
+
+    /// Selects up to `k` of the candidates to fan an attempt out to, best scoring first, skipping
+    /// candidates in the subnet of an already selected one. `key` returns the relay of a candidate
+    /// and its IP, if known. Candidates with unknown IPs aren't constrained. Fewer than `k`
+    /// candidates are returned if too few are diverse enough.
+    pub fn select_diverse<T>(
+        &self,
+        candidates: impl IntoIterator<Item = T>,
+        k: usize,
+        diversity: Option<&SubnetDiversity>,
+        key: impl Fn(&T) -> (I, Option<IpAddr>),
+    ) -> Vec<T> {
+        let mut candidates = candidates
+            .into_iter()
+            .map(|candidate| {
+                let (relay, ip) = key(&candidate);
+                (self.get(&relay).value(), ip, candidate)
+            })
+            .collect::<Vec<_>>();
+        // stable, equally scoring candidates keep their order
+        candidates
+            .sort_by(|(a, ..), (b, ..)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut selected_ips = Vec::new();
+        let mut selected = Vec::new();
+        for (_, ip, candidate) in candidates {
+            if selected.len() == k {
+                break;
+            }
+            if let (Some(diversity), Some(ip)) = (diversity, ip) {
+                if selected_ips
+                    .iter()
+                    .any(|selected| diversity.same_subnet(*selected, ip))
+                {
+                    continue;
+                }
+                selected_ips.push(ip);
+            }
+            selected.push(candidate);
+        }
+        selected
+    }
+
+    /// Sets the score of `relay`, for example to restore scores learned before a restart.
+    pub fn insert(&mut self, relay: I, score: RelayScore) {
+        self.scores.insert(relay, score);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&I, &RelayScore)> {
+        self.scores.iter()
+    }
+
+    /// Forgets the score of `relay`.
+    pub fn remove(&mut self, relay: &I) -> Option<RelayScore> {
+        self.scores.remove(relay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_diverse_relays() {
+        let mut scores = RelayScores::default();
+        let relays = [
+            (1, "192.0.2.1"),
+            (2, "192.0.2.200"),
+            (3, "198.51.100.1"),
+            (4, "2001:db8:1::1"),
+            (5, "2001:db8:1:ffff::1"),
+            (6, "2001:db8:2::1"),
+        ]
+        .map(|(relay, ip)| (relay, ip.parse::<IpAddr>().unwrap()));
+        // the relays of the shared /24 and /48 score best
+        for relay in [2, 5] {
+            scores.record_success(relay);
+        }
+        let key = |(relay, ip): &(u8, IpAddr)| (*relay, Some(*ip));
+
+        let selected = scores.select_diverse(relays, 6, Some(&SubnetDiversity::default()), key);
+        let selected = selected.iter().map(|(relay, _)| *relay).collect::<Vec<_>>();
+        assert_eq!(vec![2, 5, 3, 6], selected);
+
+        let selected = scores.select_diverse(relays, 3, None, key);
+        assert_eq!(
+            vec![2, 5, 1],
+            selected.iter().map(|(relay, _)| *relay).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use super::LruCache;
+use crate::{MessageNonce, NodeId, SharedClock};
+use std::time::{Duration, Instant};
+
+/// Default time a relayed notification is remembered to drop replays of it.
+pub const DEFAULT_REPLAY_TTL: Duration = Duration::from_secs(30);
+/// Default max number of notifications remembered to drop replays of them.
+pub const DEFAULT_REPLAY_CACHE_CAPACITY: usize = 4096;
+
+/// Remembers the notifications seen recently, indexed by the initiator's node id and the nonce of
+/// the timed out request, so that the same hole punch attempt isn't served twice.
+#[derive(Debug, Clone)]
+pub struct ReplayCache {
+    seen: LruCache<(NodeId, MessageNonce), ()>,
+}
+
+impl Default for ReplayCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_TTL)
+    }
+}
+
+impl ReplayCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_capacity(ttl, DEFAULT_REPLAY_CACHE_CAPACITY)
+    }
+
+    /// Creates a cache remembering at most `capacity` notifications. Once full, the oldest
+    /// notifications are forgotten before their time to live has passed.
+    pub fn with_capacity(ttl: Duration, capacity: usize) -> Self {
+        ReplayCache {
+            seen: LruCache::new(capacity, Some(ttl)),
+        }
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.seen = self.seen.with_clock(clock);
+        self
+    }
+
+    /// Records a notification. Returns false if it is a replay of a notification seen within the
+    /// time to live.
+    pub fn insert(&mut self, initiator: NodeId, nonce: MessageNonce) -> bool {
+        if self.seen.contains_key(&(initiator, nonce)) {
+            return false;
+        }
+        self.seen.insert((initiator, nonce), ());
+        true
+    }
+
+    /// Number of remembered notifications.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// Returns true if no notifications are remembered.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Number of notifications forgotten early to stay within capacity.
+    pub fn evictions(&self) -> u64 {
+        self.seen.evictions()
+    }
+
+    /// Forgets the notifications that have outlived the time to live at `now`.
+    pub fn prune(&mut self, now: Instant) {
+        self.seen.prune(now)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::{Enr, MessageNonce, NodeId, SessionKey};
+use async_trait::async_trait;
+use std::{
+    fmt::{Debug, Display},
+    net::SocketAddr,
+};
+
+/// The discv5 side of the [`super::HolePunchService`]. Sends the packets the service decides on
+/// and exposes the sessions of the discv5 instance.
+#[async_trait]
+pub trait NotificationSink: Send {
+    /// A type in discv5 for indexing sessions.
+    type SessionIndex: SessionKey;
+    /// A discv5 error type.
+    type Error: Display + Debug + Send;
+    /// Returns the current ENR of the local node, sent to relays in hole punch attempts initiated
+    /// by the application, see [`crate::NatHolePunch::initiate_punch`].
+    fn local_enr(&self) -> Enr;
+    /// Returns the node id of the peer in the given session.
+    fn node_id(&self, session: &Self::SessionIndex) -> NodeId;
+    /// Returns the session with the given peer, if any.
+    fn session(&self, node_id: &NodeId) -> Option<Self::SessionIndex>;
+    /// Returns true if the peer in the given session supports hole punching, for example because
+    /// its ENR advertises it, see [`crate::supports_hole_punch`]. Peers are assumed to support it
+    /// by default.
+    fn supports_hole_punch(&self, _session: &Self::SessionIndex) -> bool {
+        true
+    }
+    /// Returns the ENR of the peer in the given session, if known, for negotiating the revision
+    /// of the notification protocol spoken with it, see [`crate::supported_versions`].
+    fn enr(&self, _session: &Self::SessionIndex) -> Option<Enr> {
+        None
+    }
+    /// Returns whether the routing table has room for the peer `node_id`, if known, for
+    /// deciding whether a hole to it is worth punching, see [`super::PunchPolicy`].
+    fn is_table_candidate(&self, _node_id: &NodeId) -> Option<bool> {
+        None
+    }
+    /// Sends an encoded notification to the peer in the given session.
+    async fn send_notification(
+        &mut self,
+        session: &Self::SessionIndex,
+        notif: Vec<u8>,
+    ) -> Result<(), Self::Error>;
+    /// Sends a WHOAREYOU to the initiator of a hole punch attempt at `dst`, one of the sockets in
+    /// the initiator's ENR, using the `nonce` of the request that timed out at the initiator. See
+    /// [`crate::WhoAreYou`] for building the packet.
+    async fn send_whoareyou(
+        &mut self,
+        dst: SocketAddr,
+        initiator: &Enr,
+        nonce: MessageNonce,
+    ) -> Result<(), Self::Error>;
+    /// Sends an empty packet to keep a punched hole open.
+    async fn send_empty_packet(&mut self, dst: SocketAddr) -> Result<(), Self::Error>;
+    /// Sends an opaque packet, configured as [`super::PunchPayload`] or
+    /// [`super::KeepalivePayload`], as is.
+    async fn send_packet(&mut self, dst: SocketAddr, packet: Vec<u8>) -> Result<(), Self::Error>;
+}
@@ -0,0 +1,217 @@
+use super::{LatencyHistogram, PunchTimings};
+use crate::HolePunchError;
+use std::{
+    fmt::{Debug, Display},
+    time::Duration,
+};
+
+/// Hole punch failures observed by this node, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FailureCounts {
+    /// Attempts initiated by this node that got no WHOAREYOU from the target in time.
+    pub timed_out: u64,
+    /// Notifications that couldn't be decoded.
+    pub bad_notification: u64,
+    /// Notifications that failed validation, see [`crate::ValidationPolicy`].
+    pub invalid: u64,
+    /// Packets that discv5 failed to send.
+    pub send_failed: u64,
+    /// Notifications dropped by the rate limiter.
+    pub rate_limited: u64,
+    /// Notifications dropped as replays.
+    pub replayed: u64,
+    /// Notifications dropped because the relay queue was full.
+    pub relay_queue_full: u64,
+    /// Notifications for targets this node has no session with.
+    pub unknown_target: u64,
+    /// Notifications from initiators without a socket to punch a hole towards.
+    pub initiator_unreachable: u64,
+    /// Notifications aborted because this node was overloaded as relay.
+    pub overloaded: u64,
+    /// Attempts aborted by a peer.
+    pub aborted: u64,
+    /// Punched attempts whose discv5 session failed to be established through the hole.
+    pub session_failed: u64,
+    /// Attempts skipped because the target, or all relays, don't support hole punching.
+    pub unsupported: u64,
+    /// Notifications rejected by an inbound filter.
+    pub filtered: u64,
+    /// Attempts not initiated because of the initiation throttle.
+    pub locally_throttled: u64,
+    /// Timed out requests not punched for because of the [`super::PunchPolicy`].
+    pub declined: u64,
+    /// Notifications not sent because their encoding exceeded the max packet size.
+    pub packet_too_large: u64,
+    /// Relay inits aborted because the initiator and the target share no address family.
+    pub address_family_mismatch: u64,
+    /// Echo probes of peers refused, because of their delay or the number of pending echoes.
+    pub echo_refused: u64,
+}
+
+impl FailureCounts {
+    /// Counts the failure described by the given error.
+    pub fn record<E: Debug + Display>(&mut self, error: &HolePunchError<E>) {
+        let count = match error {
+            HolePunchError::NotificationError(_) => &mut self.bad_notification,
+            HolePunchError::Invalid(_) => &mut self.invalid,
+            HolePunchError::InitiatorError(_)
+            | HolePunchError::RelayError(_)
+            | HolePunchError::TargetError(_)
+            | HolePunchError::KeepaliveError(_)
+            | HolePunchError::EchoError(_)
+            | HolePunchError::NoRelay => &mut self.send_failed,
+            HolePunchError::UnknownTarget(_) => &mut self.unknown_target,
+            HolePunchError::InitiatorUnreachable => &mut self.initiator_unreachable,
+            HolePunchError::RateLimited => &mut self.rate_limited,
+            HolePunchError::Replayed => &mut self.replayed,
+            HolePunchError::RelayQueueFull => &mut self.relay_queue_full,
+            HolePunchError::Overloaded => &mut self.overloaded,
+            HolePunchError::Unsupported(_) | HolePunchError::NotImplemented(_) => {
+                &mut self.unsupported
+            }
+            HolePunchError::Aborted(_) => &mut self.aborted,
+            HolePunchError::Filtered => &mut self.filtered,
+            HolePunchError::LocallyThrottled => &mut self.locally_throttled,
+            HolePunchError::Declined(_) => &mut self.declined,
+            HolePunchError::PacketTooLarge(_) => &mut self.packet_too_large,
+            HolePunchError::AddressFamilyMismatch(_) => &mut self.address_family_mismatch,
+            HolePunchError::EchoRefused => &mut self.echo_refused,
+        };
+        *count += 1;
+    }
+
+    /// Total number of failures.
+    pub fn total(&self) -> u64 {
+        self.timed_out
+            + self.bad_notification
+            + self.invalid
+            + self.send_failed
+            + self.rate_limited
+            + self.replayed
+            + self.relay_queue_full
+            + self.unknown_target
+            + self.initiator_unreachable
+            + self.overloaded
+            + self.unsupported
+            + self.aborted
+            + self.session_failed
+            + self.filtered
+            + self.locally_throttled
+            + self.declined
+            + self.packet_too_large
+            + self.address_family_mismatch
+            + self.echo_refused
+    }
+}
+
+/// Entries evicted from the bounded caches of the service to stay within capacity, by cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheEvictions {
+    /// Notifications forgotten by the replay cache before their time to live passed.
+    pub replay: u64,
+    /// Attempts in flight dropped to make room for new ones.
+    pub attempts: u64,
+    /// Peers whose reachability was forgotten.
+    pub reachability: u64,
+}
+
+/// A snapshot of the hole punch statistics of the [`super::HolePunchService`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Attempts initiated by this node.
+    pub punches_initiated: u64,
+    /// Attempts of other nodes relayed by this node.
+    pub punches_relayed: u64,
+    /// Attempts initiated by this node that succeeded.
+    pub punches_succeeded: u64,
+    /// Attempts relayed by this node that the initiator reported as punched.
+    pub relayed_punched: u64,
+    /// Attempts relayed by this node that the initiator reported as failed.
+    pub relayed_failed: u64,
+    /// Attempts of other nodes this node punched a hole for as target, from a socket behind NAT.
+    pub target_punches_nat: u64,
+    /// Attempts of other nodes this node punched a hole for as target, from a publicly addressed
+    /// socket behind a stateful firewall.
+    pub target_punches_firewall: u64,
+    /// Relayed messages dropped as target for attempts already punched for, arriving over
+    /// another relay.
+    pub duplicate_relay_msgs: u64,
+    /// Failures by reason.
+    pub punches_failed: FailureCounts,
+    /// Punched holes currently kept open.
+    pub active_holes: usize,
+    /// Keep-alive packets sent.
+    pub keepalives_sent: u64,
+    /// Average time from initiating an attempt to receiving the WHOAREYOU from the target.
+    pub avg_time_to_punch: Option<Duration>,
+    /// Time from initiating an attempt to receiving the WHOAREYOU from the target.
+    pub time_to_punch: LatencyHistogram,
+    /// Time from sending the [`crate::RelayInit`] to the relay that got an attempt through to
+    /// receiving the WHOAREYOU from the target, see [`PunchTimings::relay_latency`].
+    pub relay_latency: LatencyHistogram,
+    /// Time from initiating an attempt to the session with the target being established.
+    pub time_to_establish: LatencyHistogram,
+    /// Evictions from the bounded caches.
+    pub cache_evictions: CacheEvictions,
+}
+
+/// Accumulates the statistics of the service, of which [`Stats`] is a snapshot.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StatsRecorder {
+    pub punches_initiated: u64,
+    pub punches_relayed: u64,
+    pub punches_succeeded: u64,
+    pub relayed_punched: u64,
+    pub relayed_failed: u64,
+    pub target_punches_nat: u64,
+    pub target_punches_firewall: u64,
+    pub duplicate_relay_msgs: u64,
+    pub punches_failed: FailureCounts,
+    pub keepalives_sent: u64,
+    total_time_to_punch: Duration,
+    time_to_punch: LatencyHistogram,
+    relay_latency: LatencyHistogram,
+    time_to_establish: LatencyHistogram,
+}
+
+impl StatsRecorder {
+    /// Records a successful attempt initiated by this node.
+    pub fn record_success(&mut self, timings: &PunchTimings) {
+        self.punches_succeeded += 1;
+        self.total_time_to_punch += timings.time_to_punch();
+        self.time_to_punch.record(timings.time_to_punch());
+        self.relay_latency.record(timings.relay_latency());
+    }
+
+    /// Records the session with the target of a successful attempt being established.
+    pub fn record_established(&mut self, timings: &PunchTimings) {
+        if let Some(time_to_establish) = timings.time_to_establish() {
+            self.time_to_establish.record(time_to_establish);
+        }
+    }
+
+    pub fn snapshot(&self, active_holes: usize, cache_evictions: CacheEvictions) -> Stats {
+        let avg_time_to_punch = u32::try_from(self.punches_succeeded)
+            .ok()
+            .filter(|succeeded| *succeeded > 0)
+            .map(|succeeded| self.total_time_to_punch / succeeded);
+        Stats {
+            punches_initiated: self.punches_initiated,
+            punches_relayed: self.punches_relayed,
+            punches_succeeded: self.punches_succeeded,
+            relayed_punched: self.relayed_punched,
+            relayed_failed: self.relayed_failed,
+            target_punches_nat: self.target_punches_nat,
+            target_punches_firewall: self.target_punches_firewall,
+            duplicate_relay_msgs: self.duplicate_relay_msgs,
+            punches_failed: self.punches_failed,
+            active_holes,
+            keepalives_sent: self.keepalives_sent,
+            avg_time_to_punch,
+            time_to_punch: self.time_to_punch,
+            relay_latency: self.relay_latency,
+            time_to_establish: self.time_to_establish,
+            cache_evictions,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the buckets of a [`LatencyHistogram`], in milliseconds. Latencies above the
+/// last bound fall in an overflow bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 10] = [10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// The phases of a successful attempt initiated by this node, attached to
+/// [`super::HolePunchEvent::TargetPunched`] and [`super::HolePunchEvent::Established`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PunchTimings {
+    /// The time the attempt was initiated.
+    pub initiated: Instant,
+    /// The time the [`crate::RelayInit`] was sent to the relay that got the attempt through.
+    /// Relays don't acknowledge notifications, so the time the relay got it is unknown.
+    pub relay_sent: Instant,
+    /// The time the WHOAREYOU from the target arrived.
+    pub whoareyou_received: Instant,
+    /// The time the discv5 session with the target was established, if it is yet.
+    pub established: Option<Instant>,
+}
+
+impl PunchTimings {
+    /// The time spent waiting in the registry and on relays that didn't get the attempt through.
+    pub fn time_to_relay(&self) -> Duration {
+        self.relay_sent.saturating_duration_since(self.initiated)
+    }
+
+    /// The time from the [`crate::RelayInit`] going out to the WHOAREYOU arriving, over the
+    /// relay and the target.
+    pub fn relay_latency(&self) -> Duration {
+        self.whoareyou_received
+            .saturating_duration_since(self.relay_sent)
+    }
+
+    /// The time from initiating the attempt to the WHOAREYOU arriving.
+    pub fn time_to_punch(&self) -> Duration {
+        self.whoareyou_received
+            .saturating_duration_since(self.initiated)
+    }
+
+    /// The time from initiating the attempt to the session being established.
+    pub fn time_to_establish(&self) -> Option<Duration> {
+        self.established
+            .map(|established| established.saturating_duration_since(self.initiated))
+    }
+}
+
+/// Counts of latencies by bucket, see [`LATENCY_BUCKETS_MS`], for exporting as a histogram
+/// metric.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    counts: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    sum: Duration,
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|bound| latency <= Duration::from_millis(*bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.counts[bucket] += 1;
+        self.sum += latency;
+    }
+
+    /// Number of latencies recorded.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Sum of the latencies recorded.
+    pub fn sum(&self) -> Duration {
+        self.sum
+    }
+
+    /// The upper bound of each bucket, `None` for the overflow bucket, and the number of
+    /// latencies in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        LATENCY_BUCKETS_MS
+            .iter()
+            .map(|bound| Some(Duration::from_millis(*bound)))
+            .chain([None])
+            .zip(self.counts.iter().copied())
+    }
+
+    /// The upper bound of the bucket the `q` quantile falls in, `None` if nothing was recorded or
+    /// it falls in the overflow bucket.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let rank = (q.clamp(0.0, 1.0) * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return bound;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(None, histogram.quantile(0.5));
+        for ms in [5, 10, 40, 90, 20_000] {
+            histogram.record(Duration::from_millis(ms));
+        }
+
+        assert_eq!(5, histogram.count());
+        assert_eq!(Duration::from_millis(20_145), histogram.sum());
+        let counts = histogram
+            .buckets()
+            .map(|(_, count)| count)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![2, 0, 1, 1, 0, 0, 0, 0, 0, 0, 1], counts);
+        assert_eq!(Some(Duration::from_millis(10)), histogram.quantile(0.4));
+        assert_eq!(Some(Duration::from_millis(100)), histogram.quantile(0.8));
+        assert_eq!(None, histogram.quantile(0.99));
+    }
+}
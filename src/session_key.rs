@@ -0,0 +1,67 @@
+use crate::{NodeAddress, NodeId};
+use std::{fmt::Debug, hash::Hash, net::SocketAddr};
+
+/// A key identifying a peer's session. Stacks index sessions by node id, by socket, or by both
+/// like discv5 does with [`NodeAddress`]. Some only learn the node id of a peer after the
+/// handshake, so either part is optional.
+pub trait SessionKey: Clone + Eq + Hash + Debug + Send + Sync {
+    /// The node id of the peer, if the key carries it.
+    fn node_id(&self) -> Option<NodeId>;
+    /// The socket of the peer, if the key carries it.
+    fn socket_addr(&self) -> Option<SocketAddr>;
+}
+
+impl SessionKey for NodeId {
+    fn node_id(&self) -> Option<NodeId> {
+        Some(*self)
+    }
+
+    fn socket_addr(&self) -> Option<SocketAddr> {
+        None
+    }
+}
+
+impl SessionKey for SocketAddr {
+    fn node_id(&self) -> Option<NodeId> {
+        None
+    }
+
+    fn socket_addr(&self) -> Option<SocketAddr> {
+        Some(*self)
+    }
+}
+
+impl SessionKey for NodeAddress {
+    fn node_id(&self) -> Option<NodeId> {
+        Some(self.node_id)
+    }
+
+    fn socket_addr(&self) -> Option<SocketAddr> {
+        Some(self.socket_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttemptRegistry, Reachability, ReachabilityCache};
+
+    #[test]
+    fn test_caches_accept_any_session_key() {
+        let socket: SocketAddr = "192.0.2.1:9000".parse().unwrap();
+        let node_address = NodeAddress::new(socket, NodeId::random());
+
+        let mut by_socket = ReachabilityCache::<SocketAddr>::default();
+        by_socket.insert(socket, Reachability::Punched);
+        assert_eq!(
+            Reachability::Punched,
+            by_socket.get(&socket).unwrap().reachability
+        );
+
+        let mut attempts = AttemptRegistry::<NodeAddress>::default();
+        attempts.insert([1u8; 12], [node_address], node_address, Vec::new(), None);
+        let attempt = attempts.get(&[1u8; 12]).unwrap();
+        assert_eq!(Some(socket), attempt.target.socket_addr());
+        assert_eq!(Some(node_address.node_id), attempt.relay.node_id());
+    }
+}
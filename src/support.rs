@@ -0,0 +1,110 @@
+use crate::{Enr, ProtocolVersion};
+use enr::{CombinedKey, EnrError};
+
+/// The ENR key under which a node advertises support of the hole punch notifications.
+pub const HOLE_PUNCH_ENR_KEY: &str = "nat_hp";
+/// The ENR key under which a node lists the revisions of the notification protocol it speaks, one
+/// byte each, if not all up to the one under [`HOLE_PUNCH_ENR_KEY`].
+pub const HOLE_PUNCH_VERSIONS_ENR_KEY: &str = "nat_hp_vs";
+/// The version of the notification protocol implemented by this crate. Revision 2 adds the
+/// optional attempt id to notifications, revision 3 the optional candidates, revision 4 the
+/// socket the relay observes the target at, revision 5 the optionally compressed ENR, revision 6
+/// the punch report, revision 7 the node id of the relay, revision 8 the extension list and
+/// revision 9 the echo probe.
+pub const HOLE_PUNCH_PROTOCOL_VERSION: u8 = 9;
+/// The oldest version of the notification protocol this crate can speak.
+pub const MIN_HOLE_PUNCH_PROTOCOL_VERSION: u8 = 1;
+
+/// Advertises support of the hole punch notifications in the local ENR.
+pub fn advertise_hole_punch(enr: &mut Enr, key: &CombinedKey) -> Result<(), EnrError> {
+    enr.insert(HOLE_PUNCH_ENR_KEY, &[HOLE_PUNCH_PROTOCOL_VERSION], key)?;
+    Ok(())
+}
+
+/// Returns the version of the notification protocol the ENR advertises, if any.
+pub fn hole_punch_version(enr: &Enr) -> Option<u8> {
+    match enr.get(HOLE_PUNCH_ENR_KEY)? {
+        [version] => Some(*version),
+        _ => None,
+    }
+}
+
+/// Returns true if the ENR advertises a version of the notification protocol this crate can
+/// speak.
+pub fn supports_hole_punch(enr: &Enr) -> bool {
+    hole_punch_version(enr).is_some_and(|version| version >= MIN_HOLE_PUNCH_PROTOCOL_VERSION)
+}
+
+/// Lists the revisions of the notification protocol the local node speaks in the local ENR.
+pub fn advertise_supported_versions(
+    enr: &mut Enr,
+    key: &CombinedKey,
+    versions: &[u8],
+) -> Result<(), EnrError> {
+    enr.insert(HOLE_PUNCH_VERSIONS_ENR_KEY, versions, key)?;
+    Ok(())
+}
+
+/// Returns the revisions of the notification protocol the ENR lists, or all up to the one it
+/// advertises if it lists none. Empty if it advertises no support.
+pub fn supported_versions(enr: &Enr) -> Vec<u8> {
+    if let Some(versions) = enr.get(HOLE_PUNCH_VERSIONS_ENR_KEY) {
+        return versions.to_vec();
+    }
+    match hole_punch_version(enr) {
+        Some(version) => (MIN_HOLE_PUNCH_PROTOCOL_VERSION..=version).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Returns the highest revision of the notification protocol in both `local` and `remote`.
+pub fn negotiate_version(local: &[u8], remote: &[u8]) -> Option<ProtocolVersion> {
+    local
+        .iter()
+        .filter(|version| remote.contains(version))
+        .max()
+        .map(|version| ProtocolVersion(*version))
+}
+
+/// The revisions of the notification protocol this crate speaks.
+pub fn local_versions() -> Vec<u8> {
+    (MIN_HOLE_PUNCH_PROTOCOL_VERSION..=HOLE_PUNCH_PROTOCOL_VERSION).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advertise_hole_punch() {
+        let key = CombinedKey::generate_secp256k1();
+        let mut enr = enr::EnrBuilder::new("v4").build(&key).unwrap();
+        assert!(!supports_hole_punch(&enr));
+
+        advertise_hole_punch(&mut enr, &key).unwrap();
+        assert_eq!(Some(HOLE_PUNCH_PROTOCOL_VERSION), hole_punch_version(&enr));
+        assert!(supports_hole_punch(&enr));
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let key = CombinedKey::generate_secp256k1();
+        let mut enr = enr::EnrBuilder::new("v4").build(&key).unwrap();
+        assert!(supported_versions(&enr).is_empty());
+
+        enr.insert(HOLE_PUNCH_ENR_KEY, &[4u8], &key).unwrap();
+        assert_eq!(vec![1, 2, 3, 4], supported_versions(&enr));
+        assert_eq!(
+            Some(ProtocolVersion(4)),
+            negotiate_version(&local_versions(), &supported_versions(&enr))
+        );
+
+        advertise_supported_versions(&mut enr, &key, &[2, 10]).unwrap();
+        assert_eq!(vec![2, 10], supported_versions(&enr));
+        assert_eq!(
+            Some(ProtocolVersion(2)),
+            negotiate_version(&local_versions(), &supported_versions(&enr))
+        );
+        assert_eq!(None, negotiate_version(&local_versions(), &[10]));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::{Enr, RelayMsg};
+use enr::{CombinedKey, EnrError};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{SocketAddr, TcpStream},
+    thread,
+    time::Duration,
+};
+
+/// Default number of connect attempts of a simultaneous open.
+pub const DEFAULT_TCP_CONNECT_TRIES: usize = 5;
+/// Default time a single connect attempt of a simultaneous open waits.
+pub const DEFAULT_TCP_CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How the peers of a TCP hole punch attempt connect to each other. Both peers connect from
+/// the port they advertised at the same time, so the SYNs cross and each NAT sees outbound
+/// traffic before the other peer's SYN arrives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimultaneousOpen {
+    /// Number of connect attempts before giving up.
+    pub tries: usize,
+    /// Time a single connect attempt waits for the handshake to complete.
+    pub connect_timeout: Duration,
+    /// Time to wait between connect attempts.
+    pub spacing: Duration,
+}
+
+impl Default for SimultaneousOpen {
+    fn default() -> Self {
+        SimultaneousOpen {
+            tries: DEFAULT_TCP_CONNECT_TRIES,
+            connect_timeout: DEFAULT_TCP_CONNECT_TIMEOUT,
+            spacing: Duration::ZERO,
+        }
+    }
+}
+
+impl SimultaneousOpen {
+    /// Connects from `local` to `remote`, retrying until the peer's connect attempt crosses
+    /// this node's. Blocks for at most `tries` connect timeouts and spacings.
+    pub fn connect(&self, local: SocketAddr, remote: SocketAddr) -> io::Result<TcpStream> {
+        let mut res = Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no connect attempts configured",
+        ));
+        for try_index in 0..self.tries {
+            if try_index > 0 && !self.spacing.is_zero() {
+                thread::sleep(self.spacing);
+            }
+            res = connect_from(local, remote, self.connect_timeout);
+            if res.is_ok() {
+                break;
+            }
+        }
+        res
+    }
+
+    /// Connects to the initiator of a relayed TCP hole punch attempt, at the reflexive TCP
+    /// sockets in its ENR, IPv4 first. See [`advertise_reflexive_tcp`].
+    pub fn connect_to_initiator<const N: usize, const I: usize>(
+        &self,
+        local_port: u16,
+        notif: &RelayMsg<N, I>,
+    ) -> io::Result<TcpStream> {
+        let mut res = Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "initiator ENR has no TCP socket",
+        ));
+        for remote in enr_tcp_sockets(&notif.0) {
+            let local = match remote {
+                SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], local_port)),
+                SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], local_port)),
+            };
+            res = self.connect(local, remote);
+            if res.is_ok() {
+                break;
+            }
+        }
+        res
+    }
+}
+
+/// Sets the reflexive TCP socket of the local node in the ENR it sends in a [`crate::RelayInit`].
+/// The relay forwards the ENR unchanged in the [`RelayMsg`], so the target learns where to
+/// connect to, and the relay machinery for UDP is reused as is.
+pub fn advertise_reflexive_tcp(
+    enr: &mut Enr,
+    key: &CombinedKey,
+    reflexive: SocketAddr,
+) -> Result<(), EnrError> {
+    enr.set_tcp_socket(reflexive, key)
+}
+
+/// Returns the TCP sockets of the ENR, IPv4 first.
+pub fn enr_tcp_sockets(enr: &Enr) -> Vec<SocketAddr> {
+    enr.tcp4_socket()
+        .map(SocketAddr::V4)
+        .into_iter()
+        .chain(enr.tcp6_socket().map(SocketAddr::V6))
+        .collect()
+}
+
+/// Makes a single connect attempt from the given local socket, which is shared with any other
+/// connect attempts and listeners of this node on the same port.
+fn connect_from(local: SocketAddr, remote: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let socket = Socket::new(
+        Domain::for_address(local),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(true)?;
+    socket.bind(&local.into())?;
+    socket.connect_timeout(&remote.into(), timeout)?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, net::TcpListener};
+
+    #[test]
+    fn test_connect_retries_until_peer_is_up() {
+        let remote = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote_addr = remote.local_addr().unwrap();
+        let open = SimultaneousOpen {
+            spacing: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = remote.accept().unwrap();
+            stream.write_all(b"punched").unwrap();
+        });
+        let stream = open
+            .connect("127.0.0.1:0".parse().unwrap(), remote_addr)
+            .unwrap();
+
+        assert_eq!(remote_addr, stream.peer_addr().unwrap());
+        peer.join().unwrap();
+    }
+}
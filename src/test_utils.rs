@@ -0,0 +1,259 @@
+//! Utilities for deterministic tests of code driving the hole punch service.
+
+use crate::{
+    BoxError, Clock, Enr, HolePunchError, MessageNonce, NatHolePunch, NodeAddress, NodeId,
+    RelayAbort, RelayInit, RelayMsg,
+};
+use async_trait::async_trait;
+use futures::future;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    task::{Poll, Waker},
+    time::{Duration, Instant},
+};
+
+/// A clock that only moves when advanced. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    inner: Arc<Mutex<ManualClockInner>>,
+}
+
+#[derive(Debug)]
+struct ManualClockInner {
+    now: Instant,
+    sleepers: Vec<(Instant, Waker)>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl ManualClock {
+    /// Creates a clock stopped at `now`.
+    pub fn new(now: Instant) -> Self {
+        ManualClock {
+            inner: Arc::new(Mutex::new(ManualClockInner {
+                now,
+                sleepers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Moves the clock forward by `duration`, waking sleepers whose deadline is reached.
+    pub fn advance(&self, duration: Duration) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.now += duration;
+        let now = inner.now;
+        let (due, pending) = inner
+            .sleepers
+            .drain(..)
+            .partition::<Vec<_>, _>(|(deadline, _)| *deadline <= now);
+        inner.sleepers = pending;
+        drop(inner);
+        for (_, waker) in due {
+            waker.wake();
+        }
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.inner.lock().unwrap().now
+    }
+
+    async fn sleep_until(&self, deadline: Instant) {
+        future::poll_fn(|cx| {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.now >= deadline {
+                return Poll::Ready(());
+            }
+            inner.sleepers.push((deadline, cx.waker().clone()));
+            Poll::Pending
+        })
+        .await
+    }
+}
+
+/// A call made to a [`MockHolePunch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockCall {
+    RequestTimedOut {
+        relay: NodeAddress,
+        local_enr: Enr,
+        nonce: MessageNonce,
+        target: NodeAddress,
+    },
+    InitiatePunch {
+        target: NodeId,
+        via: NodeAddress,
+    },
+    /// An encoded notification, as passed to [`NatHolePunch::on_notification`] or re-encoded
+    /// from the decoded notification passed to a handler.
+    Notification(Vec<u8>),
+    HolePunchExpired(SocketAddr),
+}
+
+/// A [`NatHolePunch`] that records the calls made to it and succeeds without sending anything,
+/// for unit testing the code driving hole punching, also as a [`crate::DynHolePunch`].
+#[derive(Debug, Default)]
+pub struct MockHolePunch {
+    /// The calls made so far, in order.
+    pub calls: Vec<MockCall>,
+    /// The nonce returned by [`NatHolePunch::initiate_punch`].
+    pub nonce: MessageNonce,
+}
+
+#[async_trait]
+impl NatHolePunch for MockHolePunch {
+    type SessionIndex = NodeAddress;
+    type Discv5Error = BoxError;
+
+    async fn on_request_time_out(
+        &mut self,
+        relay: NodeAddress,
+        local_enr: Enr,
+        timed_out_message_nonce: MessageNonce,
+        target_session_index: NodeAddress,
+    ) -> Result<(), HolePunchError> {
+        self.calls.push(MockCall::RequestTimedOut {
+            relay,
+            local_enr,
+            nonce: timed_out_message_nonce,
+            target: target_session_index,
+        });
+        Ok(())
+    }
+
+    async fn initiate_punch(
+        &mut self,
+        target: NodeId,
+        via: NodeAddress,
+    ) -> Result<MessageNonce, HolePunchError> {
+        self.calls.push(MockCall::InitiatePunch { target, via });
+        Ok(self.nonce)
+    }
+
+    async fn on_notification(&mut self, decrypted_notif: &[u8]) -> Result<(), HolePunchError> {
+        self.calls
+            .push(MockCall::Notification(decrypted_notif.to_vec()));
+        Ok(())
+    }
+
+    async fn on_relay_init(&mut self, notif: RelayInit) -> Result<(), HolePunchError> {
+        self.calls.push(MockCall::Notification(notif.rlp_encode()));
+        Ok(())
+    }
+
+    async fn on_relay_msg(&mut self, notif: RelayMsg) -> Result<(), HolePunchError> {
+        self.calls.push(MockCall::Notification(notif.rlp_encode()));
+        Ok(())
+    }
+
+    async fn on_relay_abort(&mut self, notif: RelayAbort) -> Result<(), HolePunchError> {
+        self.calls.push(MockCall::Notification(notif.rlp_encode()));
+        Ok(())
+    }
+
+    async fn on_hole_punch_expired(&mut self, dst: SocketAddr) -> Result<(), HolePunchError> {
+        self.calls.push(MockCall::HolePunchExpired(dst));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DynHolePunch, MESSAGE_NONCE_LENGTH};
+    use futures::{executor::block_on, FutureExt};
+
+    #[test]
+    fn test_manual_clock_wakes_on_advance() {
+        let clock = ManualClock::default();
+        let start = clock.now();
+        let mut sleep = clock.sleep_until(start + Duration::from_secs(5)).boxed();
+
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(4));
+        assert!((&mut sleep).now_or_never().is_none());
+        clock.advance(Duration::from_secs(1));
+        block_on(sleep);
+        assert_eq!(start + Duration::from_secs(5), clock.now());
+    }
+
+    #[test]
+    fn test_mock_records_calls_through_dyn() {
+        let mut mock = MockHolePunch {
+            nonce: [7; MESSAGE_NONCE_LENGTH],
+            ..Default::default()
+        };
+        let target = NodeId::random();
+        let via = NodeAddress::new("1.2.3.4:9000".parse().unwrap(), NodeId::random());
+        let dst = "5.6.7.8:9000".parse().unwrap();
+
+        let hole_punch: &mut dyn DynHolePunch = &mut mock;
+        let nonce = block_on(hole_punch.initiate_punch(target, via)).unwrap();
+        block_on(hole_punch.on_notification(&[1, 2, 3])).unwrap();
+        block_on(hole_punch.on_hole_punch_expired(dst)).unwrap();
+
+        assert_eq!([7; MESSAGE_NONCE_LENGTH], nonce);
+        assert_eq!(
+            vec![
+                MockCall::InitiatePunch { target, via },
+                MockCall::Notification(vec![1, 2, 3]),
+                MockCall::HolePunchExpired(dst),
+            ],
+            mock.calls
+        );
+    }
+
+    #[test]
+    fn test_initiate_punch_not_implemented_by_default() {
+        /// Implements only the hooks without a default.
+        struct Minimal;
+
+        #[async_trait]
+        impl NatHolePunch for Minimal {
+            type SessionIndex = NodeAddress;
+            type Discv5Error = BoxError;
+
+            async fn on_request_time_out(
+                &mut self,
+                _relay: NodeAddress,
+                _local_enr: Enr,
+                _timed_out_message_nonce: MessageNonce,
+                _target_session_index: NodeAddress,
+            ) -> Result<(), HolePunchError> {
+                Ok(())
+            }
+
+            async fn on_relay_init(&mut self, _notif: RelayInit) -> Result<(), HolePunchError> {
+                Ok(())
+            }
+
+            async fn on_relay_msg(&mut self, _notif: RelayMsg) -> Result<(), HolePunchError> {
+                Ok(())
+            }
+
+            async fn on_hole_punch_expired(
+                &mut self,
+                _dst: SocketAddr,
+            ) -> Result<(), HolePunchError> {
+                Ok(())
+            }
+        }
+
+        let via = NodeAddress::new("1.2.3.4:9000".parse().unwrap(), NodeId::random());
+        assert!(matches!(
+            block_on(NatHolePunch::initiate_punch(
+                &mut Minimal,
+                NodeId::random(),
+                via
+            )),
+            Err(HolePunchError::NotImplemented("initiate_punch"))
+        ));
+    }
+}
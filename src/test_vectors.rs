@@ -0,0 +1,135 @@
+//! Canonical encodings of the notifications, for asserting interoperability of other discv5
+//! implementations with this crate.
+
+use crate::{
+    rlp_backend::DecoderError, Enr, MessageNonce, NodeId, Notification, RelayInit, RelayMsg,
+    MESSAGE_NONCE_LENGTH,
+};
+use thiserror::Error;
+
+/// The initiator ENR, the example record of EIP-778.
+pub const INITIATOR_ENR: &str = "enr:-IS4QHCYrYZbAKWCBRlAy5zzaDZXJBGkcnh4MHcBFZntXNFrdvJjX04jRzjzCBOonrkTfj499SZuOh8R33Ls8RRcy5wBgmlkgnY0gmlwhH8AAAGJc2VjcDI1NmsxoQPKY0yuDUmstAHYpMa2_oxVtw0RW_QAdpzBQA8yWM0xOIN1ZHCCdl8";
+/// The target node id, node B of the discv5 wire test vectors.
+pub const TARGET_NODE_ID: &str = "bbbb9d047f0488c0b5a93c1c3f2d8bafc7c8ff337024a55434a0d0555de64db9";
+/// The nonce of the timed out request.
+pub const NONCE: &str = "0102030405060708090a0b0c";
+/// The expected encoding of the [`RelayInit`] built from the above.
+pub const RELAY_INIT_ENCODED: &str = "07f8b4f884b8407098ad865b00a582051940cb9cf36836572411a47278783077011599ed5cd16b76f2635f4e234738f30813a89eb9137e3e3df5266e3a1f11df72ecf1145ccb9c01826964827634826970847f00000189736563703235366b31a103ca634cae0d49acb401d8a4c6b6fe8c55b70d115bf400769cc1400f3258cd31388375647082765fa0bbbb9d047f0488c0b5a93c1c3f2d8bafc7c8ff337024a55434a0d0555de64db98c0102030405060708090a0b0c";
+/// The expected encoding of the [`RelayMsg`] built from the above.
+pub const RELAY_MSG_ENCODED: &str = "08f893f884b8407098ad865b00a582051940cb9cf36836572411a47278783077011599ed5cd16b76f2635f4e234738f30813a89eb9137e3e3df5266e3a1f11df72ecf1145ccb9c01826964827634826970847f00000189736563703235366b31a103ca634cae0d49acb401d8a4c6b6fe8c55b70d115bf400769cc1400f3258cd31388375647082765f8c0102030405060708090a0b0c";
+
+/// A notification and its expected encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestVector {
+    pub name: &'static str,
+    pub notification: Notification,
+    pub encoded: Vec<u8>,
+}
+
+#[derive(Debug, Error)]
+pub enum TestVectorError {
+    #[error("no test vector named {0}")]
+    UnknownVector(String),
+    #[error("test vector {0} failed to decode, {1}")]
+    Decode(&'static str, DecoderError),
+    #[error("test vector {name} mismatch, expected 0x{expected}, got 0x{actual}")]
+    Mismatch {
+        name: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+/// The initiator ENR of the test vectors.
+pub fn initiator_enr() -> Enr {
+    INITIATOR_ENR.parse().expect("valid test vector")
+}
+
+/// The target node id of the test vectors.
+pub fn target_node_id() -> NodeId {
+    NodeId::parse(&hex::decode(TARGET_NODE_ID).expect("valid test vector")).expect("32 bytes")
+}
+
+/// The nonce of the test vectors.
+pub fn nonce() -> MessageNonce {
+    let mut nonce = [0u8; MESSAGE_NONCE_LENGTH];
+    nonce.copy_from_slice(&hex::decode(NONCE).expect("valid test vector"));
+    nonce
+}
+
+/// All test vectors.
+pub fn vectors() -> Vec<TestVector> {
+    vec![
+        TestVector {
+            name: "relay-init",
+            notification: RelayInit(initiator_enr(), target_node_id(), nonce(), None, None, None)
+                .into(),
+            encoded: hex::decode(RELAY_INIT_ENCODED).expect("valid test vector"),
+        },
+        TestVector {
+            name: "relay-msg",
+            notification: RelayMsg(initiator_enr(), nonce(), None, None, None, None, None).into(),
+            encoded: hex::decode(RELAY_MSG_ENCODED).expect("valid test vector"),
+        },
+    ]
+}
+
+/// Verifies an encoding produced by another implementation against the test vector of the given
+/// name, and that it decodes to the expected notification.
+pub fn verify(name: &str, encoded: &[u8]) -> Result<(), TestVectorError> {
+    let vector = vectors()
+        .into_iter()
+        .find(|vector| vector.name == name)
+        .ok_or_else(|| TestVectorError::UnknownVector(name.to_string()))?;
+    verify_vector(&vector, encoded)
+}
+
+/// Verifies this crate's codec against all test vectors.
+pub fn verify_all() -> Result<(), TestVectorError> {
+    for vector in vectors() {
+        let encoded = vector.notification.to_rlp();
+        verify_vector(&vector, &encoded)?;
+    }
+    Ok(())
+}
+
+fn verify_vector(vector: &TestVector, encoded: &[u8]) -> Result<(), TestVectorError> {
+    if encoded != vector.encoded {
+        return Err(TestVectorError::Mismatch {
+            name: vector.name,
+            expected: hex::encode(&vector.encoded),
+            actual: hex::encode(encoded),
+        });
+    }
+    let decoded =
+        Notification::rlp_decode(encoded).map_err(|e| TestVectorError::Decode(vector.name, e))?;
+    if decoded != vector.notification {
+        return Err(TestVectorError::Mismatch {
+            name: vector.name,
+            expected: vector.notification.to_string(),
+            actual: decoded.to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_matches_vectors() {
+        verify_all().unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_foreign_encoding() {
+        let mut encoded = hex::decode(RELAY_MSG_ENCODED).unwrap();
+        *encoded.last_mut().unwrap() ^= 1;
+
+        assert!(matches!(
+            verify("relay-msg", &encoded),
+            Err(TestVectorError::Mismatch { .. })
+        ));
+    }
+}
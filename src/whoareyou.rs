@@ -0,0 +1,128 @@
+use crate::{MessageNonce, MESSAGE_NONCE_LENGTH};
+
+/// Discv5 protocol id in the static header of packets.
+pub const PROTOCOL_ID: &[u8; 6] = b"discv5";
+/// Discv5 protocol version in the static header of packets.
+pub const PROTOCOL_VERSION: u16 = 0x0001;
+/// Flag of WHOAREYOU packets in the static header.
+pub const WHOAREYOU_FLAG: u8 = 1;
+/// Length of the masking IV preceding the header of packets in bytes.
+pub const MASKING_IV_LENGTH: usize = 16;
+/// Length of the id-nonce of WHOAREYOU packets in bytes.
+pub const ID_NONCE_LENGTH: usize = 16;
+/// Length of the authdata of WHOAREYOU packets in bytes.
+pub const WHOAREYOU_AUTHDATA_LENGTH: usize = ID_NONCE_LENGTH + 8;
+/// Length of the static header of packets in bytes.
+pub const STATIC_HEADER_LENGTH: usize = PROTOCOL_ID.len() + 2 + 1 + MESSAGE_NONCE_LENGTH + 2;
+
+/// Discv5 WHOAREYOU id-nonce.
+pub type IdNonce = [u8; ID_NONCE_LENGTH];
+/// Discv5 packet masking IV.
+pub type MaskingIv = [u8; MASKING_IV_LENGTH];
+
+/// The WHOAREYOU the target sends to the initiator of a hole punch attempt, wrapping the nonce of
+/// the initiator's timed out request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhoAreYou {
+    /// The nonce of the timed out request, from the [`crate::RelayMsg`].
+    pub nonce: MessageNonce,
+    pub id_nonce: IdNonce,
+    /// The sequence number of the initiator's ENR known to the target, 0 if unknown.
+    pub enr_seq: u64,
+}
+
+impl WhoAreYou {
+    pub fn new(nonce: MessageNonce, id_nonce: IdNonce, enr_seq: u64) -> Self {
+        WhoAreYou {
+            nonce,
+            id_nonce,
+            enr_seq,
+        }
+    }
+
+    /// The authdata of the packet, `id-nonce || enr-seq`.
+    pub fn authdata(&self) -> [u8; WHOAREYOU_AUTHDATA_LENGTH] {
+        let mut authdata = [0u8; WHOAREYOU_AUTHDATA_LENGTH];
+        authdata[..ID_NONCE_LENGTH].copy_from_slice(&self.id_nonce);
+        authdata[ID_NONCE_LENGTH..].copy_from_slice(&self.enr_seq.to_be_bytes());
+        authdata
+    }
+
+    /// The unmasked header of the packet, `static-header || authdata`.
+    pub fn header(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(STATIC_HEADER_LENGTH + WHOAREYOU_AUTHDATA_LENGTH);
+        header.extend_from_slice(PROTOCOL_ID);
+        header.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        header.push(WHOAREYOU_FLAG);
+        header.extend_from_slice(&self.nonce);
+        header.extend_from_slice(&(WHOAREYOU_AUTHDATA_LENGTH as u16).to_be_bytes());
+        header.extend_from_slice(&self.authdata());
+        header
+    }
+
+    /// The challenge-data both peers derive the session keys from,
+    /// `masking-iv || static-header || authdata`.
+    pub fn challenge_data(&self, masking_iv: &MaskingIv) -> Vec<u8> {
+        let mut challenge_data = masking_iv.to_vec();
+        challenge_data.extend_from_slice(&self.header());
+        challenge_data
+    }
+
+    /// The complete packet sent to the initiator with node id `dest`, `masking-iv ||
+    /// masked-header`. The header is masked with AES-128-CTR keyed by the first 16 bytes of
+    /// `dest`.
+    #[cfg(feature = "packet")]
+    pub fn encode(&self, masking_iv: &MaskingIv, dest: &crate::NodeId) -> Vec<u8> {
+        use aes::cipher::{KeyIvInit, StreamCipher};
+
+        let mut header = self.header();
+        let key = &dest.raw()[..16];
+        let mut cipher = ctr::Ctr128BE::<aes::Aes128>::new(key.into(), masking_iv.into());
+        cipher.apply_keystream(&mut header);
+
+        let mut packet = masking_iv.to_vec();
+        packet.extend_from_slice(&header);
+        packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the WHOAREYOU test vector of the discv5 wire spec
+    const NONCE: &str = "0102030405060708090a0b0c";
+    const ID_NONCE: &str = "0102030405060708090a0b0c0d0e0f10";
+    const CHALLENGE_DATA: &str = "000000000000000000000000000000006469736376350001010102030405060708090a0b0c00180102030405060708090a0b0c0d0e0f100000000000000000";
+
+    fn whoareyou() -> WhoAreYou {
+        let mut nonce = [0u8; MESSAGE_NONCE_LENGTH];
+        nonce.copy_from_slice(&hex::decode(NONCE).unwrap());
+        let mut id_nonce = [0u8; ID_NONCE_LENGTH];
+        id_nonce.copy_from_slice(&hex::decode(ID_NONCE).unwrap());
+        WhoAreYou::new(nonce, id_nonce, 0)
+    }
+
+    #[test]
+    fn test_challenge_data() {
+        let challenge_data = whoareyou().challenge_data(&[0u8; MASKING_IV_LENGTH]);
+        assert_eq!(CHALLENGE_DATA, hex::encode(challenge_data));
+    }
+
+    #[cfg(feature = "packet")]
+    #[test]
+    fn test_encode_packet() {
+        let dest = crate::NodeId::parse(
+            &hex::decode("bbbb9d047f0488c0b5a93c1c3f2d8bafc7c8ff337024a55434a0d0555de64db9")
+                .unwrap(),
+        )
+        .unwrap();
+
+        let packet = whoareyou().encode(&[0u8; MASKING_IV_LENGTH], &dest);
+
+        assert_eq!(
+            "00000000000000000000000000000000088b3d434277464933a1ccc59f5967ad1d6035f15e528627dde75cd68292f9e6c27d6b66c8100a873fcbaed4e16b8d",
+            hex::encode(packet)
+        );
+    }
+}